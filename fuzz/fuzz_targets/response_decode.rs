@@ -0,0 +1,6 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|response: &[u8]| {
+    let _ = async_h1::fuzz::decode_response_bytes(response);
+});
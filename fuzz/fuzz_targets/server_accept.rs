@@ -47,9 +47,9 @@ impl AsyncWrite for RwWrapper {
 
 fuzz_target!(|request: &[u8]| {
     let stream = RwWrapper::new(request.to_vec());
-    async_std::task::block_on(async_h1::accept("http://localhost", stream, |req| async {
+    async_std::task::block_on(async_h1::accept(stream, |mut req| async move {
         let mut res = http_types::Response::new(http_types::StatusCode::Ok);
-        res.set_body(req);
+        res.set_body(req.take_body());
         Ok(res)
     }))
     .ok();
@@ -0,0 +1,7 @@
+//! Read structured message bodies as they arrive.
+//!
+//! - [`Multipart`] splits a `multipart/form-data` body into its parts.
+
+mod multipart;
+
+pub use multipart::{Multipart, Part};
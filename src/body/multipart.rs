@@ -0,0 +1,349 @@
+//! Split `multipart/form-data` bodies into their parts.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncRead as Read};
+use futures_lite::{future, ready};
+use http_types::{Body, Request, StatusCode};
+
+/// The maximum length of the header fields of a part.
+const MAX_PART_HEAD_LENGTH: usize = 8 * 1024;
+
+/// The maximum number of header fields in a part.
+const MAX_PART_HEADERS: usize = 32;
+
+/// The number of bytes read from the body at a time.
+const READ_SIZE: usize = 8 * 1024;
+
+/// Where a [`Multipart`] body is up to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// In the preamble or the body of a part, before the next delimiter.
+    Body,
+    /// Just past a delimiter, which is either followed by a line break or
+    /// closes the body.
+    Delimiter,
+    /// In the header fields of a part.
+    Head,
+    /// Past the closing delimiter.
+    Done,
+}
+
+/// A `multipart/form-data` body
+/// ([RFC 7578](https://www.rfc-editor.org/rfc/rfc7578)), read one part at a
+/// time.
+///
+/// Parts are yielded by [`next_part`](Multipart::next_part) as readers of
+/// their own body, so uploads are streamed rather than buffered: at most a
+/// few KiB of the body are held in memory at once. Whatever's left of a
+/// part is discarded when the next one is asked for.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::body::Multipart;
+/// use futures_lite::AsyncReadExt;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn upload(mut req: Request) -> http_types::Result<Response> {
+///     let mut multipart = Multipart::from_request(&mut req)?;
+///     while let Some(mut part) = multipart.next_part().await? {
+///         let name = part.name().unwrap_or_default().to_owned();
+///         let mut contents = Vec::new();
+///         part.read_to_end(&mut contents).await?;
+///         println!("{}: {} bytes", name, contents.len());
+///     }
+///     Ok(Response::new(StatusCode::Ok))
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Multipart<R> {
+    reader: R,
+    /// A line break followed by `--` and the boundary.
+    delimiter: Vec<u8>,
+    /// Bytes read from the body but not consumed yet.
+    buf: Vec<u8>,
+    /// Whether the body has ended.
+    eof: bool,
+    state: State,
+}
+
+impl Multipart<Body> {
+    /// Take the body of a request, using the boundary from its
+    /// `Content-Type`.
+    ///
+    /// Fails with `415 Unsupported Media Type` if the request isn't
+    /// `multipart/form-data` or has no boundary.
+    pub fn from_request(req: &mut Request) -> http_types::Result<Self> {
+        let boundary = req
+            .content_type()
+            .filter(|mime| mime.essence() == "multipart/form-data")
+            .and_then(|mime| Some(mime.param("boundary")?.as_str().to_owned()));
+        let boundary = match boundary {
+            Some(boundary) => boundary,
+            None => {
+                return Err(http_types::Error::from_str(
+                    StatusCode::UnsupportedMediaType,
+                    "The request body isn't multipart/form-data with a boundary",
+                ))
+            }
+        };
+        ensure_kind!(
+            (1..=70).contains(&boundary.len()),
+            MalformedMessage,
+            "Invalid multipart boundary {:?}",
+            boundary
+        );
+        Ok(Self::new(req.take_body(), &boundary))
+    }
+}
+
+impl<R: Read + Unpin> Multipart<R> {
+    /// Read the parts of `reader`, which are separated by `boundary`.
+    pub fn new(reader: R, boundary: &str) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Self {
+            reader,
+            delimiter,
+            // The first delimiter has no line break before it, unless it
+            // follows a preamble.
+            buf: b"\r\n".to_vec(),
+            eof: false,
+            state: State::Body,
+        }
+    }
+
+    /// The next part, or `None` once the body has been read.
+    ///
+    /// Fails with an
+    /// [`ErrorKind::BodyFraming`](crate::error::ErrorKind::BodyFraming)
+    /// error if the body ends before its closing delimiter or a part's
+    /// header fields are malformed.
+    pub async fn next_part(&mut self) -> http_types::Result<Option<Part<'_, R>>> {
+        let headers = future::poll_fn(|cx| self.poll_next_head(cx)).await?;
+        Ok(headers.map(move |headers| Part {
+            multipart: self,
+            headers,
+        }))
+    }
+
+    /// Skip to the header fields of the next part and parse them.
+    fn poll_next_head(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<Headers>>> {
+        loop {
+            match self.state {
+                State::Body => {
+                    let len = ready!(self.poll_body(cx))?;
+                    self.buf.drain(..len);
+                }
+                State::Delimiter => {
+                    if self.buf.starts_with(b"--") {
+                        self.state = State::Done;
+                        self.buf.clear();
+                    } else if let Some(end) = find(&self.buf, b"\r\n") {
+                        // Whitespace may pad the line the delimiter is on.
+                        let padding = &self.buf[..end];
+                        if !padding.iter().all(|&b| b == b' ' || b == b'\t') {
+                            return Poll::Ready(Err(malformed("Invalid multipart delimiter")));
+                        }
+                        self.buf.drain(..end + 2);
+                        self.state = State::Head;
+                    } else if self.buf.len() > MAX_PART_HEAD_LENGTH {
+                        return Poll::Ready(Err(malformed("Invalid multipart delimiter")));
+                    } else {
+                        ready!(self.poll_fill(cx))?;
+                    }
+                }
+                State::Head => {
+                    let mut headers = [httparse::EMPTY_HEADER; MAX_PART_HEADERS];
+                    let parsed = match httparse::parse_headers(&self.buf, &mut headers) {
+                        Ok(httparse::Status::Complete((len, headers))) => {
+                            let headers = headers
+                                .iter()
+                                .map(|header| {
+                                    let value = String::from_utf8_lossy(header.value);
+                                    (header.name.to_owned(), value.into_owned())
+                                })
+                                .collect();
+                            Some((len, headers))
+                        }
+                        Ok(httparse::Status::Partial) => None,
+                        Err(e) => {
+                            let message = format!("Invalid multipart header fields: {}", e);
+                            return Poll::Ready(Err(malformed(&message)));
+                        }
+                    };
+                    match parsed {
+                        Some((len, headers)) => {
+                            self.buf.drain(..len);
+                            self.state = State::Body;
+                            return Poll::Ready(Ok(Some(headers)));
+                        }
+                        None if self.buf.len() >= MAX_PART_HEAD_LENGTH => {
+                            let message = "Multipart header fields are too long";
+                            return Poll::Ready(Err(malformed(message)));
+                        }
+                        None => ready!(self.poll_fill(cx))?,
+                    }
+                }
+                State::Done => return Poll::Ready(Ok(None)),
+            }
+        }
+    }
+
+    /// The number of bytes at the start of the buffer which belong to the
+    /// current part, or 0 once its delimiter has been reached and consumed.
+    fn poll_body(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        loop {
+            match find(&self.buf, &self.delimiter) {
+                Some(0) => {
+                    self.buf.drain(..self.delimiter.len());
+                    self.state = State::Delimiter;
+                    return Poll::Ready(Ok(0));
+                }
+                Some(len) => return Poll::Ready(Ok(len)),
+                None => {
+                    // The end of the buffer may be the start of a delimiter.
+                    let len = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+                    if len > 0 {
+                        return Poll::Ready(Ok(len));
+                    }
+                    ready!(self.poll_fill(cx))?;
+                }
+            }
+        }
+    }
+
+    /// Read more of the body into the buffer, failing if it has ended.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.eof {
+            return Poll::Ready(Err(malformed(
+                "The multipart body ended before its closing delimiter",
+            )));
+        }
+        let mut chunk = [0; READ_SIZE];
+        let bytes = ready!(Pin::new(&mut self.reader).poll_read(cx, &mut chunk))?;
+        self.eof = bytes == 0;
+        self.buf.extend_from_slice(&chunk[..bytes]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The header fields of a part, in the order they were sent.
+type Headers = Vec<(String, String)>;
+
+/// A part of a [`Multipart`] body, which reads as the part's body.
+#[derive(Debug)]
+pub struct Part<'a, R> {
+    multipart: &'a mut Multipart<R>,
+    headers: Headers,
+}
+
+impl<R> Part<'_, R> {
+    /// The value of the first header field with the given name, compared
+    /// case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// The header fields of the part, in the order they were sent.
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The name of the form field, from the `Content-Disposition` header.
+    pub fn name(&self) -> Option<&str> {
+        disposition_param(self.header("content-disposition")?, "name")
+    }
+
+    /// The name of the uploaded file, from the `Content-Disposition`
+    /// header. It's sent by the client, so it shouldn't be trusted as a
+    /// path.
+    pub fn filename(&self) -> Option<&str> {
+        disposition_param(self.header("content-disposition")?, "filename")
+    }
+
+    /// The media type of the part's body, if it was sent.
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("content-type")
+    }
+}
+
+impl<R: Read + Unpin> Read for Part<'_, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let multipart = &mut *self.get_mut().multipart;
+        if multipart.state != State::Body {
+            return Poll::Ready(Ok(0));
+        }
+        let len = ready!(multipart.poll_body(cx))?.min(buf.len());
+        buf[..len].copy_from_slice(&multipart.buf[..len]);
+        multipart.buf.drain(..len);
+        Poll::Ready(Ok(len))
+    }
+}
+
+/// The value of a parameter of a `Content-Disposition` header, such as
+/// `name` in `form-data; name="field"`.
+///
+/// Browsers percent-encode quotes in quoted values rather than escaping
+/// them with backslashes, which they leave as they are, so the value ends
+/// at the next quote.
+fn disposition_param<'a>(value: &'a str, param: &str) -> Option<&'a str> {
+    let mut rest = value.split_once(';')?.1;
+    loop {
+        let (name, after) = rest.split_once('=')?;
+        let after = after.trim_start();
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, next) = quoted.split_once('"')?;
+                (value, next.split_once(';').map_or("", |(_, next)| next))
+            }
+            None => {
+                let (value, next) = after.split_once(';').unwrap_or((after, ""));
+                (value.trim_end(), next)
+            }
+        };
+        if name.trim().eq_ignore_ascii_case(param) {
+            return Some(value);
+        }
+        rest = next;
+    }
+}
+
+/// The position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn malformed(message: &str) -> io::Error {
+    err_kind!(BodyFraming, "{}", message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disposition_param;
+
+    #[test]
+    fn disposition_params() {
+        let value = r#"form-data; name="field"; filename="C:\a;b.txt""#;
+        assert_eq!(disposition_param(value, "name"), Some("field"));
+        assert_eq!(disposition_param(value, "filename"), Some(r"C:\a;b.txt"));
+        assert_eq!(
+            disposition_param("form-data; NAME=bare ", "name"),
+            Some("bare")
+        );
+        assert_eq!(disposition_param("form-data", "name"), None);
+        assert_eq!(disposition_param(r#"form-data; name="open"#, "name"), None);
+    }
+}
@@ -2,15 +2,21 @@ use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use async_std::io::Read;
+use futures_core::ready;
+use futures_lite::io::{AsyncBufRead as BufRead, AsyncRead as Read, AsyncWrite as Write};
+use http_types::headers::{Headers, TRANSFER_ENCODING};
+use http_types::trailers::{Receiver, Trailers};
 use http_types::Body;
 use pin_project::pin_project;
 
-use crate::chunked::ChunkedEncoder;
+#[cfg(feature = "chunked")]
+use crate::chunked::{ChunkBuffer, ChunkedEncoder};
+use crate::digest::BodyDigest;
 
 #[pin_project(project=BodyEncoderProjection)]
 #[derive(Debug)]
 pub(crate) enum BodyEncoder {
+    #[cfg(feature = "chunked")]
     Chunked(#[pin] ChunkedEncoder<Body>),
     Fixed(#[pin] Body),
 }
@@ -18,10 +24,98 @@ pub(crate) enum BodyEncoder {
 impl BodyEncoder {
     pub(crate) fn new(body: Body) -> Self {
         match body.len() {
-            Some(_) => Self::Fixed(body),
+            #[cfg(feature = "chunked")]
             None => Self::Chunked(ChunkedEncoder::new(body)),
+            // Without chunked encoding, bodies of unknown length are rejected
+            // before we get here.
+            _ => Self::Fixed(body),
         }
     }
+
+    /// Like `new`, but sends trailers and a digest of chunked bodies after
+    /// their last chunk.
+    pub(crate) fn with_trailers(
+        body: Body,
+        trailers: Option<PendingTrailers>,
+        digest: Option<BodyDigest>,
+    ) -> Self {
+        match (Self::new(body), trailers, digest) {
+            #[cfg(feature = "chunked")]
+            (Self::Chunked(mut encoder), trailers, digest) => {
+                if let Some(trailers) = trailers {
+                    encoder = encoder.with_trailers(trailers);
+                }
+                if let Some(digest) = digest {
+                    encoder = encoder.with_digest(digest);
+                }
+                Self::Chunked(encoder)
+            }
+            (encoder, _, _) => encoder,
+        }
+    }
+
+    /// Write the next piece of the encoded body straight from the body's
+    /// buffer to `writer`, returning the number of bytes written, or 0 once
+    /// the body is done.
+    pub(crate) fn poll_write_to<W: Write + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        writer: &mut W,
+    ) -> Poll<io::Result<usize>> {
+        match self {
+            #[cfg(feature = "chunked")]
+            Self::Chunked(encoder) => encoder.poll_write_to(cx, writer),
+            Self::Fixed(body) => {
+                let available = ready!(Pin::new(&mut *body).poll_fill_buf(cx))?;
+                if available.is_empty() {
+                    return Poll::Ready(Ok(0));
+                }
+                let bytes = ready!(Pin::new(writer).poll_write(cx, available))?;
+                if bytes == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                Pin::new(body).consume(bytes);
+                Poll::Ready(Ok(bytes))
+            }
+        }
+    }
+
+    /// The number of chunks written so far, or 0 for fixed-length bodies.
+    pub(crate) fn chunks(&self) -> usize {
+        match self {
+            #[cfg(feature = "chunked")]
+            Self::Chunked(encoder) => encoder.chunks(),
+            Self::Fixed(_) => 0,
+        }
+    }
+}
+
+/// Gather a body of unknown length into chunks of up to `size` bytes, if
+/// there's a size.
+#[cfg(feature = "chunked")]
+pub(crate) fn chunk_buffered(body: Body, size: Option<usize>) -> Body {
+    match (body.len(), size) {
+        (None, Some(size)) => Body::from_reader(ChunkBuffer::new(body, size), None),
+        _ => body,
+    }
+}
+
+/// Trailers to send after the last chunk of a body.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "chunked"), allow(dead_code))]
+pub(crate) enum PendingTrailers {
+    /// The trailers were sent before encoding started.
+    Ready(Trailers),
+    /// The trailers may still be sent.
+    Waiting(Receiver),
+}
+
+/// Whether a message with a body of unknown length says it's chunked
+/// already, so its body can be passed through as it is.
+pub(crate) fn is_chunked(len: Option<usize>, headers: &Headers) -> bool {
+    let transfer_encoding = headers.get(TRANSFER_ENCODING);
+    len.is_none()
+        && transfer_encoding.is_some_and(|te| te.last().as_str().eq_ignore_ascii_case("chunked"))
 }
 
 impl Read for BodyEncoder {
@@ -31,6 +125,7 @@ impl Read for BodyEncoder {
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
         match self.project() {
+            #[cfg(feature = "chunked")]
             BodyEncoderProjection::Chunked(encoder) => encoder.poll_read(cx, buf),
             BodyEncoderProjection::Fixed(body) => body.poll_read(cx, buf),
         }
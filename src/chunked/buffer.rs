@@ -0,0 +1,122 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncBufRead as BufRead, AsyncRead as Read};
+
+/// Gathers a body into chunks of up to a fixed size.
+///
+/// Whatever the body has ready is copied into one buffer, which is reused
+/// for every chunk, until it's full or the body would have to wait. Bodies
+/// produced in small pieces are then sent in fewer, larger chunks, while
+/// bodies with large buffers of their own are sent in chunks no larger than
+/// the buffer. Nothing is held back waiting for more of the body.
+#[derive(Debug)]
+pub(crate) struct ChunkBuffer<R> {
+    reader: R,
+    buf: Box<[u8]>,
+    /// The unconsumed bytes of `buf`.
+    start: usize,
+    end: usize,
+    /// An error from the reader, held back until the bytes gathered before
+    /// it have been consumed.
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead + Unpin> ChunkBuffer<R> {
+    pub(crate) fn new(reader: R, size: usize) -> Self {
+        Self {
+            reader,
+            buf: vec![0; size.max(1)].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            error: None,
+        }
+    }
+}
+
+impl<R: BufRead + Unpin> BufRead for ChunkBuffer<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.start == this.end {
+            if let Some(err) = this.error.take() {
+                return Poll::Ready(Err(err));
+            }
+            this.start = 0;
+            this.end = 0;
+            while this.end < this.buf.len() {
+                let bytes = match Pin::new(&mut this.reader).poll_fill_buf(cx) {
+                    Poll::Ready(Ok(available)) => {
+                        let bytes = available.len().min(this.buf.len() - this.end);
+                        this.buf[this.end..this.end + bytes].copy_from_slice(&available[..bytes]);
+                        bytes
+                    }
+                    // Send what's gathered so far rather than waiting, and
+                    // report an error once it's been sent.
+                    Poll::Ready(Err(err)) if this.end > 0 => {
+                        this.error = Some(err);
+                        break;
+                    }
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending if this.end > 0 => break,
+                    Poll::Pending => return Poll::Pending,
+                };
+                if bytes == 0 {
+                    break;
+                }
+                Pin::new(&mut this.reader).consume(bytes);
+                this.end += bytes;
+            }
+        }
+        Poll::Ready(Ok(&this.buf[this.start..this.end]))
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        self.start = (self.start + amt).min(self.end);
+    }
+}
+
+impl<R: BufRead + Unpin> Read for ChunkBuffer<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let available = futures_core::ready!(self.as_mut().poll_fill_buf(cx))?;
+        let bytes = available.len().min(buf.len());
+        buf[..bytes].copy_from_slice(&available[..bytes]);
+        self.consume(bytes);
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkBuffer;
+    use futures_lite::future::block_on;
+    use futures_lite::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader, Cursor};
+    use std::pin::Pin;
+
+    #[test]
+    fn gathers_small_reads_and_caps_large_ones() {
+        // A one-byte buffer makes the body hand out a byte at a time.
+        let body = BufReader::with_capacity(1, Cursor::new(b"hello world".to_vec()));
+        let mut buffer = ChunkBuffer::new(body, 4);
+        let mut chunks = Vec::new();
+        block_on(async {
+            loop {
+                let chunk = buffer.fill_buf().await.unwrap().to_vec();
+                if chunk.is_empty() {
+                    break;
+                }
+                Pin::new(&mut buffer).consume(chunk.len());
+                chunks.push(String::from_utf8(chunk).unwrap());
+            }
+        });
+        assert_eq!(chunks, ["hell", "o wo", "rld"]);
+
+        let mut read = String::new();
+        let body = Cursor::new(b"hello world".to_vec());
+        block_on(ChunkBuffer::new(body, 4).read_to_string(&mut read)).unwrap();
+        assert_eq!(read, "hello world");
+    }
+}
@@ -3,10 +3,13 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use async_std::io::{self, Read};
 use futures_core::ready;
+use futures_lite::io::{self, AsyncRead as Read};
 use http_types::trailers::{Sender, Trailers};
 
+/// The most bytes of chunk extensions accepted per chunk.
+pub(crate) const MAX_CHUNK_EXTENSIONS_LENGTH: usize = 4096;
+
 /// Decodes a chunked body according to
 /// https://tools.ietf.org/html/rfc7230#section-4.1
 #[derive(Debug)]
@@ -25,17 +28,29 @@ impl<R: Read> ChunkedDecoder<R> {
     pub(crate) fn new(inner: R, trailer_sender: Sender) -> Self {
         ChunkedDecoder {
             inner,
-            state: State::ChunkSize,
+            state: State::ChunkSize(0),
             chunk_size: 0,
             trailer_sender: Some(trailer_sender),
         }
     }
+
+    /// The underlying stream.
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Take back the underlying stream.
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
 }
 
 /// Decoder state.
 enum State {
-    /// Parsing bytes from a chunk size
-    ChunkSize,
+    /// Parsing bytes from a chunk size, having parsed this many digits.
+    ChunkSize(usize),
+    /// Skipping over chunk extensions, having skipped this many bytes.
+    ChunkExtensions(usize),
     /// Expecting the \n at the end of a chunk size
     ChunkSizeExpectLf,
     /// Parsing the chunk body
@@ -55,7 +70,8 @@ enum State {
 impl fmt::Debug for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            State::ChunkSize => write!(f, "State::ChunkSize"),
+            State::ChunkSize(digits) => write!(f, "State::ChunkSize({})", digits),
+            State::ChunkExtensions(len) => write!(f, "State::ChunkExtensions({})", len),
             State::ChunkSizeExpectLf => write!(f, "State::ChunkSizeExpectLf"),
             State::ChunkBody => write!(f, "State::ChunkBody"),
             State::ChunkBodyExpectCr => write!(f, "State::ChunkBodyExpectCr"),
@@ -102,21 +118,25 @@ impl<R: Read + Unpin> ChunkedDecoder<R> {
 }
 
 fn eof<T>() -> Poll<io::Result<T>> {
-    Poll::Ready(Err(io::Error::new(
-        io::ErrorKind::UnexpectedEof,
-        "Unexpected EOF when decoding chunked data",
-    )))
+    Poll::Ready(Err(err_kind!(
+        Io,
+        "Unexpected EOF when decoding chunked data"
+    )
+    .into()))
 }
 
 fn unexpected<T>(byte: u8, expected: &'static str) -> Poll<io::Result<T>> {
-    Poll::Ready(Err(io::Error::new(
-        io::ErrorKind::InvalidData,
-        format!("Unexpected byte {}; expected {}", byte, expected),
-    )))
+    Poll::Ready(Err(err_kind!(
+        BodyFraming,
+        "Unexpected byte {}; expected {}",
+        byte,
+        expected
+    )
+    .into()))
 }
 
 fn overflow() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidData, "Chunk size overflowed 64 bits")
+    err_kind!(BodyFraming, "Chunk size overflowed 64 bits").into()
 }
 
 impl<R: Read + Unpin> Read for ChunkedDecoder<R> {
@@ -130,20 +150,27 @@ impl<R: Read + Unpin> Read for ChunkedDecoder<R> {
 
         loop {
             match this.state {
-                State::ChunkSize => {
+                State::ChunkSize(digits) => {
                     let byte = ready!(this.poll_read_byte(cx))?;
                     let digit = match byte {
                         b'0'..=b'9' => byte - b'0',
                         b'a'..=b'f' => 10 + byte - b'a',
                         b'A'..=b'F' => 10 + byte - b'A',
-                        b'\r' => {
+                        b'\r' if digits > 0 => {
                             this.state = State::ChunkSizeExpectLf;
                             continue;
                         }
-                        _ => {
-                            return unexpected(byte, "hex digit or CR");
+                        // Extensions, possibly after whitespace, are
+                        // allowed and ignored.
+                        // https://www.rfc-editor.org/rfc/rfc9112#section-7.1.1
+                        b';' | b' ' | b'\t' if digits > 0 => {
+                            this.state = State::ChunkExtensions(1);
+                            continue;
                         }
+                        _ if digits == 0 => return unexpected(byte, "hex digit"),
+                        _ => return unexpected(byte, "hex digit, extension or CR"),
                     };
+                    this.state = State::ChunkSize(digits + 1);
                     this.chunk_size = this
                         .chunk_size
                         .checked_mul(16)
@@ -151,6 +178,22 @@ impl<R: Read + Unpin> Read for ChunkedDecoder<R> {
                         .checked_add(digit as u64)
                         .ok_or_else(overflow)?;
                 }
+                State::ChunkExtensions(len) => {
+                    let byte = ready!(this.poll_read_byte(cx))?;
+                    match byte {
+                        b'\r' => this.state = State::ChunkSizeExpectLf,
+                        b'\n' => return unexpected(byte, "CR"),
+                        _ if len == MAX_CHUNK_EXTENSIONS_LENGTH => {
+                            return Poll::Ready(Err(err_kind!(
+                                BodyFraming,
+                                "Chunk extensions are longer than {} bytes",
+                                MAX_CHUNK_EXTENSIONS_LENGTH
+                            )
+                            .into()))
+                        }
+                        _ => this.state = State::ChunkExtensions(len + 1),
+                    }
+                }
                 State::ChunkSizeExpectLf => {
                     ready!(this.expect_byte(cx, b'\n', "LF"))?;
                     if this.chunk_size == 0 {
@@ -180,49 +223,50 @@ impl<R: Read + Unpin> Read for ChunkedDecoder<R> {
                 }
                 State::ChunkBodyExpectLf => {
                     ready!(this.expect_byte(cx, b'\n', "LF"))?;
-                    this.state = State::ChunkSize;
+                    this.state = State::ChunkSize(0);
                 }
                 State::Trailers(ref mut len, ref mut buf) => {
-                    let bytes_read =
-                        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut buf[*len..]))?;
-                    *len += bytes_read;
-                    let len = *len;
-                    if len == 0 {
+                    // Read a byte at a time up to the empty line ending the
+                    // trailers, so nothing past the body is consumed.
+                    let mut byte = [0u8];
+                    let bytes_read = ready!(Pin::new(&mut this.inner).poll_read(cx, &mut byte))?;
+                    if bytes_read == 0 && *len == 0 {
+                        // Tolerate a body which ends without the final empty line.
                         this.send_trailers(Trailers::new());
                         continue;
-                    }
-                    if bytes_read == 0 {
+                    } else if bytes_read == 0 {
                         return eof();
                     }
+                    if *len == buf.len() {
+                        return Poll::Ready(Err(err_kind!(
+                            BodyFraming,
+                            "Trailers are longer than {} bytes",
+                            buf.len()
+                        )
+                        .into()));
+                    }
+                    buf[*len] = byte[0];
+                    *len += 1;
+                    let read = &buf[..*len];
+                    if read != b"\r\n" && !read.ends_with(b"\r\n\r\n") {
+                        continue;
+                    }
                     let mut headers = [httparse::EMPTY_HEADER; 16];
-                    let parse_result = httparse::parse_headers(&buf[..len], &mut headers)
-                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                    use httparse::Status;
-                    match parse_result {
-                        Status::Partial => {
-                            if len == buf.len() {
-                                return eof();
-                            } else {
-                                return Poll::Pending;
-                            }
-                        }
-                        Status::Complete((offset, headers)) => {
-                            if offset != len {
-                                return unexpected(buf[offset], "end of trailers");
-                            }
-                            let mut trailers = Trailers::new();
-                            for header in headers {
-                                trailers.insert(
-                                    header.name,
-                                    String::from_utf8_lossy(header.value).as_ref(),
-                                );
-                            }
-                            this.send_trailers(trailers);
-                        }
+                    let parse_result = httparse::parse_headers(read, &mut headers)
+                        .map_err(|e| io::Error::from(err_kind!(BodyFraming, "{}", e)))?;
+                    let headers = match parse_result {
+                        httparse::Status::Complete((_, headers)) => headers,
+                        httparse::Status::Partial => return unexpected(byte[0], "end of trailers"),
+                    };
+                    let mut trailers = Trailers::new();
+                    for header in headers {
+                        trailers
+                            .append(header.name, String::from_utf8_lossy(header.value).as_ref());
                     }
+                    this.send_trailers(trailers);
                 }
                 State::TrailerSending(ref mut fut) => {
-                    let _ = ready!(Pin::new(fut).poll(cx));
+                    ready!(Pin::new(fut).poll(cx));
                     this.state = State::Done;
                 }
                 State::Done => return Poll::Ready(Ok(0)),
@@ -343,4 +387,56 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn trailers_arriving_in_pieces() {
+        async_std::task::block_on(async move {
+            let input = b"3\r\nabc\r\n0\r\nx-sum: 1\r\nx-sum: 2\r\n\r\nHTTP/1.1";
+            // A one-byte buffer makes every read return a single byte.
+            let reader = futures_lite::io::BufReader::with_capacity(1, &input[..]);
+            let (s, r) = async_channel::bounded(1);
+            let mut decoder = ChunkedDecoder::new(reader, Sender::new(s));
+
+            let mut output = String::new();
+            decoder.read_to_string(&mut output).await.unwrap();
+            assert_eq!(output, "abc");
+
+            let trailers = r.recv().await.unwrap();
+            let sums: Vec<_> = trailers["x-sum"].iter().map(|v| v.as_str()).collect();
+            assert_eq!(sums, ["1", "2"]);
+
+            // Nothing past the trailers is consumed.
+            let mut rest = String::new();
+            decoder.inner.read_to_string(&mut rest).await.unwrap();
+            assert_eq!(rest, "HTTP/1.1");
+        });
+    }
+
+    #[test]
+    fn chunk_extensions_are_ignored() {
+        async_std::task::block_on(async move {
+            let input = b"5;name=value\r\nhello\r\n6 ; a=\"b;c\"\r\n world\r\n0;last\r\n\r\n";
+            let (s, _r) = async_channel::bounded(1);
+            let mut decoder = ChunkedDecoder::new(&input[..], Sender::new(s));
+            let mut output = String::new();
+            decoder.read_to_string(&mut output).await.unwrap();
+            assert_eq!(output, "hello world");
+        });
+    }
+
+    #[test]
+    fn chunk_sizes_need_digits() {
+        async_std::task::block_on(async move {
+            for input in [
+                &b"\r\n\r\n"[..],
+                b";ext\r\n\r\n",
+                b"5;ext\nhello\r\n0\r\n\r\n",
+            ] {
+                let (s, _r) = async_channel::bounded(1);
+                let mut decoder = ChunkedDecoder::new(input, Sender::new(s));
+                let mut output = String::new();
+                assert!(decoder.read_to_string(&mut output).await.is_err());
+            }
+        });
+    }
 }
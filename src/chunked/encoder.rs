@@ -1,118 +1,436 @@
+use std::future::Future;
+use std::io::IoSlice;
 use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use async_std::io;
-use async_std::io::prelude::*;
-use async_std::task::{Context, Poll};
 use futures_core::ready;
+use futures_lite::io::{
+    self, AsyncBufRead as BufRead, AsyncRead as Read, AsyncWrite as Write, Cursor,
+};
+use http_types::trailers::{Receiver, Trailers};
+
+use crate::body_encoder::PendingTrailers;
+use crate::digest::BodyDigest;
 
 /// An encoder for chunked encoding.
 #[derive(Debug)]
 pub(crate) struct ChunkedEncoder<R> {
     reader: R,
-    done: bool,
+    /// Digest of the body, sent as a trailer.
+    digest: Option<BodyDigest>,
+    /// Trailers to send after the last chunk.
+    trailers: Option<PendingTrailers>,
+    state: State,
+    /// The number of chunks of body data written so far.
+    chunks: usize,
+    /// The chunk being read or written.
+    frame: Option<Frame>,
+}
+
+/// Progress emitting a chunk straight from the reader's buffer.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    /// The chunk size line, formatted once when the chunk is started.
+    head: [u8; 18],
+    /// The length of the chunk size line.
+    head_len: usize,
+    /// The number of bytes of data in the chunk.
+    size: usize,
+    /// Bytes of the chunk emitted so far, including its framing.
+    written: usize,
 }
 
-impl<R: Read + Unpin> ChunkedEncoder<R> {
+impl Frame {
+    /// Start a chunk of `size` bytes of data.
+    fn new(size: usize) -> Self {
+        let mut head = [0; 18];
+        let mut cursor = std::io::Cursor::new(&mut head[..]);
+        std::io::Write::write_fmt(&mut cursor, format_args!("{:X}\r\n", size))
+            .expect("a chunk size line fits in 18 bytes");
+        let head_len = cursor.position() as usize;
+        Self {
+            head,
+            head_len,
+            size,
+            written: 0,
+        }
+    }
+
+    /// The length of the whole chunk, including its framing.
+    fn len(&self) -> usize {
+        self.head_len + self.size + 2
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    /// Encoding chunks of the body.
+    Body,
+    /// Waiting for the trailers to be sent.
+    Trailers(Receiver),
+    /// Writing the last chunk and the trailers.
+    Last(Cursor<Vec<u8>>),
+}
+
+impl<R: BufRead + Unpin> ChunkedEncoder<R> {
     /// Create a new instance.
     pub(crate) fn new(reader: R) -> Self {
         Self {
             reader,
-            done: false,
+            digest: None,
+            trailers: None,
+            state: State::Body,
+            chunks: 0,
+            frame: None,
         }
     }
+
+    /// Compute a digest of the body and send it as a `Content-Digest`
+    /// trailer.
+    pub(crate) fn with_digest(mut self, digest: BodyDigest) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    /// Send these trailers after the last chunk.
+    pub(crate) fn with_trailers(mut self, trailers: PendingTrailers) -> Self {
+        self.trailers = Some(trailers);
+        self
+    }
+
+    /// The number of chunks of body data written so far, not counting the
+    /// last, empty chunk.
+    pub(crate) fn chunks(&self) -> usize {
+        self.chunks
+    }
+
+    /// Move on from the body once it has ended, waiting for the trailers if
+    /// they haven't been sent yet.
+    fn end_body(&mut self) {
+        self.state = match self.trailers.take() {
+            Some(PendingTrailers::Waiting(receiver)) => State::Trailers(receiver),
+            Some(PendingTrailers::Ready(trailers)) => {
+                State::Last(Cursor::new(self.last_chunk(Some(trailers))))
+            }
+            None => State::Last(Cursor::new(self.last_chunk(None))),
+        };
+    }
+
+    /// Wait for the trailers, if we're waiting for them.
+    fn poll_trailers(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let State::Trailers(receiver) = &mut self.state {
+            let trailers = ready!(Pin::new(receiver).poll(cx));
+            self.state = State::Last(Cursor::new(self.last_chunk(trailers)));
+        }
+        Poll::Ready(())
+    }
+
+    /// The last chunk followed by the trailers.
+    fn last_chunk(&mut self, trailers: Option<Trailers>) -> Vec<u8> {
+        let mut last = b"0\r\n".to_vec();
+        for (name, values) in trailers.iter().flat_map(|trailers| trailers.iter()) {
+            for value in values.iter() {
+                last.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+            }
+        }
+        if let Some(digest) = self.digest.take() {
+            last.extend_from_slice(format!("content-digest: {}\r\n", digest.finish()).as_bytes());
+        }
+        last.extend_from_slice(b"\r\n");
+        last
+    }
 }
 
-impl<R: Read + Unpin> Read for ChunkedEncoder<R> {
+impl<R: BufRead + Unpin> ChunkedEncoder<R> {
+    /// Write the next piece of the encoded body to `writer`, returning the
+    /// number of bytes written, or 0 once the body is done.
+    ///
+    /// Each chunk is written with a vectored write of its size line, the
+    /// data in the reader's buffer, and the CRLF ending it, rather than
+    /// copying them together first.
+    pub(crate) fn poll_write_to<W: Write + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        writer: &mut W,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            ready!(self.poll_trailers(cx));
+            if let State::Last(cursor) = &mut self.state {
+                let position = cursor.position() as usize;
+                let remaining = &cursor.get_ref()[position..];
+                if remaining.is_empty() {
+                    return Poll::Ready(Ok(0));
+                }
+                let bytes = ready!(Pin::new(&mut *writer).poll_write(cx, remaining))?;
+                if bytes == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                cursor.set_position((position + bytes) as u64);
+                return Poll::Ready(Ok(bytes));
+            }
+
+            let written = self.poll_chunk(cx, usize::MAX, |cx, slices| {
+                Pin::new(&mut *writer).poll_write_vectored(cx, slices)
+            });
+            if let Some(bytes) = ready!(written)? {
+                return Poll::Ready(Ok(bytes));
+            }
+        }
+    }
+
+    /// Hand the rest of the current chunk to `emit`, starting the next
+    /// chunk of at most `limit` bytes from the reader's buffer if there's
+    /// none, and return how many bytes `emit` took, or `None` once the body
+    /// has ended.
+    ///
+    /// The data is only consumed from the reader once it's been taken, so a
+    /// chunk may be emitted a few bytes at a time over many calls without
+    /// copying it anywhere first.
+    fn poll_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+        limit: usize,
+        emit: impl FnOnce(&mut Context<'_>, &[IoSlice<'_>]) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<Option<usize>>> {
+        if self.frame.is_none() {
+            let available = ready!(Pin::new(&mut self.reader).poll_fill_buf(cx))?;
+            if available.is_empty() {
+                self.end_body();
+                return Poll::Ready(Ok(None));
+            }
+            let size = available.len().min(limit);
+            if let Some(digest) = &mut self.digest {
+                digest.update(&available[..size]);
+            }
+            self.frame = Some(Frame::new(size));
+        }
+        let mut frame = self.frame.unwrap();
+        let head_written = frame.written.min(frame.head_len);
+        let data_written = (frame.written - head_written).min(frame.size);
+        let tail_written = frame.written - head_written - data_written;
+
+        // The reader hands out the same unconsumed bytes until they're
+        // consumed, so the data is only consumed once it's been emitted.
+        let data = match frame.size - data_written {
+            0 => &[][..],
+            remaining => {
+                let available = ready!(Pin::new(&mut self.reader).poll_fill_buf(cx))?;
+                &available[..remaining.min(available.len())]
+            }
+        };
+        let slices = [
+            IoSlice::new(&frame.head[head_written..frame.head_len]),
+            IoSlice::new(data),
+            IoSlice::new(&b"\r\n"[tail_written..]),
+        ];
+        let emitted = ready!(emit(cx, &slices))?;
+        if emitted == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+
+        let data_bytes = emitted
+            .saturating_sub(frame.head_len - head_written)
+            .min(data.len());
+        frame.written += emitted;
+        if frame.written == frame.len() {
+            self.frame = None;
+            self.chunks += 1;
+        } else {
+            self.frame = Some(frame);
+        }
+        Pin::new(&mut self.reader).consume(data_bytes);
+        Poll::Ready(Ok(Some(emitted)))
+    }
+}
+
+impl<R: BufRead + Unpin> Read for ChunkedEncoder<R> {
     fn poll_read(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
-        if self.done {
+        if buf.is_empty() {
             return Poll::Ready(Ok(0));
         }
-        let reader = &mut self.reader;
+        let this = &mut *self;
+        loop {
+            ready!(this.poll_trailers(cx));
+            if let State::Last(cursor) = &mut this.state {
+                return Pin::new(cursor).poll_read(cx, buf);
+            }
 
-        let max_bytes_to_read = max_bytes_to_read(buf.len());
-
-        let bytes = ready!(Pin::new(reader).poll_read(cx, &mut buf[..max_bytes_to_read]))?;
-        if bytes == 0 {
-            self.done = true;
+            // Chunks are cut to fit in `buf`, unless it's too small to hold
+            // one at all, in which case they carry on in the next read.
+            let limit = max_chunk_size(buf.len()).unwrap_or(usize::MAX);
+            let copied =
+                this.poll_chunk(cx, limit, |_, slices| Poll::Ready(Ok(copy_to(slices, buf))));
+            if let Some(bytes) = ready!(copied)? {
+                return Poll::Ready(Ok(bytes));
+            }
         }
-        let start = format!("{:X}\r\n", bytes);
-        let start_length = start.as_bytes().len();
-        let total = bytes + start_length + 2;
-        buf.copy_within(..bytes, start_length);
-        buf[..start_length].copy_from_slice(start.as_bytes());
-        buf[total - 2..total].copy_from_slice(b"\r\n");
-        Poll::Ready(Ok(total))
     }
 }
 
-fn max_bytes_to_read(buf_len: usize) -> usize {
-    if buf_len < 6 {
-        // the minimum read size is of 6 represents one byte of
-        // content from the body. the other five bytes are 1\r\n_\r\n
-        // where _ is the actual content in question
-        panic!("buffers of length {} are too small for this implementation. if this is a problem for you, please open an issue", buf_len);
+/// The most data a whole chunk read into a buffer of `buf_len` bytes can
+/// hold, if the buffer can hold a chunk at all.
+fn max_chunk_size(buf_len: usize) -> Option<usize> {
+    // A chunk of `size` bytes takes its hex digits and two CRLFs besides.
+    let framed = |size: usize| size + hex_digits(size) + 4;
+    let mut size = buf_len.checked_sub(5).filter(|size| *size > 0)?;
+    while framed(size) > buf_len {
+        size -= 1;
     }
+    Some(size)
+}
 
-    let bytes_remaining_after_two_cr_lns = (buf_len - 4) as f64;
-
-    // the maximum number of bytes that the hex representation of remaining bytes might take
-    let max_bytes_of_hex_framing = bytes_remaining_after_two_cr_lns.log2() / 4f64;
+/// The number of hex digits in `n`.
+fn hex_digits(n: usize) -> usize {
+    let bits = (usize::BITS - n.leading_zeros()) as usize;
+    bits.div_ceil(4).max(1)
+}
 
-    (bytes_remaining_after_two_cr_lns - max_bytes_of_hex_framing.ceil()) as usize
+/// Copy as much of `slices` into `buf` as fits, returning the number of
+/// bytes copied.
+fn copy_to(slices: &[IoSlice<'_>], buf: &mut [u8]) -> usize {
+    let mut copied = 0;
+    for slice in slices {
+        let bytes = slice.len().min(buf.len() - copied);
+        buf[copied..copied + bytes].copy_from_slice(&slice[..bytes]);
+        copied += bytes;
+    }
+    copied
 }
 
 #[cfg(test)]
-mod test_bytes_to_read {
+mod test_max_chunk_size {
     #[test]
     fn simple_check_of_known_values() {
-        // the marked rows are the most important part of this test,
-        // and a nonobvious but intentional consequence of the
-        // implementation. in order to avoid overflowing, we must use
-        // one fewer than the available buffer bytes because
-        // increasing the read size increase the number of framed
-        // bytes by two. This occurs when the hex representation of
-        // the content bytes is near an increase in order of magnitude
-        // (F->10, FF->100, FFF-> 1000, etc)
+        // Just below an increase in the number of hex digits (F->10,
+        // FF->100, ...), one byte of the buffer is left over, because one
+        // more byte of data would take one more byte of framing as well.
         let values = vec![
-            (6, 1),       // 1
-            (7, 2),       // 2
-            (20, 15),     // F
-            (21, 15),     // F <-
-            (22, 16),     // 10
-            (23, 17),     // 11
-            (260, 254),   // FE
-            (261, 254),   // FE <-
-            (262, 255),   // FF <-
-            (263, 256),   // 100
-            (4100, 4093), // FFD
-            (4101, 4093), // FFD <-
-            (4102, 4094), // FFE <-
-            (4103, 4095), // FFF <-
-            (4104, 4096), // 1000
+            (5, None),
+            (6, Some(1)),       // 1
+            (7, Some(2)),       // 2
+            (20, Some(15)),     // F
+            (21, Some(15)),     // F <-
+            (22, Some(16)),     // 10
+            (23, Some(17)),     // 11
+            (260, Some(254)),   // FE
+            (261, Some(255)),   // FF
+            (262, Some(255)),   // FF <-
+            (263, Some(256)),   // 100
+            (4100, Some(4093)), // FFD
+            (4101, Some(4094)), // FFE
+            (4102, Some(4095)), // FFF
+            (4103, Some(4095)), // FFF <-
+            (4104, Some(4096)), // 1000
         ];
 
         for (input, expected) in values {
-            let actual = super::max_bytes_to_read(input);
-            assert_eq!(
-                actual, expected,
-                "\n\nexpected max_bytes_to_read({}) to be {}, but it was {}",
-                input, expected, actual
-            );
-
-            // testing the test:
-            let used_bytes = expected + 4 + format!("{:X}", expected).len();
-            assert!(
-                used_bytes == input || used_bytes == input - 1,
-                "\n\nfor an input of {}, expected used bytes to be {} or {}, but was {}",
-                input,
-                input,
-                input - 1,
-                used_bytes
-            );
+            assert_eq!(super::max_chunk_size(input), expected, "{}", input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_read {
+    use futures_lite::future;
+    use futures_lite::io::{AsyncReadExt, BufReader, Cursor};
+
+    use super::ChunkedEncoder;
+
+    /// Read the whole encoding `buf_len` bytes at a time.
+    fn encode(body: &[u8], capacity: usize, buf_len: usize) -> Vec<u8> {
+        let reader = BufReader::with_capacity(capacity, Cursor::new(body.to_vec()));
+        let mut encoder = ChunkedEncoder::new(reader);
+        let mut encoded = Vec::new();
+        let mut buf = vec![0; buf_len];
+        future::block_on(async {
+            loop {
+                let bytes = encoder.read(&mut buf).await.unwrap();
+                if bytes == 0 {
+                    break;
+                }
+                encoded.extend_from_slice(&buf[..bytes]);
+            }
+        });
+        encoded
+    }
+
+    #[test]
+    fn chunks_carry_on_across_small_reads() {
+        let expected = b"4\r\nhell\r\n4\r\no wo\r\n3\r\nrld\r\n0\r\n\r\n";
+        for buf_len in 1..6 {
+            assert_eq!(encode(b"hello world", 4, buf_len), expected, "{}", buf_len);
+        }
+    }
+
+    #[test]
+    fn chunks_are_cut_to_fit_reads() {
+        assert_eq!(
+            encode(b"hello world", 16, 8),
+            b"3\r\nhel\r\n3\r\nlo \r\n3\r\nwor\r\n2\r\nld\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn chunks_fill_large_reads() {
+        let body = vec![b'x'; 300];
+        let mut expected = b"12C\r\n".to_vec();
+        expected.extend_from_slice(&body);
+        expected.extend_from_slice(b"\r\n0\r\n\r\n");
+        assert_eq!(encode(&body, 1024, 1024), expected);
+    }
+}
+
+#[cfg(test)]
+mod test_write_to {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_lite::future;
+    use futures_lite::io::{self, AsyncWrite, BufReader, Cursor};
+
+    use super::ChunkedEncoder;
+
+    /// Accepts at most three bytes per write.
+    #[derive(Default)]
+    struct Trickle(Vec<u8>);
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let bytes = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..bytes]);
+            Poll::Ready(Ok(bytes))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
         }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn writes_chunks_across_partial_writes() {
+        let reader = BufReader::with_capacity(4, Cursor::new(b"hello world"));
+        let mut encoder = ChunkedEncoder::new(reader);
+        let mut writer = Trickle::default();
+        future::block_on(async {
+            while future::poll_fn(|cx| encoder.poll_write_to(cx, &mut writer))
+                .await
+                .unwrap()
+                > 0
+            {}
+        });
+        assert_eq!(writer.0, b"4\r\nhell\r\n4\r\no wo\r\n3\r\nrld\r\n0\r\n\r\n");
+        assert_eq!(encoder.chunks(), 3);
     }
 }
@@ -1,5 +1,9 @@
+mod buffer;
 mod decoder;
 mod encoder;
+mod passthrough;
 
+pub(crate) use buffer::ChunkBuffer;
 pub(crate) use decoder::ChunkedDecoder;
 pub(crate) use encoder::ChunkedEncoder;
+pub(crate) use passthrough::ChunkedPassthrough;
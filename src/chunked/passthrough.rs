@@ -0,0 +1,235 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::ready;
+use futures_lite::io::{self, AsyncBufRead as BufRead, AsyncRead as Read};
+
+use super::decoder::MAX_CHUNK_EXTENSIONS_LENGTH;
+
+/// The most bytes of trailers accepted, matching the decoder.
+const MAX_TRAILERS_LENGTH: usize = 8192;
+
+/// Reads a chunked body without decoding it, so it can be forwarded with its
+/// chunk boundaries and trailers intact.
+///
+/// The framing is checked as the bytes go by, the same way
+/// [`ChunkedDecoder`](super::ChunkedDecoder) checks it, and reading stops
+/// after the last chunk and the trailers.
+#[derive(Debug)]
+pub struct ChunkedPassthrough<R> {
+    reader: R,
+    state: State,
+    /// Bytes of trailers read so far.
+    trailers_length: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Expecting the first digit of a chunk size.
+    SizeStart,
+    /// Parsing a chunk size.
+    Size(u64),
+    /// Skipping over the extensions of a chunk of this size, having skipped
+    /// this many bytes.
+    Extensions(u64, usize),
+    /// Expecting the LF ending a chunk size.
+    SizeLf(u64),
+    /// Bytes left in the chunk being read.
+    Data(u64),
+    /// Expecting the CR ending a chunk.
+    DataCr,
+    /// Expecting the LF ending a chunk.
+    DataLf,
+    /// At the start of a trailer line, or of the empty line ending them.
+    TrailerStart,
+    /// In a trailer line.
+    Trailer,
+    /// Expecting the LF ending a trailer line.
+    TrailerLf,
+    /// Expecting the LF ending the body.
+    EndLf,
+    /// The whole body has been read.
+    Done,
+}
+
+impl<R: BufRead + Unpin> ChunkedPassthrough<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: State::SizeStart,
+            trailers_length: 0,
+        }
+    }
+
+    /// The underlying stream.
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Take back the underlying stream.
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead + Unpin> Read for ChunkedPassthrough<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.state == State::Done || buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let available = ready!(Pin::new(&mut this.reader).poll_fill_buf(cx))?;
+        if available.is_empty() {
+            return Poll::Ready(Err(err_kind!(
+                Io,
+                "Unexpected EOF when reading chunked data"
+            )
+            .into()));
+        }
+
+        // Find how many of the available bytes belong to the body, skipping
+        // over chunk data rather than looking at it byte by byte.
+        let limit = available.len().min(buf.len());
+        let mut bytes = 0;
+        while bytes < limit && this.state != State::Done {
+            this.state = match this.state {
+                State::Data(remaining) => {
+                    let skipped = remaining.min((limit - bytes) as u64);
+                    bytes += skipped as usize;
+                    match remaining - skipped {
+                        0 => State::DataCr,
+                        remaining => State::Data(remaining),
+                    }
+                }
+                state => {
+                    bytes += 1;
+                    step(state, available[bytes - 1], &mut this.trailers_length)?
+                }
+            };
+        }
+
+        buf[..bytes].copy_from_slice(&available[..bytes]);
+        Pin::new(&mut this.reader).consume(bytes);
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+/// Advance past one byte of framing.
+fn step(state: State, byte: u8, trailers_length: &mut usize) -> io::Result<State> {
+    Ok(match (state, byte) {
+        (State::Size(size), b'\r') => State::SizeLf(size),
+        (State::Size(size), b';' | b' ' | b'\t') => State::Extensions(size, 1),
+        (State::Extensions(size, _), b'\r') => State::SizeLf(size),
+        (State::Extensions(_, _), b'\n') => return Err(unexpected(byte, "CR")),
+        (State::Extensions(_, len), _) if len == MAX_CHUNK_EXTENSIONS_LENGTH => {
+            return Err(err_kind!(
+                BodyFraming,
+                "Chunk extensions are longer than {} bytes",
+                MAX_CHUNK_EXTENSIONS_LENGTH
+            )
+            .into())
+        }
+        (State::Extensions(size, len), _) => State::Extensions(size, len + 1),
+        (State::SizeStart, _) => State::Size(u64::from(hex_digit(byte, "hex digit")?)),
+        (State::Size(size), _) => {
+            let digit = hex_digit(byte, "hex digit, extension or CR")?;
+            let size = size
+                .checked_mul(16)
+                .and_then(|size| size.checked_add(u64::from(digit)))
+                .ok_or_else(|| err_kind!(BodyFraming, "Chunk size overflowed 64 bits"))?;
+            State::Size(size)
+        }
+        (State::SizeLf(0), b'\n') => State::TrailerStart,
+        (State::SizeLf(size), b'\n') => State::Data(size),
+        (State::DataCr, b'\r') => State::DataLf,
+        (State::DataLf, b'\n') => State::SizeStart,
+        (State::TrailerStart, b'\r') => State::EndLf,
+        (State::TrailerStart, _) | (State::Trailer, _) => {
+            *trailers_length += 1;
+            if *trailers_length > MAX_TRAILERS_LENGTH {
+                return Err(err_kind!(
+                    BodyFraming,
+                    "Trailers are longer than {} bytes",
+                    MAX_TRAILERS_LENGTH
+                )
+                .into());
+            }
+            if byte == b'\r' {
+                State::TrailerLf
+            } else {
+                State::Trailer
+            }
+        }
+        (State::TrailerLf, b'\n') => State::TrailerStart,
+        (State::EndLf, b'\n') => State::Done,
+        (_, byte) => return Err(unexpected(byte, "CR or LF")),
+    })
+}
+
+fn hex_digit(byte: u8, expected: &'static str) -> io::Result<u8> {
+    match byte {
+        b'0'..=b'9' => Ok(byte - b'0'),
+        b'a'..=b'f' => Ok(10 + byte - b'a'),
+        b'A'..=b'F' => Ok(10 + byte - b'A'),
+        _ => Err(unexpected(byte, expected)),
+    }
+}
+
+fn unexpected(byte: u8, expected: &'static str) -> io::Error {
+    err_kind!(
+        BodyFraming,
+        "Unexpected byte {}; expected {}",
+        byte,
+        expected
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkedPassthrough;
+    use futures_lite::future::block_on;
+    use futures_lite::io::{AsyncReadExt, BufReader, Cursor};
+
+    fn pass(input: &[u8], capacity: usize) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+        let mut reader = BufReader::with_capacity(capacity, Cursor::new(input.to_vec()));
+        let mut body = Vec::new();
+        block_on(ChunkedPassthrough::new(&mut reader).read_to_end(&mut body))?;
+        let mut rest = Vec::new();
+        block_on(reader.read_to_end(&mut rest))?;
+        Ok((body, rest))
+    }
+
+    #[test]
+    fn stops_after_trailers() {
+        let body = b"5\r\nhello\r\n3\r\n wo\r\n0\r\nx-sum: 1\r\n\r\n";
+        for capacity in [1, 3, 7, 1024] {
+            let mut input = body.to_vec();
+            input.extend_from_slice(b"GET / HTTP/1.1");
+            let (passed, rest) = pass(&input, capacity).unwrap();
+            assert_eq!(passed, body);
+            assert_eq!(rest, b"GET / HTTP/1.1");
+        }
+    }
+
+    #[test]
+    fn passes_extensions_through() {
+        let body = b"5;name=value\r\nhello\r\n0 ;last\r\n\r\n";
+        let (passed, rest) = pass(body, 3).unwrap();
+        assert_eq!(passed, body);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_framing() {
+        assert!(pass(b"5\r\nhello0\r\n\r\n", 1024).is_err());
+        assert!(pass(b"z\r\n", 1024).is_err());
+        assert!(pass(b"5\r\nhel", 1024).is_err());
+        assert!(pass(b"\r\n\r\n", 1024).is_err());
+    }
+}
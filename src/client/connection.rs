@@ -0,0 +1,155 @@
+//! Send several requests over one connection.
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write, BufReader};
+use http_types::headers::CONNECTION;
+use http_types::{Body, Method, Request, Response};
+
+use super::decode::{self, Framed};
+use super::pool::Hints;
+use super::{ClientOptions, Exchange};
+use crate::copy::copy;
+use crate::error::TimeoutPhase;
+use crate::has_connection_option;
+use crate::timer::TimedStream;
+
+/// The body of the last response, shared between the response and the
+/// connection, which takes it back to discard what's left of it before
+/// sending the next request.
+type Slot<RW> = Arc<Mutex<Option<Framed<RW>>>>;
+
+/// A keep-alive connection which requests are sent over one after another,
+/// rather than one per connection as with [`connect`](super::connect).
+///
+/// Whatever's left of a response body when the next request is sent is
+/// read and discarded first, so responses may be dropped without reading
+/// them. Reading one after the next request has been sent fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::client::Connection;
+/// use async_std::net::TcpStream;
+/// use http_types::{Method, Request, Url};
+///
+/// # async fn run() -> http_types::Result<()> {
+/// let stream = TcpStream::connect("example.com:80").await?;
+/// let mut connection = Connection::new(stream);
+/// for path in ["/", "/about"] {
+///     let url = Url::parse("http://example.com/")?.join(path)?;
+///     let mut res = connection.send(Request::new(Method::Get, url)).await?;
+///     println!("{}", res.body_string().await?);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Connection<RW: Read + Unpin> {
+    opts: ClientOptions,
+    /// The stream, while no response body is being read from it.
+    reader: Option<BufReader<RW>>,
+    /// The body of the last response.
+    body: Option<Slot<RW>>,
+    /// Whether another request may be sent.
+    reusable: bool,
+}
+
+impl<RW> Connection<RW>
+where
+    RW: Read + Write + Send + Sync + Unpin + 'static,
+{
+    /// Create a new instance with the default options.
+    pub fn new(stream: RW) -> Self {
+        Self::with_opts(stream, Default::default())
+    }
+
+    /// Create a new instance with these options.
+    pub fn with_opts(stream: RW, opts: ClientOptions) -> Self {
+        Self {
+            opts,
+            reader: Some(BufReader::new(stream)),
+            body: None,
+            reusable: true,
+        }
+    }
+
+    /// Whether another request may be sent, which isn't the case once a
+    /// request or response has said the connection closes after it, or an
+    /// exchange has failed.
+    pub fn is_reusable(&self) -> bool {
+        self.reusable
+    }
+
+    /// Send a request, and return its response once the head has arrived.
+    pub async fn send(&mut self, req: Request) -> http_types::Result<Response> {
+        http_types::ensure!(self.reusable, "The connection can't be reused");
+        // Until the exchange is done, the connection can't be trusted.
+        self.reusable = false;
+        let mut reader = self.reclaim().await?;
+
+        let closes = has_connection_option(req.header(CONNECTION), "close");
+        let mut exchange = Exchange::start(req, &self.opts);
+        let method = exchange.method;
+        exchange.write_to(reader.get_mut(), &self.opts).await?;
+
+        let mut res = decode::decode_head(&mut reader, &self.opts).await?;
+        let status = res.status();
+        let tunnel = method == Method::Connect && status.is_success();
+        let framed = decode::frame_body(reader, &mut res, method, &self.opts)?;
+        let len = framed.len();
+        let slot = Arc::new(Mutex::new(Some(framed)));
+        if len != Some(0) {
+            let body = SharedBody(slot.clone());
+            let body = TimedStream::new(body, self.opts.body_timeout, TimeoutPhase::Body);
+            let body = BufReader::new(body);
+            res.set_body(Body::from_reader(body, len));
+        }
+        self.body = Some(slot);
+        self.reusable = !closes && !tunnel && Hints::of(&res).reusable;
+
+        Ok(exchange.finish(res, &self.opts))
+    }
+
+    /// Take back the stream, discarding what's left of the last response
+    /// body.
+    async fn reclaim(&mut self) -> io::Result<BufReader<RW>> {
+        if let Some(reader) = self.reader.take() {
+            return Ok(reader);
+        }
+        let framed = self
+            .body
+            .take()
+            .and_then(|slot| slot.lock().unwrap().take());
+        match framed {
+            Some(mut framed) => {
+                let timeout = self.opts.body_timeout;
+                let mut unread = TimedStream::new(&mut framed, timeout, TimeoutPhase::Body);
+                let discarded = copy(&mut unread, &mut io::sink(), self.opts.poll_budget).await?;
+                trace!("discarded {} unread response body bytes", discarded);
+                Ok(framed.into_inner())
+            }
+            None => Err(io::Error::other("The connection was lost")),
+        }
+    }
+}
+
+/// Reads a response body out of its slot, for as long as the connection
+/// hasn't taken it back.
+struct SharedBody<RW: Read + Unpin>(Slot<RW>);
+
+impl<RW: Read + Unpin> Read for SharedBody<RW> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut *self.0.lock().unwrap() {
+            Some(framed) => Pin::new(framed).poll_read(cx, buf),
+            None => Poll::Ready(Err(io::Error::other(
+                "The response body was discarded when the next request was sent",
+            ))),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Open TCP connections to an origin.
+
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_io::Async;
+
+use super::{PoolKey, Resolve, SystemResolver};
+use crate::error::{Error, TimeoutPhase};
+use crate::timer::timeout;
+
+/// A TCP connection opened by a [`TcpConnector`], ready to be passed to
+/// [`connect`](super::connect).
+pub type TcpConnection = async_dup::Arc<Async<TcpStream>>;
+
+/// Opens plain TCP connections, resolving host names with a [`Resolve`]
+/// implementation.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::client::{PoolKey, TcpConnector};
+/// use http_types::{Method, Request, Url};
+///
+/// # async fn run() -> http_types::Result<()> {
+/// let url = Url::parse("http://example.com/")?;
+/// let connector = TcpConnector::new();
+/// let stream = connector.connect(&PoolKey::from_url(&url).unwrap()).await?;
+/// let res = async_h1::connect(stream, Request::new(Method::Get, url)).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TcpConnector {
+    resolver: Arc<dyn Resolve>,
+    connect_timeout: Option<Duration>,
+}
+
+impl Default for TcpConnector {
+    fn default() -> Self {
+        Self {
+            resolver: Arc::new(SystemResolver),
+            connect_timeout: None,
+        }
+    }
+}
+
+impl TcpConnector {
+    /// Create a new connector using the system resolver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve host names with this resolver.
+    pub fn with_resolver(mut self, resolver: impl Resolve) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Give up on an address which doesn't accept the connection within
+    /// `timeout`, moving on to the next one. If none are left, connecting
+    /// fails with a [`TimeoutPhase::Connect`] timeout error. Defaults to no
+    /// timeout, leaving it to the operating system.
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Connect to an origin, trying each of its addresses in turn.
+    pub async fn connect(&self, key: &PoolKey) -> io::Result<TcpConnection> {
+        let addrs = self.resolver.resolve(key.host(), key.port()).await?;
+        let mut last_err = None;
+        for addr in addrs {
+            let connecting = Async::<TcpStream>::connect(addr);
+            let connected = match self.connect_timeout {
+                Some(duration) => timeout(duration, connecting).await.unwrap_or_else(|_| {
+                    let message = format!("Couldn't connect to {} within {:?}", addr, duration);
+                    Err(Error::timed_out(TimeoutPhase::Connect, message).into())
+                }),
+                None => connecting.await,
+            };
+            match connected {
+                Ok(stream) => return Ok(async_dup::Arc::new(stream)),
+                Err(err) => {
+                    trace!("connecting to {} failed: {}", addr, err);
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} didn't resolve to any addresses", key.host()),
+            )
+        }))
+    }
+}
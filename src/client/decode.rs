@@ -1,18 +1,23 @@
-use async_std::io::{BufReader, Read};
-use async_std::prelude::*;
-use http_types::{ensure, ensure_eq, format_err};
+use futures_lite::io::{self, AsyncBufReadExt, AsyncRead as Read, AsyncReadExt, BufReader, Take};
 use http_types::{
     headers::{CONTENT_LENGTH, DATE, TRANSFER_ENCODING},
-    Body, Response, StatusCode,
+    Body, Method, Response, StatusCode,
 };
 
+use std::borrow::Cow;
 use std::convert::TryFrom;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use crate::chunked::ChunkedDecoder;
-use crate::date::fmt_http_date;
-use crate::{MAX_HEADERS, MAX_HEAD_LENGTH};
+#[cfg(feature = "chunked")]
+use crate::chunked::{ChunkedDecoder, ChunkedPassthrough};
+use crate::client::RawHeaders;
+use crate::date::{cached_http_date, now};
+use crate::error::{malformed, parse_error, Error, Limit, TimeoutPhase};
+use crate::head::{unfold, HeadScanner};
+use crate::timer::{timeout, TimedStream};
+use crate::ClientOptions;
 
-const CR: u8 = b'\r';
 const LF: u8 = b'\n';
 
 /// Decode an HTTP response on the client.
@@ -20,10 +25,211 @@ pub async fn decode<R>(reader: R) -> http_types::Result<Response>
 where
     R: Read + Unpin + Send + Sync + 'static,
 {
-    let mut reader = BufReader::new(reader);
+    decode_with_opts(reader, Method::Get, &ClientOptions::default()).await
+}
+
+/// Decode an HTTP response to a request with this method on the client,
+/// applying the limits in `opts`.
+pub(crate) async fn decode_with_opts<R>(
+    reader: R,
+    method: Method,
+    opts: &ClientOptions,
+) -> http_types::Result<Response>
+where
+    R: Read + Unpin + Send + Sync + 'static,
+{
+    // The head has a deadline of its own, so the body's only starts once
+    // the head is in.
+    let mut reader = BufReader::new(TimedStream::new(reader, None, TimeoutPhase::Body));
+    let mut res = decode_head(&mut reader, opts).await?;
+    reader.get_mut().set_duration(opts.body_timeout);
+    match frame_body(reader, &mut res, method, opts)? {
+        Framed::Empty(_) => {}
+        Framed::Fixed(reader, len) => res.set_body(Body::from_reader(reader, Some(len))),
+        Framed::UntilClose(reader) => res.set_body(Body::from_reader(reader, None)),
+        #[cfg(feature = "chunked")]
+        Framed::Chunked(reader) => res.set_body(Body::from_reader(BufReader::new(reader), None)),
+        #[cfg(feature = "chunked")]
+        Framed::Passthrough(reader, frame_size) => {
+            let reader = BufReader::with_capacity(frame_size, reader);
+            res.set_body(Body::from_reader(reader, None));
+        }
+    }
+
+    // Return the response.
+    Ok(res)
+}
+
+/// A response body as it's framed on the stream it arrives on.
+#[derive(Debug)]
+pub(crate) enum Framed<R: Read> {
+    /// There's no body.
+    Empty(BufReader<R>),
+    /// A body of this length.
+    Fixed(Take<BufReader<R>>, usize),
+    /// A body which ends when the connection closes.
+    UntilClose(BufReader<R>),
+    /// A chunked body.
+    #[cfg(feature = "chunked")]
+    Chunked(ChunkedDecoder<BufReader<R>>),
+    /// A chunked body passed through as it is, read in frames of this size.
+    #[cfg(feature = "chunked")]
+    Passthrough(ChunkedPassthrough<BufReader<R>>, usize),
+}
+
+impl<R: Read + Unpin> Framed<R> {
+    /// The length of the body, if it's known.
+    pub(crate) fn len(&self) -> Option<usize> {
+        match self {
+            Framed::Empty(_) => Some(0),
+            Framed::Fixed(_, len) => Some(*len),
+            Framed::UntilClose(_) => None,
+            #[cfg(feature = "chunked")]
+            Framed::Chunked(_) | Framed::Passthrough(..) => None,
+        }
+    }
+
+    /// Take back the stream, with whatever was read past the body still
+    /// buffered.
+    pub(crate) fn into_inner(self) -> BufReader<R> {
+        match self {
+            Framed::Empty(reader) => reader,
+            Framed::Fixed(reader, _) => reader.into_inner(),
+            Framed::UntilClose(reader) => reader,
+            #[cfg(feature = "chunked")]
+            Framed::Chunked(reader) => reader.into_inner(),
+            #[cfg(feature = "chunked")]
+            Framed::Passthrough(reader, _) => reader.into_inner(),
+        }
+    }
+}
+
+impl<R: Read + Unpin> Read for Framed<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Framed::Empty(_) => Poll::Ready(Ok(0)),
+            Framed::Fixed(reader, _) => Pin::new(reader).poll_read(cx, buf),
+            Framed::UntilClose(reader) => Pin::new(reader).poll_read(cx, buf),
+            #[cfg(feature = "chunked")]
+            Framed::Chunked(reader) => Pin::new(reader).poll_read(cx, buf),
+            #[cfg(feature = "chunked")]
+            Framed::Passthrough(reader, _) => Pin::new(reader).poll_read(cx, buf),
+        }
+    }
+}
+
+/// Work out how the body of `res` is framed, following its head on
+/// `reader`. Responses to `HEAD` requests, successful `CONNECT` requests,
+/// and those with a 1xx, 204 or 304 status have no body whatever their
+/// headers say.
+pub(crate) fn frame_body<R>(
+    reader: BufReader<R>,
+    res: &mut Response,
+    method: Method,
+    opts: &ClientOptions,
+) -> http_types::Result<Framed<R>>
+where
+    R: Read + Unpin,
+{
+    let status = res.status();
+    let bodiless = method == Method::Head
+        || (method == Method::Connect && status.is_success())
+        || status.is_informational()
+        || status == StatusCode::NoContent
+        || status == StatusCode::NotModified;
+    if bodiless {
+        return Ok(Framed::Empty(reader));
+    }
+
+    let content_length = res.header(CONTENT_LENGTH);
+    let transfer_encoding = res.header(TRANSFER_ENCODING);
+
+    ensure_kind!(
+        content_length.is_none() || transfer_encoding.is_none(),
+        BodyFraming,
+        "Unexpected Content-Length header"
+    );
+
+    #[cfg(not(feature = "chunked"))]
+    ensure_kind!(
+        transfer_encoding.is_none(),
+        BodyFraming,
+        "Chunked response bodies aren't supported"
+    );
+    #[cfg(not(feature = "chunked"))]
+    let _ = opts;
+
+    #[cfg(feature = "chunked")]
+    if let Some(encoding) = transfer_encoding {
+        if encoding.last().as_str() == "chunked" {
+            if let Some(frame_size) = opts.passthrough {
+                let reader = ChunkedPassthrough::new(reader);
+                return Ok(Framed::Passthrough(reader, frame_size));
+            }
+
+            let trailers_sender = res.send_trailers();
+            return Ok(Framed::Chunked(ChunkedDecoder::new(
+                reader,
+                trailers_sender,
+            )));
+        }
+    }
+
+    // Check for Content-Length.
+    if let Some(len) = content_length {
+        let len = len.last().as_str().parse::<usize>().map_err(|e| {
+            err_kind!(BodyFraming, "Invalid Content-Length header: {}", e).into_http()
+        })?;
+        return Ok(Framed::Fixed(reader.take(len as u64), len));
+    }
+
+    // Without a length or a chunked encoding, the body is everything until
+    // the server closes the connection (RFC 7230, section 3.3.3), which
+    // can't be reused after it.
+    res.ext_mut().insert(CloseDelimited);
+    Ok(Framed::UntilClose(reader))
+}
+
+/// Marks a response whose body ends when its connection closes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CloseDelimited;
+
+/// Read a response head from `reader`, leaving what follows it unread,
+/// failing if it takes longer than the head timeout.
+pub(crate) async fn decode_head<R>(
+    reader: &mut BufReader<R>,
+    opts: &ClientOptions,
+) -> http_types::Result<Response>
+where
+    R: Read + Unpin,
+{
+    match opts.head_timeout {
+        Some(duration) => timeout(duration, read_head(reader, opts))
+            .await
+            .map_err(|_| {
+                let message = format!("No response head within {:?}", duration);
+                Error::timed_out(TimeoutPhase::Head, message).into_http()
+            })?,
+        None => read_head(reader, opts).await,
+    }
+}
+
+/// Read a response head from `reader`, leaving what follows it unread.
+async fn read_head<R>(
+    reader: &mut BufReader<R>,
+    opts: &ClientOptions,
+) -> http_types::Result<Response>
+where
+    R: Read + Unpin,
+{
     let mut buf = Vec::new();
-    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut headers = vec![httparse::EMPTY_HEADER; opts.max_headers];
     let mut httparse_res = httparse::Response::new(&mut headers);
+    let mut scanner = HeadScanner::new();
 
     // Keep reading bytes from the stream until we hit the end of the stream.
     loop {
@@ -31,74 +237,104 @@ where
         // No more bytes are yielded from the stream.
 
         match (bytes_read, buf.len()) {
-            (0, 0) => return Err(format_err!("connection closed")),
-            (0, _) => return Err(format_err!("empty response")),
+            (0, 0) => return Err(err_kind!(ConnectionClosed, "connection closed").into_http()),
+            (0, _) => {
+                trace!(
+                    "connection closed {} bytes into a response head, at least {} more were needed",
+                    scanner.bytes_buffered(),
+                    scanner.bytes_needed()
+                );
+                return Err(err_kind!(ConnectionClosed, "empty response").into_http());
+            }
             _ => {}
         }
 
         // Prevent CWE-400 DDOS with large HTTP Headers.
-        ensure!(
-            buf.len() < MAX_HEAD_LENGTH,
-            "Head byte length should be less than 8kb"
-        );
+        if buf.len() >= opts.max_head_length {
+            let message = format!(
+                "Head byte length should be less than {} bytes",
+                opts.max_head_length
+            );
+            return Err(Error::limit_exceeded(Limit::HeadLength, message).into_http());
+        }
 
         // We've hit the end delimiter of the stream.
-        let idx = buf.len() - 1;
-        if idx >= 3 && buf[idx - 3..=idx] == [CR, LF, CR, LF] {
-            break;
-        }
-        if idx >= 1 && buf[idx - 1..=idx] == [LF, LF] {
+        if scanner.feed(&buf[buf.len() - bytes_read..]).is_some() {
             break;
         }
     }
 
+    // Clients must unfold header values folded across lines.
+    if let Some(unfolded) = unfold(&buf, true)? {
+        buf = unfolded;
+    }
+
     // Convert our header buf into an httparse instance, and validate.
-    let status = httparse_res.parse(&buf)?;
-    ensure!(!status.is_partial(), "Malformed HTTP head");
+    let status = httparse_res.parse(&buf).map_err(parse_error)?;
+    ensure_kind!(
+        !status.is_partial(),
+        MalformedMessage,
+        "Malformed HTTP head"
+    );
 
     let code = httparse_res.code;
-    let code = code.ok_or_else(|| format_err!("No status code found"))?;
+    let code = code.ok_or_else(|| malformed("No status code found"))?;
 
     // Convert httparse headers + body into a `http_types::Response` type.
     let version = httparse_res.version;
-    let version = version.ok_or_else(|| format_err!("No version found"))?;
-    ensure_eq!(version, 1, "Unsupported HTTP version");
+    let version = version.ok_or_else(|| malformed("No version found"))?;
+    ensure_kind!(version == 1, MalformedMessage, "Unsupported HTTP version");
 
-    let mut res = Response::new(StatusCode::try_from(code)?);
-    for header in httparse_res.headers.iter() {
-        res.append_header(header.name, std::str::from_utf8(header.value)?);
+    if opts.strict_utf8 {
+        // httparse doesn't hand out reason phrases with bytes outside ASCII,
+        // so check the raw status line.
+        let line = buf.split(|byte| *byte == LF).next().unwrap_or_default();
+        let reason = line.splitn(3, |byte| *byte == b' ').nth(2);
+        if let Some(reason) = reason {
+            std::str::from_utf8(reason)
+                .map_err(|_| malformed("Reason phrase isn't valid UTF-8"))?;
+        }
     }
 
-    if res.header(DATE).is_none() {
-        let date = fmt_http_date(std::time::SystemTime::now());
-        res.insert_header(DATE, &format!("date: {}\r\n", date)[..]);
+    let mut res = Response::new(StatusCode::try_from(code).map_err(malformed)?);
+    let mut raw_headers = RawHeaders::default();
+    for header in httparse_res.headers.iter() {
+        let value = header_value(header.value, opts.strict_utf8)?;
+        res.append_header(header.name, &*value);
+        raw_headers.push(header.name, header.value);
     }
+    res.ext_mut().insert(raw_headers);
 
-    let content_length = res.header(CONTENT_LENGTH);
-    let transfer_encoding = res.header(TRANSFER_ENCODING);
-
-    ensure!(
-        content_length.is_none() || transfer_encoding.is_none(),
-        "Unexpected Content-Length header"
-    );
-
-    if let Some(encoding) = transfer_encoding {
-        if encoding.last().as_str() == "chunked" {
-            let trailers_sender = res.send_trailers();
-            let reader = BufReader::new(ChunkedDecoder::new(reader, trailers_sender));
-            res.set_body(Body::from_reader(reader, None));
-
-            // Return the response.
-            return Ok(res);
+    if res.header(DATE).is_none() {
+        if let Some(now) = now() {
+            let date = cached_http_date(now);
+            res.insert_header(DATE, &format!("date: {}\r\n", date)[..]);
         }
     }
 
-    // Check for Content-Length.
-    if let Some(len) = content_length {
-        let len = len.last().as_str().parse::<usize>()?;
-        res.set_body(Body::from_reader(reader.take(len as u64), Some(len)));
-    }
-
-    // Return the response.
     Ok(res)
 }
+
+/// Convert a header value to the ASCII string http-types stores.
+///
+/// Bytes outside ASCII are opaque to HTTP, so they're replaced with `?`. In
+/// strict mode values which aren't valid UTF-8 are rejected instead, and each
+/// non-ASCII character is replaced.
+fn header_value(value: &[u8], strict: bool) -> http_types::Result<Cow<'_, str>> {
+    match std::str::from_utf8(value) {
+        Ok(value) if value.is_ascii() => Ok(Cow::Borrowed(value)),
+        Ok(value) if strict => Ok(Cow::Owned(
+            value
+                .chars()
+                .map(|c| if c.is_ascii() { c } else { '?' })
+                .collect(),
+        )),
+        Err(_) if strict => Err(malformed("Header value isn't valid UTF-8")),
+        _ => Ok(Cow::Owned(
+            value
+                .iter()
+                .map(|byte| if byte.is_ascii() { *byte as char } else { '?' })
+                .collect(),
+        )),
+    }
+}
@@ -1,12 +1,18 @@
 use std::io::Write;
 use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use async_std::io::{self, Cursor, Read};
-use async_std::task::{Context, Poll};
+use futures_core::ready;
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite, AsyncWriteExt, Cursor};
 use http_types::headers::{CONTENT_LENGTH, HOST, TRANSFER_ENCODING};
+use http_types::url::Position;
 use http_types::{Method, Request};
 
-use crate::body_encoder::BodyEncoder;
+#[cfg(feature = "chunked")]
+use crate::body_encoder::chunk_buffered;
+use crate::body_encoder::{is_chunked, BodyEncoder};
+use crate::owned::{read_owned, BufResult};
 use crate::read_to_end;
 use crate::EncoderState;
 
@@ -16,6 +22,15 @@ use crate::EncoderState;
 pub struct Encoder {
     request: Request,
     state: EncoderState,
+    /// Whether to send chunked bodies as they are.
+    passthrough: bool,
+    /// Whether the body is sent as it is.
+    raw_body: bool,
+    /// Whether the target is written in absolute-form, for proxies.
+    absolute_form: bool,
+    /// The size of chunks bodies of unknown length are gathered into.
+    #[cfg(feature = "chunked")]
+    chunk_buffer_size: Option<usize>,
 }
 
 impl Encoder {
@@ -24,6 +39,129 @@ impl Encoder {
         Self {
             request,
             state: EncoderState::Start,
+            passthrough: false,
+            raw_body: false,
+            absolute_form: false,
+            #[cfg(feature = "chunked")]
+            chunk_buffer_size: None,
+        }
+    }
+
+    /// Send a body of unknown length as it is if the request already has a
+    /// `Transfer-Encoding: chunked` header, rather than chunking it again.
+    pub fn with_passthrough(mut self) -> Self {
+        self.passthrough = true;
+        self
+    }
+
+    /// Write the request target in absolute-form, as HTTP proxies expect.
+    pub(crate) fn with_absolute_form(mut self) -> Self {
+        self.absolute_form = true;
+        self
+    }
+
+    /// Gather bodies of unknown length into chunks of up to `size` bytes
+    /// in a buffer reused for every chunk, rather than sending each piece
+    /// the body hands out as a chunk of its own.
+    #[cfg(feature = "chunked")]
+    pub fn with_chunk_buffer_size(mut self, size: usize) -> Self {
+        self.chunk_buffer_size = Some(size);
+        self
+    }
+
+    /// Encode into the spare capacity of an owned buffer, after its current
+    /// contents, for submission to a completion-based runtime.
+    ///
+    /// Returns the number of bytes encoded, 0 once the request has been
+    /// encoded completely, and the buffer.
+    pub async fn read_owned(&mut self, buf: Vec<u8>) -> BufResult<usize> {
+        read_owned(self, buf).await
+    }
+
+    /// Write the request to `writer`, yielding to the executor after every
+    /// `budget` bytes, and return the number of bytes written.
+    ///
+    /// Like the server's encoder, this writes straight from the head and the
+    /// body's buffers, and flushes whenever the body isn't ready, so a
+    /// streaming upload goes out as it's produced rather than sitting in the
+    /// stream's buffers.
+    pub(crate) async fn write_to<W: AsyncWrite + Unpin>(
+        &mut self,
+        writer: &mut W,
+        budget: Option<usize>,
+    ) -> io::Result<u64> {
+        let budget = budget.map(|budget| budget.max(1) as u64);
+        let (mut written, mut since_yield) = (0, 0);
+        let mut unflushed = false;
+        loop {
+            let bytes = future::poll_fn(|cx| match self.poll_write_to(cx, writer) {
+                Poll::Pending if unflushed => {
+                    ready!(Pin::new(&mut *writer).poll_flush(cx))?;
+                    unflushed = false;
+                    Poll::Pending
+                }
+                poll => poll,
+            })
+            .await? as u64;
+            if bytes == 0 {
+                writer.flush().await?;
+                return Ok(written);
+            }
+            unflushed = true;
+            written += bytes;
+            since_yield += bytes;
+            if budget.is_some_and(|budget| since_yield >= budget) {
+                since_yield = 0;
+                future::yield_now().await;
+            }
+        }
+    }
+
+    fn poll_write_to<W: AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        writer: &mut W,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            self.state = match self.state {
+                EncoderState::Start => EncoderState::Head(self.compute_head()?),
+
+                EncoderState::Head(ref mut cursor) => {
+                    let position = cursor.position() as usize;
+                    let remaining = &cursor.get_ref()[position..];
+                    if !remaining.is_empty() {
+                        let bytes = ready!(Pin::new(&mut *writer).poll_write(cx, remaining))?;
+                        if bytes == 0 {
+                            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                        }
+                        cursor.set_position((position + bytes) as u64);
+                        return Poll::Ready(Ok(bytes));
+                    }
+                    self.body_state()
+                }
+
+                EncoderState::Body(ref mut encoder) => {
+                    read_to_end!(encoder.poll_write_to(cx, writer));
+                    EncoderState::End
+                }
+
+                EncoderState::End => return Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    /// The state following the head.
+    fn body_state(&mut self) -> EncoderState {
+        if self.request.len() == Some(0) {
+            return EncoderState::End;
+        }
+        let body = self.request.take_body();
+        #[cfg(feature = "chunked")]
+        let body = chunk_buffered(body, self.chunk_buffer_size);
+        if self.raw_body {
+            EncoderState::Body(BodyEncoder::Fixed(body))
+        } else {
+            EncoderState::Body(BodyEncoder::new(body))
         }
     }
 
@@ -32,7 +170,7 @@ impl Encoder {
             let url = self.request.url();
             let host = url
                 .host_str()
-                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing hostname"))?
+                .ok_or_else(|| io::Error::from(err_kind!(Encode, "Missing hostname")))?
                 .to_owned();
 
             if let Some(port) = url.port() {
@@ -50,10 +188,18 @@ impl Encoder {
 
         // If the body isn't streaming, we can set the content-length ahead of time. Else we need to
         // send all items in chunks.
-        if let Some(len) = self.request.len() {
+        if self.passthrough && is_chunked(self.request.len(), self.request.as_ref()) {
+            self.raw_body = true;
+        } else if let Some(len) = self.request.len() {
             self.request.insert_header(CONTENT_LENGTH, len.to_string());
-        } else {
+        } else if cfg!(feature = "chunked") {
             self.request.insert_header(TRANSFER_ENCODING, "chunked");
+        } else {
+            return Err(err_kind!(
+                Encode,
+                "Request bodies of unknown length aren't supported without chunked encoding"
+            )
+            .into());
         }
 
         Ok(())
@@ -71,16 +217,21 @@ impl Encoder {
         if method == Method::Connect {
             let host = url
                 .host_str()
-                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing hostname"))?;
+                .ok_or_else(|| io::Error::from(err_kind!(Encode, "Missing hostname")))?;
 
             let port = url.port_or_known_default().ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Unexpected scheme with no default port",
-                )
+                io::Error::from(err_kind!(Encode, "Unexpected scheme with no default port"))
             })?;
 
             write!(buf, "{}:{}", host, port)?;
+        } else if self.absolute_form {
+            // Userinfo must not be sent in http(s) URIs, least of all to a
+            // proxy.
+            // See: https://www.rfc-editor.org/rfc/rfc9110#section-4.2.4
+            let mut target = url.clone();
+            let _ = target.set_username("");
+            let _ = target.set_password(None);
+            write!(buf, "{}", &target[..Position::AfterQuery])?;
         } else {
             write!(buf, "{}", url.path())?;
             if let Some(query) = url.query() {
@@ -116,7 +267,7 @@ impl Read for Encoder {
 
                 EncoderState::Head(ref mut cursor) => {
                     read_to_end!(Pin::new(cursor).poll_read(cx, buf));
-                    EncoderState::Body(BodyEncoder::new(self.request.take_body()))
+                    self.body_state()
                 }
 
                 EncoderState::Body(ref mut encoder) => {
@@ -0,0 +1,39 @@
+//! Hooks applied to every exchange made by the client.
+
+use std::fmt::Debug;
+
+use http_types::{Method, Request, Response, Url};
+
+/// Observes or modifies requests and responses passing through the client.
+///
+/// Register interceptors with
+/// [`ClientOptions::with_interceptor`](super::ClientOptions::with_interceptor).
+/// Requests pass through interceptors in the order they were registered, and
+/// responses pass through them in reverse order, so that the first
+/// interceptor sees the request first and the response last.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::client::{ClientOptions, Interceptor};
+/// use http_types::{headers::AUTHORIZATION, Request};
+///
+/// #[derive(Debug)]
+/// struct BearerAuth(String);
+///
+/// impl Interceptor for BearerAuth {
+///     fn before_request(&self, req: &mut Request) {
+///         req.insert_header(AUTHORIZATION, format!("Bearer {}", self.0));
+///     }
+/// }
+///
+/// let opts = ClientOptions::new().with_interceptor(BearerAuth("token".into()));
+/// ```
+pub trait Interceptor: Debug + Send + Sync + 'static {
+    /// Called before the request is encoded.
+    fn before_request(&self, _req: &mut Request) {}
+
+    /// Called after the response head has been decoded, before the response
+    /// is returned. The body hasn't been read yet.
+    fn after_response(&self, _method: Method, _url: &Url, _res: &mut Response) {}
+}
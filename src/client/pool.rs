@@ -0,0 +1,643 @@
+//! Reuse idle client connections.
+
+use std::collections::{HashMap, VecDeque};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Weak;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use std::future::Future;
+
+use event_listener::Event;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write};
+use http_types::headers::CONNECTION;
+use http_types::{Request, Response, Url, Version};
+
+use super::decode::CloseDelimited;
+use super::retry::{self, Watched};
+use super::{connect_with_opts, ClientOptions};
+use crate::error::{Error, TimeoutPhase};
+use crate::timer::{timeout, TimedOut};
+
+/// Identifies the origin a pooled connection is connected to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    scheme: String,
+    host: String,
+    port: u16,
+}
+
+impl PoolKey {
+    /// Create a new key.
+    pub fn new(scheme: impl Into<String>, host: impl Into<String>, port: u16) -> Self {
+        Self {
+            scheme: scheme.into(),
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// The key for the origin of a URL, or `None` if it has no host or its
+    /// scheme has no known default port.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        Some(Self::new(
+            url.scheme(),
+            url.host_str()?,
+            url.port_or_known_default()?,
+        ))
+    }
+
+    /// The scheme, e.g. `http`.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The host name or address.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The port.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+/// A cache of idle connections, keyed by origin.
+///
+/// The pool doesn't open connections itself. Reserve a connection with
+/// [`Pool::checkout`], which hands out an idle connection if there is one,
+/// open a new one if there's none, and hand it back with [`Slot::put_after`]
+/// once the response body has been read to the end.
+///
+/// When an origin already has the maximum number of connections open,
+/// [`Pool::checkout`] waits in line until one is returned or closed.
+/// Connections are handed out in the order they were asked for.
+///
+/// Idle connections expire after the idle timeout, or sooner if the server
+/// said with a `Keep-Alive` header that it closes them sooner. Expired
+/// connections are
+/// never handed out, and are dropped by [`Pool::reap`] or by running
+/// [`Pool::reaper`] in the background.
+///
+/// Cloning a pool is cheap and yields a handle to the same connections.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::client::{Pool, PoolKey};
+/// use async_std::net::TcpStream;
+/// use http_types::{Method, Request, Url};
+///
+/// # async fn run(pool: Pool<TcpStream>) -> http_types::Result<()> {
+/// let url = Url::parse("http://example.com/")?;
+/// let key = PoolKey::from_url(&url).unwrap();
+///
+/// let mut slot = pool.checkout(&key).await?;
+/// let stream = match slot.take() {
+///     Some(stream) => stream,
+///     None => TcpStream::connect(("example.com", 80)).await?,
+/// };
+/// let mut res = async_h1::connect(stream.clone(), Request::new(Method::Get, url)).await?;
+/// res.body_string().await?;
+/// slot.put_after(stream, &res);
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Pool<RW> {
+    hosts: Arc<Mutex<HashMap<PoolKey, Host<RW>>>>,
+    idle_timeout: Duration,
+    max_idle_per_host: usize,
+    max_connections_per_host: usize,
+    checkout_timeout: Option<Duration>,
+}
+
+/// The connections to a single origin.
+#[derive(Debug)]
+struct Host<RW> {
+    idle: VecDeque<Idle<RW>>,
+    /// The number of connections currently checked out.
+    active: usize,
+    /// Ids of the checkouts waiting in line, in order of arrival.
+    waiters: VecDeque<u64>,
+    next_waiter: u64,
+    /// Notified whenever a connection is returned or closed.
+    event: Arc<Event>,
+}
+
+#[derive(Debug)]
+struct Idle<RW> {
+    io: RW,
+    /// When the connection became idle, or `None` without a clock, where
+    /// it never expires by time.
+    since: Option<Instant>,
+    /// How long the connection may stay idle.
+    timeout: Duration,
+}
+
+impl<RW> Idle<RW> {
+    fn is_fresh(&self) -> bool {
+        match self.since {
+            Some(since) => since.elapsed() < self.timeout,
+            None => true,
+        }
+    }
+}
+
+/// What a response says about reusing its connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Hints {
+    /// Whether the server keeps the connection open.
+    pub(crate) reusable: bool,
+    /// How long the server keeps the connection open while it's idle.
+    timeout: Option<Duration>,
+}
+
+impl Hints {
+    pub(crate) fn of(res: &Response) -> Self {
+        let connection = res.header(CONNECTION).map(|values| {
+            values
+                .iter()
+                .flat_map(|value| value.as_str().split(','))
+                .map(str::trim)
+                .map(str::to_ascii_lowercase)
+                .collect::<Vec<_>>()
+        });
+        let has_option = |option: &str| {
+            connection
+                .as_ref()
+                .is_some_and(|options| options.iter().any(|o| o == option))
+        };
+        let mut hints = Self {
+            reusable: match res.version() {
+                Some(Version::Http1_0) => has_option("keep-alive"),
+                _ => !has_option("close"),
+            } && res.ext().get::<CloseDelimited>().is_none(),
+            timeout: None,
+        };
+
+        let keep_alive = res.header("keep-alive");
+        let params = keep_alive
+            .into_iter()
+            .flat_map(|values| values.iter())
+            .flat_map(|value| value.as_str().split(','));
+        for param in params {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => continue,
+            };
+            if name.eq_ignore_ascii_case("timeout") {
+                if let Ok(secs) = value.parse() {
+                    hints.timeout = Some(Duration::from_secs(secs));
+                }
+            } else if name.eq_ignore_ascii_case("max") && value == "0" {
+                hints.reusable = false;
+            }
+        }
+        hints
+    }
+}
+
+impl<RW> Default for Host<RW> {
+    fn default() -> Self {
+        Self {
+            idle: VecDeque::new(),
+            active: 0,
+            waiters: VecDeque::new(),
+            next_waiter: 0,
+            event: Arc::default(),
+        }
+    }
+}
+
+impl<RW> Host<RW> {
+    fn is_unused(&self) -> bool {
+        self.idle.is_empty() && self.active == 0 && self.waiters.is_empty()
+    }
+}
+
+impl<RW> Clone for Pool<RW> {
+    fn clone(&self) -> Self {
+        Self {
+            hosts: self.hosts.clone(),
+            idle_timeout: self.idle_timeout,
+            max_idle_per_host: self.max_idle_per_host,
+            max_connections_per_host: self.max_connections_per_host,
+            checkout_timeout: self.checkout_timeout,
+        }
+    }
+}
+
+impl<RW> Default for Pool<RW> {
+    fn default() -> Self {
+        Self {
+            hosts: Arc::default(),
+            idle_timeout: Duration::from_secs(90),
+            max_idle_per_host: usize::MAX,
+            max_connections_per_host: usize::MAX,
+            checkout_timeout: None,
+        }
+    }
+}
+
+impl<RW> Pool<RW> {
+    /// Create a new, empty pool. Idle connections expire after 90 seconds,
+    /// and neither the number of idle connections nor the number of open
+    /// connections per host is limited.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how long a connection may stay idle before it expires.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Set the maximum number of idle connections kept per origin. When a
+    /// connection is returned to a full pool, the one which has been idle the
+    /// longest is closed.
+    pub fn with_max_idle_per_host(mut self, max: usize) -> Self {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /// Set the maximum number of connections per origin, counting both idle
+    /// and checked out ones.
+    pub fn with_max_connections_per_host(mut self, max: usize) -> Self {
+        self.max_connections_per_host = max;
+        self
+    }
+
+    /// Set how long [`Pool::checkout`] waits in line before giving up, or
+    /// `None` to wait indefinitely.
+    pub fn with_checkout_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.checkout_timeout = timeout;
+        self
+    }
+
+    fn hosts(&self) -> MutexGuard<'_, HashMap<PoolKey, Host<RW>>> {
+        self.hosts.lock().unwrap()
+    }
+
+    /// Reserve a connection to an origin, waiting in line if the origin
+    /// already has the maximum number of connections open.
+    ///
+    /// Fails with a timeout error if the checkout timeout passes first.
+    pub async fn checkout(&self, key: &PoolKey) -> http_types::Result<Slot<RW>> {
+        let waiter = {
+            let mut hosts = self.hosts();
+            let host = hosts.entry(key.clone()).or_default();
+            if host.waiters.is_empty() {
+                if let Some(io) = self.try_checkout(host) {
+                    return Ok(self.slot(key, io));
+                }
+            }
+            let id = host.next_waiter;
+            host.next_waiter += 1;
+            host.waiters.push_back(id);
+            Waiter {
+                pool: self,
+                key,
+                id,
+            }
+        };
+
+        let wait = waiter.wait();
+        let io = match self.checkout_timeout {
+            Some(duration) => timeout(duration, wait).await.map_err(|TimedOut| {
+                Error::timed_out(
+                    TimeoutPhase::Connect,
+                    format!("timed out waiting for a connection to {}", key.host()),
+                )
+                .into_http()
+            })?,
+            None => wait.await,
+        };
+        Ok(self.slot(key, io))
+    }
+
+    /// Reserve a connection if the host has room, returning the idle
+    /// connection to use, if any.
+    fn try_checkout(&self, host: &mut Host<RW>) -> Option<Option<RW>> {
+        while let Some(conn) = host.idle.pop_back() {
+            if conn.is_fresh() {
+                host.active += 1;
+                return Some(Some(conn.io));
+            }
+        }
+        if host.active < self.max_connections_per_host {
+            host.active += 1;
+            Some(None)
+        } else {
+            None
+        }
+    }
+
+    fn slot(&self, key: &PoolKey, io: Option<RW>) -> Slot<RW> {
+        Slot {
+            pool: self.clone(),
+            key: key.clone(),
+            io,
+            returned: false,
+        }
+    }
+
+    /// Take the most recently used idle connection to an origin.
+    ///
+    /// Unlike [`Pool::checkout`], this doesn't reserve the connection, so it
+    /// doesn't count towards the maximum number of connections.
+    pub fn get(&self, key: &PoolKey) -> Option<RW> {
+        let mut hosts = self.hosts();
+        let host = hosts.get_mut(key)?;
+        while let Some(conn) = host.idle.pop_back() {
+            if conn.is_fresh() {
+                return Some(conn.io);
+            }
+        }
+        if host.is_unused() {
+            hosts.remove(key);
+        }
+        None
+    }
+
+    /// Return a connection which is ready for another request.
+    pub fn put(&self, key: PoolKey, io: RW) {
+        let mut hosts = self.hosts();
+        let host = hosts.entry(key).or_default();
+        self.push_idle(host, io, self.idle_timeout);
+    }
+
+    /// Return a connection after the exchange which produced `res`, unless
+    /// the response said the server closes it.
+    ///
+    /// The connection is dropped if the response has `Connection: close`,
+    /// is an HTTP/1.0 response without `Connection: keep-alive`, has
+    /// `Keep-Alive: max=0`, or has a body which ends when the connection
+    /// closes. A `Keep-Alive: timeout` shorter than the idle
+    /// timeout expires the connection sooner, so it isn't handed out just
+    /// as the server closes it.
+    pub fn put_after(&self, key: PoolKey, io: RW, res: &Response) {
+        let hints = Hints::of(res);
+        if hints.reusable {
+            let mut hosts = self.hosts();
+            let host = hosts.entry(key).or_default();
+            self.push_idle(host, io, self.timeout_for(hints));
+        }
+    }
+
+    fn timeout_for(&self, hints: Hints) -> Duration {
+        hints
+            .timeout
+            .map_or(self.idle_timeout, |timeout| timeout.min(self.idle_timeout))
+    }
+
+    fn push_idle(&self, host: &mut Host<RW>, io: RW, timeout: Duration) {
+        host.idle.push_back(Idle {
+            io,
+            since: crate::timer::now(),
+            timeout,
+        });
+        while host.idle.len() > self.max_idle_per_host {
+            host.idle.pop_front();
+        }
+        host.event.notify(usize::MAX);
+    }
+
+    /// The number of idle connections to an origin, including expired ones
+    /// which haven't been reaped yet.
+    pub fn idle(&self, key: &PoolKey) -> usize {
+        self.hosts().get(key).map_or(0, |host| host.idle.len())
+    }
+
+    /// The number of connections to an origin which are checked out.
+    pub fn active(&self, key: &PoolKey) -> usize {
+        self.hosts().get(key).map_or(0, |host| host.active)
+    }
+
+    /// The number of checkouts waiting in line for a connection to an origin.
+    pub fn waiting(&self, key: &PoolKey) -> usize {
+        self.hosts().get(key).map_or(0, |host| host.waiters.len())
+    }
+
+    /// Close all expired connections.
+    pub fn reap(&self) {
+        self.hosts().retain(|_, host| {
+            host.idle.retain(Idle::is_fresh);
+            !host.is_unused()
+        });
+    }
+
+    /// Close expired connections every `interval`, until all handles to the
+    /// pool have been dropped.
+    ///
+    /// The returned future should be spawned on the application's executor.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reaper(&self, interval: Duration) -> impl std::future::Future<Output = ()> {
+        let pool = Reaper {
+            hosts: Arc::downgrade(&self.hosts),
+            idle_timeout: self.idle_timeout,
+        };
+        async move {
+            loop {
+                async_io::Timer::after(interval).await;
+                match pool.upgrade() {
+                    Some(pool) => pool.reap(),
+                    None => return,
+                }
+            }
+        }
+    }
+}
+
+/// A place in line for a connection. Leaves the line when dropped.
+struct Waiter<'a, RW> {
+    pool: &'a Pool<RW>,
+    key: &'a PoolKey,
+    id: u64,
+}
+
+impl<RW> Waiter<'_, RW> {
+    async fn wait(&self) -> Option<RW> {
+        loop {
+            let listener = {
+                let mut hosts = self.pool.hosts();
+                let host = hosts.get_mut(self.key).expect("host has waiters");
+                if host.waiters.front() == Some(&self.id) {
+                    if let Some(io) = self.pool.try_checkout(host) {
+                        host.waiters.pop_front();
+                        // Let the next in line check whether it can go too.
+                        host.event.notify(usize::MAX);
+                        return io;
+                    }
+                }
+                host.event.clone().listen()
+            };
+            listener.await;
+        }
+    }
+}
+
+impl<RW> Drop for Waiter<'_, RW> {
+    fn drop(&mut self) {
+        let mut hosts = self.pool.hosts();
+        if let Some(host) = hosts.get_mut(self.key) {
+            if let Some(index) = host.waiters.iter().position(|id| *id == self.id) {
+                host.waiters.remove(index);
+                host.event.notify(usize::MAX);
+            }
+        }
+    }
+}
+
+/// A reserved connection to an origin, obtained from [`Pool::checkout`].
+///
+/// Dropping the slot without calling [`Slot::put`] counts the connection as
+/// closed, making room for another one.
+#[derive(Debug)]
+pub struct Slot<RW> {
+    pool: Pool<RW>,
+    key: PoolKey,
+    io: Option<RW>,
+    returned: bool,
+}
+
+impl<RW> Slot<RW> {
+    /// Take the idle connection handed out by the pool. Returns `None` if
+    /// there was none, in which case a new connection should be opened.
+    pub fn take(&mut self) -> Option<RW> {
+        self.io.take()
+    }
+
+    /// Return the connection to the pool, ready for another request.
+    pub fn put(self, io: RW) {
+        let timeout = self.pool.idle_timeout;
+        self.release(io, timeout);
+    }
+
+    /// Return the connection after the exchange which produced `res`, or
+    /// count it as closed if the response said the server closes it. See
+    /// [`Pool::put_after`] for the hints which are honored.
+    pub fn put_after(self, io: RW, res: &Response) {
+        let hints = Hints::of(res);
+        if hints.reusable {
+            let timeout = self.pool.timeout_for(hints);
+            self.release(io, timeout);
+        }
+    }
+
+    fn release(mut self, io: RW, timeout: Duration) {
+        let mut hosts = self.pool.hosts();
+        let host = hosts.entry(self.key.clone()).or_default();
+        host.active -= 1;
+        self.pool.push_idle(host, io, timeout);
+        self.returned = true;
+    }
+}
+
+impl<RW> Slot<RW>
+where
+    RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Send `req` over the reserved connection, opening a new one with
+    /// `connect` if the pool didn't hand out an idle one.
+    ///
+    /// Servers close idle connections when they please, so one from the
+    /// pool may turn out to be closed once the request is sent. If it ends
+    /// before any of the response arrives, requests which may safely be
+    /// sent twice are sent again over a connection from `connect`, unless
+    /// that's turned off with
+    /// [`ClientOptions::with_stale_retry`](ClientOptions::with_stale_retry).
+    /// The request is resent as a clone, which has no extensions.
+    ///
+    /// The connection the response arrived on is left in the slot: take it
+    /// back out with [`Slot::take`] to return it with [`Slot::put_after`]
+    /// once the body has been read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use async_h1::client::{ClientOptions, Pool, PoolKey};
+    /// use async_std::net::TcpStream;
+    /// use http_types::{Method, Request, Url};
+    ///
+    /// # async fn run(pool: Pool<TcpStream>) -> http_types::Result<()> {
+    /// let url = Url::parse("http://example.com/")?;
+    /// let key = PoolKey::from_url(&url).unwrap();
+    ///
+    /// let mut slot = pool.checkout(&key).await?;
+    /// let req = Request::new(Method::Get, url);
+    /// let connect = || TcpStream::connect(("example.com", 80));
+    /// let mut res = slot.send(req, ClientOptions::new(), connect).await?;
+    /// res.body_string().await?;
+    /// let stream = slot.take().unwrap();
+    /// slot.put_after(stream, &res);
+    /// # Ok(()) }
+    /// ```
+    pub async fn send<F, Fut>(
+        &mut self,
+        req: Request,
+        opts: ClientOptions,
+        mut connect: F,
+    ) -> http_types::Result<Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = io::Result<RW>>,
+    {
+        let (io, retry) = match self.io.take() {
+            Some(io) => {
+                let retry = opts.stale_retry && retry::is_retriable(&req);
+                (io, retry.then(|| req.clone()))
+            }
+            None => (connect().await?, None),
+        };
+        let watched = Watched::new(io.clone());
+        match connect_with_opts(watched.clone(), req, opts.clone()).await {
+            Ok(res) => {
+                self.io = Some(io);
+                Ok(res)
+            }
+            Err(err) => match retry {
+                Some(req) if retry::is_stale(&err, &watched) => {
+                    trace!("resending a request over a new connection: {}", err);
+                    let io = connect().await?;
+                    let res = connect_with_opts(io.clone(), req, opts).await?;
+                    self.io = Some(io);
+                    Ok(res)
+                }
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+impl<RW> Drop for Slot<RW> {
+    fn drop(&mut self) {
+        if self.returned {
+            return;
+        }
+        let mut hosts = self.pool.hosts();
+        if let Some(host) = hosts.get_mut(&self.key) {
+            host.active -= 1;
+            host.event.notify(usize::MAX);
+        }
+    }
+}
+
+/// A handle to a pool which doesn't keep it alive.
+#[cfg(not(target_arch = "wasm32"))]
+struct Reaper<RW> {
+    hosts: Weak<Mutex<HashMap<PoolKey, Host<RW>>>>,
+    idle_timeout: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<RW> Reaper<RW> {
+    fn upgrade(&self) -> Option<Pool<RW>> {
+        Some(Pool {
+            hosts: self.hosts.upgrade()?,
+            idle_timeout: self.idle_timeout,
+            ..Pool::default()
+        })
+    }
+}
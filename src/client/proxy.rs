@@ -0,0 +1,182 @@
+//! Send requests through HTTP proxies.
+
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write, BufReader};
+use http_types::headers::PROXY_AUTHORIZATION;
+use http_types::{Method, Request, Response, Url};
+
+use super::{decode, ClientOptions, Encoder};
+use crate::digest::base64;
+use crate::server::upgrade::Upgraded;
+
+/// An HTTP proxy, and the credentials to send it.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::client::{PoolKey, Proxy, TcpConnector};
+/// use http_types::{Method, Request, Url};
+///
+/// # async fn run() -> http_types::Result<()> {
+/// let proxy = Proxy::new(Url::parse("http://proxy.internal:3128")?)
+///     .with_basic_auth("user", "secret");
+/// let stream = TcpConnector::new()
+///     .connect(&PoolKey::from_url(proxy.url()).unwrap())
+///     .await?;
+/// let req = Request::new(Method::Get, Url::parse("http://example.com/")?);
+/// let res = async_h1::client::connect_via_proxy(stream, &proxy, req).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct Proxy {
+    url: Url,
+    authorization: Option<String>,
+}
+
+impl Proxy {
+    /// Create a new instance for the proxy at `url`.
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            authorization: None,
+        }
+    }
+
+    /// Send this `Proxy-Authorization` header value with every request.
+    pub fn with_authorization(mut self, value: impl Into<String>) -> Self {
+        self.authorization = Some(value.into());
+        self
+    }
+
+    /// Authenticate with the `Basic` scheme.
+    pub fn with_basic_auth(self, user: &str, password: &str) -> Self {
+        let credentials = base64(format!("{}:{}", user, password).as_bytes());
+        self.with_authorization(format!("Basic {}", credentials))
+    }
+
+    /// The proxy's URL, which connections are opened to.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn authorize(&self, req: &mut Request) {
+        if let Some(authorization) = &self.authorization {
+            req.insert_header(PROXY_AUTHORIZATION, authorization.as_str());
+        }
+    }
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("url", &self.url)
+            .field(
+                "authorization",
+                &self.authorization.as_ref().map(|_| "REDACTED"),
+            )
+            .finish()
+    }
+}
+
+/// Send a request for an `http` URL through a proxy, over a connection to
+/// the proxy.
+pub async fn connect_via_proxy<RW>(
+    stream: RW,
+    proxy: &Proxy,
+    req: Request,
+) -> http_types::Result<Response>
+where
+    RW: Read + Write + Send + Sync + Unpin + 'static,
+{
+    connect_via_proxy_with_opts(stream, proxy, req, Default::default()).await
+}
+
+/// Send a request for an `http` URL through a proxy, over a connection to
+/// the proxy.
+///
+/// The request target is sent in absolute-form, as proxies expect. Requests
+/// for `https` URLs are refused: those need a [`tunnel`] to the origin for
+/// the TLS handshake, which
+/// [`tls::connect_via_proxy`](crate::tls::connect_via_proxy) takes care of.
+pub async fn connect_via_proxy_with_opts<RW>(
+    stream: RW,
+    proxy: &Proxy,
+    mut req: Request,
+    mut opts: ClientOptions,
+) -> http_types::Result<Response>
+where
+    RW: Read + Write + Send + Sync + Unpin + 'static,
+{
+    ensure_kind!(
+        req.url().scheme() == "http",
+        MalformedMessage,
+        "Only http URLs can be sent to a proxy as they are, not {}",
+        req.url().scheme()
+    );
+    proxy.authorize(&mut req);
+    opts.absolute_form = true;
+    super::connect_with_opts(stream, req, opts).await
+}
+
+/// Open a tunnel through a proxy to the origin of `url` with a `CONNECT`
+/// request, over a connection to the proxy.
+///
+/// Fails with the proxy's status code if it refuses, e.g. with
+/// `407 Proxy Authentication Required` for missing or wrong credentials.
+pub async fn tunnel<RW>(stream: RW, proxy: &Proxy, url: &Url) -> http_types::Result<Tunnel<RW>>
+where
+    RW: Read + Write + Unpin,
+{
+    let mut req = Request::new(Method::Connect, url.clone());
+    proxy.authorize(&mut req);
+
+    let mut reader = BufReader::new(stream);
+    Encoder::new(req).write_to(reader.get_mut(), None).await?;
+    let opts = ClientOptions::default();
+    let res = decode::decode_head(&mut reader, &opts).await?;
+    if !res.status().is_success() {
+        return Err(http_types::Error::from_str(
+            res.status(),
+            format!("The proxy refused to open a tunnel: {}", res.status()),
+        ));
+    }
+
+    // The proxy may already have sent bytes from the origin.
+    let buffered = reader.buffer().to_vec();
+    Ok(Tunnel(Upgraded::new(buffered, reader.into_inner())))
+}
+
+/// A tunnel to an origin opened through a proxy, as returned by [`tunnel`].
+#[derive(Debug)]
+pub struct Tunnel<IO>(Upgraded<IO>);
+
+impl<IO: Read + Unpin> Read for Tunnel<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<IO: Write + Unpin> Write for Tunnel<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
@@ -0,0 +1,62 @@
+//! Keep the header fields of a response as they were sent.
+
+/// The header fields of a response exactly as the server sent them.
+///
+/// `http_types` headers are keyed case-insensitively and grouped by name,
+/// which loses the original casing and the order fields arrived in, and
+/// can't hold bytes outside ASCII. This keeps both the names and the raw
+/// value bytes, in order, for tools such as debuggers, signature verifiers,
+/// and proxies which need to reproduce the wire form.
+///
+/// Available in the extensions of every decoded response.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::client::RawHeaders;
+/// use http_types::Response;
+///
+/// fn dump(res: &Response) {
+///     if let Some(raw) = res.ext().get::<RawHeaders>() {
+///         for (name, value) in raw.iter() {
+///             println!("{}: {}", name, String::from_utf8_lossy(value));
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawHeaders {
+    fields: Vec<(String, Vec<u8>)>,
+}
+
+impl RawHeaders {
+    pub(crate) fn push(&mut self, name: &str, value: &[u8]) {
+        self.fields.push((name.to_owned(), value.to_owned()));
+    }
+
+    /// Iterate over the fields in the order they were received, with their
+    /// names cased as they were sent.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_slice()))
+    }
+
+    /// The values of every field with the given name, compared
+    /// case-insensitively, in the order they were received.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [u8]> {
+        self.iter()
+            .filter(move |(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// The number of fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Whether the response had no header fields.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
@@ -0,0 +1,35 @@
+//! Resolve host names to socket addresses.
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+
+/// The future returned by [`Resolve::resolve`].
+pub type Resolving<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>>;
+
+/// Turns a host name into the addresses to try connecting to, in order.
+///
+/// Implement this to plug in caching resolvers, DNS-over-HTTPS, or static
+/// overrides, and pass it to
+/// [`TcpConnector::with_resolver`](super::TcpConnector::with_resolver).
+pub trait Resolve: Debug + Send + Sync + 'static {
+    /// Resolve a host name or IP address literal.
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> Resolving<'a>;
+}
+
+/// Resolves host names using the operating system's resolver.
+///
+/// Lookups block, so they're run on a thread pool.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str, port: u16) -> Resolving<'a> {
+        let host = host.to_owned();
+        Box::pin(blocking::unblock(move || {
+            Ok((host.as_str(), port).to_socket_addrs()?.collect())
+        }))
+    }
+}
@@ -0,0 +1,92 @@
+//! Tell when a pooled connection went stale before the request got through.
+
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write};
+use http_types::{Method, Request};
+
+use crate::error::TimeoutPhase;
+
+/// Whether `req` may be sent again after it might have reached the server:
+/// its method is idempotent, and it has no body which sending it consumed.
+pub(super) fn is_retriable(req: &Request) -> bool {
+    let idempotent = matches!(
+        req.method(),
+        Method::Get | Method::Head | Method::Put | Method::Delete | Method::Options | Method::Trace
+    );
+    idempotent && req.len() == Some(0)
+}
+
+/// Whether a failed exchange failed because the server had closed the
+/// connection: it ended or was reset before any of the response arrived.
+/// Timeouts don't count, as the server may still be working on the request.
+pub(super) fn is_stale(err: &http_types::Error, watched: &Watched<impl Sized>) -> bool {
+    !watched.responded() && TimeoutPhase::of(err).is_none()
+}
+
+/// Wraps a stream, noting whether any bytes were read from it.
+#[derive(Debug, Clone)]
+pub(super) struct Watched<RW> {
+    inner: RW,
+    responded: Arc<AtomicBool>,
+}
+
+impl<RW> Watched<RW> {
+    pub(super) fn new(inner: RW) -> Self {
+        Self {
+            inner,
+            responded: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether any of a response has been read.
+    pub(super) fn responded(&self) -> bool {
+        self.responded.load(Ordering::Relaxed)
+    }
+}
+
+impl<RW: Read + Unpin> Read for Watched<RW> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(bytes)) = poll {
+            if bytes > 0 {
+                self.responded.store(true, Ordering::Relaxed);
+            }
+        }
+        poll
+    }
+}
+
+impl<RW: Write + Unpin> Write for Watched<RW> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
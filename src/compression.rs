@@ -0,0 +1,251 @@
+//! Content-coding support for request and response bodies.
+//!
+//! This module doesn't ship any codecs of its own. Instead it provides the
+//! plumbing to wire codecs such as those from
+//! [`async-compression`](https://docs.rs/async-compression) into both
+//! directions of a connection, taking care of the header fix-ups
+//! (`Content-Encoding`, `Content-Length`, `Vary`) so that each application
+//! doesn't have to glue it on ad hoc.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use async_compression::futures::bufread::{GzipDecoder, GzipEncoder};
+//! use async_h1::compression::{Coder, Compression};
+//! use http_types::Body;
+//!
+//! #[derive(Debug)]
+//! struct Gzip;
+//!
+//! impl Coder for Gzip {
+//!     fn name(&self) -> &'static str {
+//!         "gzip"
+//!     }
+//!
+//!     fn encode(&self, body: Body) -> Body {
+//!         Body::from_reader(async_std::io::BufReader::new(GzipEncoder::new(body)), None)
+//!     }
+//!
+//!     fn decode(&self, body: Body) -> Body {
+//!         Body::from_reader(async_std::io::BufReader::new(GzipDecoder::new(body)), None)
+//!     }
+//! }
+//!
+//! let compression = Compression::new().with_coder(Gzip);
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use http_types::headers::{
+    HeaderValues, Headers, ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH,
+    CONTENT_RANGE, VARY,
+};
+use http_types::{Body, Request, Response, StatusCode};
+
+use crate::negotiate::{Kind, Preferences};
+
+/// A content-coding which can wrap bodies in both directions.
+pub trait Coder: Debug + Send + Sync + 'static {
+    /// The `Content-Encoding` token handled by this coder, e.g. `gzip`.
+    fn name(&self) -> &'static str;
+
+    /// Wrap a body so that reading from it yields encoded bytes.
+    fn encode(&self, body: Body) -> Body;
+
+    /// Wrap a body so that reading from it yields decoded bytes.
+    fn decode(&self, body: Body) -> Body;
+}
+
+/// A set of content-codings applied to bodies on the server and the client.
+///
+/// - Server-side, request bodies are decoded and responses are encoded
+///   according to the request's `Accept-Encoding` header.
+/// - Client-side, `Accept-Encoding` is advertised and responses are decoded.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    coders: Vec<Arc<dyn Coder>>,
+    /// Whether servers decode request bodies.
+    decode_requests: bool,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            coders: Vec::new(),
+            decode_requests: true,
+        }
+    }
+}
+
+impl Compression {
+    /// Create a new instance without any coders.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether servers decode request bodies. Enabled by default.
+    ///
+    /// Endpoints then read request bodies decoded, without the
+    /// `Content-Encoding` and `Content-Length` headers which described the
+    /// encoded body. Requests in a coding there's no coder for are answered
+    /// with `415 Unsupported Media Type`, listing the codings there are in
+    /// `Accept-Encoding`. The server's
+    /// [maximum body size](crate::server::ServerOptions::with_max_body_size)
+    /// applies to the decoded body too, so small bodies which decode to
+    /// huge ones are cut off.
+    ///
+    /// When disabled, request bodies are handed to endpoints as they were
+    /// sent.
+    pub fn with_request_decoding(mut self, enabled: bool) -> Self {
+        self.decode_requests = enabled;
+        self
+    }
+
+    /// Whether servers decode request bodies.
+    pub(crate) fn decodes_requests(&self) -> bool {
+        self.decode_requests
+    }
+
+    /// Register a coder. Among the codings a client accepts equally, those
+    /// registered first are preferred when encoding.
+    pub fn with_coder(mut self, coder: impl Coder) -> Self {
+        self.coders.push(Arc::new(coder));
+        self
+    }
+
+    fn coder(&self, name: &str) -> Option<&Arc<dyn Coder>> {
+        self.coders
+            .iter()
+            .find(|coder| coder.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The value advertised in the `Accept-Encoding` header of requests.
+    pub fn accept_encoding(&self) -> Option<String> {
+        if self.coders.is_empty() {
+            return None;
+        }
+        let names: Vec<_> = self.coders.iter().map(|coder| coder.name()).collect();
+        Some(names.join(", "))
+    }
+
+    /// Decode a request body according to its `Content-Encoding` header.
+    ///
+    /// Bodies with an encoding we don't have a coder for are left untouched.
+    pub fn decode_request(&self, req: &mut Request) {
+        if let Ok(coders) = self.decoders(req.as_ref()) {
+            strip_encoding_headers(req.as_mut());
+            let body = coders
+                .iter()
+                .fold(req.take_body(), |body, coder| coder.decode(body));
+            req.set_body(body);
+        }
+    }
+
+    /// Decode a response body according to its `Content-Encoding` header.
+    ///
+    /// Bodies with an encoding we don't have a coder for are left untouched.
+    pub fn decode_response(&self, res: &mut Response) {
+        if let Ok(coders) = self.decoders(res.as_ref()) {
+            strip_encoding_headers(res.as_mut());
+            let body = coders
+                .iter()
+                .fold(res.take_body(), |body, coder| coder.decode(body));
+            res.set_body(body);
+        }
+    }
+
+    /// Encode a response body using the coder the request's
+    /// `Accept-Encoding` header prefers, by its quality values.
+    ///
+    /// Responses which are already encoded, which must not carry a body,
+    /// which carry part of a representation (`206 Partial Content`), or
+    /// which say `Cache-Control: no-transform` are left untouched.
+    pub fn encode_response(&self, accept_encoding: Option<&HeaderValues>, res: &mut Response) {
+        if res.header(CONTENT_ENCODING).is_some()
+            || res.len() == Some(0)
+            || res.status().is_informational()
+            || res.status() == StatusCode::NoContent
+            || res.status() == StatusCode::NotModified
+            || res.status() == StatusCode::PartialContent
+            || res.header(CONTENT_RANGE).is_some()
+            || no_transform(res.header(CACHE_CONTROL))
+        {
+            return;
+        }
+
+        let accept_encoding = match accept_encoding {
+            Some(accept_encoding) => accept_encoding,
+            None => return,
+        };
+
+        // The first of the coders with the highest quality wins.
+        let preferences = Preferences::new(Kind::Encoding, Some(accept_encoding));
+        let name = preferences.choose(self.coders.iter().map(|coder| coder.name()));
+        if let Some(coder) = name.and_then(|name| self.coder(name)) {
+            res.remove_header(CONTENT_LENGTH);
+            res.insert_header(CONTENT_ENCODING, coder.name());
+            res.append_header(VARY, ACCEPT_ENCODING.as_str());
+            let body = coder.encode(res.take_body());
+            res.set_body(body);
+        }
+    }
+
+    /// The response to a request whose body is in a coding there's no
+    /// coder for, if there's no coder for it.
+    pub(crate) fn unsupported_request_coding(&self, req: &Request) -> Option<Response> {
+        if !matches!(self.decoders(req.as_ref()), Err(Unsupported::Coding)) {
+            return None;
+        }
+        let mut res = Response::new(StatusCode::UnsupportedMediaType);
+        let codings = self.accept_encoding();
+        res.insert_header(ACCEPT_ENCODING, codings.as_deref().unwrap_or("identity"));
+        Some(res)
+    }
+
+    /// The coders needed to undo a `Content-Encoding`, in the order they
+    /// need to be applied. Fails if there's nothing to do, or if any of the
+    /// codings is unknown.
+    fn decoders(&self, headers: &Headers) -> Result<Vec<&Arc<dyn Coder>>, Unsupported> {
+        let encodings = headers.get(CONTENT_ENCODING).ok_or(Unsupported::Nothing)?;
+        let mut coders = vec![];
+        for value in encodings.iter() {
+            for token in value.as_str().split(',').map(str::trim) {
+                if token.is_empty() || token.eq_ignore_ascii_case("identity") {
+                    continue;
+                }
+                coders.push(self.coder(token).ok_or(Unsupported::Coding)?);
+            }
+        }
+        coders.reverse();
+        if coders.is_empty() {
+            Err(Unsupported::Nothing)
+        } else {
+            Ok(coders)
+        }
+    }
+}
+
+/// Why a body can't be decoded.
+#[derive(Debug, PartialEq, Eq)]
+enum Unsupported {
+    /// The body isn't encoded.
+    Nothing,
+    /// The body is in a coding there's no coder for.
+    Coding,
+}
+
+fn strip_encoding_headers(headers: &mut Headers) {
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+}
+
+/// Whether a `Cache-Control` header forbids transforming the body.
+fn no_transform(cache_control: Option<&HeaderValues>) -> bool {
+    cache_control.is_some_and(|values| {
+        values
+            .iter()
+            .flat_map(|value| value.as_str().split(','))
+            .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"))
+    })
+}
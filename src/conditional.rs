@@ -0,0 +1,107 @@
+//! Evaluate conditional requests.
+//!
+//! Given the validators of the resource a request targets, [`evaluate`]
+//! checks the request's `If-None-Match` and `If-Modified-Since` headers
+//! ([RFC 9110, section 13](https://www.rfc-editor.org/rfc/rfc9110#section-13))
+//! and produces a `304 Not Modified` response if the client's copy is still
+//! fresh.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::conditional;
+//! use http_types::{Method, Request, Response, StatusCode, Url};
+//!
+//! let mut req = Request::new(Method::Get, Url::parse("http://example.com/").unwrap());
+//! req.insert_header("if-none-match", "\"v1\"");
+//!
+//! let res = conditional::evaluate(&req, Some("\"v1\""), None)
+//!     .unwrap_or_else(|| Response::new(StatusCode::Ok));
+//! assert_eq!(res.status(), StatusCode::NotModified);
+//! ```
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http_types::headers::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http_types::{Method, Request, Response, StatusCode};
+
+use crate::date::{fmt_http_date, parse_http_date};
+
+/// Respond with `304 Not Modified` if the request's preconditions show that
+/// the client already has the current representation.
+///
+/// `etag` is the resource's entity-tag including its quotes, e.g. `"v1"` or
+/// `W/"v1"`. Returns `None` if the request should be served normally.
+pub fn evaluate(
+    req: &Request,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Option<Response> {
+    if is_not_modified(req, etag, last_modified) {
+        Some(not_modified(etag, last_modified))
+    } else {
+        None
+    }
+}
+
+/// Whether the request's preconditions show that the client already has
+/// the current representation.
+///
+/// Only `GET` and `HEAD` requests are considered. `If-None-Match` takes
+/// precedence over `If-Modified-Since`, which is ignored if it isn't a valid
+/// date.
+pub fn is_not_modified(
+    req: &Request,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    if !matches!(req.method(), Method::Get | Method::Head) {
+        return false;
+    }
+
+    if let Some(if_none_match) = req.header(IF_NONE_MATCH) {
+        let etag = match etag {
+            Some(etag) => etag,
+            None => return false,
+        };
+        return if_none_match
+            .iter()
+            .flat_map(|value| value.as_str().split(','))
+            .map(str::trim)
+            .any(|tag| tag == "*" || weak_eq(tag, etag));
+    }
+
+    match (req.header(IF_MODIFIED_SINCE), last_modified) {
+        (Some(since), Some(last_modified)) => match parse_http_date(since.as_str()) {
+            Ok(since) => truncate(last_modified) <= since,
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// A `304 Not Modified` response carrying the resource's validators.
+pub fn not_modified(etag: Option<&str>, last_modified: Option<SystemTime>) -> Response {
+    let mut res = Response::new(StatusCode::NotModified);
+    if let Some(etag) = etag {
+        res.insert_header(ETAG, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        res.insert_header(LAST_MODIFIED, fmt_http_date(last_modified));
+    }
+    res
+}
+
+/// Compare entity-tags, ignoring whether they're weak.
+fn weak_eq(a: &str, b: &str) -> bool {
+    let opaque = |tag: &str| tag.strip_prefix("W/").unwrap_or(tag).to_owned();
+    opaque(a) == opaque(b)
+}
+
+/// HTTP dates have a resolution of one second.
+fn truncate(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs()),
+        Err(_) => time,
+    }
+}
@@ -0,0 +1,60 @@
+//! Copy between streams without monopolizing the executor.
+
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncReadExt, AsyncWrite as Write};
+
+/// Copy all bytes from `reader` to `writer`, like [`io::copy`], but yield to
+/// the executor after every `budget` bytes.
+///
+/// When both streams are always ready, e.g. a large in-memory body written
+/// to a fast socket, `io::copy` never returns `Pending` and starves the other
+/// tasks on its thread. With no budget this behaves exactly like `io::copy`.
+pub(crate) async fn copy<R, W>(
+    mut reader: R,
+    writer: &mut W,
+    budget: Option<usize>,
+) -> io::Result<u64>
+where
+    R: Read + Unpin,
+    W: Write + Unpin,
+{
+    let budget = match budget {
+        Some(budget) => budget.max(1) as u64,
+        None => return io::copy(reader, writer).await,
+    };
+
+    let mut copied = 0;
+    loop {
+        let bytes = io::copy((&mut reader).take(budget), &mut *writer).await?;
+        copied += bytes;
+        if bytes < budget {
+            return Ok(copied);
+        }
+        future::yield_now().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::io::Cursor;
+
+    #[test]
+    fn yields_after_budget() {
+        let mut out = Vec::new();
+        let mut fut = Box::pin(copy(Cursor::new(b"0123456789"), &mut out, Some(4)));
+        assert!(future::block_on(future::poll_once(&mut fut)).is_none());
+        assert_eq!(future::block_on(fut).unwrap(), 10);
+        assert_eq!(out, b"0123456789");
+    }
+
+    #[test]
+    fn copies_without_budget() {
+        let mut out = Vec::new();
+        let fut = copy(Cursor::new(b"0123456789"), &mut out, None);
+        assert_eq!(
+            future::block_on(future::poll_once(fut)).unwrap().unwrap(),
+            10
+        );
+    }
+}
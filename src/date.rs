@@ -1,5 +1,6 @@
 use std::fmt::{self, Display, Formatter};
 use std::str::{from_utf8, FromStr};
+use std::sync::RwLock;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use http_types::{bail, ensure, format_err};
@@ -38,11 +39,23 @@ pub struct HttpDate {
 /// Supports the preferred IMF-fixdate and the legacy RFC 805 and
 /// ascdate formats. Two digit years are mapped to dates between
 /// 1970 and 2069.
-#[allow(dead_code)]
 pub(crate) fn parse_http_date(s: &str) -> http_types::Result<SystemTime> {
     s.parse::<HttpDate>().map(|d| d.into())
 }
 
+/// The current time, or `None` on targets without a system clock.
+///
+/// `SystemTime::now` panics on `wasm32-unknown-unknown`. An origin server
+/// without a clock must not generate a `Date` header, so callers skip it.
+/// https://tools.ietf.org/html/rfc7231#section-7.1.1.2
+pub(crate) fn now() -> Option<SystemTime> {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        None
+    } else {
+        Some(SystemTime::now())
+    }
+}
+
 /// Format a date to be used in a HTTP header field.
 ///
 /// Dates are formatted as IMF-fixdate: `Fri, 15 May 2015 15:34:21 GMT`.
@@ -50,6 +63,34 @@ pub(crate) fn fmt_http_date(d: SystemTime) -> String {
     format!("{}", HttpDate::from(d))
 }
 
+/// The last date formatted by [`cached_http_date`], and the second it's
+/// for.
+static CACHED_DATE: RwLock<Option<(u64, String)>> = RwLock::new(None);
+
+/// Format a date like [`fmt_http_date`], reusing the string formatted for
+/// the same second if there is one.
+///
+/// Dates only have a granularity of a second, so a busy server would
+/// otherwise format the same `Date` header for every response.
+pub(crate) fn cached_http_date(d: SystemTime) -> String {
+    let second = match d.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch.as_secs(),
+        Err(_) => return fmt_http_date(d),
+    };
+    if let Ok(cached) = CACHED_DATE.read() {
+        if let Some((cached_second, date)) = &*cached {
+            if *cached_second == second {
+                return date.clone();
+            }
+        }
+    }
+    let date = fmt_http_date(d);
+    if let Ok(mut cached) = CACHED_DATE.write() {
+        *cached = Some((second, date.clone()));
+    }
+    date
+}
+
 impl HttpDate {
     fn is_valid(self) -> bool {
         self.second < 60
@@ -375,8 +416,8 @@ impl Display for HttpDate {
         buf[0] = week_day[0];
         buf[1] = week_day[1];
         buf[2] = week_day[2];
-        buf[5] = b'0' + (self.day / 10) as u8;
-        buf[6] = b'0' + (self.day % 10) as u8;
+        buf[5] = b'0' + (self.day / 10);
+        buf[6] = b'0' + (self.day % 10);
         buf[8] = month[0];
         buf[9] = month[1];
         buf[10] = month[2];
@@ -384,12 +425,12 @@ impl Display for HttpDate {
         buf[13] = b'0' + (self.year / 100 % 10) as u8;
         buf[14] = b'0' + (self.year / 10 % 10) as u8;
         buf[15] = b'0' + (self.year % 10) as u8;
-        buf[17] = b'0' + (self.hour / 10) as u8;
-        buf[18] = b'0' + (self.hour % 10) as u8;
-        buf[20] = b'0' + (self.minute / 10) as u8;
-        buf[21] = b'0' + (self.minute % 10) as u8;
-        buf[23] = b'0' + (self.second / 10) as u8;
-        buf[24] = b'0' + (self.second % 10) as u8;
+        buf[17] = b'0' + (self.hour / 10);
+        buf[18] = b'0' + (self.hour % 10);
+        buf[20] = b'0' + (self.minute / 10);
+        buf[21] = b'0' + (self.minute % 10);
+        buf[23] = b'0' + (self.second / 10);
+        buf[24] = b'0' + (self.second % 10);
         f.write_str(from_utf8(&buf[..]).unwrap())
     }
 }
@@ -414,7 +455,9 @@ fn is_leap_year(year: u16) -> bool {
 mod tests {
     use std::time::{Duration, UNIX_EPOCH};
 
-    use super::{fmt_http_date, parse_http_date, HttpDate, SECONDS_IN_DAY, SECONDS_IN_HOUR};
+    use super::{
+        cached_http_date, fmt_http_date, parse_http_date, HttpDate, SECONDS_IN_DAY, SECONDS_IN_HOUR,
+    };
 
     #[test]
     fn test_rfc_example() {
@@ -430,6 +473,17 @@ mod tests {
         assert_eq!(d, parse_http_date("Sun Nov  6 08:49:37 1994").expect("#3"));
     }
 
+    #[test]
+    fn cached_dates_follow_the_second() {
+        let d = UNIX_EPOCH + Duration::from_secs(253402300000);
+        assert_eq!(cached_http_date(d), fmt_http_date(d));
+        let later = d + Duration::from_millis(999);
+        assert_eq!(cached_http_date(later), fmt_http_date(d));
+        let next = d + Duration::from_secs(1);
+        assert_eq!(cached_http_date(next), fmt_http_date(next));
+        assert_eq!(cached_http_date(d), fmt_http_date(d));
+    }
+
     #[test]
     fn test2() {
         let d = UNIX_EPOCH + Duration::from_secs(1475419451);
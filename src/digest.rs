@@ -0,0 +1,269 @@
+//! `Content-Digest` support for message bodies.
+//!
+//! This module doesn't ship any hash functions of its own. Instead it
+//! provides the plumbing to compute a digest such as SHA-256, using a crate
+//! like [`sha2`](https://docs.rs/sha2), over a body as it streams, and to
+//! send it as a `Content-Digest` trailer
+//! ([RFC 9530](https://www.rfc-editor.org/rfc/rfc9530)) or check it against
+//! the digest sent with a request.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use async_h1::digest::{DigestAlgorithm, Hasher};
+//! use async_h1::server::ServerOptions;
+//! use sha2::Digest;
+//!
+//! #[derive(Debug)]
+//! struct Sha256;
+//!
+//! impl DigestAlgorithm for Sha256 {
+//!     fn name(&self) -> &'static str {
+//!         "sha-256"
+//!     }
+//!
+//!     fn hasher(&self) -> Box<dyn Hasher> {
+//!         Box::new(sha2::Sha256::new())
+//!     }
+//! }
+//!
+//! impl Hasher for sha2::Sha256 {
+//!     fn update(&mut self, bytes: &[u8]) {
+//!         sha2::Digest::update(self, bytes)
+//!     }
+//!
+//!     fn finish(self: Box<Self>) -> Vec<u8> {
+//!         self.finalize().to_vec()
+//!     }
+//! }
+//!
+//! let opts = ServerOptions::new().with_content_digest(Sha256);
+//! ```
+
+// Digests are only sent as trailers, which need chunked encoding.
+#![cfg_attr(not(feature = "chunked"), allow(dead_code))]
+
+use std::fmt::Debug;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncRead as Read, BufReader};
+use http_types::{Body, Request};
+
+/// A hash algorithm which can be named in a `Content-Digest` field.
+pub trait DigestAlgorithm: Debug + Send + Sync + 'static {
+    /// The algorithm's key in the `Content-Digest` field, e.g. `sha-256`.
+    ///
+    /// An algorithm named `md5` is also used to check the legacy
+    /// `Content-MD5` header.
+    fn name(&self) -> &'static str;
+
+    /// Start computing a new digest.
+    fn hasher(&self) -> Box<dyn Hasher>;
+}
+
+/// A digest in the making.
+pub trait Hasher: Send + Sync {
+    /// Feed bytes into the digest.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Finish computing the digest.
+    fn finish(self: Box<Self>) -> Vec<u8>;
+}
+
+/// A digest being computed over a body, along with its algorithm's name.
+pub(crate) struct BodyDigest {
+    name: &'static str,
+    hasher: Box<dyn Hasher>,
+}
+
+impl BodyDigest {
+    pub(crate) fn new(algorithm: &dyn DigestAlgorithm) -> Self {
+        Self {
+            name: algorithm.name(),
+            hasher: algorithm.hasher(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+    }
+
+    /// Finish the digest and format it as a `Content-Digest` field value.
+    pub(crate) fn finish(self) -> String {
+        format!("{}=:{}:", self.name, base64(&self.hasher.finish()))
+    }
+}
+
+impl Debug for BodyDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BodyDigest")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+/// Check request bodies against their `Content-Digest` or `Content-MD5`
+/// header as they're read, using the first of the given algorithms the
+/// request has a digest for.
+///
+/// Requests without a digest in one of these algorithms are left untouched.
+pub(crate) fn verify_request(algorithms: &[Arc<dyn DigestAlgorithm>], req: &mut Request) {
+    let expected = algorithms.iter().find_map(|algorithm| {
+        let digest = match req.header("content-digest") {
+            Some(values) => values
+                .iter()
+                .find_map(|value| find_digest(value.as_str(), algorithm.name())),
+            None if algorithm.name() == "md5" => req
+                .header("content-md5")
+                .and_then(|value| unbase64(value.as_str().trim())),
+            None => None,
+        }?;
+        Some((algorithm, digest))
+    });
+
+    if let Some((algorithm, expected)) = expected {
+        let len = req.len();
+        let body = req.take_body();
+        let mime = body.mime().clone();
+        let reader = VerifyReader {
+            reader: body,
+            hasher: Some(algorithm.hasher()),
+            expected,
+            remaining: len,
+        };
+        let mut body = Body::from_reader(BufReader::new(reader), len);
+        body.set_mime(mime);
+        req.set_body(body);
+    }
+}
+
+/// Find the digest for an algorithm in a `Content-Digest` field value.
+fn find_digest(value: &str, name: &str) -> Option<Vec<u8>> {
+    value.split(',').find_map(|member| {
+        let (key, digest) = member.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case(name) {
+            return None;
+        }
+        let digest = digest.trim().strip_prefix(':')?.strip_suffix(':')?;
+        unbase64(digest)
+    })
+}
+
+/// Hashes a body as it's read, failing the last read if the digest doesn't
+/// match.
+struct VerifyReader<R> {
+    reader: R,
+    hasher: Option<Box<dyn Hasher>>,
+    expected: Vec<u8>,
+    /// Bytes left to read in a body of known length. Readers of such bodies
+    /// stop once they have them all, so the digest is checked right away
+    /// rather than at EOF.
+    remaining: Option<usize>,
+}
+
+impl<R: Read + Unpin> Read for VerifyReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let bytes = futures_core::ready!(Pin::new(&mut this.reader).poll_read(cx, buf))?;
+        if let Some(hasher) = &mut this.hasher {
+            hasher.update(&buf[..bytes]);
+        }
+        if let Some(remaining) = &mut this.remaining {
+            *remaining = remaining.saturating_sub(bytes);
+        }
+
+        let done = bytes == 0 || this.remaining == Some(0);
+        if let (true, Some(hasher)) = (done, this.hasher.take()) {
+            if hasher.finish() != this.expected {
+                return Poll::Ready(Err(err_kind!(
+                    DigestMismatch,
+                    "Request body doesn't match its digest"
+                )
+                .into()));
+            }
+        }
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as padded base64, as used by structured field byte
+/// sequences.
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | u32::from(*byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode padded base64, returning `None` if it's invalid.
+pub(crate) fn unbase64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if encoded.len() % 4 != 0 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(encoded.len() / 4 * 3);
+    for chunk in encoded.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|byte| **byte == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, byte) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64.iter().position(|b| b == byte)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        let bytes = n.to_be_bytes();
+        out.extend_from_slice(&bytes[1..4 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, find_digest, unbase64};
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(unbase64("").unwrap(), b"");
+        assert_eq!(unbase64("Zg==").unwrap(), b"f");
+        assert_eq!(unbase64("Zm8=").unwrap(), b"fo");
+        assert_eq!(unbase64("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(unbase64("Zm9"), None);
+        assert_eq!(unbase64("Zm9*"), None);
+    }
+
+    #[test]
+    fn finds_digest() {
+        let value = "sha-512=:Zm9v:, sha-256=:YmFy:";
+        assert_eq!(find_digest(value, "sha-256").unwrap(), b"bar");
+        assert_eq!(find_digest(value, "md5"), None);
+    }
+}
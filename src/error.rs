@@ -0,0 +1,477 @@
+//! Classifying errors produced while processing connections.
+//!
+//! Errors returned by this crate are [`http_types::Error`]s. Errors which
+//! originate in the protocol handling carry an [`Error`] which can be
+//! retrieved through [`http_types::Error::downcast_ref`]; errors from the
+//! underlying stream carry an [`std::io::Error`]. [`ErrorKind::of`] takes
+//! care of both, as do [`TimeoutPhase::of`], [`Limit::of`] and
+//! [`ForeignProtocol::of`] for the details of some kinds.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::error::ErrorKind;
+//!
+//! fn should_log(err: &http_types::Error) -> bool {
+//!     ErrorKind::of(err) != ErrorKind::Io
+//! }
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use http_types::headers::{CONNECTION, CONTENT_TYPE};
+use http_types::{mime, Response, StatusCode};
+
+/// The category of an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Reading from or writing to the underlying stream failed, or the peer
+    /// went away.
+    Io,
+    /// An operation didn't complete in time.
+    Timeout,
+    /// The message head couldn't be parsed or is invalid.
+    MalformedMessage,
+    /// The message exceeded one of the configured limits. See [`Limit`].
+    LimitExceeded,
+    /// The message body is framed incorrectly, e.g. an invalid chunk or
+    /// conflicting length headers.
+    BodyFraming,
+    /// The message body doesn't match the digest sent along with it.
+    DigestMismatch,
+    /// The peer speaks another protocol than HTTP/1, such as TLS or
+    /// HTTP/2. See [`ForeignProtocol`].
+    ProtocolMismatch,
+    /// The peer closed the connection before a message was complete.
+    ConnectionClosed,
+    /// A message couldn't be encoded, e.g. a request without a host or a
+    /// body which can't be framed.
+    Encode,
+    /// The error didn't originate in this crate or in the underlying
+    /// stream, e.g. it was returned by an endpoint. Its own status code
+    /// says what went wrong.
+    Other,
+}
+
+impl ErrorKind {
+    /// Classify an error returned by this crate.
+    ///
+    /// Errors which didn't originate in this crate, such as those returned
+    /// by an endpoint, are classified as [`ErrorKind::Io`] if they wrap an
+    /// `io::Error` and as [`ErrorKind::Other`] otherwise.
+    pub fn of(error: &http_types::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            error.kind()
+        } else if let Some(error) = error.downcast_ref::<io::Error>() {
+            Self::of_io(error)
+        } else {
+            ErrorKind::Other
+        }
+    }
+
+    /// The status code a server should respond with for this kind of error.
+    ///
+    /// For [`ErrorKind::Other`] errors, prefer the error's own status.
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorKind::Io | ErrorKind::Encode | ErrorKind::Other => StatusCode::InternalServerError,
+            ErrorKind::Timeout => StatusCode::RequestTimeout,
+            ErrorKind::MalformedMessage
+            | ErrorKind::BodyFraming
+            | ErrorKind::DigestMismatch
+            | ErrorKind::ProtocolMismatch
+            | ErrorKind::ConnectionClosed => StatusCode::BadRequest,
+            ErrorKind::LimitExceeded => StatusCode::RequestHeaderFieldsTooLarge,
+        }
+    }
+
+    /// Classify an `io::Error`, such as one returned while reading a body.
+    pub fn of_io(error: &io::Error) -> Self {
+        match error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+        {
+            Some(error) => error.kind(),
+            None if error.kind() == io::ErrorKind::TimedOut => ErrorKind::Timeout,
+            None => ErrorKind::Io,
+        }
+    }
+}
+
+/// The phase of an exchange during which an operation timed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TimeoutPhase {
+    /// Establishing a connection, or waiting for one from a pool.
+    Connect,
+    /// Reading a message head.
+    Head,
+    /// Reading a message body.
+    Body,
+    /// Writing a message.
+    Write,
+    /// Waiting for the next request on a keep-alive connection.
+    Idle,
+}
+
+impl TimeoutPhase {
+    /// The phase an error timed out in, if it's a timeout from this crate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use async_h1::error::TimeoutPhase;
+    ///
+    /// fn should_retry(err: &http_types::Error) -> bool {
+    ///     TimeoutPhase::of(err).is_some_and(TimeoutPhase::is_retry_safe)
+    /// }
+    /// ```
+    pub fn of(error: &http_types::Error) -> Option<Self> {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            error.timeout_phase()
+        } else {
+            Self::of_io(error.downcast_ref::<io::Error>()?)
+        }
+    }
+
+    /// The phase an `io::Error` timed out in, if it's a timeout from this
+    /// crate.
+    pub fn of_io(error: &io::Error) -> Option<Self> {
+        error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+            .and_then(Error::timeout_phase)
+    }
+
+    /// Whether the request can't have been processed by the peer yet, so
+    /// that retrying it is safe whatever its method.
+    ///
+    /// That's the case if the connection was never established or the
+    /// connection was idle. In the other phases the request may have been
+    /// partially or fully processed, and should only be retried if it's
+    /// idempotent.
+    pub fn is_retry_safe(self) -> bool {
+        matches!(self, TimeoutPhase::Connect | TimeoutPhase::Idle)
+    }
+}
+
+impl Display for TimeoutPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeoutPhase::Connect => "connect",
+            TimeoutPhase::Head => "head",
+            TimeoutPhase::Body => "body",
+            TimeoutPhase::Write => "write",
+            TimeoutPhase::Idle => "idle",
+        })
+    }
+}
+
+/// The limit a message exceeded, for [`ErrorKind::LimitExceeded`] errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Limit {
+    /// The length of a request line.
+    RequestLine,
+    /// The length of a message head.
+    HeadLength,
+    /// The number of headers in a message.
+    HeaderCount,
+    /// The size of a request body.
+    BodySize,
+    /// The memory a connection may use to buffer messages.
+    ConnectionMemory,
+}
+
+impl Limit {
+    /// The limit an error exceeded, if it's a limit from this crate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use async_h1::error::Limit;
+    ///
+    /// fn body_too_large(err: &http_types::Error) -> bool {
+    ///     Limit::of(err) == Some(Limit::BodySize)
+    /// }
+    /// ```
+    pub fn of(error: &http_types::Error) -> Option<Self> {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            error.limit()
+        } else {
+            Self::of_io(error.downcast_ref::<io::Error>()?)
+        }
+    }
+
+    /// The limit an `io::Error` exceeded, if it's a limit from this crate.
+    pub fn of_io(error: &io::Error) -> Option<Self> {
+        error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+            .and_then(Error::limit)
+    }
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::RequestLine => "request line length",
+            Limit::HeadLength => "head length",
+            Limit::HeaderCount => "header count",
+            Limit::BodySize => "body size",
+            Limit::ConnectionMemory => "connection memory",
+        })
+    }
+}
+
+/// A protocol other than HTTP/1 recognized at the start of a connection,
+/// for [`ErrorKind::ProtocolMismatch`] errors.
+///
+/// These usually mean a client was pointed at the wrong port, or assumes
+/// the server speaks a protocol it wasn't configured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ForeignProtocol {
+    /// A TLS handshake: the client expected HTTPS.
+    Tls,
+    /// The HTTP/2 connection preface: the client expected HTTP/2 with prior
+    /// knowledge.
+    Http2,
+}
+
+impl ForeignProtocol {
+    /// The protocol the peer spoke, if the error is a protocol mismatch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use async_h1::error::ForeignProtocol;
+    ///
+    /// fn diagnose(err: &http_types::Error) {
+    ///     if ForeignProtocol::of(err) == Some(ForeignProtocol::Tls) {
+    ///         eprintln!("a client tried to speak HTTPS to the plain HTTP port");
+    ///     }
+    /// }
+    /// ```
+    pub fn of(error: &http_types::Error) -> Option<Self> {
+        error.downcast_ref::<Error>()?.foreign_protocol()
+    }
+
+    /// The status code a server should respond with, for clients which
+    /// can read an HTTP/1 response at all.
+    fn status(self) -> StatusCode {
+        match self {
+            ForeignProtocol::Tls => StatusCode::BadRequest,
+            ForeignProtocol::Http2 => StatusCode::HttpVersionNotSupported,
+        }
+    }
+}
+
+impl Display for ForeignProtocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ForeignProtocol::Tls => "TLS",
+            ForeignProtocol::Http2 => "HTTP/2",
+        })
+    }
+}
+
+/// An error originating in the protocol handling of this crate.
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    message: Cow<'static, str>,
+    timeout: Option<TimeoutPhase>,
+    foreign: Option<ForeignProtocol>,
+    limit: Option<Limit>,
+    /// A more specific status code than the kind's.
+    status: Option<StatusCode>,
+}
+
+impl Error {
+    pub(crate) fn new(kind: ErrorKind, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            timeout: None,
+            foreign: None,
+            limit: None,
+            status: None,
+        }
+    }
+
+    /// An [`ErrorKind::Timeout`] error for an operation in `phase`.
+    pub(crate) fn timed_out(phase: TimeoutPhase, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            timeout: Some(phase),
+            ..Self::new(ErrorKind::Timeout, message)
+        }
+    }
+
+    /// An [`ErrorKind::LimitExceeded`] error for a message exceeding
+    /// `limit`.
+    pub(crate) fn limit_exceeded(limit: Limit, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::new(ErrorKind::LimitExceeded, message)
+        }
+    }
+
+    /// An [`ErrorKind::ProtocolMismatch`] error for a peer speaking
+    /// `protocol`.
+    pub(crate) fn foreign(
+        protocol: ForeignProtocol,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            foreign: Some(protocol),
+            status: Some(protocol.status()),
+            ..Self::new(ErrorKind::ProtocolMismatch, message)
+        }
+    }
+
+    /// The category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The phase in which the operation timed out, for
+    /// [`ErrorKind::Timeout`] errors.
+    pub fn timeout_phase(&self) -> Option<TimeoutPhase> {
+        self.timeout
+    }
+
+    /// The protocol the peer spoke instead of HTTP/1, for
+    /// [`ErrorKind::ProtocolMismatch`] errors.
+    pub fn foreign_protocol(&self) -> Option<ForeignProtocol> {
+        self.foreign
+    }
+
+    /// The limit the message exceeded, for [`ErrorKind::LimitExceeded`]
+    /// errors.
+    pub fn limit(&self) -> Option<Limit> {
+        self.limit
+    }
+
+    /// Whether the request which failed with this error can be retried
+    /// safely whatever its method. See [`TimeoutPhase::is_retry_safe`].
+    pub fn is_retry_safe(&self) -> bool {
+        self.timeout.is_some_and(TimeoutPhase::is_retry_safe)
+    }
+
+    /// Give this error a more specific status code than its kind's, for
+    /// errors which only reach [`into_http`](Error::into_http) later on,
+    /// like those met while reading a body.
+    pub(crate) fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Wrap this error, setting the status code a server should respond with.
+    pub(crate) fn into_http(self) -> http_types::Error {
+        let status = self.status.unwrap_or_else(|| self.kind.status());
+        http_types::Error::new(status, self)
+    }
+
+    /// Wrap this error with a more specific status code than its kind's.
+    pub(crate) fn into_http_with_status(self, status: StatusCode) -> http_types::Error {
+        self.with_status(status).into_http()
+    }
+}
+
+/// The response a server should send after failing to process a request.
+///
+/// The status code is taken from the error itself, unless it wraps an
+/// `io::Error`, whose status is that of its [`ErrorKind`]. So errors from
+/// this crate and from endpoints keep their status. The body is a short plain text
+/// description of the status, and the connection is marked to be closed,
+/// since after a protocol error the rest of the stream can't be trusted.
+///
+/// Returns `None` for [`ErrorKind::Io`] and [`ErrorKind::ConnectionClosed`]
+/// errors: the connection is gone, so there's no one to respond to.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::error::recommended_response;
+/// use http_types::{Error, StatusCode};
+/// use std::io;
+///
+/// let err = Error::from(io::Error::new(io::ErrorKind::TimedOut, "too slow"));
+/// let res = recommended_response(&err).unwrap();
+/// assert_eq!(res.status(), StatusCode::RequestTimeout);
+/// ```
+pub fn recommended_response(error: &http_types::Error) -> Option<Response> {
+    let kind = ErrorKind::of(error);
+    let status = match (kind, error.downcast_ref::<io::Error>()) {
+        (ErrorKind::Io, _) | (ErrorKind::ConnectionClosed, _) => return None,
+        (kind, Some(_)) => kind.status(),
+        (_, None) => error.status(),
+    };
+
+    let mut res = Response::new(status);
+    res.insert_header(CONNECTION, "close");
+    res.insert_header(CONTENT_TYPE, mime::PLAIN);
+    res.set_body(format!("{} {}\n", status, status.canonical_reason()));
+    Some(res)
+}
+
+/// Wrap an error from httparse, which is a malformed message unless it
+/// exceeded the number of headers we're willing to parse.
+pub(crate) fn parse_error(error: httparse::Error) -> http_types::Error {
+    match error {
+        httparse::Error::TooManyHeaders => {
+            Error::limit_exceeded(Limit::HeaderCount, error.to_string()).into_http()
+        }
+        error => malformed(error),
+    }
+}
+
+/// Wrap the cause of a malformed message. Meant for use with `map_err`.
+pub(crate) fn malformed(error: impl Display) -> http_types::Error {
+    Error::new(ErrorKind::MalformedMessage, error.to_string()).into_http()
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        let kind = match error.kind {
+            ErrorKind::Io | ErrorKind::ConnectionClosed => io::ErrorKind::UnexpectedEof,
+            ErrorKind::Timeout => io::ErrorKind::TimedOut,
+            ErrorKind::Encode => io::ErrorKind::InvalidInput,
+            ErrorKind::Other => io::ErrorKind::Other,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// Return early with an [`Error`] of the given kind if a condition isn't met.
+macro_rules! ensure_kind {
+    ($cond:expr, $kind:ident, $($msg:tt)+) => {
+        if !$cond {
+            return Err($crate::error::Error::new(
+                $crate::error::ErrorKind::$kind,
+                format!($($msg)+),
+            )
+            .into_http());
+        }
+    };
+}
+
+/// Construct an [`Error`] of the given kind.
+macro_rules! err_kind {
+    ($kind:ident, $($msg:tt)+) => {
+        $crate::error::Error::new($crate::error::ErrorKind::$kind, format!($($msg)+))
+    };
+}
@@ -0,0 +1,109 @@
+//! Deterministic entry points for fuzzing and property testing the parsers.
+//!
+//! Each function decodes a complete message from a byte slice, body
+//! included, and blocks until it's done, so no async runtime is needed.
+//! They never panic on malformed input: anything which can't be decoded
+//! is returned as an error. The `fuzz` directory of the repository holds
+//! `cargo fuzz` targets built on them.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::fuzz;
+//!
+//! let input = b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 2\r\n\r\nhi";
+//! let (req, body) = fuzz::decode_request_bytes(input).unwrap().unwrap();
+//! assert_eq!(req.url().as_str(), "http://example.com/");
+//! assert_eq!(body, b"hi");
+//! ```
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write, Cursor};
+use http_types::{Request, Response};
+
+/// Decode a request from `bytes` as the server would, and read its body to
+/// the end. Returns `None` if `bytes` is empty.
+///
+/// The default [`ServerOptions`](crate::ServerOptions) apply. Anything the
+/// server would write back, like `100 Continue`, is discarded.
+pub fn decode_request_bytes(bytes: &[u8]) -> http_types::Result<Option<(Request, Vec<u8>)>> {
+    future::block_on(async {
+        let (mut req, _) = match crate::server::decode(Input::new(bytes)).await? {
+            Some(decoded) => decoded,
+            None => return Ok(None),
+        };
+        let body = req.body_bytes().await?;
+        Ok(Some((req, body)))
+    })
+}
+
+/// Decode a response to a `GET` request from `bytes` as the client would,
+/// and read its body to the end.
+///
+/// The default [`ClientOptions`](crate::ClientOptions) apply.
+pub fn decode_response_bytes(bytes: &[u8]) -> http_types::Result<(Response, Vec<u8>)> {
+    future::block_on(async {
+        let mut res = crate::client::decode(Cursor::new(bytes.to_vec())).await?;
+        let body = res.body_bytes().await?;
+        Ok((res, body))
+    })
+}
+
+/// Decode a body sent with the chunked transfer-coding from `bytes`,
+/// returning the data it carries. Trailers are parsed and discarded.
+#[cfg(feature = "chunked")]
+pub fn decode_chunked_bytes(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    use futures_lite::io::{AsyncReadExt, BufReader};
+
+    let mut res = Response::new(200);
+    let reader = BufReader::new(Cursor::new(bytes.to_vec()));
+    let mut decoder = crate::chunked::ChunkedDecoder::new(reader, res.send_trailers());
+    future::block_on(async {
+        let mut body = Vec::new();
+        decoder.read_to_end(&mut body).await?;
+        Ok(body)
+    })
+}
+
+/// Bytes standing in for a connection, which discards what's written to
+/// it.
+#[derive(Debug, Clone)]
+struct Input(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+impl Input {
+    fn new(bytes: &[u8]) -> Self {
+        Self(Arc::new(Mutex::new(Cursor::new(bytes.to_vec()))))
+    }
+}
+
+impl Read for Input {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0.lock().unwrap()).poll_read(cx, buf)
+    }
+}
+
+impl Write for Input {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,172 @@
+//! Track the progress of reading a message head.
+//!
+//! The decoders in this crate read a head line by line until they see the
+//! empty line which ends it. [`HeadScanner`] exposes the same bookkeeping to
+//! custom read loops, so they can size their reads and report what they're
+//! waiting for.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::head::HeadScanner;
+//!
+//! let mut scanner = HeadScanner::new();
+//! assert_eq!(scanner.feed(b"GET / HTTP/1.1\r\nHost: example.com\r\n"), None);
+//! assert_eq!(scanner.bytes_needed(), 1);
+//!
+//! assert_eq!(scanner.feed(b"\r\nbody"), Some(2));
+//! assert!(scanner.is_complete());
+//! assert_eq!(scanner.bytes_buffered(), 37);
+//! ```
+
+/// Finds the end of a message head as its bytes arrive.
+///
+/// The head ends with an empty line. Lines may end in CRLF or, leniently, in
+/// a bare LF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeadScanner {
+    buffered: usize,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum State {
+    /// In the middle of a line, including the first one.
+    #[default]
+    Line,
+    /// At the start of a line.
+    LineStart,
+    /// At the start of a line, after a CR.
+    LineStartCr,
+    /// The head has ended.
+    Complete,
+}
+
+impl HeadScanner {
+    /// Create a scanner for a new head.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan bytes which were read after the previous ones.
+    ///
+    /// Returns the number of bytes up to and including the end of the head
+    /// if it ends within `bytes`. The bytes after it belong to the body and
+    /// aren't counted. Once the head is complete, further bytes are ignored.
+    pub fn feed(&mut self, bytes: &[u8]) -> Option<usize> {
+        if self.state == State::Complete {
+            return None;
+        }
+        for (i, byte) in bytes.iter().enumerate() {
+            self.state = match (self.state, byte) {
+                (State::LineStart | State::LineStartCr, b'\n') => State::Complete,
+                (State::LineStart, b'\r') => State::LineStartCr,
+                (_, b'\n') => State::LineStart,
+                _ => State::Line,
+            };
+            if self.state == State::Complete {
+                self.buffered += i + 1;
+                return Some(i + 1);
+            }
+        }
+        self.buffered += bytes.len();
+        None
+    }
+
+    /// The number of head bytes scanned so far.
+    pub fn bytes_buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Whether the end of the head has been seen.
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Complete
+    }
+
+    /// The minimum number of bytes which must still arrive before the head
+    /// can be complete, or 0 if it already is.
+    pub fn bytes_needed(&self) -> usize {
+        match self.state {
+            State::Line => 2,
+            State::LineStart | State::LineStartCr => 1,
+            State::Complete => 0,
+        }
+    }
+}
+
+/// Replace each `obs-fold` in a message head, i.e. a line break followed by
+/// whitespace continuing the previous header value, with a single space. RFC
+/// 9112 section 5.2 allows servers to, and requires clients to. `None` if the
+/// head has no folds.
+///
+/// Folds are rejected unless `unfold` is set, and whitespace directly after
+/// the start line is rejected either way.
+pub(crate) fn unfold(head: &[u8], unfold: bool) -> http_types::Result<Option<Vec<u8>>> {
+    let is_fold = |i: usize| head[i] == b'\n' && matches!(head.get(i + 1), Some(b' ' | b'\t'));
+    let first = match (0..head.len()).find(|&i| is_fold(i)) {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    let start_line_end = head.iter().position(|&byte| byte == b'\n');
+    ensure_kind!(
+        Some(first) != start_line_end,
+        MalformedMessage,
+        "Unexpected whitespace after the start line"
+    );
+    ensure_kind!(
+        unfold,
+        MalformedMessage,
+        "Header values folded across lines aren't allowed"
+    );
+
+    let mut unfolded = Vec::with_capacity(head.len());
+    let mut i = 0;
+    while i < head.len() {
+        if is_fold(i) {
+            while matches!(unfolded.last(), Some(b'\r' | b' ' | b'\t')) {
+                unfolded.pop();
+            }
+            unfolded.push(b' ');
+            i += 1;
+            while matches!(head.get(i), Some(b' ' | b'\t')) {
+                i += 1;
+            }
+        } else {
+            unfolded.push(head[i]);
+            i += 1;
+        }
+    }
+    Ok(Some(unfolded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeadScanner;
+
+    #[test]
+    fn finds_end_across_reads() {
+        let mut scanner = HeadScanner::new();
+        for (bytes, needed) in [(&b"HTTP/1.1 200 OK\r"[..], 2), (b"\n", 1), (b"\r", 1)] {
+            assert_eq!(scanner.feed(bytes), None);
+            assert_eq!(scanner.bytes_needed(), needed);
+        }
+        assert_eq!(scanner.feed(b"\nhello"), Some(1));
+        assert_eq!(scanner.bytes_buffered(), 19);
+        assert_eq!(scanner.bytes_needed(), 0);
+        assert_eq!(scanner.feed(b"\r\n\r\n"), None);
+        assert_eq!(scanner.bytes_buffered(), 19);
+    }
+
+    #[test]
+    fn accepts_bare_lf() {
+        let mut scanner = HeadScanner::new();
+        assert_eq!(scanner.feed(b"HTTP/1.1 200 OK\n\n"), Some(17));
+    }
+
+    #[test]
+    fn cr_within_line_is_not_an_end() {
+        let mut scanner = HeadScanner::new();
+        assert_eq!(scanner.feed(b"a\r\n\rb\r\n"), None);
+        assert!(!scanner.is_complete());
+    }
+}
@@ -19,8 +19,28 @@
 //! 4. decode            3. encode
 //! ```
 //!
-//! See also [`async-tls`](https://docs.rs/async-tls),
-//! [`async-std`](https://docs.rs/async-std).
+//! The crate doesn't depend on a particular runtime: it works with any
+//! stream implementing the `futures-io` traits, and timeouts are driven by
+//! [`async-io`](https://docs.rs/async-io), so it runs unmodified on
+//! async-std, smol, or a custom executor.
+//!
+//! With the `tls` feature, the `tls` module runs connections over a TLS
+//! implementation of your choice, such as
+//! [`futures-rustls`](https://docs.rs/futures-rustls).
+//!
+//! With the `sendfile` feature, on Linux, the `sendfile` module sends file
+//! bodies from the kernel straight to plain TCP sockets.
+//!
+//! With the `uds` feature, on Linux, `server::accept_unix` serves Unix
+//! domain sockets, telling endpoints which process is on the other end.
+//!
+//! With the `fuzz` feature, the `fuzz` module decodes messages from byte
+//! slices without a runtime, for fuzzing and property tests.
+//!
+//! With the `testing` feature, the `testing` module provides in-memory
+//! connections for testing endpoints without sockets.
+//!
+//! See also [`async-std`](https://docs.rs/async-std).
 //!
 //! # Example
 //!
@@ -94,29 +114,82 @@
 #![cfg_attr(test, deny(warnings))]
 #![allow(clippy::if_same_then_else)]
 #![allow(clippy::len_zero)]
+#![allow(clippy::manual_is_multiple_of)]
 #![allow(clippy::match_bool)]
 #![allow(clippy::unreadable_literal)]
 
-/// The maximum amount of headers parsed on the server.
+/// The default maximum amount of headers parsed.
 const MAX_HEADERS: usize = 128;
 
-/// The maximum length of the head section we'll try to parse.
+/// The default maximum length of the head section we'll try to parse.
 /// See: https://nodejs.org/en/blog/vulnerability/november-2018-security-releases/#denial-of-service-with-large-http-headers-cve-2018-12121
 const MAX_HEAD_LENGTH: usize = 233 * 1024;
 
+/// The default maximum length of a request line. Most servers and proxies
+/// accept around 8 KiB, so longer URLs wouldn't work reliably anyway.
+const MAX_REQUEST_LINE_LENGTH: usize = 8 * 1024;
+
+/// The default number of bytes copied to or from a stream before yielding
+/// to the executor.
+const POLL_BUDGET: usize = 64 * 1024;
+
+/// Forwards to `log::trace!` when the `log` feature is enabled, and compiles
+/// to nothing otherwise, without evaluating the arguments.
+macro_rules! trace {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        #[cfg(feature = "log")]
+        log::trace!($fmt $(, $arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = || { $(let _ = &$arg;)* };
+    };
+}
+
+#[macro_use]
+pub mod error;
+
 mod body_encoder;
+#[cfg(feature = "chunked")]
 mod chunked;
+mod copy;
 mod date;
-mod read_notifier;
+mod profile;
+mod timer;
 
+pub mod body;
 pub mod client;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod conditional;
+pub mod digest;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod head;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod negotiate;
+pub mod owned;
+pub mod range;
+#[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+pub mod sendfile;
 pub mod server;
+pub mod tee;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "tracing")]
+pub mod tracing;
 
-use async_std::io::Cursor;
 use body_encoder::BodyEncoder;
-pub use client::connect;
+pub use client::{connect, connect_with_opts, ClientOptions};
+use futures_lite::io::Cursor;
+use http_types::headers::HeaderValues;
+pub use profile::Profile;
 pub use server::{accept, accept_with_opts, ServerOptions};
 
+// Encoders spend nearly all their time in the body state, so boxing it would
+// only add an allocation.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub(crate) enum EncoderState {
     Start,
@@ -125,6 +198,16 @@ pub(crate) enum EncoderState {
     End,
 }
 
+/// Whether a `Connection` header lists `option`, such as `close`.
+pub(crate) fn has_connection_option(connection: Option<&HeaderValues>, option: &str) -> bool {
+    connection.is_some_and(|values| {
+        values
+            .iter()
+            .flat_map(|value| value.as_str().split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case(option))
+    })
+}
+
 /// like ready! but early-returns the Poll<Result<usize>> early in all situations other than Ready(Ok(0))
 #[macro_export]
 macro_rules! read_to_end {
@@ -0,0 +1,193 @@
+//! Hooks for exporting metrics.
+//!
+//! Implement [`Metrics`] to forward measurements to an exporter such as
+//! Prometheus or StatsD, and pass it to the server through
+//! [`ServerOptions::with_metrics`](crate::ServerOptions::with_metrics) or to
+//! the client through
+//! [`ClientOptions::with_metrics`](crate::ClientOptions::with_metrics).
+//!
+//! All methods have no-op default implementations, so exporters only need to
+//! implement the kinds of measurements they support.
+
+use std::fmt::Debug;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write};
+use http_types::{Method, StatusCode};
+
+/// Number of connections accepted by the server.
+pub const SERVER_CONNECTIONS: &str = "http.server.connections";
+/// Number of connections currently open on the server.
+pub const SERVER_ACTIVE_CONNECTIONS: &str = "http.server.active_connections";
+/// Number of requests served, labeled by `method` and `status`.
+pub const SERVER_REQUESTS: &str = "http.server.requests";
+/// Time in seconds from decoding a request head to finishing its response.
+pub const SERVER_REQUEST_DURATION: &str = "http.server.request.duration";
+/// Number of response bytes written by the server.
+pub const SERVER_BYTES_WRITTEN: &str = "http.server.bytes_written";
+/// Number of request bytes read by the server, heads and bodies included.
+pub const SERVER_BYTES_READ: &str = "http.server.bytes_read";
+/// Time in seconds from decoding a request head to writing the first byte
+/// of its response, labeled by `method` and `status`.
+pub const SERVER_TIME_TO_FIRST_BYTE: &str = "http.server.time_to_first_byte";
+/// Number of requests sent by the client, labeled by `method` and `status`.
+pub const CLIENT_REQUESTS: &str = "http.client.requests";
+/// Time in seconds from encoding a request to decoding its response head.
+pub const CLIENT_REQUEST_DURATION: &str = "http.client.request.duration";
+
+/// Labels attached to a measurement.
+pub type Labels<'a> = &'a [(&'static str, &'a str)];
+
+/// Receives measurements from the server and the client.
+pub trait Metrics: Debug + Send + Sync + 'static {
+    /// Increment a counter.
+    fn increment_counter(&self, _name: &'static str, _value: u64, _labels: Labels<'_>) {}
+
+    /// Add `delta` to a gauge, which may be negative.
+    fn adjust_gauge(&self, _name: &'static str, _delta: f64, _labels: Labels<'_>) {}
+
+    /// Record a value in a histogram.
+    fn record_histogram(&self, _name: &'static str, _value: f64, _labels: Labels<'_>) {}
+}
+
+/// A [`Metrics`] implementation which discards all measurements.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+/// Counts a server connection as active for as long as it's alive.
+#[derive(Debug)]
+pub(crate) struct ActiveConnection(Arc<dyn Metrics>);
+
+impl ActiveConnection {
+    pub(crate) fn new(metrics: Arc<dyn Metrics>) -> Self {
+        metrics.increment_counter(SERVER_CONNECTIONS, 1, &[]);
+        metrics.adjust_gauge(SERVER_ACTIVE_CONNECTIONS, 1.0, &[]);
+        Self(metrics)
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.0.adjust_gauge(SERVER_ACTIVE_CONNECTIONS, -1.0, &[]);
+    }
+}
+
+pub(crate) use crate::timer::now;
+
+/// Record a request/response exchange.
+pub(crate) fn record_exchange(
+    metrics: &dyn Metrics,
+    (requests, duration): (&'static str, &'static str),
+    method: Method,
+    status: StatusCode,
+    started: Option<Instant>,
+) {
+    let status = (status as u16).to_string();
+    let labels = [("method", method.as_ref()), ("status", status.as_str())];
+    metrics.increment_counter(requests, 1, &labels);
+    if let Some(started) = started {
+        metrics.record_histogram(duration, started.elapsed().as_secs_f64(), &labels);
+    }
+}
+
+/// A stream which counts the bytes read from it, in a counter shared by
+/// its clones.
+#[derive(Debug, Clone)]
+pub(crate) struct CountedReads<IO> {
+    io: IO,
+    read: Arc<AtomicU64>,
+}
+
+impl<IO> CountedReads<IO> {
+    pub(crate) fn new(io: IO, read: Arc<AtomicU64>) -> Self {
+        Self { io, read }
+    }
+}
+
+impl<IO: Read + Unpin> Read for CountedReads<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.io).poll_read(cx, buf);
+        if let Poll::Ready(Ok(bytes)) = poll {
+            self.read.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+impl<IO: Write + Unpin> Write for CountedReads<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_close(cx)
+    }
+}
+
+/// A writer which notes when the first byte was written to it.
+#[derive(Debug)]
+pub(crate) struct FirstWrite<W> {
+    writer: W,
+    pub(crate) at: Option<Instant>,
+}
+
+impl<W> FirstWrite<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self { writer, at: None }
+    }
+
+    fn note(&mut self, poll: &Poll<io::Result<usize>>) {
+        if let (Poll::Ready(Ok(1..)), None) = (poll, self.at) {
+            self.at = now();
+        }
+    }
+}
+
+impl<W: Write + Unpin> Write for FirstWrite<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.writer).poll_write(cx, buf);
+        self.note(&poll);
+        poll
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.writer).poll_write_vectored(cx, bufs);
+        self.note(&poll);
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_close(cx)
+    }
+}
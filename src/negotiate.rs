@@ -0,0 +1,236 @@
+//! Content negotiation.
+//!
+//! [`Preferences`] parses the `Accept`, `Accept-Encoding` and
+//! `Accept-Language` headers of a request, with their quality values
+//! ([RFC 9110, section 12.5](https://www.rfc-editor.org/rfc/rfc9110#section-12.5)),
+//! and chooses which of the representations a server can produce the client
+//! would like best.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::negotiate::Preferences;
+//! use http_types::{Method, Request, Url};
+//!
+//! let mut req = Request::new(Method::Get, Url::parse("http://example.com/").unwrap());
+//! req.insert_header("accept", "text/*;q=0.5, application/json");
+//! req.insert_header("accept-language", "de-CH, en;q=0.8");
+//!
+//! let accept = Preferences::accept(&req);
+//! assert_eq!(accept.choose(["text/html", "application/json"]), Some("application/json"));
+//!
+//! let accept_language = Preferences::accept_language(&req);
+//! assert_eq!(accept_language.choose(["en-US", "fr"]), Some("en-US"));
+//! ```
+
+use http_types::headers::{HeaderValues, Headers, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE};
+
+/// The quality of `identity` when `Accept-Encoding` doesn't mention it:
+/// acceptable, but less so than anything the client listed.
+const IMPLICIT_IDENTITY: f32 = 0.001;
+
+/// The preferences a request states in one of its `Accept*` headers.
+///
+/// A missing header accepts anything, while a header which is present only
+/// accepts what it lists.
+#[derive(Debug, Clone)]
+pub struct Preferences {
+    kind: Kind,
+    ranges: Option<Vec<Range>>,
+}
+
+/// Which header the preferences come from, deciding how candidates are
+/// matched against them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// `Accept`: media ranges such as `text/*;q=0.5`.
+    MediaType,
+    /// `Accept-Encoding`: content-codings such as `gzip`.
+    Encoding,
+    /// `Accept-Language`: language ranges such as `en`.
+    Language,
+}
+
+/// One entry of an `Accept*` header.
+#[derive(Debug, Clone)]
+struct Range {
+    value: String,
+    params: Vec<(String, String)>,
+    quality: f32,
+}
+
+impl Preferences {
+    /// The media types accepted by a request's `Accept` header.
+    ///
+    /// Ranges match the candidates they're most specific for, so
+    /// `text/html;level=1` takes precedence over `text/html`, which takes
+    /// precedence over `text/*` and `*/*`.
+    pub fn accept(headers: impl AsRef<Headers>) -> Self {
+        Self::new(Kind::MediaType, headers.as_ref().get(ACCEPT))
+    }
+
+    /// The content-codings accepted by a request's `Accept-Encoding` header.
+    ///
+    /// `identity` is acceptable unless the header excludes it, explicitly
+    /// or through `*;q=0`.
+    pub fn accept_encoding(headers: impl AsRef<Headers>) -> Self {
+        Self::new(Kind::Encoding, headers.as_ref().get(ACCEPT_ENCODING))
+    }
+
+    /// The languages accepted by a request's `Accept-Language` header.
+    ///
+    /// Ranges match the language tags they're a prefix of, so `en` matches
+    /// `en-US`, and the longest matching range decides.
+    pub fn accept_language(headers: impl AsRef<Headers>) -> Self {
+        Self::new(Kind::Language, headers.as_ref().get(ACCEPT_LANGUAGE))
+    }
+
+    pub(crate) fn new(kind: Kind, values: Option<&HeaderValues>) -> Self {
+        let ranges = values.map(|values| {
+            values
+                .iter()
+                .flat_map(|value| value.as_str().split(','))
+                .filter_map(Range::parse)
+                .collect()
+        });
+        Self { kind, ranges }
+    }
+
+    /// Whether the request had the header at all.
+    pub fn is_present(&self) -> bool {
+        self.ranges.is_some()
+    }
+
+    /// The quality, from 0 to 1, the client gives a candidate. A quality of
+    /// 0 means the candidate isn't acceptable.
+    pub fn quality(&self, candidate: &str) -> f32 {
+        let ranges = match &self.ranges {
+            Some(ranges) => ranges,
+            None => return 1.0,
+        };
+        // The first of the most specific matching ranges decides.
+        let mut best: Option<(usize, f32)> = None;
+        for range in ranges {
+            if let Some(specificity) = range.specificity(self.kind, candidate) {
+                let more_specific = match best {
+                    Some((best, _)) => specificity > best,
+                    None => true,
+                };
+                if more_specific {
+                    best = Some((specificity, range.quality));
+                }
+            }
+        }
+        match best {
+            Some((_, quality)) => quality,
+            None if self.kind == Kind::Encoding && candidate.eq_ignore_ascii_case("identity") => {
+                IMPLICIT_IDENTITY
+            }
+            None => 0.0,
+        }
+    }
+
+    /// The acceptable candidate with the highest quality, or the first of
+    /// those if several share it, so candidates should be listed in the
+    /// server's order of preference. `None` if none is acceptable.
+    pub fn choose<'a>(&self, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        let mut best: Option<(&str, f32)> = None;
+        for candidate in candidates {
+            let quality = self.quality(candidate);
+            if quality > best.map_or(0.0, |(_, best)| best) {
+                best = Some((candidate, quality));
+            }
+        }
+        best.map(|(candidate, _)| candidate)
+    }
+}
+
+impl Range {
+    /// Parses one comma-separated entry, skipping empty ones.
+    fn parse(item: &str) -> Option<Self> {
+        let mut parts = item.split(';').map(str::trim);
+        let value = parts.next().filter(|value| !value.is_empty())?;
+        let mut params = vec![];
+        let mut quality = 1.0;
+        for param in parts {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            let (name, value) = (name.trim(), value.trim().trim_matches('"'));
+            if name.eq_ignore_ascii_case("q") {
+                // Anything after the quality is an extension parameter.
+                quality = parse_quality(value).unwrap_or(0.0);
+                break;
+            }
+            params.push((name.to_ascii_lowercase(), value.to_owned()));
+        }
+        Some(Self {
+            value: value.to_owned(),
+            params,
+            quality,
+        })
+    }
+
+    /// How specifically this range matches a candidate, higher being more
+    /// specific, or `None` if it doesn't match at all.
+    fn specificity(&self, kind: Kind, candidate: &str) -> Option<usize> {
+        match kind {
+            Kind::Encoding if self.value == "*" => Some(0),
+            Kind::Encoding => self.value.eq_ignore_ascii_case(candidate).then_some(1),
+            Kind::Language if self.value == "*" => Some(0),
+            Kind::Language => {
+                let prefix = candidate.get(..self.value.len())?;
+                let rest = &candidate[self.value.len()..];
+                (prefix.eq_ignore_ascii_case(&self.value)
+                    && (rest.is_empty() || rest.starts_with('-')))
+                .then_some(self.value.len())
+            }
+            Kind::MediaType => self.media_type_specificity(candidate),
+        }
+    }
+
+    fn media_type_specificity(&self, candidate: &str) -> Option<usize> {
+        let candidate = Range::parse(candidate)?;
+        let (ty, subtype) = split_media_type(&self.value)?;
+        let (candidate_ty, candidate_subtype) = split_media_type(&candidate.value)?;
+
+        let mut specificity = 0;
+        if ty != "*" {
+            ty.eq_ignore_ascii_case(candidate_ty).then_some(())?;
+            specificity += 1;
+        }
+        if subtype != "*" {
+            subtype
+                .eq_ignore_ascii_case(candidate_subtype)
+                .then_some(())?;
+            specificity += 1;
+        }
+        for (name, value) in &self.params {
+            candidate
+                .params
+                .iter()
+                .any(|(n, v)| n == name && v.eq_ignore_ascii_case(value))
+                .then_some(())?;
+        }
+        Some(specificity + self.params.len())
+    }
+}
+
+/// Parse a quality value, which RFC 9110 section 12.4.2 restricts to 0 to 1
+/// with at most three decimals.
+fn parse_quality(value: &str) -> Option<f32> {
+    let (whole, decimals) = value.split_once('.').unwrap_or((value, ""));
+    let valid = match whole {
+        "0" => decimals.bytes().all(|byte| byte.is_ascii_digit()),
+        "1" => decimals.bytes().all(|byte| byte == b'0'),
+        _ => false,
+    };
+    if valid && decimals.len() <= 3 {
+        value.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn split_media_type(media_type: &str) -> Option<(&str, &str)> {
+    let (ty, subtype) = media_type.split_once('/')?;
+    Some((ty.trim(), subtype.trim()))
+}
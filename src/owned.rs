@@ -0,0 +1,326 @@
+//! Drive the protocol with owned buffers.
+//!
+//! Completion-based IO runtimes, such as those built on io_uring or IOCP,
+//! hand buffers to the kernel and get them back once an operation is done,
+//! so they can't lend out a `&mut [u8]`. This module bridges them to this
+//! crate:
+//!
+//! - [`Encoder::read_owned`](crate::server::Encoder::read_owned) and its
+//!   client counterpart encode straight into an owned buffer, ready to be
+//!   submitted as a write.
+//! - [`OwnedIo`] turns a stream implementing [`OwnedRead`] and
+//!   [`OwnedWrite`] into an `AsyncRead + AsyncWrite` which the decoders and
+//!   [`accept`](crate::accept) accept. Wrap it in an
+//!   `async_dup::Arc<async_dup::Mutex<_>>` where a cloneable stream is
+//!   needed.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::owned::{BufResult, OwnedRead, OwnedWrite};
+//! use std::future::Future;
+//! use std::pin::Pin;
+//!
+//! /// A stand-in for a completion-based socket.
+//! struct Socket;
+//!
+//! impl OwnedRead for Socket {
+//!     fn read_owned(
+//!         &mut self,
+//!         buf: Vec<u8>,
+//!     ) -> Pin<Box<dyn Future<Output = BufResult<usize>> + Send>> {
+//!         // Submit `buf` to the kernel and resolve once it's been filled.
+//!         Box::pin(async move { (Ok(0), buf) })
+//!     }
+//! }
+//!
+//! impl OwnedWrite for Socket {
+//!     fn write_owned(
+//!         &mut self,
+//!         buf: Vec<u8>,
+//!     ) -> Pin<Box<dyn Future<Output = BufResult<usize>> + Send>> {
+//!         let len = buf.len();
+//!         Box::pin(async move { (Ok(len), buf) })
+//!     }
+//! }
+//!
+//! let io = async_h1::owned::OwnedIo::new(Socket);
+//! ```
+
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::ready;
+use futures_lite::io::{self, AsyncRead as Read, AsyncReadExt, AsyncWrite as Write};
+
+/// The result of an operation on an owned buffer, along with the buffer.
+pub type BufResult<T> = (io::Result<T>, Vec<u8>);
+
+type BufFuture = Pin<Box<dyn Future<Output = BufResult<usize>> + Send>>;
+
+/// The default capacity of the buffers used by [`OwnedIo`].
+const BUF_CAPACITY: usize = 8 * 1024;
+
+/// A stream which reads into owned buffers.
+pub trait OwnedRead {
+    /// Read into the spare capacity of `buf`, after its current contents,
+    /// returning the number of bytes read and the buffer.
+    ///
+    /// Reading 0 bytes into a buffer with spare capacity means the stream
+    /// has ended.
+    fn read_owned(
+        &mut self,
+        buf: Vec<u8>,
+    ) -> Pin<Box<dyn Future<Output = BufResult<usize>> + Send>>;
+}
+
+/// A stream which writes from owned buffers.
+pub trait OwnedWrite {
+    /// Write from `buf`, returning the number of bytes written and the
+    /// buffer.
+    fn write_owned(
+        &mut self,
+        buf: Vec<u8>,
+    ) -> Pin<Box<dyn Future<Output = BufResult<usize>> + Send>>;
+}
+
+/// Adapts a stream using owned buffers to [`AsyncRead`](Read) and
+/// [`AsyncWrite`](Write).
+///
+/// Reads are served from a buffer refilled by [`OwnedRead::read_owned`].
+/// Writes are collected in a buffer which is submitted to
+/// [`OwnedWrite::write_owned`] as soon as the previous write has completed,
+/// and on flush. An error from a submitted write is returned by the next
+/// write or flush.
+pub struct OwnedIo<T> {
+    io: T,
+    read: ReadState,
+    write: WriteState,
+}
+
+enum ReadState {
+    /// Bytes read but not yet handed out.
+    Idle(Vec<u8>, usize),
+    Reading(BufFuture),
+}
+
+enum WriteState {
+    /// Bytes waiting to be submitted.
+    Idle(Vec<u8>),
+    Writing(BufFuture),
+    Failed(io::Error),
+}
+
+impl<T> OwnedIo<T> {
+    /// Wrap a stream.
+    pub fn new(io: T) -> Self {
+        Self {
+            io,
+            read: ReadState::Idle(Vec::with_capacity(BUF_CAPACITY), 0),
+            write: WriteState::Idle(Vec::with_capacity(BUF_CAPACITY)),
+        }
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+}
+
+impl<T> Debug for OwnedIo<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedIo").finish_non_exhaustive()
+    }
+}
+
+impl<T: OwnedRead + Unpin> Read for OwnedIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        loop {
+            match &mut this.read {
+                ReadState::Idle(data, pos) if *pos < data.len() => {
+                    let bytes = buf.len().min(data.len() - *pos);
+                    buf[..bytes].copy_from_slice(&data[*pos..*pos + bytes]);
+                    *pos += bytes;
+                    return Poll::Ready(Ok(bytes));
+                }
+                ReadState::Idle(data, _) => {
+                    let mut data = mem::take(data);
+                    data.clear();
+                    data.reserve(buf.len().max(BUF_CAPACITY));
+                    this.read = ReadState::Reading(this.io.read_owned(data));
+                }
+                ReadState::Reading(fut) => {
+                    let (res, data) = ready!(fut.as_mut().poll(cx));
+                    this.read = ReadState::Idle(data, 0);
+                    if res? == 0 {
+                        return Poll::Ready(Ok(0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: OwnedWrite + Unpin> OwnedIo<T> {
+    /// Wait for the write in flight, if any, to complete.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            match &mut self.write {
+                WriteState::Idle(_) => return Poll::Ready(Ok(())),
+                WriteState::Failed(_) => {
+                    let state = mem::replace(&mut self.write, WriteState::Idle(Vec::new()));
+                    if let WriteState::Failed(err) = state {
+                        return Poll::Ready(Err(err));
+                    }
+                }
+                WriteState::Writing(fut) => {
+                    let (res, mut data) = ready!(fut.as_mut().poll(cx));
+                    match res {
+                        Ok(0) if !data.is_empty() => {
+                            self.write = WriteState::Failed(io::ErrorKind::WriteZero.into());
+                        }
+                        Ok(bytes) if bytes < data.len() => {
+                            data.drain(..bytes);
+                            self.write = WriteState::Writing(self.io.write_owned(data));
+                        }
+                        Ok(_) => {
+                            data.clear();
+                            self.write = WriteState::Idle(data);
+                        }
+                        Err(err) => self.write = WriteState::Failed(err),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Submit the buffered bytes, if any.
+    fn submit(&mut self) {
+        if let WriteState::Idle(data) = &mut self.write {
+            if !data.is_empty() {
+                let data = mem::take(data);
+                self.write = WriteState::Writing(self.io.write_owned(data));
+            }
+        }
+    }
+}
+
+impl<T: OwnedWrite + Unpin> Write for OwnedIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if let WriteState::Idle(data) = &mut this.write {
+            if data.len() >= BUF_CAPACITY {
+                this.submit();
+            }
+        }
+        ready!(this.poll_idle(cx))?;
+        if let WriteState::Idle(data) = &mut this.write {
+            data.extend_from_slice(buf);
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        ready!(this.poll_idle(cx))?;
+        this.submit();
+        this.poll_idle(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Read from `reader` into the spare capacity of `buf`.
+pub(crate) async fn read_owned<R>(reader: &mut R, mut buf: Vec<u8>) -> BufResult<usize>
+where
+    R: Read + Unpin,
+{
+    let len = buf.len();
+    buf.resize(buf.capacity(), 0);
+    let res = reader.read(&mut buf[len..]).await;
+    buf.truncate(len + res.as_ref().map_or(0, |bytes| *bytes));
+    (res, buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future;
+    use futures_lite::io::{AsyncReadExt, AsyncWriteExt};
+    use std::sync::{Arc, Mutex};
+
+    /// Reads from fixed chunks, and writes at most 3 bytes at a time.
+    #[derive(Default)]
+    struct Chunks {
+        input: Vec<&'static [u8]>,
+        output: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl OwnedRead for Chunks {
+        fn read_owned(&mut self, mut buf: Vec<u8>) -> BufFuture {
+            let chunk = if self.input.is_empty() {
+                &[][..]
+            } else {
+                self.input.remove(0)
+            };
+            buf.extend_from_slice(chunk);
+            Box::pin(async move { (Ok(chunk.len()), buf) })
+        }
+    }
+
+    impl OwnedWrite for Chunks {
+        fn write_owned(&mut self, buf: Vec<u8>) -> BufFuture {
+            let bytes = buf.len().min(3);
+            self.output.lock().unwrap().extend_from_slice(&buf[..bytes]);
+            Box::pin(async move { (Ok(bytes), buf) })
+        }
+    }
+
+    #[test]
+    fn reads_chunks() {
+        let chunks = Chunks {
+            input: vec![b"hello ", b"world"],
+            ..Chunks::default()
+        };
+        let mut out = String::new();
+        future::block_on(OwnedIo::new(chunks).read_to_string(&mut out)).unwrap();
+        assert_eq!(out, "hello world");
+    }
+
+    #[test]
+    fn writes_partially() {
+        let chunks = Chunks::default();
+        let output = chunks.output.clone();
+        let mut io = OwnedIo::new(chunks);
+        future::block_on(async {
+            io.write_all(b"hello ").await?;
+            io.write_all(b"world").await?;
+            io.flush().await
+        })
+        .unwrap();
+        assert_eq!(*output.lock().unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn reads_into_spare_capacity() {
+        let mut buf = Vec::with_capacity(8);
+        buf.extend_from_slice(b"ab");
+        let (res, buf) = future::block_on(read_owned(&mut &b"cdefghijk"[..], buf));
+        assert_eq!(res.unwrap(), buf.capacity() - 2);
+        assert_eq!(&buf[..5], b"abcde");
+    }
+}
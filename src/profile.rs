@@ -0,0 +1,142 @@
+//! Preset configurations.
+
+use std::time::Duration;
+
+use crate::{MAX_HEADERS, MAX_HEAD_LENGTH, MAX_REQUEST_LINE_LENGTH};
+
+/// A named preset bundling values for all limits and strictness flags.
+///
+/// [`Balanced`](Profile::Balanced) holds the default values of the
+/// options. Clients apply the settings which concern responses.
+///
+/// Profiles are a starting point: apply one with
+/// [`ServerOptions::with_profile`](crate::ServerOptions::with_profile) or
+/// [`ClientOptions::with_profile`](crate::ClientOptions::with_profile), then
+/// override individual settings as needed.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::{Profile, ServerOptions};
+/// use std::time::Duration;
+///
+/// let opts = ServerOptions::new()
+///     .with_profile(Profile::Strict)
+///     .with_headers_timeout(Some(Duration::from_secs(5)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Profile {
+    /// Tight limits for servers exposed to untrusted clients.
+    Strict,
+    /// Limits suitable for most applications. This is the default.
+    #[default]
+    Balanced,
+    /// Generous limits for trusted peers and unusual workloads.
+    Lenient,
+}
+
+impl Profile {
+    /// How long to wait for a request head to arrive.
+    pub(crate) fn headers_timeout(self) -> Option<Duration> {
+        match self {
+            Profile::Strict => Some(Duration::from_secs(10)),
+            Profile::Balanced => Some(Duration::from_secs(60)),
+            Profile::Lenient => Some(Duration::from_secs(300)),
+        }
+    }
+
+    /// How long a keep-alive connection may wait for the next request.
+    pub(crate) fn idle_timeout(self) -> Option<Duration> {
+        match self {
+            Profile::Strict => Some(Duration::from_secs(5)),
+            Profile::Balanced | Profile::Lenient => None,
+        }
+    }
+
+    /// How long reading a body may go without progress.
+    pub(crate) fn body_timeout(self) -> Option<Duration> {
+        match self {
+            Profile::Strict => Some(Duration::from_secs(10)),
+            Profile::Balanced | Profile::Lenient => None,
+        }
+    }
+
+    /// How long writing a message may go without progress.
+    pub(crate) fn write_timeout(self) -> Option<Duration> {
+        match self {
+            Profile::Strict => Some(Duration::from_secs(10)),
+            Profile::Balanced | Profile::Lenient => None,
+        }
+    }
+
+    /// The maximum length of a message head in bytes.
+    pub(crate) fn max_head_length(self) -> usize {
+        match self {
+            Profile::Strict => 16 * 1024,
+            Profile::Balanced => MAX_HEAD_LENGTH,
+            Profile::Lenient => 1024 * 1024,
+        }
+    }
+
+    /// The maximum length of a request line in bytes.
+    pub(crate) fn max_request_line_length(self) -> usize {
+        match self {
+            Profile::Strict => 4 * 1024,
+            Profile::Balanced => MAX_REQUEST_LINE_LENGTH,
+            Profile::Lenient => 64 * 1024,
+        }
+    }
+
+    /// The maximum length of a response head generated by the server.
+    pub(crate) fn max_response_head_length(self) -> usize {
+        match self {
+            Profile::Strict => 64 * 1024,
+            Profile::Balanced => 1024 * 1024,
+            Profile::Lenient => 16 * 1024 * 1024,
+        }
+    }
+
+    /// The maximum number of header fields in a message head.
+    pub(crate) fn max_headers(self) -> usize {
+        match self {
+            Profile::Strict => 64,
+            Profile::Balanced => MAX_HEADERS,
+            Profile::Lenient => 512,
+        }
+    }
+
+    /// Whether to unfold header values folded across lines, instead of
+    /// rejecting them.
+    pub(crate) fn unfold_headers(self) -> bool {
+        matches!(self, Profile::Lenient)
+    }
+
+    /// The maximum number of bytes a connection may buffer.
+    pub(crate) fn max_connection_memory(self) -> Option<usize> {
+        match self {
+            Profile::Strict => Some(1024 * 1024),
+            Profile::Balanced | Profile::Lenient => None,
+        }
+    }
+
+    /// Whether to reject responses which aren't valid UTF-8.
+    pub(crate) fn strict_utf8(self) -> bool {
+        matches!(self, Profile::Strict)
+    }
+
+    /// The number of requests served on a connection before closing it.
+    pub(crate) fn max_requests_per_connection(self) -> Option<usize> {
+        match self {
+            Profile::Strict => Some(100),
+            Profile::Balanced | Profile::Lenient => None,
+        }
+    }
+
+    /// The maximum length of a request body in bytes.
+    pub(crate) fn max_body_size(self) -> Option<u64> {
+        match self {
+            Profile::Strict => Some(1024 * 1024),
+            Profile::Balanced | Profile::Lenient => None,
+        }
+    }
+}
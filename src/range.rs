@@ -0,0 +1,487 @@
+//! Parse `Range` and `If-Range` request headers, and respond to them.
+//!
+//! [`ranges`] works out which byte ranges of a resource a request asks for
+//! ([RFC 9110, section 14](https://www.rfc-editor.org/rfc/rfc9110#section-14)),
+//! validated against the resource's length. [`respond`] goes on to build
+//! the `206 Partial Content` response from a seekable body.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::range::{self, ByteRange};
+//! use http_types::{Method, Request, Url};
+//!
+//! let mut req = Request::new(Method::Get, Url::parse("http://example.com/").unwrap());
+//! req.insert_header("range", "bytes=0-99, -100");
+//!
+//! let ranges = range::ranges(&req, 1000, None, None).unwrap().unwrap();
+//! assert_eq!(ranges, [ByteRange::new(0, 99), ByteRange::new(900, 999)]);
+//! assert_eq!(ranges[1].content_range(1000), "bytes 900-999/1000");
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{BuildHasher, Hasher};
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use futures_lite::io::{self, AsyncRead as Read, AsyncSeek as Seek, BufReader};
+use futures_lite::ready;
+use http_types::headers::{
+    ACCEPT_RANGES, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED,
+};
+use http_types::{Body, Method, Request, Response, StatusCode};
+
+use crate::date::parse_http_date;
+
+/// An inclusive range of byte positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    /// Create a range from `start` to `end`, both inclusive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end` is before `start`.
+    pub fn new(start: u64, end: u64) -> Self {
+        assert!(start <= end, "range ends before it starts");
+        Self { start, end }
+    }
+
+    /// The position of the first byte.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The position of the last byte.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// The number of bytes in the range.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// The `Content-Range` value describing this range of a resource of
+    /// `complete_length` bytes.
+    pub fn content_range(&self, complete_length: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, complete_length)
+    }
+}
+
+/// None of the requested ranges overlap the resource. The server should
+/// respond with `416 Range Not Satisfiable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsatisfiable;
+
+impl Unsatisfiable {
+    /// The `Content-Range` value to send with the `416` response for a
+    /// resource of `complete_length` bytes.
+    pub fn content_range(&self, complete_length: u64) -> String {
+        format!("bytes */{}", complete_length)
+    }
+}
+
+impl Display for Unsatisfiable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("none of the requested ranges are satisfiable")
+    }
+}
+
+impl std::error::Error for Unsatisfiable {}
+
+/// The byte ranges a request asks for from a resource of `len` bytes.
+///
+/// Returns `Ok(None)` if the whole resource should be sent: the request
+/// isn't a `GET`, it has no `Range` header, the header can't be parsed or
+/// uses a unit other than bytes, or its `If-Range` condition doesn't match
+/// the resource's validators. `etag` is the resource's entity-tag including
+/// its quotes.
+pub fn ranges(
+    req: &Request,
+    len: u64,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Result<Option<Vec<ByteRange>>, Unsatisfiable> {
+    if req.method() != Method::Get {
+        return Ok(None);
+    }
+    let range = match req.header("range") {
+        Some(range) => range.last().as_str(),
+        None => return Ok(None),
+    };
+    if !if_range_matches(req, etag, last_modified) {
+        return Ok(None);
+    }
+    parse_range(range, len)
+}
+
+/// Whether a request's `If-Range` condition holds, so that its `Range`
+/// header applies. Requests without `If-Range` always match.
+///
+/// Entity-tags are compared strongly, so weak tags never match, and dates
+/// must match the last modification time exactly.
+pub fn if_range_matches(
+    req: &Request,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    let if_range = match req.header(IF_RANGE) {
+        Some(if_range) => if_range.last().as_str().trim(),
+        None => return true,
+    };
+
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return match etag {
+            Some(etag) => !etag.starts_with("W/") && etag == if_range,
+            None => false,
+        };
+    }
+
+    match (parse_http_date(if_range), last_modified) {
+        (Ok(date), Some(last_modified)) => {
+            let seconds = |time: SystemTime| {
+                time.duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .ok()
+            };
+            seconds(date) == seconds(last_modified)
+        }
+        _ => false,
+    }
+}
+
+/// Parse a `Range` header value for a resource of `len` bytes.
+///
+/// Returns `Ok(None)` if the value can't be parsed, has no ranges at all or
+/// uses a unit other than bytes, in which case it should be ignored. Ranges which start past the
+/// end of the resource are dropped; if that leaves none, the request is
+/// [`Unsatisfiable`]. Ranges which extend past the end are truncated.
+pub fn parse_range(value: &str, len: u64) -> Result<Option<Vec<ByteRange>>, Unsatisfiable> {
+    let specs = match value.trim().split_once('=') {
+        Some((unit, specs)) if unit.trim().eq_ignore_ascii_case("bytes") => specs,
+        _ => return Ok(None),
+    };
+
+    let mut ranges = vec![];
+    let mut parsed = false;
+    for spec in specs.split(',').map(str::trim) {
+        if spec.is_empty() {
+            continue;
+        }
+        parsed = true;
+        let (first, last) = match spec.split_once('-') {
+            Some(bounds) => bounds,
+            None => return Ok(None),
+        };
+        let (first, last) = (first.trim(), last.trim());
+
+        let range = if first.is_empty() {
+            // A suffix range: the last `n` bytes.
+            match parse_pos(last) {
+                Some(0) => None,
+                Some(n) if len > 0 => Some(ByteRange::new(len.saturating_sub(n), len - 1)),
+                Some(_) => None,
+                None => return Ok(None),
+            }
+        } else {
+            let first = match parse_pos(first) {
+                Some(first) => first,
+                None => return Ok(None),
+            };
+            let last = if last.is_empty() {
+                u64::MAX
+            } else {
+                match parse_pos(last) {
+                    Some(last) if last >= first => last,
+                    _ => return Ok(None),
+                }
+            };
+            if first < len {
+                Some(ByteRange::new(first, last.min(len - 1)))
+            } else {
+                None
+            }
+        };
+        ranges.extend(range);
+    }
+
+    match (parsed, ranges.is_empty()) {
+        // A value without any range-spec isn't valid either.
+        (false, _) => Ok(None),
+        (true, true) => Err(Unsatisfiable),
+        (true, false) => Ok(Some(ranges)),
+    }
+}
+
+/// Respond to `req` with the parts of a resource it asks for.
+///
+/// `res` is the response which would send the whole resource, whose `len`
+/// bytes are read from `body`: its status and headers, including the
+/// `Content-Type` and the `ETag` and `Last-Modified` validators which
+/// `If-Range` conditions are checked against. Depending on the request's
+/// `Range` header, it's returned
+///
+/// - with the whole of `body`, if the request doesn't ask for ranges or
+///   they don't apply, as explained for [`ranges`];
+/// - as `206 Partial Content` with one range and its `Content-Range`;
+/// - as `206 Partial Content` with a `multipart/byteranges` body, if it
+///   asks for several ranges which don't overlap;
+/// - as `416 Range Not Satisfiable` without a body, if none of the ranges
+///   overlap the resource.
+///
+/// Overlapping and adjacent ranges are merged. Only the requested bytes are
+/// read from `body`, seeking to each range in turn, and the response's
+/// length is worked out up front so it's sent with a `Content-Length`.
+/// Responses other than `200 OK` are returned as they are.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::range;
+/// use futures_lite::io::Cursor;
+/// use http_types::{Method, Request, Response, StatusCode, Url};
+///
+/// # futures_lite::future::block_on(async {
+/// let mut req = Request::new(Method::Get, Url::parse("http://example.com/").unwrap());
+/// req.insert_header("range", "bytes=6-");
+///
+/// let file = Cursor::new(b"hello world".to_vec());
+/// let mut res = range::respond(&req, Response::new(StatusCode::Ok), file, 11);
+/// assert_eq!(res.status(), StatusCode::PartialContent);
+/// assert_eq!(res["content-range"], "bytes 6-10/11");
+/// assert_eq!(res.body_string().await.unwrap(), "world");
+/// # });
+/// ```
+pub fn respond<R>(req: &Request, mut res: Response, body: R, len: u64) -> Response
+where
+    R: Read + Seek + Unpin + Send + Sync + 'static,
+{
+    if res.status() != StatusCode::Ok {
+        return res;
+    }
+    res.insert_header(ACCEPT_RANGES, "bytes");
+
+    let etag = res.header(ETAG).map(|etag| etag.last().as_str().to_owned());
+    let last_modified = res
+        .header(LAST_MODIFIED)
+        .and_then(|date| parse_http_date(date.last().as_str()).ok());
+    let ranges = match ranges(req, len, etag.as_deref(), last_modified) {
+        Ok(Some(ranges)) => coalesce(ranges),
+        Ok(None) => {
+            res.set_body(Body::from_reader(BufReader::new(body), Some(len as usize)));
+            return res;
+        }
+        Err(unsatisfiable) => {
+            res.set_status(StatusCode::RequestedRangeNotSatisfiable);
+            res.insert_header(CONTENT_RANGE, unsatisfiable.content_range(len));
+            res.remove_header(CONTENT_TYPE);
+            res.set_body(Body::empty());
+            return res;
+        }
+    };
+
+    res.set_status(StatusCode::PartialContent);
+    let mut segments = VecDeque::new();
+    if let [range] = ranges[..] {
+        res.insert_header(CONTENT_RANGE, range.content_range(len));
+        segments.push_back(Segment::range(range));
+    } else {
+        let boundary = boundary();
+        let content_type = res
+            .header(CONTENT_TYPE)
+            .map(|t| t.last().as_str().to_owned());
+        for (i, range) in ranges.iter().enumerate() {
+            let mut head = if i == 0 { "--" } else { "\r\n--" }.to_owned();
+            head.push_str(&boundary);
+            head.push_str("\r\n");
+            if let Some(content_type) = &content_type {
+                head.push_str(&format!("content-type: {}\r\n", content_type));
+            }
+            head.push_str(&format!(
+                "content-range: {}\r\n\r\n",
+                range.content_range(len)
+            ));
+            segments.push_back(Segment::Bytes(head.into_bytes()));
+            segments.push_back(Segment::range(*range));
+        }
+        segments.push_back(Segment::Bytes(
+            format!("\r\n--{}--\r\n", boundary).into_bytes(),
+        ));
+        let content_type = format!("multipart/byteranges; boundary={}", boundary);
+        res.insert_header(CONTENT_TYPE, content_type);
+    }
+
+    let total = segments.iter().map(Segment::len).sum::<u64>();
+    let body = Segments {
+        body,
+        segments,
+        sought: false,
+    };
+    res.set_body(Body::from_reader(
+        BufReader::new(body),
+        Some(total as usize),
+    ));
+    res
+}
+
+/// Sort ranges and merge those which overlap or touch.
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(ByteRange::start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// A boundary for `multipart/byteranges` bodies which is unlikely to turn
+/// up in the resource.
+fn boundary() -> String {
+    let random = RandomState::new().build_hasher().finish();
+    format!("byteranges-{:016x}", random)
+}
+
+/// A piece of a partial response body.
+#[derive(Debug)]
+enum Segment {
+    /// Bytes of the multipart framing, still to be sent.
+    Bytes(Vec<u8>),
+    /// The bytes of the resource from `start`, of which `remaining` are
+    /// still to be sent.
+    Range { start: u64, remaining: u64 },
+}
+
+impl Segment {
+    fn range(range: ByteRange) -> Self {
+        Segment::Range {
+            start: range.start,
+            remaining: range.len(),
+        }
+    }
+
+    fn len(&self) -> u64 {
+        match self {
+            Segment::Bytes(bytes) => bytes.len() as u64,
+            Segment::Range { remaining, .. } => *remaining,
+        }
+    }
+}
+
+/// Reads the segments of a partial response body in turn.
+#[derive(Debug)]
+struct Segments<R> {
+    body: R,
+    segments: VecDeque<Segment>,
+    /// Whether `body` has been seeked to the range at the front.
+    sought: bool,
+}
+
+impl<R: Read + Seek + Unpin> Read for Segments<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let segment = match this.segments.front_mut() {
+            Some(segment) => segment,
+            None => return Poll::Ready(Ok(0)),
+        };
+        let bytes = match segment {
+            Segment::Bytes(bytes) => {
+                let len = bytes.len().min(buf.len());
+                buf[..len].copy_from_slice(&bytes[..len]);
+                bytes.drain(..len);
+                len
+            }
+            Segment::Range { start, remaining } => {
+                if !this.sought {
+                    ready!(Pin::new(&mut this.body).poll_seek(cx, SeekFrom::Start(*start)))?;
+                    this.sought = true;
+                }
+                let len = (buf.len() as u64).min(*remaining) as usize;
+                let bytes = ready!(Pin::new(&mut this.body).poll_read(cx, &mut buf[..len]))?;
+                if bytes == 0 && len > 0 {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the body ended before the range did",
+                    )));
+                }
+                *remaining -= bytes as u64;
+                bytes
+            }
+        };
+        if segment.len() == 0 {
+            this.segments.pop_front();
+            this.sought = false;
+        }
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+fn parse_pos(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<Option<Vec<(u64, u64)>>, Unsatisfiable> {
+        parse_range(value, 1000).map(|ranges| {
+            ranges.map(|ranges| ranges.iter().map(|r| (r.start(), r.end())).collect())
+        })
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse("bytes=0-499"), Ok(Some(vec![(0, 499)])));
+        assert_eq!(parse("bytes=500-"), Ok(Some(vec![(500, 999)])));
+        assert_eq!(parse("bytes=-200"), Ok(Some(vec![(800, 999)])));
+        assert_eq!(parse("bytes=-2000"), Ok(Some(vec![(0, 999)])));
+        assert_eq!(parse("bytes=900-1500"), Ok(Some(vec![(900, 999)])));
+        assert_eq!(
+            parse("Bytes = 0-0 , 10-19,-1"),
+            Ok(Some(vec![(0, 0), (10, 19), (999, 999)]))
+        );
+    }
+
+    #[test]
+    fn ignores_invalid_ranges() {
+        assert_eq!(parse("items=0-1"), Ok(None));
+        assert_eq!(parse("bytes=5-1"), Ok(None));
+        assert_eq!(parse("bytes=a-b"), Ok(None));
+        assert_eq!(parse("bytes=+1-2"), Ok(None));
+        assert_eq!(parse("bytes=1"), Ok(None));
+        assert_eq!(parse("0-1"), Ok(None));
+        assert_eq!(parse("bytes="), Ok(None));
+        assert_eq!(parse("bytes=,"), Ok(None));
+    }
+
+    #[test]
+    fn unsatisfiable_ranges() {
+        assert_eq!(parse("bytes=1000-"), Err(Unsatisfiable));
+        assert_eq!(parse("bytes=-0"), Err(Unsatisfiable));
+        assert_eq!(parse("bytes=1000-1001, 2000-"), Err(Unsatisfiable));
+        assert_eq!(parse("bytes=1000-1001, 0-1"), Ok(Some(vec![(0, 1)])));
+        assert_eq!(parse_range("bytes=-5", 0), Err(Unsatisfiable));
+    }
+}
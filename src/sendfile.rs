@@ -0,0 +1,153 @@
+//! Send file bodies straight from the page cache with `sendfile(2)`.
+//!
+//! A response whose body was set with [`set_file_body`] carries the file
+//! alongside a regular body reading it. When it's written to a connection
+//! given a [`ZeroCopySocket`], the head is encoded as usual and the file is
+//! then handed to the kernel, which copies it to the socket without it
+//! passing through userspace. Anywhere else, such as over TLS or without
+//! the socket, the regular body is written instead.
+//!
+//! The file is only sent in place of the body if the body still has the
+//! file's length and nothing re-encodes it: responses which were
+//! compressed, cut into ranges or given a content digest are written as
+//! they are.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use async_h1::sendfile::{self, ZeroCopySocket};
+//! use async_h1::server::Server;
+//! use async_std::net::TcpStream;
+//! use http_types::{Request, Response, StatusCode};
+//!
+//! # async fn serve(stream: TcpStream) -> http_types::Result<()> {
+//! let socket = ZeroCopySocket::new(&stream)?;
+//! let mut server = Server::new(stream, |_req: Request| async {
+//!     let mut res = Response::new(StatusCode::Ok);
+//!     sendfile::set_file_body(&mut res, "static/index.html").await?;
+//!     Ok(res)
+//! })
+//! .with_zero_copy(socket);
+//! server.accept().await
+//! # }
+//! ```
+
+use std::fs::File;
+use std::os::unix::io::{AsFd, OwnedFd};
+use std::path::Path;
+use std::time::Duration;
+
+use async_io::Async;
+use futures_lite::io::{self, BufReader};
+use http_types::headers::{CONTENT_ENCODING, CONTENT_RANGE};
+use http_types::{Body, Method, Response, StatusCode};
+
+use crate::error::{Error, TimeoutPhase};
+use crate::timer::timeout;
+
+/// The most `sendfile(2)` transfers in one call on Linux.
+const MAX_SENDFILE: u64 = 0x7fff_f000;
+
+/// A file to send as a response body, kept in the response's extensions.
+#[derive(Debug)]
+pub(crate) struct FileBody {
+    file: File,
+    len: u64,
+}
+
+/// Set the body of `res` to the file at `path`, to be sent with
+/// `sendfile(2)` where the connection allows it.
+///
+/// The body's length is the file's length at the time it's opened. The
+/// `Content-Type` isn't set.
+pub async fn set_file_body(res: &mut Response, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_owned();
+    let (file, len) = blocking::unblock(move || {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        io::Result::Ok((file, len))
+    })
+    .await?;
+    let reader = BufReader::new(blocking::Unblock::new(file.try_clone()?));
+    res.set_body(Body::from_reader(reader, Some(len as usize)));
+    res.ext_mut().insert(FileBody { file, len });
+    Ok(())
+}
+
+/// Take the file to send in place of the body of `res`, if it still
+/// stands for the body and the body is sent at all.
+pub(crate) fn take_file(res: &mut Response, method: &Method) -> Option<FileBody> {
+    let file = res.ext_mut().remove::<FileBody>()?;
+    let bodiless = *method == Method::Head
+        || matches!(
+            res.status(),
+            StatusCode::NoContent | StatusCode::NotModified
+        )
+        || (*method == Method::Connect && res.status().is_success());
+    let transformed = res.len() != Some(file.len as usize)
+        || res.header(CONTENT_ENCODING).is_some()
+        || res.header(CONTENT_RANGE).is_some();
+    if bodiless || transformed || file.len == 0 {
+        None
+    } else {
+        Some(file)
+    }
+}
+
+/// A connection's socket, which file bodies are written to directly.
+///
+/// It's a duplicate of the socket's descriptor, so the stream itself is
+/// still handed to the server as usual. It must be the plain TCP (or
+/// Unix domain) socket the responses are written to: for a connection
+/// which wraps the stream, like TLS, the bytes wouldn't go through the
+/// wrapper.
+#[derive(Debug)]
+pub struct ZeroCopySocket {
+    socket: Async<OwnedFd>,
+}
+
+impl ZeroCopySocket {
+    /// Duplicate the descriptor of `socket`.
+    pub fn new(socket: &impl AsFd) -> io::Result<Self> {
+        let socket = socket.as_fd().try_clone_to_owned()?;
+        Ok(Self {
+            socket: Async::new(socket)?,
+        })
+    }
+
+    /// Send all of `file`, failing if the socket doesn't accept any of it
+    /// for longer than `write_timeout`, and return the number of bytes
+    /// sent.
+    pub(crate) async fn send(
+        &self,
+        file: &FileBody,
+        write_timeout: Option<Duration>,
+    ) -> io::Result<u64> {
+        let mut offset = 0;
+        while offset < file.len {
+            let count = (file.len - offset).min(MAX_SENDFILE) as usize;
+            let sent = self.socket.write_with(|socket| {
+                Ok(rustix::fs::sendfile(
+                    socket,
+                    &file.file,
+                    Some(&mut offset),
+                    count,
+                )?)
+            });
+            let sent = match write_timeout {
+                Some(duration) => timeout(duration, sent).await.map_err(|_| {
+                    let message = format!("No progress for {:?}", duration);
+                    io::Error::from(Error::timed_out(TimeoutPhase::Write, message))
+                })??,
+                None => sent.await?,
+            };
+            if sent == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The file was truncated while it was being sent",
+                ));
+            }
+        }
+        Ok(offset)
+    }
+}
@@ -0,0 +1,357 @@
+//! Deliver request bodies to the endpoint through a bounded channel.
+//!
+//! The connection task reads the body from the socket in frames and sends
+//! them to the endpoint, waiting while the channel is full. A slow endpoint
+//! thus stops the socket from being read, which the peer sees as TCP
+//! backpressure, while reads stay in the hands of the connection task.
+//! Endpoints can also stop the flow explicitly with a [`BodyFlow`], and take
+//! the frames themselves with [`BodyFrames`] rather than copying them out of
+//! the body.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncReadExt};
+
+use super::memory::ConnectionMemory;
+
+/// The largest number of bytes sent in one frame.
+const FRAME_SIZE: usize = 8 * 1024;
+
+/// The task feeding a body channel.
+pub(crate) type Pump = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+#[derive(Debug)]
+struct Shared {
+    frames: VecDeque<Vec<u8>>,
+    capacity: usize,
+    /// How the body ended, once it has.
+    end: Option<io::Result<()>>,
+    /// Whether the receiver has asked for the body yet.
+    demanded: bool,
+    /// Whether the endpoint asked to stop reading for now.
+    paused: bool,
+    /// The number of live handles reading frames.
+    receivers: usize,
+    receiver: Option<Waker>,
+    sender: Option<Waker>,
+    /// Accounts for the frames in the channel.
+    memory: ConnectionMemory,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        let queued = self.frames.iter().map(Vec::len).sum();
+        self.memory.release(queued);
+    }
+}
+
+/// Create a channel holding at most `capacity` frames, accounting for them
+/// in `memory`.
+pub(crate) fn channel(capacity: usize, memory: ConnectionMemory) -> (Sender, Receiver) {
+    let shared = Arc::new(Mutex::new(Shared {
+        frames: VecDeque::new(),
+        capacity: capacity.max(1),
+        end: None,
+        demanded: false,
+        paused: false,
+        receivers: 1,
+        receiver: None,
+        sender: None,
+        memory,
+    }));
+    let receiver = Receiver {
+        shared: shared.clone(),
+        frame: Vec::new(),
+        pos: 0,
+    };
+    (Sender { shared }, receiver)
+}
+
+/// Read `body` into the channel until it ends or the receiver goes away.
+pub(crate) fn pump(mut body: impl Read + Unpin + Send + 'static, sender: Sender) -> Pump {
+    Box::pin(async move {
+        loop {
+            if !future::poll_fn(|cx| sender.poll_ready(cx)).await {
+                return;
+            }
+            let mut frame = vec![0; FRAME_SIZE];
+            match body.read(&mut frame).await {
+                Ok(0) => return sender.finish(Ok(())),
+                Ok(bytes) => {
+                    frame.truncate(bytes);
+                    if let Err(err) = sender.send(frame) {
+                        return sender.finish(Err(err));
+                    }
+                }
+                Err(err) => return sender.finish(Err(err)),
+            }
+        }
+    })
+}
+
+/// Run `fut`, driving `pump` in the meantime until it's done.
+pub(crate) async fn alongside<F: Future>(pump: &mut Option<Pump>, fut: F) -> F::Output {
+    futures_lite::pin!(fut);
+    future::poll_fn(|cx| {
+        if let Some(feeding) = pump {
+            if feeding.as_mut().poll(cx).is_ready() {
+                *pump = None;
+            }
+        }
+        fut.as_mut().poll(cx)
+    })
+    .await
+}
+
+/// The connection task's end of a body channel.
+#[derive(Debug)]
+pub(crate) struct Sender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Sender {
+    /// Wait until the receiver wants more frames. Returns `false` if it went
+    /// away.
+    ///
+    /// Nothing is read until the receiver first asks for the body, so that
+    /// `Expect: 100-continue` is only answered once the endpoint reads.
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<bool> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.receivers == 0 {
+            Poll::Ready(false)
+        } else if shared.demanded && !shared.paused && shared.frames.len() < shared.capacity {
+            Poll::Ready(true)
+        } else {
+            shared.sender = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn send(&self, frame: Vec<u8>) -> io::Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.memory.reserve(frame.len())?;
+        shared.frames.push_back(frame);
+        if let Some(waker) = shared.receiver.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn finish(&self, end: io::Result<()>) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.end = Some(end);
+        if let Some(waker) = shared.receiver.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.end.is_none() {
+            shared.end = Some(Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "connection stopped reading the request body",
+            )));
+        }
+        if let Some(waker) = shared.receiver.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Pauses and resumes reading a request body delivered through a body
+/// channel.
+///
+/// When the server is configured with
+/// [`ServerOptions::with_body_channel`](super::ServerOptions::with_body_channel),
+/// a `BodyFlow` is available in the extensions of each request with a body.
+/// While paused, the frames already in the channel can still be read, but no
+/// more are read from the stream, without dropping the connection.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::BodyFlow;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn upload(mut req: Request) -> http_types::Result<Response> {
+///     if let Some(flow) = req.ext().get::<BodyFlow>().cloned() {
+///         flow.pause();
+///         // ... check the client's quota ...
+///         flow.resume();
+///     }
+///     req.body_bytes().await?;
+///     Ok(Response::new(StatusCode::Ok))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BodyFlow {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl BodyFlow {
+    /// Stop reading the body from the stream until [`BodyFlow::resume`] is
+    /// called.
+    pub fn pause(&self) {
+        self.shared.lock().unwrap().paused = true;
+    }
+
+    /// Continue reading the body from the stream.
+    pub fn resume(&self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.paused = false;
+        if let Some(waker) = shared.sender.take() {
+            waker.wake();
+        }
+    }
+
+    /// Whether reading is paused.
+    pub fn is_paused(&self) -> bool {
+        self.shared.lock().unwrap().paused
+    }
+}
+
+/// The endpoint's end of a body channel.
+#[derive(Debug)]
+pub(crate) struct Receiver {
+    shared: Arc<Mutex<Shared>>,
+    /// The frame being read.
+    frame: Vec<u8>,
+    pos: usize,
+}
+
+impl Receiver {
+    /// A handle to pause and resume the flow of frames.
+    pub(crate) fn flow(&self) -> BodyFlow {
+        BodyFlow {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// A handle to take frames from the channel directly.
+    pub(crate) fn frames(&self) -> BodyFrames {
+        self.shared.lock().unwrap().receivers += 1;
+        BodyFrames {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Take the next frame from the channel, or `None` once the body has ended.
+fn poll_frame(shared: &Mutex<Shared>, cx: &mut Context<'_>) -> Poll<io::Result<Option<Vec<u8>>>> {
+    let mut shared = shared.lock().unwrap();
+    shared.demanded = true;
+    match shared.frames.pop_front() {
+        Some(frame) => {
+            shared.memory.release(frame.len());
+            if let Some(waker) = shared.sender.take() {
+                waker.wake();
+            }
+            Poll::Ready(Ok(Some(frame)))
+        }
+        None => match shared.end.take() {
+            Some(end) => {
+                shared.end = Some(Ok(()));
+                Poll::Ready(end.map(|()| None))
+            }
+            None => {
+                shared.receiver = Some(cx.waker().clone());
+                if let Some(waker) = shared.sender.take() {
+                    waker.wake();
+                }
+                Poll::Pending
+            }
+        },
+    }
+}
+
+impl Read for Receiver {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.pos == this.frame.len() {
+            match futures_core::ready!(poll_frame(&this.shared, cx))? {
+                Some(frame) => {
+                    this.frame = frame;
+                    this.pos = 0;
+                }
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+
+        let bytes = buf.len().min(this.frame.len() - this.pos);
+        buf[..bytes].copy_from_slice(&this.frame[this.pos..this.pos + bytes]);
+        this.pos += bytes;
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+/// Hands the frames of a request body delivered through a body channel to
+/// the endpoint, without copying them.
+///
+/// When the server is configured with
+/// [`ServerOptions::with_body_channel`](super::ServerOptions::with_body_channel),
+/// a `BodyFrames` is available in the extensions of each request with a
+/// body. Each frame is the buffer the body was read into, moved rather than
+/// copied, which saves a copy per frame when relaying bodies elsewhere.
+///
+/// Frames taken this way are gone from the request's body, so don't mix
+/// reading frames and reading the body.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::BodyFrames;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn relay(mut req: Request) -> http_types::Result<Response> {
+///     if let Some(mut frames) = req.ext_mut().remove::<BodyFrames>() {
+///         while let Some(frame) = frames.next().await? {
+///             // ... pass `frame` on ...
+///         }
+///     }
+///     Ok(Response::new(StatusCode::Ok))
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BodyFrames {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl BodyFrames {
+    /// Take the next frame of the body, or `None` once it has ended.
+    pub async fn next(&mut self) -> io::Result<Option<Vec<u8>>> {
+        future::poll_fn(|cx| poll_frame(&self.shared, cx)).await
+    }
+}
+
+/// Stop feeding the channel once the last handle reading from it is gone.
+fn release_receiver(shared: &Mutex<Shared>) {
+    let mut shared = shared.lock().unwrap();
+    shared.receivers -= 1;
+    if let Some(waker) = shared.sender.take() {
+        waker.wake();
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        release_receiver(&self.shared);
+    }
+}
+
+impl Drop for BodyFrames {
+    fn drop(&mut self) {
+        release_receiver(&self.shared);
+    }
+}
@@ -1,21 +1,144 @@
-use crate::chunked::ChunkedDecoder;
+#[cfg(feature = "chunked")]
+use crate::chunked::{ChunkedDecoder, ChunkedPassthrough};
+use crate::error::{Error, ErrorKind, Limit, TimeoutPhase};
+use crate::timer::timeout;
+use crate::POLL_BUDGET;
 use async_dup::{Arc, Mutex};
-use async_std::io::{BufReader, Read, Take};
-use async_std::task::{Context, Poll};
+use futures_lite::future;
+use futures_lite::io::{AsyncBufRead as BufRead, AsyncRead as Read, BufReader, Take};
+use futures_lite::ready;
+use http_types::StatusCode;
+use std::task::{Context, Poll};
+use std::time::Duration;
 use std::{fmt::Debug, io, pin::Pin};
 
+/// The body of a request read by [`decode`](super::decode), framed as the
+/// request said.
+///
+/// Reading it yields the body's bytes and stops at its end, leaving the
+/// connection at the start of the next request. A body which isn't needed
+/// must still be read to its end, or [drained](BodyReader::drain), before
+/// the next request can be decoded from the connection.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::server;
+/// use async_std::net::TcpStream;
+///
+/// # async fn serve(stream: TcpStream) -> http_types::Result<()> {
+/// while let Some((req, mut body)) = server::decode(stream.clone()).await? {
+///     println!("{} {}", req.method(), req.url());
+///     // Ignore the body, but keep the connection usable.
+///     body.drain().await?;
+/// }
+/// # Ok(()) }
+/// ```
 pub enum BodyReader<IO: Read + Unpin> {
+    /// A body sent with the chunked transfer-coding, decoded.
+    #[cfg(feature = "chunked")]
     Chunked(Arc<Mutex<ChunkedDecoder<BufReader<IO>>>>),
+    /// A body sent with the chunked transfer-coding, passed through with
+    /// its framing.
+    #[cfg(feature = "chunked")]
+    Passthrough(Arc<Mutex<ChunkedPassthrough<BufReader<IO>>>>),
+    /// A body of the length given by `Content-Length`.
     Fixed(Arc<Mutex<Take<BufReader<IO>>>>),
-    None,
+    /// No body.
+    None(BufReader<IO>),
 }
 
 impl<IO: Read + Unpin> Debug for BodyReader<IO> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            #[cfg(feature = "chunked")]
             BodyReader::Chunked(_) => f.write_str("BodyReader::Chunked"),
+            #[cfg(feature = "chunked")]
+            BodyReader::Passthrough(_) => f.write_str("BodyReader::Passthrough"),
             BodyReader::Fixed(_) => f.write_str("BodyReader::Fixed"),
-            BodyReader::None => f.write_str("BodyReader::None"),
+            BodyReader::None(_) => f.write_str("BodyReader::None"),
+        }
+    }
+}
+
+impl<IO: Read + Unpin> BodyReader<IO> {
+    /// Take the bytes read from the stream past what has been read of the
+    /// body, so that they can be read again from elsewhere.
+    pub(crate) fn take_buffered(&mut self) -> Vec<u8> {
+        fn take<IO: Read + Unpin>(reader: &mut BufReader<IO>) -> Vec<u8> {
+            let buffered = reader.buffer().to_vec();
+            Pin::new(reader).consume(buffered.len());
+            buffered
+        }
+        match self {
+            #[cfg(feature = "chunked")]
+            BodyReader::Chunked(r) => take(r.lock().get_mut()),
+            #[cfg(feature = "chunked")]
+            BodyReader::Passthrough(r) => take(r.lock().get_mut()),
+            BodyReader::Fixed(r) => take(r.lock().get_mut()),
+            BodyReader::None(r) => take(r),
+        }
+    }
+
+    /// Discard the rest of the body, returning the number of bytes
+    /// discarded.
+    ///
+    /// Bodies of a known length are skipped in the stream's buffer rather
+    /// than copied out of it. Chunked bodies are still decoded, so the
+    /// maximum body size and malformed framing fail as they do when
+    /// reading.
+    pub async fn drain(&mut self) -> io::Result<u64> {
+        self.drain_with(Some(POLL_BUDGET), None).await
+    }
+
+    /// Discard the rest of the body, yielding to the executor after every
+    /// `budget` bytes and failing with a [`TimeoutPhase::Body`] timeout
+    /// error if no more of it arrives for `no_progress`.
+    pub(crate) async fn drain_with(
+        &mut self,
+        budget: Option<usize>,
+        no_progress: Option<Duration>,
+    ) -> io::Result<u64> {
+        let budget = budget.map_or(u64::MAX, |budget| budget.max(1) as u64);
+        let mut drained = 0;
+        let mut unyielded = 0;
+        loop {
+            let skipped = future::poll_fn(|cx| self.poll_skip(cx));
+            let bytes = match no_progress {
+                Some(duration) => timeout(duration, skipped).await.map_err(|_| {
+                    let message = format!("No progress for {:?}", duration);
+                    io::Error::from(Error::timed_out(TimeoutPhase::Body, message))
+                })??,
+                None => skipped.await?,
+            } as u64;
+            if bytes == 0 {
+                return Ok(drained);
+            }
+            drained += bytes;
+            unyielded += bytes;
+            if unyielded >= budget {
+                unyielded = 0;
+                future::yield_now().await;
+            }
+        }
+    }
+
+    /// Discard the next piece of the body, returning its length, or 0 at
+    /// the end of the body.
+    fn poll_skip(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        match self {
+            BodyReader::Fixed(r) => {
+                let mut r = r.lock();
+                let bytes = ready!(Pin::new(&mut *r).poll_fill_buf(cx))?.len();
+                Pin::new(&mut *r).consume(bytes);
+                Poll::Ready(Ok(bytes))
+            }
+            BodyReader::None(_) => Poll::Ready(Ok(0)),
+            #[cfg(feature = "chunked")]
+            _ => {
+                let mut scratch = [0; 8 * 1024];
+                Pin::new(self).poll_read(cx, &mut scratch)
+            }
         }
     }
 }
@@ -27,9 +150,108 @@ impl<IO: Read + Unpin> Read for BodyReader<IO> {
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
         match &*self {
+            #[cfg(feature = "chunked")]
             BodyReader::Chunked(r) => Pin::new(&mut *r.lock()).poll_read(cx, buf),
+            #[cfg(feature = "chunked")]
+            BodyReader::Passthrough(r) => Pin::new(&mut *r.lock()).poll_read(cx, buf),
             BodyReader::Fixed(r) => Pin::new(&mut *r.lock()).poll_read(cx, buf),
-            BodyReader::None => Poll::Ready(Ok(0)),
+            BodyReader::None(_) => Poll::Ready(Ok(0)),
+        }
+    }
+}
+
+/// The first protocol error met while reading a request body.
+///
+/// Shared between the body handed to the endpoint and the connection loop,
+/// so the server can answer a malformed body itself, whatever the endpoint
+/// made of the error.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BodyError(std::sync::Arc<std::sync::Mutex<Option<Error>>>);
+
+impl BodyError {
+    /// Record the errors `reader` fails with.
+    pub(crate) fn watch<R>(&self, reader: R) -> Watched<R> {
+        Watched {
+            reader,
+            error: self.clone(),
+        }
+    }
+
+    /// The error recorded, if any.
+    pub(crate) fn take(&self) -> Option<Error> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// A reader which fails once more than the maximum body size has been read
+/// from it, if there's a maximum.
+#[cfg(any(feature = "chunked", feature = "compression"))]
+#[derive(Debug)]
+pub(crate) struct Limited<R> {
+    reader: R,
+    remaining: Option<u64>,
+}
+
+#[cfg(any(feature = "chunked", feature = "compression"))]
+impl<R> Limited<R> {
+    pub(crate) fn new(reader: R, max: Option<u64>) -> Self {
+        Self {
+            reader,
+            remaining: max,
+        }
+    }
+}
+
+#[cfg(any(feature = "chunked", feature = "compression"))]
+impl<R: Read + Unpin> Read for Limited<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let bytes = ready!(Pin::new(&mut self.reader).poll_read(cx, buf))?;
+        if let Some(remaining) = &mut self.remaining {
+            match remaining.checked_sub(bytes as u64) {
+                Some(left) => *remaining = left,
+                None => return Poll::Ready(Err(body_too_large().into())),
+            }
+        }
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+/// The error for a request body longer than the maximum body size.
+pub(crate) fn body_too_large() -> Error {
+    let message = "Request body is longer than the maximum body size";
+    Error::limit_exceeded(Limit::BodySize, message).with_status(StatusCode::PayloadTooLarge)
+}
+
+/// A reader whose errors are recorded in a [`BodyError`].
+#[derive(Debug)]
+pub(crate) struct Watched<R> {
+    reader: R,
+    error: BodyError,
+}
+
+impl<R: Read + Unpin> Read for Watched<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.reader).poll_read(cx, buf);
+        if let Poll::Ready(Err(err)) = &poll {
+            // Errors from the stream itself leave no one to respond to.
+            let ours = err.get_ref().and_then(|e| e.downcast_ref::<Error>());
+            let gone = |e: &&Error| matches!(e.kind(), ErrorKind::Io | ErrorKind::ConnectionClosed);
+            if let Some(ours) = ours.filter(|e| !gone(e)) {
+                self.error
+                    .0
+                    .lock()
+                    .unwrap()
+                    .get_or_insert_with(|| ours.clone());
+            }
         }
+        poll
     }
 }
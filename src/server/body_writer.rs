@@ -0,0 +1,213 @@
+//! Write response bodies as they're produced, e.g. for server-sent events.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use futures_lite::io::{self, AsyncBufRead as BufRead, AsyncRead as Read, AsyncWrite as Write};
+use http_types::Body;
+
+/// The most bytes held for the connection before writes wait for it.
+const CAPACITY: usize = 64 * 1024;
+
+/// Writes a response body from the endpoint, or a task it spawns, while the
+/// response is being sent.
+///
+/// Each [flush](futures_lite::AsyncWriteExt::flush) hands the bytes written
+/// since the previous one to the connection, which sends them right away as
+/// one chunk and flushes the stream, so a client receives each server-sent
+/// event as soon as it's complete. Writes wait while more than 64 KiB are
+/// waiting to be sent. The body ends when the writer is closed or dropped.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::BodyWriter;
+/// use futures_lite::AsyncWriteExt;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn events(_req: Request) -> http_types::Result<Response> {
+///     let (mut writer, body) = BodyWriter::new();
+///     async_std::task::spawn(async move {
+///         for n in 0..3 {
+///             let event = format!("data: {}\n\n", n);
+///             writer.write_all(event.as_bytes()).await?;
+///             writer.flush().await?;
+///         }
+///         std::io::Result::Ok(())
+///     });
+///
+///     let mut res = Response::new(StatusCode::Ok);
+///     res.set_body(body);
+///     res.set_content_type("text/event-stream".parse()?);
+///     Ok(res)
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BodyWriter {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// The reading end, handed to the connection as the body.
+#[derive(Debug)]
+struct BodyFrames {
+    shared: Arc<Mutex<Shared>>,
+    /// The frame being read, and how much of it has been.
+    frame: Vec<u8>,
+    consumed: usize,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// Bytes written since the last flush.
+    pending: Vec<u8>,
+    /// Flushed frames which haven't been read yet.
+    frames: VecDeque<Vec<u8>>,
+    /// The number of flushed bytes not read yet.
+    queued: usize,
+    /// Whether the writer is done.
+    closed: bool,
+    /// Whether the body was dropped, e.g. because the client went away.
+    abandoned: bool,
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+}
+
+impl Shared {
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            let frame = std::mem::take(&mut self.pending);
+            self.queued += frame.len();
+            self.frames.push_back(frame);
+            if let Some(waker) = self.reader.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl BodyWriter {
+    /// Create a writer and the body it writes, of unknown length.
+    pub fn new() -> (Self, Body) {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let frames = BodyFrames {
+            shared: shared.clone(),
+            frame: Vec::new(),
+            consumed: 0,
+        };
+        (Self { shared }, Body::from_reader(frames, None))
+    }
+}
+
+impl Write for BodyWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut shared = lock(&self.shared);
+        if shared.abandoned {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if shared.pending.len() >= CAPACITY {
+            // Don't hold an unbounded amount back waiting for a flush.
+            shared.flush();
+        }
+        if shared.queued >= CAPACITY {
+            shared.writer = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let bytes = buf.len().min(CAPACITY - shared.pending.len());
+        shared.pending.extend_from_slice(&buf[..bytes]);
+        Poll::Ready(Ok(bytes))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut shared = lock(&self.shared);
+        if shared.abandoned {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        shared.flush();
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut shared = lock(&self.shared);
+        shared.flush();
+        shared.closed = true;
+        if let Some(waker) = shared.reader.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for BodyWriter {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.flush();
+        shared.closed = true;
+        if let Some(waker) = shared.reader.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl BufRead for BodyFrames {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.consumed == this.frame.len() {
+            let mut shared = lock(&this.shared);
+            match shared.frames.pop_front() {
+                Some(frame) => {
+                    this.frame = frame;
+                    this.consumed = 0;
+                }
+                None if shared.closed => return Poll::Ready(Ok(&[])),
+                None => {
+                    shared.reader = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        }
+        Poll::Ready(Ok(&this.frame[this.consumed..]))
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        self.consumed += amt;
+        let mut shared = lock(&self.shared);
+        shared.queued -= amt;
+        if let Some(waker) = shared.writer.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Read for BodyFrames {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let available = futures_core::ready!(self.as_mut().poll_fill_buf(cx))?;
+        let bytes = available.len().min(buf.len());
+        buf[..bytes].copy_from_slice(&available[..bytes]);
+        self.consume(bytes);
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+impl Drop for BodyFrames {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.abandoned = true;
+        if let Some(waker) = shared.writer.take() {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Drive a connection one request at a time.
+
+use futures_lite::io::{AsyncRead as Read, AsyncWrite as Write};
+use http_types::{Request, Response};
+
+use super::drain::{Drain, Tracked};
+use super::{ConnectionStatus, Exchange, Server, ServerOptions};
+#[cfg(feature = "metrics")]
+use crate::metrics::ActiveConnection;
+#[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+use crate::sendfile::ZeroCopySocket;
+
+/// A connection whose requests are read and answered one at a time, rather
+/// than passed to an endpoint by [`accept`](super::accept), for frameworks
+/// which schedule or measure requests themselves.
+///
+/// Each request read with [`read_request`](Connection::read_request) is
+/// answered with [`write_response`](Connection::write_response) before the
+/// next one is read. The [`ServerOptions`] apply as they do to `accept`,
+/// except for those which work alongside an endpoint while it runs: body
+/// channels, heartbeats and [`Interim`](super::Interim) responses.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::server::Connection;
+/// use async_std::net::TcpStream;
+/// use http_types::{Response, StatusCode};
+///
+/// # async fn serve(stream: TcpStream) -> http_types::Result<()> {
+/// let mut connection = Connection::new(stream);
+/// while let Some(req) = connection.read_request().await? {
+///     let mut res = Response::new(StatusCode::Ok);
+///     res.set_body(format!("You asked for {}", req.url().path()));
+///     connection.write_response(res).await?;
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Connection<RW: Read + Unpin> {
+    server: Server<RW, (), ()>,
+    /// The request waiting for its response.
+    exchange: Option<Exchange<RW>>,
+    closed: bool,
+    _tracked: Option<Tracked>,
+    #[cfg(feature = "metrics")]
+    _active: ActiveConnection,
+}
+
+impl<RW> Connection<RW>
+where
+    RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Create a new instance with the default options.
+    pub fn new(io: RW) -> Self {
+        Self::with_opts(io, Default::default())
+    }
+
+    /// Create a new instance with these options.
+    pub fn with_opts(io: RW, opts: ServerOptions) -> Self {
+        let server = Server::new(io, ()).with_opts(opts);
+        // Spans aren't entered, since the connection isn't polled by the
+        // server, but requests are still traced within the connection.
+        #[cfg(feature = "tracing")]
+        let server = {
+            let mut server = server;
+            server.open_span();
+            server
+        };
+        Self {
+            _tracked: server.opts.drain.as_ref().map(Drain::track),
+            #[cfg(feature = "metrics")]
+            _active: ActiveConnection::new(server.opts.metrics.clone()),
+            server,
+            exchange: None,
+            closed: false,
+        }
+    }
+
+    /// Send file bodies straight to `socket`, as
+    /// [`Server::with_zero_copy`] does.
+    #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+    pub fn with_zero_copy(mut self, socket: ZeroCopySocket) -> Self {
+        self.server = self.server.with_zero_copy(socket);
+        self
+    }
+
+    /// Read the next request, or `None` once the connection is closed.
+    ///
+    /// Requests turned away before they'd be handed on, because the server
+    /// is overloaded or a [`Hook`](super::Hook) responded to them, are
+    /// answered here, and the next one is read instead.
+    pub async fn read_request(&mut self) -> http_types::Result<Option<Request>> {
+        http_types::ensure!(
+            self.exchange.is_none(),
+            "The previous request hasn't been responded to"
+        );
+        while !self.closed {
+            let (mut req, mut exchange) = match self.server.read_exchange().await {
+                Ok(Ok(read)) => read,
+                Ok(Err(_)) => {
+                    self.closed = true;
+                    break;
+                }
+                Err(e) => {
+                    self.closed = true;
+                    return Err(e);
+                }
+            };
+            match self.server.admit(&mut req, &mut exchange) {
+                Some(res) => {
+                    let written = self.server.write_exchange(exchange, res, &mut None).await;
+                    self.settle(written)?;
+                }
+                None => {
+                    self.exchange = Some(exchange);
+                    return Ok(Some(req));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Respond to the request last read, and discard what's left of its
+    /// body.
+    ///
+    /// Fails without sending the response if the request body turned out to
+    /// be malformed, answering that instead.
+    pub async fn write_response(&mut self, res: Response) -> http_types::Result<ConnectionStatus> {
+        let exchange = match self.exchange.take() {
+            Some(exchange) => exchange,
+            None => http_types::bail!("There's no request to respond to"),
+        };
+        let written = match self.server.check_body(&exchange).await {
+            Ok(()) => self.server.write_exchange(exchange, res, &mut None).await,
+            Err(e) => Err(e),
+        };
+        self.settle(written)
+    }
+
+    /// The number of bytes this connection currently holds in its head and
+    /// body buffers.
+    pub fn buffered_bytes(&self) -> usize {
+        self.server.buffered_bytes()
+    }
+
+    /// Mark the connection closed once a response leaves it closed or
+    /// fails.
+    fn settle(
+        &mut self,
+        written: http_types::Result<ConnectionStatus>,
+    ) -> http_types::Result<ConnectionStatus> {
+        if !matches!(written, Ok(ConnectionStatus::KeepAlive)) {
+            self.closed = true;
+        }
+        written
+    }
+}
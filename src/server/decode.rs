@@ -1,172 +1,409 @@
 //! Process HTTP connections on the server.
 
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use async_dup::{Arc, Mutex};
-use async_std::io::{BufReader, Read, Write};
-use async_std::{prelude::*, task};
-use http_types::content::ContentLength;
-use http_types::headers::{EXPECT, TRANSFER_ENCODING};
-use http_types::{ensure, ensure_eq, format_err};
-use http_types::{Body, Method, Request, Url};
-
-use super::body_reader::BodyReader;
-use crate::chunked::ChunkedDecoder;
-use crate::read_notifier::ReadNotifier;
-use crate::{MAX_HEADERS, MAX_HEAD_LENGTH};
+use futures_lite::io::{
+    AsyncBufReadExt, AsyncRead as Read, AsyncReadExt, AsyncWrite as Write, BufReader,
+};
+use http_types::headers::{HeaderValues, CONTENT_LENGTH, EXPECT, TRANSFER_ENCODING};
+use http_types::{Body, Method, Request, StatusCode, Url};
+
+#[cfg(feature = "chunked")]
+use super::body_reader::Limited;
+use super::body_reader::{body_too_large, BodyError, BodyReader};
+use super::expect_continue::{ExpectContinue, PendingContinue};
+use super::memory::ConnectionMemory;
+use super::ServerOptions;
+#[cfg(feature = "chunked")]
+use crate::chunked::{ChunkedDecoder, ChunkedPassthrough};
+use crate::error::{
+    malformed, parse_error, Error, ErrorKind, ForeignProtocol, Limit, TimeoutPhase,
+};
+use crate::head::{unfold, HeadScanner};
+use crate::timer::TimedStream;
 
 const LF: u8 = b'\n';
 
+/// The number returned from httparse when the request is HTTP 1.0
+const HTTP_1_0_VERSION: u8 = 0;
 /// The number returned from httparse when the request is HTTP 1.1
 const HTTP_1_1_VERSION: u8 = 1;
 
 const CONTINUE_HEADER_VALUE: &str = "100-continue";
-const CONTINUE_RESPONSE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+/// The first line of the connection preface of HTTP/2 with prior knowledge.
+const HTTP2_PREFACE_LINE: &[u8] = b"PRI * HTTP/2.0";
+
+/// Whether the first bytes read from a connection start a TLS record
+/// carrying a handshake: content type 22, then major version 3.
+///
+/// No request line starts with a control character, so a lone first byte
+/// of 22 is telling enough by itself.
+fn looks_like_tls(first: &[u8]) -> bool {
+    matches!(first, [0x16] | [0x16, 0x03, ..])
+}
 
 /// Decode an HTTP request on the server.
-pub async fn decode<IO>(mut io: IO) -> http_types::Result<Option<(Request, BodyReader<IO>)>>
+pub async fn decode<IO>(io: IO) -> http_types::Result<Option<(Request, BodyReader<IO>)>>
+where
+    IO: Read + Write + Clone + Send + Sync + Unpin + 'static,
+{
+    let memory = ConnectionMemory::default();
+    decode_with_opts(
+        io,
+        &ServerOptions::default(),
+        &memory,
+        &AtomicBool::new(false),
+    )
+    .await
+}
+
+/// Decode an HTTP request on the server, applying the limits in `opts` and
+/// accounting for the head buffer in `memory`. `started` is set once the
+/// first bytes of the head have arrived.
+pub(crate) async fn decode_with_opts<IO>(
+    io: IO,
+    opts: &ServerOptions,
+    memory: &ConnectionMemory,
+    started: &AtomicBool,
+) -> http_types::Result<Option<(Request, BodyReader<IO>)>>
 where
     IO: Read + Write + Clone + Send + Sync + Unpin + 'static,
 {
     let mut reader = BufReader::new(io.clone());
     let mut buf = Vec::new();
-    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut headers = vec![httparse::EMPTY_HEADER; opts.max_headers];
     let mut httparse_req = httparse::Request::new(&mut headers);
+    let mut scanner = HeadScanner::new();
+    let mut buffered = memory.buffer();
 
     // Keep reading bytes from the stream until we hit the end of the stream.
+    // Reads stop at the limits, so an endless line isn't buffered whole.
+    let mut in_request_line = true;
+    let first = reader.fill_buf().await?;
+    if looks_like_tls(first) {
+        let message = "Received a TLS handshake on a plain HTTP connection";
+        return Err(Error::foreign(ForeignProtocol::Tls, message).into_http());
+    }
     loop {
-        let bytes_read = reader.read_until(LF, &mut buf).await?;
+        let mut room = opts.max_head_length - buf.len();
+        if in_request_line {
+            // Leave room for the line ending, and a byte to notice the
+            // line is too long.
+            room = room.min(opts.max_request_line_length + 3);
+        }
+        let bytes_read = (&mut reader)
+            .take(room as u64)
+            .read_until(LF, &mut buf)
+            .await?;
         // No more bytes are yielded from the stream.
         if bytes_read == 0 {
+            if scanner.bytes_buffered() > 0 {
+                trace!(
+                    "connection closed {} bytes into a request head, at least {} more were needed",
+                    scanner.bytes_buffered(),
+                    scanner.bytes_needed()
+                );
+            }
             return Ok(None);
         }
 
+        // Empty lines ahead of the request line, such as a CRLF sent after
+        // the previous request's body, are ignored as RFC 7230 recommends.
+        if in_request_line && (buf == b"\r\n" || buf == b"\n") {
+            buf.clear();
+            continue;
+        }
+
+        if in_request_line {
+            let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.len() > opts.max_request_line_length {
+                let message = format!(
+                    "Request line should be at most {} bytes",
+                    opts.max_request_line_length
+                );
+                let error = Error::limit_exceeded(Limit::RequestLine, message);
+                return Err(error.into_http_with_status(StatusCode::UriTooLong));
+            }
+            in_request_line = !buf.ends_with(&[LF]);
+            if !in_request_line && line == HTTP2_PREFACE_LINE {
+                let message = "Received the HTTP/2 connection preface on an HTTP/1 connection";
+                return Err(Error::foreign(ForeignProtocol::Http2, message).into_http());
+            }
+        }
+
         // Prevent CWE-400 DDOS with large HTTP Headers.
-        ensure!(
-            buf.len() < MAX_HEAD_LENGTH,
-            "Head byte length should be less than 8kb"
-        );
+        if buf.len() >= opts.max_head_length {
+            let message = format!(
+                "Head byte length should be less than {} bytes",
+                opts.max_head_length
+            );
+            return Err(Error::limit_exceeded(Limit::HeadLength, message).into_http());
+        }
+        buffered.grow_to(buf.len())?;
+        started.store(true, Ordering::Relaxed);
 
         // We've hit the end delimiter of the stream.
-        let idx = buf.len() - 1;
-        if idx >= 3 && &buf[idx - 3..=idx] == b"\r\n\r\n" {
+        if scanner.feed(&buf[buf.len() - bytes_read..]).is_some() {
             break;
         }
     }
 
+    // Header values folded across lines would otherwise fail to parse, or
+    // worse, have their continuations read as headers of their own.
+    if let Some(unfolded) = unfold(&buf, opts.unfold_headers)? {
+        buf = unfolded;
+    }
+
     // Convert our header buf into an httparse instance, and validate.
-    let status = httparse_req.parse(&buf)?;
+    let status = httparse_req.parse(&buf).map_err(parse_error)?;
 
-    ensure!(!status.is_partial(), "Malformed HTTP head");
+    ensure_kind!(
+        !status.is_partial(),
+        MalformedMessage,
+        "Malformed HTTP head"
+    );
 
     // Convert httparse headers + body into a `http_types::Request` type.
     let method = httparse_req.method;
-    let method = method.ok_or_else(|| format_err!("No method found"))?;
+    let method = method.ok_or_else(|| malformed("No method found"))?;
 
     let version = httparse_req.version;
-    let version = version.ok_or_else(|| format_err!("No version found"))?;
-
-    ensure_eq!(
-        version,
-        HTTP_1_1_VERSION,
-        "Unsupported HTTP version 1.{}",
-        version
-    );
+    let version = version.ok_or_else(|| malformed("No version found"))?;
+
+    let version = match version {
+        HTTP_1_0_VERSION => http_types::Version::Http1_0,
+        HTTP_1_1_VERSION => http_types::Version::Http1_1,
+        _ => {
+            return Err(
+                err_kind!(MalformedMessage, "Unsupported HTTP version 1.{}", version).into_http(),
+            )
+        }
+    };
 
-    let url = url_from_httparse_req(&httparse_req)?;
+    let url = url_from_httparse_req(&httparse_req, opts.default_host.as_deref())?;
 
-    let mut req = Request::new(Method::from_str(method)?, url);
+    let mut req = Request::new(Method::from_str(method).map_err(malformed)?, url);
 
-    req.set_version(Some(http_types::Version::Http1_1));
+    req.set_version(Some(version));
 
     for header in httparse_req.headers.iter() {
-        req.append_header(header.name, std::str::from_utf8(header.value)?);
+        let value = std::str::from_utf8(header.value).map_err(malformed)?;
+        req.append_header(header.name, value);
     }
 
-    let content_length = ContentLength::from_headers(&req)?;
-    let transfer_encoding = req.header(TRANSFER_ENCODING);
-
-    // Return a 400 status if both Content-Length and Transfer-Encoding headers
-    // are set to prevent request smuggling attacks.
+    // Requests whose framing could be read differently by another server in
+    // front of this one are rejected with a 400 status, to prevent request
+    // smuggling attacks.
     //
     // https://tools.ietf.org/html/rfc7230#section-3.3.3
-    http_types::ensure_status!(
+    let content_length = content_length(req.header(CONTENT_LENGTH))?;
+    let transfer_encoding = req.header(TRANSFER_ENCODING).cloned();
+    ensure_kind!(
         content_length.is_none() || transfer_encoding.is_none(),
-        400,
+        BodyFraming,
         "Unexpected Content-Length header"
     );
-
-    // Establish a channel to wait for the body to be read. This
-    // allows us to avoid sending 100-continue in situations that
-    // respond without reading the body, saving clients from uploading
-    // their body.
-    let (body_read_sender, body_read_receiver) = async_channel::bounded(1);
-
-    if Some(CONTINUE_HEADER_VALUE) == req.header(EXPECT).map(|h| h.as_str()) {
-        task::spawn(async move {
-            // If the client expects a 100-continue header, spawn a
-            // task to wait for the first read attempt on the body.
-            if let Ok(()) = body_read_receiver.recv().await {
-                io.write_all(CONTINUE_RESPONSE).await.ok();
-            };
-            // Since the sender is moved into the Body, this task will
-            // finish when the client disconnects, whether or not
-            // 100-continue was sent.
-        });
+    let chunked = is_chunked(transfer_encoding.as_ref())?;
+    if let (Some(len), Some(max)) = (content_length, opts.max_body_size) {
+        if len > max {
+            return Err(body_too_large().into_http());
+        }
     }
 
+    // If the client expects a 100-continue, it is sent on the first read
+    // attempt on the body. The connection loop claims it if the endpoint
+    // responds without reading, since the body won't be sent then.
+    // HTTP/1.0 has no interim responses, so such clients don't get one.
+    let expects_continue = version == http_types::Version::Http1_1
+        && Some(CONTINUE_HEADER_VALUE) == req.header(EXPECT).map(|h| h.as_str())
+        && content_length.map_or(chunked, |len| len > 0);
+    let pending = PendingContinue::new(expects_continue);
+    req.ext_mut().insert(pending.clone());
+    let errors = BodyError::default();
+    req.ext_mut().insert(errors.clone());
+
+    #[cfg(not(feature = "chunked"))]
+    ensure_kind!(
+        transfer_encoding.is_none(),
+        BodyFraming,
+        "Chunked request bodies aren't supported"
+    );
+
     // Check for Transfer-Encoding
-    if transfer_encoding
-        .map(|te| te.as_str().eq_ignore_ascii_case("chunked"))
-        .unwrap_or(false)
-    {
+    #[cfg(feature = "chunked")]
+    if chunked {
+        if let Some(frame_size) = opts.passthrough {
+            let reader = Arc::new(Mutex::new(ChunkedPassthrough::new(reader)));
+            let body = ExpectContinue::new(reader.clone(), io, pending);
+            let body = TimedStream::new(body, opts.body_timeout, TimeoutPhase::Body);
+            let body = errors.watch(Limited::new(body, opts.max_body_size));
+            req.set_body(Body::from_reader(
+                BufReader::with_capacity(frame_size, body),
+                None,
+            ));
+            return Ok(Some((req, BodyReader::Passthrough(reader))));
+        }
+
         let trailer_sender = req.send_trailers();
         let reader = ChunkedDecoder::new(reader, trailer_sender);
         let reader = Arc::new(Mutex::new(reader));
         let reader_clone = reader.clone();
-        let reader = ReadNotifier::new(reader, body_read_sender);
-        let reader = BufReader::new(reader);
+        let reader = ExpectContinue::new(reader, io, pending);
+        let reader = TimedStream::new(reader, opts.body_timeout, TimeoutPhase::Body);
+        let reader = Limited::new(reader, opts.max_body_size);
+        let reader = BufReader::new(errors.watch(reader));
         req.set_body(Body::from_reader(reader, None));
-        Ok(Some((req, BodyReader::Chunked(reader_clone))))
-    } else if let Some(len) = content_length {
-        let len = len.len();
+        return Ok(Some((req, BodyReader::Chunked(reader_clone))));
+    }
+
+    if let Some(len) = content_length {
         let reader = Arc::new(Mutex::new(reader.take(len)));
-        req.set_body(Body::from_reader(
-            BufReader::new(ReadNotifier::new(reader.clone(), body_read_sender)),
-            Some(len as usize),
-        ));
+        let body = ExpectContinue::new(reader.clone(), io, pending);
+        let body = TimedStream::new(body, opts.body_timeout, TimeoutPhase::Body);
+        let body = errors.watch(body);
+        let body = match opts.passthrough {
+            Some(frame_size) => BufReader::with_capacity(frame_size, body),
+            None => BufReader::new(body),
+        };
+        req.set_body(Body::from_reader(body, Some(len as usize)));
         Ok(Some((req, BodyReader::Fixed(reader))))
     } else {
-        Ok(Some((req, BodyReader::None)))
+        Ok(Some((req, BodyReader::None(reader))))
+    }
+}
+
+/// The body length stated by a request's `Content-Length` headers. Repeated
+/// values are accepted as long as they all agree.
+fn content_length(values: Option<&HeaderValues>) -> http_types::Result<Option<u64>> {
+    let values = values
+        .into_iter()
+        .flat_map(|values| values.iter())
+        .flat_map(|value| value.as_str().split(','))
+        .map(str::trim);
+    let mut length = None;
+    for value in values {
+        ensure_kind!(
+            !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()),
+            BodyFraming,
+            "Invalid Content-Length header: {:?}",
+            value
+        );
+        let value = value.parse().map_err(|e| {
+            err_kind!(BodyFraming, "Invalid Content-Length header: {}", e).into_http()
+        })?;
+        ensure_kind!(
+            length.is_none() || length == Some(value),
+            BodyFraming,
+            "Conflicting Content-Length headers"
+        );
+        length = Some(value);
     }
+    Ok(length)
 }
 
-fn url_from_httparse_req(req: &httparse::Request<'_, '_>) -> http_types::Result<Url> {
-    let path = req.path.ok_or_else(|| format_err!("No uri found"))?;
+/// Whether a request's `Transfer-Encoding` headers say its body is chunked.
+/// `chunked` must be the final coding and appear only once, since the body
+/// can't be delimited otherwise. Other codings aren't supported.
+fn is_chunked(values: Option<&HeaderValues>) -> http_types::Result<bool> {
+    let values = match values {
+        Some(values) => values,
+        None => return Ok(false),
+    };
+    let codings: Vec<_> = values
+        .iter()
+        .flat_map(|value| value.as_str().split(','))
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty())
+        .collect();
+    let is_chunked = |coding: &str| coding.eq_ignore_ascii_case("chunked");
+    let chunked = codings.iter().filter(|coding| is_chunked(coding)).count();
+    ensure_kind!(
+        codings.last().is_some_and(|coding| is_chunked(coding)) && chunked == 1,
+        BodyFraming,
+        "chunked must be the final transfer coding, and appear once"
+    );
+    if codings.len() > 1 {
+        let message = format!("Unsupported transfer coding: {}", codings[0]);
+        return Err(Error::new(ErrorKind::BodyFraming, message)
+            .into_http_with_status(StatusCode::NotImplemented));
+    }
+    Ok(true)
+}
+
+/// The URL of a request, whose host is `default_host` if the request
+/// doesn't name one.
+fn url_from_httparse_req(
+    req: &httparse::Request<'_, '_>,
+    default_host: Option<&str>,
+) -> http_types::Result<Url> {
+    let path = req.path.ok_or_else(|| malformed("No uri found"))?;
 
     let host = req
         .headers
         .iter()
         .find(|x| x.name.eq_ignore_ascii_case("host"))
-        .ok_or_else(|| format_err!("Mandatory Host header missing"))?
-        .value;
-
-    let host = std::str::from_utf8(host)?;
+        .map(|header| std::str::from_utf8(header.value).map_err(malformed))
+        .transpose()?;
+    let host = match (host, default_host) {
+        (Some(host), _) if !host.is_empty() => host,
+        (_, Some(default_host)) => default_host,
+        (Some(host), None) => host,
+        (None, None) => return Err(malformed("Mandatory Host header missing")),
+    };
+
+    // CONNECT requests name the tunnel's destination in authority-form,
+    // and only they may.
+    // https://www.rfc-editor.org/rfc/rfc9112#section-3.2.3
+    if req.method.unwrap().eq_ignore_ascii_case("connect") {
+        ensure_kind!(
+            is_authority_form(path),
+            MalformedMessage,
+            "CONNECT requests need a host:port target"
+        );
+        return Url::parse(&format!("http://{}/", path)).map_err(malformed);
+    }
 
-    if path.starts_with("http://") || path.starts_with("https://") {
-        Ok(Url::parse(path)?)
+    // Absolute-form, as sent to proxies. The Host header is ignored.
+    // https://www.rfc-editor.org/rfc/rfc9112#section-3.2.2
+    let scheme = path.split_once("://").map(|(scheme, _)| scheme);
+    if scheme.is_some_and(|scheme| {
+        scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+    }) {
+        let url = Url::parse(path).map_err(malformed)?;
+        ensure_kind!(
+            url.has_host(),
+            MalformedMessage,
+            "Absolute request target without a host"
+        );
+        Ok(url)
     } else if path.starts_with('/') {
-        Ok(Url::parse(&format!("http://{}{}", host, path))?)
-    } else if req.method.unwrap().eq_ignore_ascii_case("connect") {
-        Ok(Url::parse(&format!("http://{}/", path))?)
+        Url::parse(&format!("http://{}{}", host, path)).map_err(malformed)
     } else {
-        Err(format_err!("unexpected uri format"))
+        Err(malformed("unexpected uri format"))
+    }
+}
+
+/// Whether a request target is a bare `host:port`.
+fn is_authority_form(target: &str) -> bool {
+    match target.rsplit_once(':') {
+        Some((host, port)) => {
+            !host.is_empty()
+                && !host.contains(['/', '?', '#', '@'])
+                && !port.is_empty()
+                && port.bytes().all(|byte| byte.is_ascii_digit())
+        }
+        None => false,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::MAX_HEADERS;
 
     fn httparse_req(buf: &str, f: impl Fn(httparse::Request<'_, '_>)) {
         let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
@@ -180,7 +417,7 @@ mod tests {
         httparse_req(
             "CONNECT server.example.com:443 HTTP/1.1\r\nHost: server.example.com:443\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(url.as_str(), "http://server.example.com:443/");
             },
         );
@@ -191,7 +428,7 @@ mod tests {
         httparse_req(
             "GET /some/resource HTTP/1.1\r\nHost: server.example.com:443\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(url.as_str(), "http://server.example.com:443/some/resource");
             },
         )
@@ -202,7 +439,7 @@ mod tests {
         httparse_req(
             "GET http://domain.com/some/resource HTTP/1.1\r\nHost: server.example.com\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(url.as_str(), "http://domain.com/some/resource"); // host header MUST be ignored according to spec
             },
         )
@@ -213,18 +450,68 @@ mod tests {
         httparse_req(
             "CONNECT server.example.com:443 HTTP/1.1\r\nHost: conflicting.host\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(url.as_str(), "http://server.example.com:443/");
             },
         )
     }
 
+    #[test]
+    fn url_for_connect_to_default_port() {
+        httparse_req("CONNECT [::1]:80 HTTP/1.1\r\nHost: [::1]:80\r\n", |req| {
+            let url = url_from_httparse_req(&req, None).unwrap();
+            assert_eq!(url.host_str(), Some("[::1]"));
+            assert_eq!(url.port_or_known_default(), Some(80));
+        })
+    }
+
+    #[test]
+    fn url_for_connect_without_port() {
+        for target in ["server.example.com", "/", "http://server.example.com:443/"] {
+            let head = format!(
+                "CONNECT {} HTTP/1.1\r\nHost: server.example.com\r\n",
+                target
+            );
+            httparse_req(&head, |req| {
+                assert!(url_from_httparse_req(&req, None).is_err());
+            })
+        }
+    }
+
+    #[test]
+    fn url_for_absolute_url_with_uppercase_scheme() {
+        httparse_req(
+            "GET HTTPS://domain.com:8443/some/resource HTTP/1.1\r\nHost: server.example.com\r\n",
+            |req| {
+                let url = url_from_httparse_req(&req, None).unwrap();
+                assert_eq!(url.as_str(), "https://domain.com:8443/some/resource");
+            },
+        )
+    }
+
+    #[test]
+    fn url_for_default_host() {
+        httparse_req("GET /some/resource HTTP/1.1\r\n", |req| {
+            assert!(url_from_httparse_req(&req, None).is_err());
+            let url = url_from_httparse_req(&req, Some("localhost")).unwrap();
+            assert_eq!(url.as_str(), "http://localhost/some/resource");
+        });
+        httparse_req("GET / HTTP/1.1\r\nHost: \r\n", |req| {
+            let url = url_from_httparse_req(&req, Some("localhost")).unwrap();
+            assert_eq!(url.as_str(), "http://localhost/");
+        });
+        httparse_req("GET / HTTP/1.1\r\nHost: example.com\r\n", |req| {
+            let url = url_from_httparse_req(&req, Some("localhost")).unwrap();
+            assert_eq!(url.as_str(), "http://example.com/");
+        });
+    }
+
     #[test]
     fn url_for_malformed_resource_path() {
         httparse_req(
             "GET not-a-url HTTP/1.1\r\nHost: server.example.com\r\n",
             |req| {
-                assert!(url_from_httparse_req(&req).is_err());
+                assert!(url_from_httparse_req(&req, None).is_err());
             },
         )
     }
@@ -234,7 +521,7 @@ mod tests {
         httparse_req(
             "GET //double/slashes HTTP/1.1\r\nHost: server.example.com:443\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(
                     url.as_str(),
                     "http://server.example.com:443//double/slashes"
@@ -247,7 +534,7 @@ mod tests {
         httparse_req(
             "GET ///triple/slashes HTTP/1.1\r\nHost: server.example.com:443\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(
                     url.as_str(),
                     "http://server.example.com:443///triple/slashes"
@@ -261,7 +548,7 @@ mod tests {
         httparse_req(
             "GET /foo?bar=1 HTTP/1.1\r\nHost: server.example.com:443\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(url.as_str(), "http://server.example.com:443/foo?bar=1");
             },
         )
@@ -272,7 +559,7 @@ mod tests {
         httparse_req(
             "GET /foo?bar=1#anchor HTTP/1.1\r\nHost: server.example.com:443\r\n",
             |req| {
-                let url = url_from_httparse_req(&req).unwrap();
+                let url = url_from_httparse_req(&req, None).unwrap();
                 assert_eq!(
                     url.as_str(),
                     "http://server.example.com:443/foo?bar=1#anchor"
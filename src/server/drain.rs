@@ -0,0 +1,197 @@
+//! Wind down connections ahead of a restart.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use event_listener::Event;
+
+use crate::timer::timeout;
+
+/// A switch which puts every connection sharing it into draining mode.
+///
+/// Pass it to the server with
+/// [`ServerOptions::with_drain`](super::ServerOptions::with_drain) and call
+/// [`Drain::start`] when the process should stop taking traffic. From then
+/// on:
+///
+/// - responses already being handled are sent with `Connection: close`,
+/// - connections waiting for their next request are closed, and
+/// - [`Drain::drained`] resolves once the last connection has closed.
+///
+/// Alternatively, [`Drain::shutdown`] drains with a deadline after which the
+/// remaining connections are closed forcefully.
+///
+/// Connections accepted after draining has started are closed without
+/// serving a request, but the listener is the application's, so its accept
+/// loop should stop too, e.g. by racing it against [`Drain::draining`].
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::server::{Drain, ServerOptions};
+/// use async_std::net::TcpListener;
+/// use async_std::task;
+/// use futures_lite::future;
+/// use http_types::Response;
+/// use std::time::Duration;
+///
+/// # async fn run() -> std::io::Result<()> {
+/// let drain = Drain::new();
+/// let opts = ServerOptions::new().with_drain(drain.clone());
+///
+/// let listener = TcpListener::bind("127.0.0.1:8080").await?;
+/// loop {
+///     let accepted = async { Some(listener.accept().await) };
+///     let stopped = async {
+///         drain.draining().await;
+///         None
+///     };
+///     let Some(accepted) = future::or(accepted, stopped).await else {
+///         break;
+///     };
+///     let (stream, _) = accepted?;
+///     let opts = opts.clone();
+///     task::spawn(async_h1::accept_with_opts(
+///         stream,
+///         |_req| async { Ok(Response::new(200)) },
+///         opts,
+///     ));
+/// }
+///
+/// // Once `drain.shutdown(..)` has been called elsewhere, e.g. on SIGTERM:
+/// drain.drained().await;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Drain {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    draining: AtomicBool,
+    connections: AtomicUsize,
+    forced: AtomicBool,
+    closed_gracefully: AtomicUsize,
+    closed_forcefully: AtomicUsize,
+    /// Notified when draining starts, when connections are forced to close,
+    /// and when the last connection closes.
+    event: Event,
+}
+
+/// How the connections open during a [`Drain::shutdown`] were closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShutdownReport {
+    /// Connections which finished their last response and closed.
+    pub graceful: usize,
+    /// Connections which were still open when the grace period expired, and
+    /// whose in-flight handlers were cancelled.
+    pub forced: usize,
+}
+
+impl Drain {
+    /// Create a new switch which isn't draining yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Switch to draining mode. Calling this more than once has no effect.
+    pub fn start(&self) {
+        if !self.inner.draining.swap(true, Ordering::SeqCst) {
+            self.inner.event.notify(usize::MAX);
+        }
+    }
+
+    /// Whether [`Drain::start`] has been called.
+    pub fn is_draining(&self) -> bool {
+        self.inner.draining.load(Ordering::SeqCst)
+    }
+
+    /// The number of connections currently open.
+    pub fn connections(&self) -> usize {
+        self.inner.connections.load(Ordering::SeqCst)
+    }
+
+    /// Wait until draining has started and all connections have closed.
+    pub async fn drained(&self) {
+        self.wait_until(|drain| drain.is_draining() && drain.connections() == 0)
+            .await
+    }
+
+    /// Drain connections, waiting at most `grace` for them to close before
+    /// closing the remaining ones forcefully.
+    ///
+    /// On `wasm32` targets there's no timer to enforce the deadline, so this
+    /// waits until all connections have closed gracefully.
+    pub async fn shutdown(&self, grace: Duration) -> ShutdownReport {
+        self.start();
+        if timeout(grace, self.drained()).await.is_err() {
+            self.inner.forced.store(true, Ordering::SeqCst);
+            self.inner.event.notify(usize::MAX);
+            self.drained().await;
+        }
+        ShutdownReport {
+            graceful: self.inner.closed_gracefully.load(Ordering::SeqCst),
+            forced: self.inner.closed_forcefully.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Wait until draining has started, e.g. to stop accepting new
+    /// connections.
+    pub async fn draining(&self) {
+        self.wait_until(Drain::is_draining).await
+    }
+
+    /// Wait until the remaining connections must be closed forcefully.
+    pub(crate) async fn forced(&self) {
+        self.wait_until(|drain| drain.inner.forced.load(Ordering::SeqCst))
+            .await
+    }
+
+    /// Count a connection as open until the returned guard is dropped.
+    pub(crate) fn track(&self) -> Tracked {
+        self.inner.connections.fetch_add(1, Ordering::SeqCst);
+        Tracked {
+            drain: self.clone(),
+            forced: false,
+        }
+    }
+
+    async fn wait_until(&self, done: impl Fn(&Drain) -> bool) {
+        loop {
+            if done(self) {
+                return;
+            }
+            // Register before checking again so a notification in between
+            // isn't missed.
+            let listener = self.inner.event.listen();
+            if done(self) {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
+
+/// Counts a connection as open for as long as it's alive.
+#[derive(Debug)]
+pub(crate) struct Tracked {
+    drain: Drain,
+    /// Whether the connection was closed by [`Drain::shutdown`].
+    pub(crate) forced: bool,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let inner = &self.drain.inner;
+        if self.forced {
+            inner.closed_forcefully.fetch_add(1, Ordering::SeqCst);
+        } else if self.drain.is_draining() {
+            inner.closed_gracefully.fetch_add(1, Ordering::SeqCst);
+        }
+        if inner.connections.fetch_sub(1, Ordering::SeqCst) == 1 {
+            inner.event.notify(usize::MAX);
+        }
+    }
+}
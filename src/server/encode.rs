@@ -1,16 +1,27 @@
 //! Process HTTP connections on the server.
 
+use std::future::Future;
 use std::io::Write;
+use std::mem;
 use std::pin::Pin;
-use std::time::SystemTime;
+use std::sync::Arc;
 
-use async_std::io::{self, Cursor, Read};
-use async_std::task::{Context, Poll};
-use http_types::headers::{CONTENT_LENGTH, DATE, TRANSFER_ENCODING};
-use http_types::{Method, Response};
+use std::task::{Context, Poll};
 
-use crate::body_encoder::BodyEncoder;
-use crate::date::fmt_http_date;
+use futures_core::ready;
+
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite, AsyncWriteExt, Cursor};
+use http_types::headers::{CONTENT_LENGTH, DATE, TRAILER, TRANSFER_ENCODING};
+use http_types::{Method, Response, StatusCode};
+
+#[cfg(feature = "chunked")]
+use crate::body_encoder::chunk_buffered;
+use crate::body_encoder::{is_chunked, BodyEncoder, PendingTrailers};
+use crate::date::{cached_http_date, now};
+use crate::digest::{BodyDigest, DigestAlgorithm};
+use crate::error::{Error, Limit};
+use crate::owned::{read_owned, BufResult};
 use crate::read_to_end;
 use crate::EncoderState;
 
@@ -20,6 +31,55 @@ pub struct Encoder {
     response: Response,
     state: EncoderState,
     method: Method,
+    digest: Option<Arc<dyn DigestAlgorithm>>,
+    max_head_length: Option<usize>,
+    /// Whether to yield to the executor once between the head and the body.
+    yield_before_body: bool,
+    /// Bytes of the encoded body read so far.
+    body_bytes: u64,
+    /// Chunks of the body read so far.
+    chunks: usize,
+    /// Whether to send chunked bodies as they are.
+    passthrough: bool,
+    /// Whether the body is sent as it is.
+    raw_body: bool,
+    /// Whether the response is for an HTTP/1.0 client.
+    http_1_0: bool,
+    /// Trailers to send after a chunked body.
+    trailers: Option<PendingTrailers>,
+    /// The size of chunks bodies of unknown length are gathered into.
+    #[cfg(feature = "chunked")]
+    chunk_buffer_size: Option<usize>,
+    /// A buffer to encode the head into, and the buffer it was encoded into
+    /// once it's been read.
+    head_buffer: Option<Vec<u8>>,
+    /// Whether the body is written by the caller once the head has been,
+    /// rather than by the encoder.
+    head_only: bool,
+}
+
+/// The largest head buffer kept for reuse. Heads are usually far shorter,
+/// and one unusually long head shouldn't pin its buffer for the rest of
+/// the connection.
+const MAX_REUSED_HEAD_BUFFER: usize = 8 * 1024;
+
+/// Take the buffer of a head which has been read, leaving an empty one.
+fn take_buffer(cursor: &mut Cursor<Vec<u8>>) -> Vec<u8> {
+    cursor.set_position(0);
+    mem::take(cursor.get_mut())
+}
+
+/// How far an [`Encoder`] has got with a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EncoderPhase {
+    /// Nothing has been read yet.
+    Start,
+    /// The response head is being read.
+    Head,
+    /// The response body is being read.
+    Body,
+    /// The whole response has been read.
+    End,
 }
 
 impl Read for Encoder {
@@ -30,20 +90,24 @@ impl Read for Encoder {
     ) -> Poll<io::Result<usize>> {
         loop {
             self.state = match self.state {
-                EncoderState::Start => EncoderState::Head(self.compute_head()?),
+                EncoderState::Start => EncoderState::Head(self.compute_head(cx)?),
 
                 EncoderState::Head(ref mut cursor) => {
-                    read_to_end!(Pin::new(cursor).poll_read(cx, buf));
-
-                    if self.method == Method::Head {
-                        EncoderState::End
-                    } else {
-                        EncoderState::Body(BodyEncoder::new(self.response.take_body()))
+                    read_to_end!(Pin::new(&mut *cursor).poll_read(cx, buf));
+                    self.head_buffer = Some(take_buffer(cursor));
+                    match self.next_after_head(cx) {
+                        Some(state) => state,
+                        None => return Poll::Pending,
                     }
                 }
 
                 EncoderState::Body(ref mut encoder) => {
-                    read_to_end!(Pin::new(encoder).poll_read(cx, buf));
+                    let poll = Pin::new(&mut *encoder).poll_read(cx, buf);
+                    self.chunks = encoder.chunks();
+                    if let Poll::Ready(Ok(bytes)) = poll {
+                        self.body_bytes += bytes as u64;
+                    }
+                    read_to_end!(poll);
                     EncoderState::End
                 }
 
@@ -60,40 +124,382 @@ impl Encoder {
             method,
             response,
             state: EncoderState::Start,
+            digest: None,
+            max_head_length: None,
+            yield_before_body: false,
+            body_bytes: 0,
+            chunks: 0,
+            passthrough: false,
+            raw_body: false,
+            http_1_0: false,
+            trailers: None,
+            #[cfg(feature = "chunked")]
+            chunk_buffer_size: None,
+            head_buffer: None,
+            head_only: false,
         }
     }
 
-    fn finalize_headers(&mut self) {
+    /// Encode the head into this buffer rather than allocating one, e.g.
+    /// the buffer of the previous response on the same connection.
+    pub(crate) fn with_head_buffer(mut self, buffer: Vec<u8>) -> Self {
+        self.head_buffer = Some(buffer);
+        self
+    }
+
+    /// The buffer the head was encoded into, once it's been read, for the
+    /// next response to reuse.
+    pub(crate) fn take_head_buffer(&mut self) -> Option<Vec<u8>> {
+        self.head_buffer
+            .take()
+            .filter(|buffer| buffer.capacity() <= MAX_REUSED_HEAD_BUFFER)
+    }
+
+    /// Send a body of unknown length as it is if the response already has a
+    /// `Transfer-Encoding: chunked` header, rather than chunking it again.
+    ///
+    /// This is for bodies which are chunked already, such as those passed
+    /// through from another connection, keeping their chunk boundaries and
+    /// trailers.
+    pub fn with_passthrough(mut self) -> Self {
+        self.passthrough = true;
+        self
+    }
+
+    /// Encode the response for an HTTP/1.0 client, which doesn't understand
+    /// chunked bodies.
+    ///
+    /// The status line says `HTTP/1.0`, and bodies of unknown length are
+    /// sent as they are, with neither a `Content-Length` nor a
+    /// `Transfer-Encoding` header: the client reads them until the
+    /// connection closes, so it needs closing once the response is written.
+    /// Trailers aren't sent.
+    pub fn with_http_1_0(mut self) -> Self {
+        self.http_1_0 = true;
+        self
+    }
+
+    /// Compute a digest of chunked response bodies as they're encoded and
+    /// send it as a `Content-Digest` trailer.
+    pub fn with_content_digest(mut self, algorithm: Arc<dyn DigestAlgorithm>) -> Self {
+        self.digest = Some(algorithm);
+        self
+    }
+
+    /// Gather bodies of unknown length into chunks of up to `size` bytes
+    /// in a buffer reused for every chunk, rather than sending each piece
+    /// the body hands out as a chunk of its own.
+    #[cfg(feature = "chunked")]
+    pub fn with_chunk_buffer_size(mut self, size: usize) -> Self {
+        self.chunk_buffer_size = Some(size);
+        self
+    }
+
+    /// Fail with an
+    /// [`ErrorKind::LimitExceeded`](crate::error::ErrorKind::LimitExceeded)
+    /// error, before writing anything, if the response head would be longer
+    /// than `max` bytes.
+    pub fn with_max_head_length(mut self, max: usize) -> Self {
+        self.max_head_length = Some(max);
+        self
+    }
+
+    /// Encode into the spare capacity of an owned buffer, after its current
+    /// contents, for submission to a completion-based runtime.
+    ///
+    /// Returns the number of bytes encoded, 0 once the response has been
+    /// encoded completely, and the buffer.
+    pub async fn read_owned(&mut self, buf: Vec<u8>) -> BufResult<usize> {
+        read_owned(self, buf).await
+    }
+
+    /// How far encoding has got.
+    pub fn phase(&self) -> EncoderPhase {
+        match self.state {
+            EncoderState::Start => EncoderPhase::Start,
+            EncoderState::Head(_) => EncoderPhase::Head,
+            EncoderState::Body(_) => EncoderPhase::Body,
+            EncoderState::End => EncoderPhase::End,
+        }
+    }
+
+    /// The number of bytes of the response head left to read, or `None` if
+    /// the head hasn't been computed yet.
+    pub fn head_bytes_remaining(&self) -> Option<usize> {
+        match &self.state {
+            EncoderState::Start => None,
+            EncoderState::Head(cursor) => Some(cursor.get_ref().len() - cursor.position() as usize),
+            EncoderState::Body(_) | EncoderState::End => Some(0),
+        }
+    }
+
+    /// The number of bytes of the body read so far, including any chunked
+    /// framing.
+    pub fn body_bytes_written(&self) -> u64 {
+        self.body_bytes
+    }
+
+    /// The number of chunks of a chunked body read so far, not counting the
+    /// last, empty chunk. Always 0 for bodies of known length.
+    pub fn chunks_emitted(&self) -> usize {
+        self.chunks
+    }
+
+    /// Write the response to `writer`, yielding to the executor after every
+    /// `budget` bytes, and return the number of bytes written.
+    ///
+    /// Unlike copying from the encoder, this writes straight from the head
+    /// and the body's buffers, with chunk framing written alongside the data
+    /// in vectored writes rather than copied in front of it.
+    pub(crate) async fn write_to<W: AsyncWrite + Unpin>(
+        &mut self,
+        writer: &mut W,
+        budget: Option<usize>,
+    ) -> io::Result<u64> {
+        let budget = budget.map(|budget| budget.max(1) as u64);
+        let (mut written, mut since_yield) = (0, 0);
+        let mut unflushed = false;
+        loop {
+            let bytes = future::poll_fn(|cx| match self.poll_write_to(cx, writer) {
+                // Flush while waiting for more of the body, so what's been
+                // written so far, like a server-sent event, isn't held back
+                // in the stream's buffers.
+                Poll::Pending if unflushed => {
+                    ready!(Pin::new(&mut *writer).poll_flush(cx))?;
+                    unflushed = false;
+                    Poll::Pending
+                }
+                poll => poll,
+            })
+            .await? as u64;
+            if bytes == 0 {
+                writer.flush().await?;
+                return Ok(written);
+            }
+            unflushed = true;
+            written += bytes;
+            since_yield += bytes;
+            if budget.is_some_and(|budget| since_yield >= budget) {
+                since_yield = 0;
+                future::yield_now().await;
+            }
+        }
+    }
+
+    fn poll_write_to<W: AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        writer: &mut W,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            self.state = match self.state {
+                EncoderState::Start => EncoderState::Head(self.compute_head(cx)?),
+
+                EncoderState::Head(ref mut cursor) => {
+                    let position = cursor.position() as usize;
+                    let remaining = &cursor.get_ref()[position..];
+                    if !remaining.is_empty() {
+                        let bytes = ready!(Pin::new(&mut *writer).poll_write(cx, remaining))?;
+                        if bytes == 0 {
+                            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                        }
+                        cursor.set_position((position + bytes) as u64);
+                        return Poll::Ready(Ok(bytes));
+                    }
+                    self.head_buffer = Some(take_buffer(cursor));
+                    match self.next_after_head(cx) {
+                        Some(state) => state,
+                        None => return Poll::Pending,
+                    }
+                }
+
+                EncoderState::Body(ref mut encoder) => {
+                    let poll = encoder.poll_write_to(cx, writer);
+                    self.chunks = encoder.chunks();
+                    if let Poll::Ready(Ok(bytes)) = poll {
+                        self.body_bytes += bytes as u64;
+                    }
+                    read_to_end!(poll);
+                    EncoderState::End
+                }
+
+                EncoderState::End => return Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    /// The state following the head, or `None` after scheduling a wakeup to
+    /// yield before the body.
+    fn next_after_head(&mut self, cx: &mut Context<'_>) -> Option<EncoderState> {
+        if self.method == Method::Head
+            || self.is_bodiless()
+            || self.response.len() == Some(0)
+            || self.head_only
+        {
+            Some(EncoderState::End)
+        } else if self.yield_before_body {
+            self.yield_before_body = false;
+            cx.waker().wake_by_ref();
+            None
+        } else {
+            let body = self.response.take_body();
+            #[cfg(feature = "chunked")]
+            let body = chunk_buffered(body, self.chunk_buffer_size);
+            if self.raw_body {
+                Some(EncoderState::Body(BodyEncoder::Fixed(body)))
+            } else {
+                let digest = self.digest.as_deref().map(BodyDigest::new);
+                let trailers = self.trailers.take();
+                let encoder = BodyEncoder::with_trailers(body, trailers, digest);
+                Some(EncoderState::Body(encoder))
+            }
+        }
+    }
+
+    /// Stop after the head, leaving the body, whose length is in the head,
+    /// for the caller to write.
+    #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+    pub(crate) fn with_head_only(mut self) -> Self {
+        self.head_only = true;
+        self
+    }
+
+    /// Yield to the executor once after the head has been read, before
+    /// starting on the body.
+    pub(crate) fn with_yield_before_body(mut self) -> Self {
+        self.yield_before_body = true;
+        self
+    }
+
+    /// Whether none of the response has been produced yet, e.g. because the
+    /// head couldn't be encoded.
+    pub(crate) fn is_unstarted(&self) -> bool {
+        matches!(self.state, EncoderState::Start)
+    }
+
+    fn finalize_headers(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
         // If the body isn't streaming, we can set the content-length ahead of time. Else we need to
-        // send all items in chunks.
-        if let Some(len) = self.response.len() {
+        // send all items in chunks. 204 and 304 responses have no body, and any framing headers
+        // would describe a representation the client doesn't get or already has.
+        if self.is_bodiless() {
+            self.response.remove_header(CONTENT_LENGTH);
+            self.response.remove_header(TRANSFER_ENCODING);
+        } else if self.passthrough && is_chunked(self.response.len(), self.response.as_ref()) {
+            self.raw_body = true;
+        } else if self.is_head_without_body() {
+            // A response to HEAD describes the body a GET would get, which
+            // endpoints may state without producing it: keep their length.
+        } else if let Some(len) = self.response.len() {
             self.response.insert_header(CONTENT_LENGTH, len.to_string());
-        } else {
+        } else if self.http_1_0 {
+            // The body is delimited by closing the connection.
+            self.response.remove_header(TRANSFER_ENCODING);
+            self.raw_body = true;
+        } else if cfg!(feature = "chunked") {
             self.response.insert_header(TRANSFER_ENCODING, "chunked");
+            if self.response.has_trailers() && self.method != Method::Head {
+                self.trailers = self.pending_trailers(cx);
+            }
+            if self.digest.is_some() && self.method != Method::Head {
+                self.response.append_header(TRAILER, "content-digest");
+            }
+        } else if self.method == Method::Head {
+            // No body follows, so there's nothing to frame.
+        } else {
+            return Err(err_kind!(
+                Encode,
+                "Response bodies of unknown length aren't supported without chunked encoding"
+            )
+            .into());
         }
 
         if self.response.header(DATE).is_none() {
-            let date = fmt_http_date(SystemTime::now());
-            self.response.insert_header(DATE, date);
+            if let Some(now) = now() {
+                self.response.insert_header(DATE, cached_http_date(now));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the response status rules out a body. A successful response
+    /// to CONNECT starts a tunnel instead.
+    fn is_bodiless(&self) -> bool {
+        matches!(
+            self.response.status(),
+            StatusCode::NoContent | StatusCode::NotModified
+        ) || (self.method == Method::Connect && self.response.status().is_success())
+    }
+
+    /// Whether this is a response to HEAD with an empty body, whose
+    /// `Content-Length` was set by the endpoint.
+    fn is_head_without_body(&self) -> bool {
+        self.method == Method::Head
+            && self.response.len() == Some(0)
+            && self.response.header(CONTENT_LENGTH).is_some()
+    }
+
+    /// Take the trailers the endpoint will send. If it has sent them
+    /// already, they're advertised in the `Trailer` header.
+    fn pending_trailers(&mut self, cx: &mut Context<'_>) -> Option<PendingTrailers> {
+        let mut receiver = self.response.recv_trailers();
+        match Pin::new(&mut receiver).poll(cx) {
+            Poll::Ready(Some(trailers)) => {
+                let advertised: Vec<String> = self
+                    .response
+                    .header(TRAILER)
+                    .map(|values| values.iter().map(|v| v.as_str().to_owned()).collect())
+                    .unwrap_or_default();
+                for name in trailers.names() {
+                    let is_advertised = advertised
+                        .iter()
+                        .flat_map(|value| value.split(','))
+                        .any(|listed| listed.trim().eq_ignore_ascii_case(name.as_str()));
+                    if !is_advertised {
+                        self.response.append_header(TRAILER, name.as_str());
+                    }
+                }
+                Some(PendingTrailers::Ready(trailers))
+            }
+            Poll::Ready(None) => None,
+            Poll::Pending => Some(PendingTrailers::Waiting(receiver)),
         }
     }
 
     /// Encode the headers to a buffer, the first time we poll.
-    fn compute_head(&mut self) -> io::Result<Cursor<Vec<u8>>> {
-        let mut head = Vec::with_capacity(128);
+    fn compute_head(&mut self, cx: &mut Context<'_>) -> io::Result<Cursor<Vec<u8>>> {
+        let mut head = match self.head_buffer.take() {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer
+            }
+            None => Vec::with_capacity(128),
+        };
         let reason = self.response.status().canonical_reason();
         let status = self.response.status();
-        write!(head, "HTTP/1.1 {} {}\r\n", status, reason)?;
+        let version = if self.http_1_0 { "1.0" } else { "1.1" };
+        write!(head, "HTTP/{} {} {}\r\n", version, status, reason)?;
 
-        self.finalize_headers();
+        self.finalize_headers(cx)?;
         let mut headers = self.response.iter().collect::<Vec<_>>();
         headers.sort_unstable_by_key(|(h, _)| h.as_str());
         for (header, values) in headers {
             for value in values.iter() {
                 write!(head, "{}: {}\r\n", header, value)?;
             }
+            self.check_head_length(head.len())?;
         }
         write!(head, "\r\n")?;
+        self.check_head_length(head.len())?;
         Ok(Cursor::new(head))
     }
+
+    fn check_head_length(&self, len: usize) -> io::Result<()> {
+        match self.max_head_length {
+            Some(max) if len > max => {
+                let message = format!("Response head exceeds the limit of {} bytes", max);
+                Err(Error::limit_exceeded(Limit::HeadLength, message).into())
+            }
+            _ => Ok(()),
+        }
+    }
 }
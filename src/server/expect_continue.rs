@@ -0,0 +1,175 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncBufRead as BufRead, AsyncRead as Read, AsyncWrite as Write};
+
+use super::interim::Interim;
+
+pub(crate) const CONTINUE_RESPONSE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+/// The progress of sending the `100 Continue` interim response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The interim response may be owed, unless the connection loop has
+    /// given up on the body already.
+    Unclaimed,
+    /// The client expects a `100 Continue` which hasn't been sent yet.
+    Writing(usize),
+    /// The interim response has been written and needs flushing.
+    Flushing,
+    /// Nothing (more) to send.
+    Done,
+}
+
+/// ExpectContinue forwards [`futures_lite::io::AsyncRead`] and
+/// [`futures_lite::io::AsyncBufRead`] to an inner reader. If the client
+/// sent `Expect: 100-continue`, the first read writes the interim
+/// `100 Continue` response to the writer before reading from the body.
+///
+/// This lets us avoid sending 100-continue in situations that respond
+/// without reading the body, saving clients from uploading their body,
+/// without having to spawn a task to wait for the first read.
+#[pin_project::pin_project]
+pub(crate) struct ExpectContinue<B, W> {
+    #[pin]
+    reader: B,
+    writer: W,
+    pending: PendingContinue,
+    state: State,
+}
+
+/// Whether a `100 Continue` is still owed to the client.
+///
+/// Shared between the request body, which sends it on the first read, and
+/// the connection loop, which needs to know the body was never asked for
+/// once the endpoint has responded. Whichever claims it first settles it.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingContinue(Arc<Shared>);
+
+#[derive(Debug)]
+struct Shared {
+    owed: AtomicBool,
+    /// The queue of interim responses to send it through, if the endpoint
+    /// can send interim responses of its own.
+    interim: Mutex<Option<Interim>>,
+}
+
+impl PendingContinue {
+    pub(crate) fn new(expects_continue: bool) -> Self {
+        Self(Arc::new(Shared {
+            owed: AtomicBool::new(expects_continue),
+            interim: Mutex::new(None),
+        }))
+    }
+
+    /// Claim the interim response, returning whether it was still owed.
+    pub(crate) fn claim(&self) -> bool {
+        self.0.owed.swap(false, Ordering::AcqRel)
+    }
+
+    /// Send the interim response through `interim` instead of writing it
+    /// directly, so that it can't interleave with the endpoint's.
+    pub(crate) fn send_through(&self, interim: &Interim) {
+        *self.interim() = Some(interim.clone());
+    }
+
+    fn interim(&self) -> std::sync::MutexGuard<'_, Option<Interim>> {
+        self.0.interim.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The queue the interim response was sent through, if it was.
+    pub(crate) fn queue(&self) -> Option<Interim> {
+        self.interim().clone()
+    }
+}
+
+impl<B, W> fmt::Debug for ExpectContinue<B, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpectContinue")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<B: Read, W: Write + Unpin> ExpectContinue<B, W> {
+    pub(crate) fn new(reader: B, writer: W, pending: PendingContinue) -> Self {
+        Self {
+            reader,
+            writer,
+            pending,
+            state: State::Unclaimed,
+        }
+    }
+}
+
+/// Send the interim response, if needed. Write errors are ignored here: if
+/// the connection went away, reading the body will surface that.
+fn poll_send_continue<W: Write + Unpin>(
+    writer: &mut W,
+    pending: &PendingContinue,
+    state: &mut State,
+    cx: &mut Context<'_>,
+) -> Poll<()> {
+    loop {
+        *state = match *state {
+            State::Unclaimed if pending.claim() => match pending.queue() {
+                Some(interim) => {
+                    interim.push_continue();
+                    State::Done
+                }
+                None => State::Writing(0),
+            },
+            State::Unclaimed => State::Done,
+            State::Writing(written) if written == CONTINUE_RESPONSE.len() => State::Flushing,
+            State::Writing(written) => {
+                match Pin::new(&mut *writer).poll_write(cx, &CONTINUE_RESPONSE[written..]) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) => State::Done,
+                    Poll::Ready(Ok(n)) => State::Writing(written + n),
+                }
+            }
+            State::Flushing => match Pin::new(&mut *writer).poll_flush(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(_) => State::Done,
+            },
+            State::Done => return Poll::Ready(()),
+        }
+    }
+}
+
+impl<B: BufRead, W: Write + Unpin> BufRead for ExpectContinue<B, W> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.project();
+        futures_core::ready!(poll_send_continue(
+            this.writer,
+            this.pending,
+            this.state,
+            cx
+        ));
+        this.reader.poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.project().reader.consume(amt)
+    }
+}
+
+impl<B: Read, W: Write + Unpin> Read for ExpectContinue<B, W> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        futures_core::ready!(poll_send_continue(
+            this.writer,
+            this.pending,
+            this.state,
+            cx
+        ));
+        this.reader.poll_read(cx, buf)
+    }
+}
@@ -0,0 +1,30 @@
+//! Send interim responses while an endpoint is working on a request.
+
+use std::time::Duration;
+
+/// Periodic interim responses sent while a request is being handled.
+#[derive(Debug, Clone)]
+pub(crate) struct Heartbeat {
+    pub(crate) interval: Duration,
+    pub(crate) head: Vec<u8>,
+}
+
+impl Heartbeat {
+    /// Send a response head with this 1xx status every `interval`.
+    pub(crate) fn new(interval: Duration, status: u16) -> Self {
+        assert!(
+            (100..200).contains(&status) && status != 101,
+            "heartbeats must be informational responses other than 101"
+        );
+        let reason = match status {
+            100 => "Continue",
+            102 => "Processing",
+            103 => "Early Hints",
+            _ => "",
+        };
+        Self {
+            interval,
+            head: format!("HTTP/1.1 {} {}\r\n\r\n", status, reason).into_bytes(),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Hooks applied to every exchange handled by the server.
+
+use std::fmt::Debug;
+
+use http_types::{Request, Response};
+
+/// Observes or modifies requests before they reach the endpoint, and
+/// responses before they're encoded.
+///
+/// Register hooks with
+/// [`ServerOptions::with_hook`](super::ServerOptions::with_hook). Requests
+/// pass through hooks in the order they were registered, and responses pass
+/// through them in reverse order, so that the first hook sees the request
+/// first and the response last.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::{Hook, ServerOptions};
+/// use http_types::{Request, Response, StatusCode};
+///
+/// /// Serves the application under `/api` by stripping the prefix.
+/// #[derive(Debug)]
+/// struct StripPrefix;
+///
+/// impl Hook for StripPrefix {
+///     fn before_endpoint(&self, req: &mut Request) -> Option<Response> {
+///         let path = match req.url().path().strip_prefix("/api") {
+///             Some(path) => path.to_owned(),
+///             None => return Some(Response::new(StatusCode::NotFound)),
+///         };
+///         req.url_mut().set_path(&path);
+///         None
+///     }
+/// }
+///
+/// let opts = ServerOptions::new().with_hook(StripPrefix);
+/// ```
+pub trait Hook: Debug + Send + Sync + 'static {
+    /// Called after a request has been decoded, before it's passed to the
+    /// endpoint.
+    ///
+    /// Return a response to send it instead of calling the endpoint. Hooks
+    /// registered after this one are skipped in that case.
+    fn before_endpoint(&self, _req: &mut Request) -> Option<Response> {
+        None
+    }
+
+    /// Called on every response right before it's encoded, including
+    /// responses returned by hooks and error responses sent after a request
+    /// failed to decode.
+    fn before_encode(&self, _res: &mut Response) {}
+
+    /// Called when an exchange fails and the connection is about to be
+    /// closed, e.g. because the request couldn't be decoded or the response
+    /// head exceeded
+    /// [`ServerOptions::with_max_response_head_length`](super::ServerOptions::with_max_response_head_length).
+    fn on_error(&self, _error: &http_types::Error) {}
+}
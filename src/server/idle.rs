@@ -0,0 +1,93 @@
+//! Limit the number of idle keep-alive connections across a server.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use event_listener::Event;
+
+/// The set of connections waiting for their next request, shared by every
+/// connection of a server.
+#[derive(Debug, Clone)]
+pub(crate) struct IdleConnections {
+    max: usize,
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    next_id: u64,
+    /// Idle connections by id. Ids only grow, so the first entry is the least
+    /// recently used connection.
+    idle: BTreeMap<u64, Arc<Slot>>,
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    evicted: AtomicBool,
+    event: Event,
+}
+
+impl IdleConnections {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            max,
+            inner: Arc::default(),
+        }
+    }
+
+    /// Mark a connection as idle until the returned guard is dropped,
+    /// evicting the least recently used connections if there are too many.
+    pub(crate) fn idle(&self) -> Idle {
+        let slot = Arc::new(Slot::default());
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.idle.insert(id, slot.clone());
+
+        while inner.idle.len() > self.max {
+            if let Some((_, evicted)) = inner.idle.pop_first() {
+                trace!("evicting idle connection");
+                evicted.evicted.store(true, Ordering::SeqCst);
+                evicted.event.notify(usize::MAX);
+            }
+        }
+
+        Idle {
+            connections: self.clone(),
+            id,
+            slot,
+        }
+    }
+}
+
+/// Marks a connection as idle for as long as it's alive.
+#[derive(Debug)]
+pub(crate) struct Idle {
+    connections: IdleConnections,
+    id: u64,
+    slot: Arc<Slot>,
+}
+
+impl Idle {
+    /// Wait until this connection is evicted to make room for another.
+    pub(crate) async fn evicted(&self) {
+        loop {
+            if self.slot.evicted.load(Ordering::SeqCst) {
+                return;
+            }
+            let listener = self.slot.event.listen();
+            if self.slot.evicted.load(Ordering::SeqCst) {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
+
+impl Drop for Idle {
+    fn drop(&mut self) {
+        let mut inner = self.connections.inner.lock().unwrap();
+        inner.idle.remove(&self.id);
+    }
+}
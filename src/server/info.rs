@@ -0,0 +1,111 @@
+//! Tell endpoints where their connection comes from.
+
+use std::net::SocketAddr;
+
+use super::TlsInfo;
+
+/// The addresses of the connection a request arrived on, and what its TLS
+/// layer negotiated.
+///
+/// Available in the extensions of every request. The server can't learn
+/// these from the stream it's given, so they're filled in from
+/// [`ServerOptions::with_peer_addr`](super::ServerOptions::with_peer_addr),
+/// [`ServerOptions::with_local_addr`](super::ServerOptions::with_local_addr)
+/// and [`ServerOptions::with_tls_info`](super::ServerOptions::with_tls_info)
+/// by the code accepting connections. The addresses are also set as the
+/// request's [`peer_addr`](http_types::Request::peer_addr) and
+/// [`local_addr`](http_types::Request::local_addr).
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::ConnectionInfo;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn endpoint(req: Request) -> http_types::Result<Response> {
+///     let info = req.ext().get::<ConnectionInfo>();
+///     let loopback = info
+///         .and_then(|info| info.peer_addr())
+///         .is_some_and(|addr| addr.ip().is_loopback());
+///     if !loopback {
+///         return Ok(Response::new(StatusCode::Forbidden));
+///     }
+///     Ok(Response::new(StatusCode::Ok))
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub(crate) peer_addr: Option<SocketAddr>,
+    pub(crate) local_addr: Option<SocketAddr>,
+    pub(crate) tls: Option<TlsInfo>,
+    pub(crate) peer_credentials: Option<PeerCredentials>,
+}
+
+impl ConnectionInfo {
+    /// The address of the client, or of the proxy in front of the server.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// The address the connection was accepted on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// What was negotiated by the TLS layer, if the connection runs over
+    /// one.
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        self.tls.as_ref()
+    }
+
+    /// The process on the other end of a Unix domain socket.
+    pub fn peer_credentials(&self) -> Option<PeerCredentials> {
+        self.peer_credentials
+    }
+}
+
+/// The process on the other end of a Unix domain socket, as the kernel
+/// saw it when the connection was made.
+///
+/// Read from the socket by `accept_unix`, with the `uds` feature, or set
+/// with
+/// [`ServerOptions::with_peer_credentials`](super::ServerOptions::with_peer_credentials).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pid: u32,
+    uid: u32,
+    gid: u32,
+}
+
+impl PeerCredentials {
+    /// Create an instance from a process id, user id and group id.
+    pub fn new(pid: u32, uid: u32, gid: u32) -> Self {
+        Self { pid, uid, gid }
+    }
+
+    /// Read the credentials of the peer of a connected Unix domain socket.
+    #[cfg(all(feature = "uds", any(target_os = "linux", target_os = "android")))]
+    pub fn of(socket: &impl std::os::unix::io::AsFd) -> std::io::Result<Self> {
+        let cred = rustix::net::sockopt::socket_peercred(socket)?;
+        Ok(Self {
+            pid: cred.pid.as_raw_pid() as u32,
+            uid: cred.uid.as_raw(),
+            gid: cred.gid.as_raw(),
+        })
+    }
+
+    /// The id of the peer's process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// The id of the user the peer's process runs as.
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// The id of the group the peer's process runs as.
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+}
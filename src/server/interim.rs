@@ -0,0 +1,250 @@
+//! Let endpoints send informational responses before their final one.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+#[cfg(not(target_arch = "wasm32"))]
+use async_io::Timer;
+use futures_lite::future;
+use futures_lite::io::AsyncWrite as Write;
+use http_types::{Response, StatusCode, Version};
+
+use super::expect_continue::CONTINUE_RESPONSE;
+use super::heartbeat::Heartbeat;
+
+/// Sends interim responses, such as `103 Early Hints`, ahead of the final
+/// response to a request.
+///
+/// Available in the extensions of every request. Interim responses are
+/// queued and written by the connection while the endpoint keeps working,
+/// in the order they were sent and before the final response.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::Interim;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn endpoint(req: Request) -> http_types::Result<Response> {
+///     if let Some(interim) = req.ext().get::<Interim>() {
+///         let mut hints = Response::new(StatusCode::EarlyHints);
+///         hints.insert_header("link", "</style.css>; rel=preload; as=style");
+///         interim.send(&hints);
+///     }
+///     // ...
+///     Ok(Response::new(StatusCode::Ok))
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Interim {
+    queue: Arc<Mutex<Queue>>,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    heads: VecDeque<Vec<u8>>,
+    /// Whether the final response has been produced.
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Interim {
+    /// Interim responses for a request of the given version. HTTP/1.0 has
+    /// none, so nothing is sent for those.
+    pub(crate) fn new(version: Version) -> Self {
+        let interim = Self::default();
+        if version == Version::Http1_0 {
+            interim.close();
+        }
+        interim
+    }
+
+    /// Queue the head of an informational response: its status line and
+    /// headers. Its body, if any, is ignored.
+    ///
+    /// Returns `false` if the endpoint has already produced its final
+    /// response, or if the request is an HTTP/1.0 request, whose clients
+    /// don't expect interim responses. Nothing is sent then.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the status isn't a 1xx status other than `101 Switching
+    /// Protocols`, which is sent as the final response instead.
+    pub fn send(&self, res: &Response) -> bool {
+        let status = res.status();
+        assert!(
+            status.is_informational() && status != StatusCode::SwitchingProtocols,
+            "interim responses must be informational responses other than 101"
+        );
+
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status as u16,
+            status.canonical_reason()
+        );
+        for (name, values) in res.iter() {
+            for value in values.iter() {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        head.push_str("\r\n");
+        self.push(head.into_bytes())
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, head: Vec<u8>) -> bool {
+        let mut queue = self.queue();
+        if queue.closed {
+            return false;
+        }
+        queue.heads.push_back(head);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        true
+    }
+
+    /// Queue a `100 Continue`. It's queued even once the final response
+    /// has been produced, since the request body may be read later: the
+    /// connection writes what's left in the queue before the response.
+    pub(crate) fn push_continue(&self) {
+        let mut queue = self.queue();
+        queue.heads.push_back(CONTINUE_RESPONSE.to_vec());
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Take the next head to write, registering for a wakeup if there's
+    /// none.
+    fn pop(&self, cx: &mut Context<'_>) -> Option<Vec<u8>> {
+        let mut queue = self.queue();
+        let head = queue.heads.pop_front();
+        if head.is_none() {
+            queue.waker = Some(cx.waker().clone());
+        }
+        head
+    }
+
+    fn close(&self) {
+        self.queue().closed = true;
+    }
+
+    /// Await `fut`, writing the interim responses sent meanwhile to
+    /// `writer`, as well as a heartbeat each time `fut` has been pending for
+    /// a whole interval, if there's one.
+    ///
+    /// Interim responses which are queued or partly written when `fut`
+    /// completes are finished first, so the final response never
+    /// interleaves with them. Write errors stop the interim responses but
+    /// are otherwise ignored: if the connection went away, writing the
+    /// response will surface that.
+    pub(crate) async fn alongside<W, F>(
+        &self,
+        mut writer: W,
+        heartbeat: Option<&Heartbeat>,
+        fut: F,
+    ) -> F::Output
+    where
+        W: Write + Unpin,
+        F: Future,
+    {
+        enum State {
+            Waiting,
+            Writing(Vec<u8>, usize),
+            Flushing,
+            Stopped,
+        }
+
+        futures_lite::pin!(fut);
+        let mut timer = heartbeat.map(|heartbeat| Timer::after(heartbeat.interval));
+        let mut state = State::Waiting;
+        let mut output = None;
+
+        future::poll_fn(|cx| {
+            if output.is_none() {
+                if let Poll::Ready(out) = fut.as_mut().poll(cx) {
+                    output = Some(out);
+                    self.close();
+                }
+            }
+
+            loop {
+                match &mut state {
+                    State::Waiting => {
+                        if let Some(head) = self.pop(cx) {
+                            state = State::Writing(head, 0);
+                        } else if output.is_some() {
+                            break;
+                        } else if let (Some(heartbeat), Some(timer)) = (heartbeat, &mut timer) {
+                            if Pin::new(timer).poll(cx).is_pending() {
+                                break;
+                            }
+                            trace!("sending heartbeat");
+                            state = State::Writing(heartbeat.head.clone(), 0);
+                        } else {
+                            break;
+                        }
+                    }
+                    State::Writing(head, written) if *written == head.len() => {
+                        state = State::Flushing;
+                    }
+                    State::Writing(head, written) => {
+                        match Pin::new(&mut writer).poll_write(cx, &head[*written..]) {
+                            Poll::Pending => return Poll::Pending,
+                            Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) => state = State::Stopped,
+                            Poll::Ready(Ok(n)) => *written += n,
+                        }
+                    }
+                    State::Flushing => match Pin::new(&mut writer).poll_flush(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(())) => {
+                            // Any interim response shows the client the
+                            // request is alive.
+                            if let (Some(heartbeat), Some(timer)) = (heartbeat, &mut timer) {
+                                timer.set_after(heartbeat.interval);
+                            }
+                            state = State::Waiting;
+                        }
+                        Poll::Ready(Err(_)) => state = State::Stopped,
+                    },
+                    State::Stopped => break,
+                }
+            }
+
+            match output.take() {
+                Some(out) => Poll::Ready(out),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+/// There's no timer available on WASM targets, so no heartbeats are sent.
+#[cfg(target_arch = "wasm32")]
+struct Timer;
+
+#[cfg(target_arch = "wasm32")]
+impl Timer {
+    fn after(_: std::time::Duration) -> Self {
+        Timer
+    }
+
+    fn set_after(&mut self, _: std::time::Duration) {}
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        Poll::Pending
+    }
+}
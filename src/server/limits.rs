@@ -0,0 +1,125 @@
+//! Limits shared by all connections of a server.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_types::headers::{CONNECTION, RETRY_AFTER};
+use http_types::{Response, StatusCode};
+
+/// Limits on the load across all connections sharing them, beyond which
+/// new requests are shed with `503 Service Unavailable`.
+///
+/// Pass the same `Limits` to every connection with
+/// [`ServerOptions::with_limits`](super::ServerOptions::with_limits). A
+/// request is admitted if, when it arrives, both the requests in flight and
+/// the bytes buffered by all connections are below their limits. Otherwise
+/// it's answered with `503 Service Unavailable` without calling the
+/// endpoint, and the connection is closed. The response carries a
+/// `Retry-After` header set with
+/// [`ServerOptions::with_retry_after`](super::ServerOptions::with_retry_after).
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::{Limits, ServerOptions};
+///
+/// let limits = Limits::new()
+///     .with_max_in_flight(1024)
+///     .with_max_buffered(256 * 1024 * 1024);
+/// let opts = ServerOptions::new().with_limits(limits.clone());
+/// // ... pass `opts` to `accept_with_opts` for every connection ...
+/// assert_eq!(limits.in_flight(), 0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    max_in_flight: Option<usize>,
+    max_buffered: Option<usize>,
+    state: Arc<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    in_flight: AtomicUsize,
+    buffered: AtomicUsize,
+}
+
+impl Limits {
+    /// Create limits which admit every request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shed requests while `max` requests are already being handled.
+    pub fn with_max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    /// Shed requests while connections buffer `max` bytes or more in total.
+    ///
+    /// The bytes counted are those limited per connection by
+    /// [`ServerOptions::with_max_connection_memory`](super::ServerOptions::with_max_connection_memory).
+    pub fn with_max_buffered(mut self, max: usize) -> Self {
+        self.max_buffered = Some(max);
+        self
+    }
+
+    /// The number of requests being handled.
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// The number of bytes buffered by all connections.
+    pub fn buffered(&self) -> usize {
+        self.state.buffered.load(Ordering::SeqCst)
+    }
+
+    /// Count a request as in flight until the returned guard is dropped, or
+    /// return `None` if it should be shed.
+    pub(crate) fn admit(&self) -> Option<InFlight> {
+        if self.max_buffered.is_some_and(|max| self.buffered() >= max) {
+            return None;
+        }
+        let in_flight = self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight {
+            limits: self.clone(),
+        };
+        match self.max_in_flight {
+            Some(max) if in_flight >= max => None,
+            _ => Some(guard),
+        }
+    }
+
+    pub(crate) fn add_buffered(&self, bytes: usize) {
+        self.state.buffered.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    pub(crate) fn remove_buffered(&self, bytes: usize) {
+        self.state.buffered.fetch_sub(bytes, Ordering::SeqCst);
+    }
+}
+
+/// Counts a request as in flight for as long as it's alive.
+#[derive(Debug)]
+pub(crate) struct InFlight {
+    limits: Limits,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.limits.state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The response sent to requests which are shed, asking clients to retry
+/// after `retry_after`, rounded up to whole seconds.
+pub(crate) fn overloaded(retry_after: Option<Duration>) -> Response {
+    let mut res = Response::new(StatusCode::ServiceUnavailable);
+    res.insert_header(CONNECTION, "close");
+    if let Some(retry_after) = retry_after {
+        let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        res.insert_header(RETRY_AFTER, seconds.to_string());
+    }
+    res
+}
@@ -0,0 +1,114 @@
+//! Account for the memory buffered by a connection.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures_lite::io;
+use http_types::StatusCode;
+
+use super::Limits;
+use crate::error::{Error, Limit};
+
+/// The bytes a connection holds in its head and body buffers.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConnectionMemory {
+    used: Arc<AtomicUsize>,
+    limit: Option<usize>,
+    /// Limits shared with other connections, which count these bytes too.
+    shared: Option<Limits>,
+}
+
+impl ConnectionMemory {
+    pub(crate) fn new(limit: Option<usize>, shared: Option<Limits>) -> Self {
+        Self {
+            used: Arc::default(),
+            limit,
+            shared,
+        }
+    }
+
+    /// The number of bytes currently accounted for.
+    pub(crate) fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// Account for `bytes` more, failing if that would exceed the limit.
+    pub(crate) fn reserve(&self, bytes: usize) -> io::Result<()> {
+        let used = self.used.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        match self.limit {
+            Some(limit) if used > limit => {
+                self.used.fetch_sub(bytes, Ordering::SeqCst);
+                let message = format!("Connection buffered more than {} bytes", limit);
+                let err = Error::limit_exceeded(Limit::ConnectionMemory, message)
+                    .with_status(StatusCode::ServiceUnavailable);
+                Err(err.into())
+            }
+            _ => {
+                if let Some(shared) = &self.shared {
+                    shared.add_buffered(bytes);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Stop accounting for `bytes`.
+    pub(crate) fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::SeqCst);
+        if let Some(shared) = &self.shared {
+            shared.remove_buffered(bytes);
+        }
+    }
+
+    /// Account for a buffer which grows over time, until the returned guard
+    /// is dropped.
+    pub(crate) fn buffer(&self) -> Buffer {
+        Buffer {
+            memory: self.clone(),
+            bytes: 0,
+        }
+    }
+}
+
+/// Keeps the bytes of a growing buffer accounted for as long as it's alive.
+#[derive(Debug)]
+pub(crate) struct Buffer {
+    memory: ConnectionMemory,
+    bytes: usize,
+}
+
+impl Buffer {
+    /// Account for the buffer having grown to `len` bytes.
+    pub(crate) fn grow_to(&mut self, len: usize) -> io::Result<()> {
+        if len > self.bytes {
+            self.memory.reserve(len - self.bytes)?;
+            self.bytes = len;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.memory.release(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConnectionMemory;
+
+    #[test]
+    fn enforces_limit() {
+        let memory = ConnectionMemory::new(Some(10), None);
+        let mut buffer = memory.buffer();
+        buffer.grow_to(6).unwrap();
+        assert!(memory.reserve(5).is_err());
+        memory.reserve(4).unwrap();
+        assert_eq!(memory.used(), 10);
+
+        drop(buffer);
+        memory.release(4);
+        assert_eq!(memory.used(), 0);
+    }
+}
@@ -1,36 +1,699 @@
 //! Process HTTP connections on the server.
 
-use async_std::future::{timeout, Future, TimeoutError};
-use async_std::io::{self, Read, Write};
-use http_types::headers::{CONNECTION, UPGRADE};
-use http_types::upgrade::Connection;
-use http_types::{Request, Response, StatusCode};
-use std::{marker::PhantomData, time::Duration};
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write, BufReader};
+#[cfg(feature = "compression")]
+use http_types::headers::{HeaderValues, ACCEPT_ENCODING, CONTENT_ENCODING};
+use http_types::headers::{CONNECTION, EXPECT, UPGRADE};
+use http_types::upgrade::Connection as UpgradedConnection;
+use http_types::{Body, Method, Request, Response, StatusCode, Version};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use std::{future::Future, marker::PhantomData};
+mod body_channel;
 mod body_reader;
+mod body_writer;
+mod connection;
 mod decode;
+mod drain;
 mod encode;
+mod expect_continue;
+mod heartbeat;
+mod hook;
+mod idle;
+mod info;
+mod interim;
+mod limits;
+mod memory;
+mod negotiation;
+mod pipeline;
+#[cfg(all(unix, feature = "reuseport"))]
+mod reuseport;
+#[cfg(all(feature = "uds", any(target_os = "linux", target_os = "android")))]
+mod unix;
+pub(crate) mod upgrade;
+mod usage;
+#[cfg(all(not(target_arch = "wasm32"), feature = "workers"))]
+mod workers;
 
+pub use body_channel::{BodyFlow, BodyFrames};
+pub use body_reader::BodyReader;
+pub use body_writer::BodyWriter;
+pub use connection::Connection;
 pub use decode::decode;
-pub use encode::Encoder;
+pub use drain::{Drain, ShutdownReport};
+pub use encode::{Encoder, EncoderPhase};
+pub use hook::Hook;
+pub use info::{ConnectionInfo, PeerCredentials};
+pub use interim::Interim;
+pub use limits::Limits;
+pub use negotiation::{Expectation, Negotiation, TlsInfo};
+#[cfg(all(unix, feature = "reuseport"))]
+pub use reuseport::{bind_reuseport, serve_reuseport};
+#[cfg(all(feature = "uds", any(target_os = "linux", target_os = "android")))]
+pub use unix::accept_unix;
+pub use usage::ConnectionUsage;
+#[cfg(all(not(target_arch = "wasm32"), feature = "workers"))]
+pub use workers::{Distribution, WorkerPool};
+
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::digest::{self, DigestAlgorithm};
+use crate::error::{recommended_response, Error, ForeignProtocol, TimeoutPhase};
+#[cfg(feature = "metrics")]
+use crate::metrics::{self, Metrics, NoopMetrics};
+#[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+use crate::sendfile::{self, ZeroCopySocket};
+use crate::timer::{timeout, TimedOut, TimedStream};
+#[cfg(feature = "tracing")]
+use crate::tracing::{self, NoopTracer, Span, Tracer, Value};
+use crate::{has_connection_option, Profile, POLL_BUDGET};
+use body_channel::{alongside, Pump};
+use body_reader::BodyError;
+#[cfg(feature = "compression")]
+use body_reader::Limited;
+use expect_continue::PendingContinue;
+use heartbeat::Heartbeat;
+use idle::IdleConnections;
+use limits::InFlight;
+use memory::ConnectionMemory;
+use pipeline::{Outcome, Queued, Rewind, Started, Unread};
+#[cfg(feature = "metrics")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use upgrade::Upgraded;
 
 /// Configure the server.
 #[derive(Debug, Clone)]
 pub struct ServerOptions {
     /// Timeout to handle headers. Defaults to 60s.
     headers_timeout: Option<Duration>,
+    /// How long to wait for the next request on a keep-alive connection.
+    idle_timeout: Option<Duration>,
+    /// How long reading a request body may go without progress.
+    pub(crate) body_timeout: Option<Duration>,
+    /// How long writing a response may go without progress.
+    write_timeout: Option<Duration>,
+    /// The maximum length of a request head in bytes.
+    pub(crate) max_head_length: usize,
+    /// The maximum number of header fields in a request.
+    pub(crate) max_headers: usize,
+    /// The maximum length of a request line in bytes.
+    pub(crate) max_request_line_length: usize,
+    /// The maximum length of a response head in bytes.
+    max_response_head_length: usize,
+    /// The maximum length of a request body in bytes.
+    pub(crate) max_body_size: Option<u64>,
+    /// Whether header values folded across lines are unfolded rather than
+    /// rejected.
+    pub(crate) unfold_headers: bool,
+    /// Bytes copied per poll before yielding to the executor.
+    poll_budget: Option<usize>,
+    /// Limits shared with other connections.
+    limits: Option<Limits>,
+    /// The delay suggested to clients whose requests are shed.
+    retry_after: Option<Duration>,
+    /// The maximum number of bytes a connection may buffer.
+    max_connection_memory: Option<usize>,
+    /// The number of requests served on a connection before closing it.
+    max_requests_per_connection: Option<usize>,
+    /// The number of requests on a connection which may be worked on at
+    /// once.
+    pipeline_depth: usize,
+    /// The capacity in frames of the channel delivering request bodies, if
+    /// they're delivered through one.
+    body_channel: Option<usize>,
+    /// Whether to yield between requests and between response heads and
+    /// bodies.
+    cooperative_yielding: bool,
+    /// Whether to answer clients speaking another protocol with an HTTP/1
+    /// error response.
+    protocol_mismatch_response: bool,
+    /// Interim responses sent while endpoints are working.
+    heartbeat: Option<Heartbeat>,
+    /// Content-codings applied to request and response bodies.
+    #[cfg(feature = "compression")]
+    compression: Option<Compression>,
+    /// Receives measurements about connections and requests.
+    #[cfg(feature = "metrics")]
+    metrics: Arc<dyn Metrics>,
+    #[cfg(feature = "tracing")]
+    tracer: Arc<dyn Tracer>,
+    /// Switch for winding down connections.
+    drain: Option<Drain>,
+    /// Connections waiting for their next request, if there's a limit.
+    idle_connections: Option<IdleConnections>,
+    /// Hooks applied to every exchange.
+    hooks: Vec<Arc<dyn Hook>>,
+    /// Algorithm for `Content-Digest` trailers on chunked responses.
+    content_digest: Option<Arc<dyn DigestAlgorithm>>,
+    /// Algorithms for checking request bodies against their digest.
+    digest_validation: Vec<Arc<dyn DigestAlgorithm>>,
+    /// What the TLS layer negotiated for the connection.
+    tls_info: Option<TlsInfo>,
+    /// The address of the peer on the other end of the connection.
+    peer_addr: Option<SocketAddr>,
+    /// The address the connection was accepted on.
+    local_addr: Option<SocketAddr>,
+    /// The process on the other end of a Unix domain socket.
+    peer_credentials: Option<PeerCredentials>,
+    /// The host of requests without a `Host` header.
+    pub(crate) default_host: Option<String>,
+    /// The size of chunks response bodies of unknown length are gathered
+    /// into.
+    #[cfg(feature = "chunked")]
+    chunk_buffer_size: Option<usize>,
+    /// The frame size for bodies passed through as they are, if they are.
+    pub(crate) passthrough: Option<usize>,
 }
 
 impl Default for ServerOptions {
     fn default() -> Self {
+        let profile = Profile::default();
         Self {
-            headers_timeout: Some(Duration::from_secs(60)),
+            headers_timeout: profile.headers_timeout(),
+            idle_timeout: profile.idle_timeout(),
+            body_timeout: profile.body_timeout(),
+            write_timeout: profile.write_timeout(),
+            max_head_length: profile.max_head_length(),
+            max_headers: profile.max_headers(),
+            max_request_line_length: profile.max_request_line_length(),
+            max_response_head_length: profile.max_response_head_length(),
+            max_body_size: profile.max_body_size(),
+            unfold_headers: profile.unfold_headers(),
+            poll_budget: Some(POLL_BUDGET),
+            cooperative_yielding: true,
+            protocol_mismatch_response: false,
+            heartbeat: None,
+            body_channel: None,
+            max_connection_memory: profile.max_connection_memory(),
+            max_requests_per_connection: profile.max_requests_per_connection(),
+            pipeline_depth: 1,
+            limits: None,
+            retry_after: Some(Duration::from_secs(1)),
+            #[cfg(feature = "compression")]
+            compression: None,
+            #[cfg(feature = "metrics")]
+            metrics: Arc::new(NoopMetrics),
+            #[cfg(feature = "tracing")]
+            tracer: Arc::new(NoopTracer),
+            drain: None,
+            idle_connections: None,
+            hooks: Vec::new(),
+            content_digest: None,
+            digest_validation: Vec::new(),
+            tls_info: None,
+            peer_addr: None,
+            local_addr: None,
+            peer_credentials: None,
+            default_host: None,
+            #[cfg(feature = "chunked")]
+            chunk_buffer_size: None,
+            passthrough: None,
         }
     }
 }
 
+impl ServerOptions {
+    /// Create a new instance with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set all limits and strictness flags to those of a preset profile.
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.headers_timeout = profile.headers_timeout();
+        self.idle_timeout = profile.idle_timeout();
+        self.body_timeout = profile.body_timeout();
+        self.write_timeout = profile.write_timeout();
+        self.max_head_length = profile.max_head_length();
+        self.max_headers = profile.max_headers();
+        self.max_request_line_length = profile.max_request_line_length();
+        self.max_response_head_length = profile.max_response_head_length();
+        self.max_body_size = profile.max_body_size();
+        self.unfold_headers = profile.unfold_headers();
+        self.max_connection_memory = profile.max_connection_memory();
+        self.max_requests_per_connection = profile.max_requests_per_connection();
+        self
+    }
+
+    /// Set how long to wait for a request head to arrive, or `None` to wait
+    /// indefinitely.
+    pub fn with_headers_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.headers_timeout = timeout;
+        self
+    }
+
+    /// Set how long a keep-alive connection may wait for the next request
+    /// to start arriving before it's closed, or `None` to only apply the
+    /// [headers timeout](ServerOptions::with_headers_timeout). Defaults to
+    /// `None`.
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set how long reading a request body may go without receiving any
+    /// bytes, or `None` to wait indefinitely. Defaults to `None`.
+    ///
+    /// Reads which time out fail with a
+    /// [`TimeoutPhase::Body`](crate::error::TimeoutPhase::Body) error, and
+    /// endpoints passing it on are answered with `408 Request Timeout`.
+    /// Slow clients which keep sending aren't cut off.
+    pub fn with_body_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.body_timeout = timeout;
+        self
+    }
+
+    /// Set how long writing a response may go without the client accepting
+    /// any bytes, or `None` to wait indefinitely. Defaults to `None`.
+    ///
+    /// The connection is closed with a
+    /// [`TimeoutPhase::Write`](crate::error::TimeoutPhase::Write) error
+    /// when the timeout passes.
+    pub fn with_write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum length of a request head in bytes.
+    pub fn with_max_head_length(mut self, max_head_length: usize) -> Self {
+        self.max_head_length = max_head_length;
+        self
+    }
+
+    /// Set the maximum number of header fields in a request.
+    pub fn with_max_headers(mut self, max_headers: usize) -> Self {
+        self.max_headers = max_headers;
+        self
+    }
+
+    /// Set the maximum length in bytes of a request line, i.e. the method,
+    /// request target and version, excluding the line ending. Longer
+    /// requests are answered with `414 URI Too Long`.
+    pub fn with_max_request_line_length(mut self, max_request_line_length: usize) -> Self {
+        self.max_request_line_length = max_request_line_length;
+        self
+    }
+
+    /// Set the maximum length of a response head in bytes.
+    ///
+    /// Responses whose head would be longer, e.g. because a misbehaving hook
+    /// added too many headers, are replaced with a bare `500 Internal Server
+    /// Error` and the connection is closed.
+    pub fn with_max_response_head_length(mut self, max_response_head_length: usize) -> Self {
+        self.max_response_head_length = max_response_head_length;
+        self
+    }
+
+    /// Set the maximum length of a request body in bytes. Defaults to no
+    /// limit.
+    ///
+    /// Requests declaring a longer `Content-Length` are answered with `413
+    /// Payload Too Large` before their body is read. Reading a chunked body
+    /// fails once it grows past the limit, and the request is answered with
+    /// a 413 whatever the endpoint made of the error. In
+    /// [passthrough](ServerOptions::with_passthrough) mode, the chunk
+    /// framing counts towards the limit.
+    pub fn with_max_body_size(mut self, bytes: u64) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Set whether to accept header values folded across lines (`obs-fold`),
+    /// replacing each fold with a space. Defaults to `false`, in which case
+    /// such requests are answered with `400 Bad Request`, as are folds
+    /// directly after the request line either way.
+    ///
+    /// Enabled by [`Profile::Lenient`](crate::Profile::Lenient).
+    pub fn with_unfold_headers(mut self, unfold_headers: bool) -> Self {
+        self.unfold_headers = unfold_headers;
+        self
+    }
+
+    /// Set how many bytes of a response, or of an unread request body being
+    /// discarded, are copied before yielding to the executor, or `None` to
+    /// copy until the stream isn't ready. Defaults to 64 KiB.
+    ///
+    /// Yielding keeps a connection with a large, always-ready body from
+    /// starving the other connections on the same executor thread.
+    pub fn with_poll_budget(mut self, poll_budget: Option<usize>) -> Self {
+        self.poll_budget = poll_budget;
+        self
+    }
+
+    /// Set whether to yield to the executor between the requests on a
+    /// connection and between each response's head and body. Enabled by
+    /// default.
+    ///
+    /// Without these yield points, a client pipelining many small requests
+    /// can keep a connection busy without ever returning `Pending`, starving
+    /// other connections on single-threaded executors.
+    pub fn with_cooperative_yielding(mut self, enabled: bool) -> Self {
+        self.cooperative_yielding = enabled;
+        self
+    }
+
+    /// Set whether to answer a client which opens the connection with a TLS
+    /// handshake or the HTTP/2 preface with a `400 Bad Request` or `505 HTTP
+    /// Version Not Supported` response. Disabled by default, as such clients
+    /// can't read an HTTP/1 response, so the connection is just closed.
+    ///
+    /// Either way, the connection fails with an
+    /// [`ErrorKind::ProtocolMismatch`](crate::error::ErrorKind::ProtocolMismatch)
+    /// error naming the [`ForeignProtocol`](crate::error::ForeignProtocol),
+    /// which is a sign of a client pointed at the wrong port.
+    pub fn with_protocol_mismatch_response(mut self, enabled: bool) -> Self {
+        self.protocol_mismatch_response = enabled;
+        self
+    }
+
+    /// Send a `102 Processing` interim response every `interval` while an
+    /// endpoint hasn't produced its response yet.
+    ///
+    /// This keeps intermediaries with short read timeouts from giving up on
+    /// slow requests. Heartbeats aren't sent on `wasm32` targets, which have
+    /// no timer.
+    pub fn with_heartbeat(self, interval: Duration) -> Self {
+        self.with_heartbeat_status(interval, 102)
+    }
+
+    /// Like [`ServerOptions::with_heartbeat`], but sending interim responses
+    /// with another informational status.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `status` isn't a 1xx status, or is `101 Switching
+    /// Protocols`.
+    pub fn with_heartbeat_status(mut self, interval: Duration, status: u16) -> Self {
+        self.heartbeat = Some(Heartbeat::new(interval, status));
+        self
+    }
+
+    /// Set the maximum number of bytes each connection may hold in its
+    /// buffers: the request head being read, and the frames queued in the
+    /// [body channel](ServerOptions::with_body_channel). A connection going
+    /// over the limit fails with an
+    /// [`ErrorKind::LimitExceeded`](crate::error::ErrorKind::LimitExceeded)
+    /// error, answered with `503 Service Unavailable`, and is closed.
+    /// Defaults to no limit.
+    pub fn with_max_connection_memory(mut self, bytes: usize) -> Self {
+        self.max_connection_memory = Some(bytes);
+        self
+    }
+
+    /// Close connections once they have served `max` requests, answering
+    /// the last one with `Connection: close`. Defaults to no limit.
+    ///
+    /// Clients then reconnect, which spreads long-lived clients across the
+    /// servers behind a load balancer and bounds what any one connection
+    /// can accumulate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn with_max_requests_per_connection(mut self, max: usize) -> Self {
+        assert!(max > 0, "connections must be allowed at least one request");
+        self.max_requests_per_connection = Some(max);
+        self
+    }
+
+    /// Work on up to `depth` requests a client pipelined at once, rather
+    /// than one at a time. Defaults to 1.
+    ///
+    /// Requests without a body which arrived along with the one before them
+    /// are read while the earlier ones are being answered, and passed to the
+    /// endpoint straight away, so their endpoints run concurrently.
+    /// Responses are still written one at a time, in the order the requests
+    /// arrived. Reading ahead stops at a request with a body, or one which
+    /// closes or upgrades the connection, until it's been answered.
+    ///
+    /// If the connection closes after a response, the requests read ahead
+    /// of it are dropped unanswered, as a client pipelining requests must
+    /// be prepared for.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is zero.
+    pub fn with_pipeline_depth(mut self, depth: usize) -> Self {
+        assert!(depth > 0, "the pipeline depth must be at least one");
+        self.pipeline_depth = depth;
+        self
+    }
+
+    /// Shed requests with `503 Service Unavailable` once these limits,
+    /// shared with the other connections using them, are reached.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Set the delay suggested in the `Retry-After` header of `503 Service
+    /// Unavailable` responses, or `None` to leave the header out. Defaults
+    /// to one second.
+    ///
+    /// Requests are answered with a 503 when they're shed by the
+    /// [limits](ServerOptions::with_limits), or when their head was still
+    /// arriving as the connection started [draining](ServerOptions::with_drain).
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Deliver request bodies to the endpoint through a channel holding at
+    /// most `frames` frames of up to 8 KiB.
+    ///
+    /// The connection task reads the body from the stream and stops reading
+    /// while the channel is full, so a slow endpoint applies backpressure to
+    /// the client without reading from the stream itself. Endpoints can also
+    /// pause the flow with the [`BodyFlow`] in the request's extensions, or
+    /// take the frames without copying them with the [`BodyFrames`].
+    pub fn with_body_channel(mut self, frames: usize) -> Self {
+        self.body_channel = Some(frames);
+        self
+    }
+
+    /// Gather response bodies of unknown length into chunks of up to `size`
+    /// bytes, in a buffer allocated once per response and reused for every
+    /// chunk.
+    ///
+    /// By default each piece a body hands out is sent as a chunk of its own,
+    /// so a body produced in small writes costs a chunk header per write,
+    /// and one with a large buffer of its own is sent in chunks as large.
+    /// With a buffer, whatever the body has ready is gathered up to this
+    /// high-water mark, and anything gathered is sent as soon as the body
+    /// would have to wait, so nothing is held back.
+    #[cfg(feature = "chunked")]
+    pub fn with_chunk_buffer_size(mut self, size: usize) -> Self {
+        self.chunk_buffer_size = Some(size);
+        self
+    }
+
+    /// Decode request bodies and encode response bodies using these
+    /// content-codings.
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Report measurements about connections and requests to this exporter.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: impl Metrics) -> Self {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+
+    /// Open spans for connections and requests with this tracer.
+    #[cfg(feature = "tracing")]
+    pub fn with_tracer(mut self, tracer: impl Tracer) -> Self {
+        self.tracer = Arc::new(tracer);
+        self
+    }
+
+    /// Set the maximum number of connections which may wait for their next
+    /// request at once. When there are more, the connection which has been
+    /// idle the longest is closed.
+    ///
+    /// The limit is shared by all connections served with clones of these
+    /// options.
+    pub fn with_max_idle_connections(mut self, max: usize) -> Self {
+        self.idle_connections = Some(IdleConnections::new(max));
+        self
+    }
+
+    /// Apply this hook to every request, after the hooks which are already
+    /// registered.
+    pub fn with_hook(mut self, hook: impl Hook) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Send a `Content-Digest` trailer computed with this algorithm on
+    /// responses with a chunked body.
+    pub fn with_content_digest(mut self, algorithm: impl DigestAlgorithm) -> Self {
+        self.content_digest = Some(Arc::new(algorithm));
+        self
+    }
+
+    /// Check request bodies carrying a `Content-Digest` in this algorithm, or
+    /// a `Content-MD5` if the algorithm is named `md5`, as they're read.
+    /// Reading a body which doesn't match its digest fails with an
+    /// [`ErrorKind::DigestMismatch`](crate::error::ErrorKind::DigestMismatch)
+    /// error.
+    ///
+    /// When called more than once, the first algorithm the request has a
+    /// digest for is used.
+    pub fn with_digest_validation(mut self, algorithm: impl DigestAlgorithm) -> Self {
+        self.digest_validation.push(Arc::new(algorithm));
+        self
+    }
+
+    /// Wind down connections once this switch is flipped.
+    pub fn with_drain(mut self, drain: Drain) -> Self {
+        self.drain = Some(drain);
+        self
+    }
+
+    /// Pass message bodies through without decoding or re-encoding their
+    /// chunked framing, for use as a reverse proxy, reading bodies in frames
+    /// of up to `frame_size` bytes.
+    ///
+    /// Chunked request bodies are handed to the endpoint still chunked, with
+    /// their `Transfer-Encoding` header, so they can be forwarded with
+    /// [`client::Encoder::with_passthrough`](crate::client::Encoder::with_passthrough),
+    /// keeping their chunk boundaries and trailers. Likewise, response bodies
+    /// of unknown length whose response has a `Transfer-Encoding: chunked`
+    /// header are sent as they are.
+    ///
+    /// Content-codings and digest checks aren't applied in this mode.
+    pub fn with_passthrough(mut self, frame_size: usize) -> Self {
+        self.passthrough = Some(frame_size);
+        self
+    }
+
+    /// Tell endpoints what the TLS layer negotiated for the connection,
+    /// through the [`Negotiation`] in each request's extensions.
+    ///
+    /// Unlike the other options, this describes a single connection, so set
+    /// it on the options passed to `accept_with_opts` for that connection.
+    pub fn with_tls_info(mut self, tls_info: TlsInfo) -> Self {
+        self.tls_info = Some(tls_info);
+        self
+    }
+
+    /// Tell endpoints the address of the peer on the other end of the
+    /// connection, through the [`ConnectionInfo`] in each request's
+    /// extensions. It's also used in logs and tracing spans.
+    ///
+    /// Like [`with_tls_info`](Self::with_tls_info), this describes a single
+    /// connection.
+    pub fn with_peer_addr(mut self, peer_addr: SocketAddr) -> Self {
+        self.peer_addr = Some(peer_addr);
+        self
+    }
+
+    /// Tell endpoints the address the connection was accepted on, through
+    /// the [`ConnectionInfo`] in each request's extensions.
+    ///
+    /// Like [`with_tls_info`](Self::with_tls_info), this describes a single
+    /// connection.
+    pub fn with_local_addr(mut self, local_addr: SocketAddr) -> Self {
+        self.local_addr = Some(local_addr);
+        self
+    }
+
+    /// Tell endpoints which process is on the other end of a Unix domain
+    /// socket, through the [`ConnectionInfo`] in each request's extensions.
+    ///
+    /// Like [`with_tls_info`](Self::with_tls_info), this describes a single
+    /// connection.
+    pub fn with_peer_credentials(mut self, peer_credentials: PeerCredentials) -> Self {
+        self.peer_credentials = Some(peer_credentials);
+        self
+    }
+
+    /// Accept requests without a `Host` header, or with an empty one, as if
+    /// they were for `host`. Their URLs name the host without a port.
+    ///
+    /// Requests need a host otherwise, but there's no authority to name
+    /// over a Unix domain socket, so clients often leave it out there.
+    pub fn with_default_host(mut self, host: impl Into<String>) -> Self {
+        self.default_host = Some(host.into());
+        self
+    }
+}
+
 /// Accept a new incoming HTTP/1.1 connection.
 ///
 /// Supports `KeepAlive` requests by default.
+///
+/// # Upgrades
+///
+/// An endpoint accepts an upgrade by responding with `101 Switching
+/// Protocols` and taking the receiving end of the upgrade with
+/// [`Response::recv_upgrade`](http_types::Response::recv_upgrade). Once the
+/// response has been written, the connection is sent through it and the
+/// server stops reading from it, so the endpoint needs to spawn a task to
+/// wait for it:
+///
+/// ```
+/// use async_h1::server::Negotiation;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn endpoint(req: Request) -> http_types::Result<Response> {
+///     let negotiation = req.ext().get::<Negotiation>().unwrap();
+///     if !negotiation.upgrade_offers().iter().any(|p| p == "websocket") {
+///         return Ok(Response::new(StatusCode::Ok));
+///     }
+///
+///     let mut res = Response::new(StatusCode::SwitchingProtocols);
+///     res.insert_header("upgrade", "websocket");
+///     res.insert_header("connection", "Upgrade");
+///     let upgrade = res.recv_upgrade().await;
+///     async_std::task::spawn(async move {
+///         if let Some(connection) = upgrade.await {
+///             // Speak the new protocol over `connection`.
+///         }
+///     });
+///     Ok(res)
+/// }
+/// ```
+///
+/// The connection is only handed over if the request asked for an upgrade
+/// with `Connection: Upgrade` and an `Upgrade` header; otherwise the
+/// receiver yields `None`.
+///
+/// # Tunnels
+///
+/// `CONNECT` requests are handed over the same way, to build forward
+/// proxies: an endpoint which takes the receiving end of the upgrade and
+/// responds with a 2xx status gets the connection once the response head
+/// has been written, and relays it to the destination named by the
+/// request's URL. Such responses have no body, and no `Content-Length` or
+/// `Transfer-Encoding` headers.
+///
+/// ```
+/// use http_types::{Method, Request, Response, StatusCode};
+///
+/// async fn endpoint(req: Request) -> http_types::Result<Response> {
+///     if req.method() != Method::Connect {
+///         return Ok(Response::new(StatusCode::MethodNotAllowed));
+///     }
+///     let destination = req.url().clone();
+///
+///     let mut res = Response::new(StatusCode::Ok);
+///     let tunnel = res.recv_upgrade().await;
+///     async_std::task::spawn(async move {
+///         if let Some(connection) = tunnel.await {
+///             // Connect to `destination` and copy bytes both ways.
+///         }
+///     });
+///     Ok(res)
+/// }
+/// ```
 pub async fn accept<RW, F, Fut>(io: RW, endpoint: F) -> http_types::Result<()>
 where
     RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
@@ -62,9 +725,75 @@ pub struct Server<RW, F, Fut> {
     io: RW,
     endpoint: F,
     opts: ServerOptions,
+    /// The number of requests served on this connection.
+    requests: usize,
+    /// The number of requests which were read but not answered yet, while
+    /// the next one is read.
+    ahead: usize,
+    /// The bytes read from the connection which belong to the next
+    /// requests.
+    unread: Unread,
+    /// The bytes buffered by this connection.
+    memory: ConnectionMemory,
+    /// When the connection was opened, if there's a clock.
+    opened: Option<Instant>,
+    /// The buffer the last response head was encoded into, for the next
+    /// one to reuse.
+    head_buffer: Option<Vec<u8>>,
+    /// The bytes read from the connection since they were last reported.
+    #[cfg(feature = "metrics")]
+    bytes_read: Arc<AtomicU64>,
+    /// The span requests on this connection are traced within.
+    #[cfg(feature = "tracing")]
+    span: Option<Arc<dyn Span>>,
+    /// The socket file bodies are sent to directly.
+    #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+    zero_copy: Option<ZeroCopySocket>,
     _phantom: PhantomData<Fut>,
 }
 
+/// The stream requests are read from, which first yields the bytes the
+/// previous request left unread, and counts the bytes read when there are
+/// metrics to report them to.
+#[cfg(feature = "metrics")]
+type Reader<RW> = Rewind<metrics::CountedReads<RW>>;
+#[cfg(not(feature = "metrics"))]
+type Reader<RW> = Rewind<RW>;
+
+/// What's left to do for a request once it's been read: the response to
+/// write, and the rest of the body to discard.
+#[derive(Debug)]
+struct Exchange<RW: Read + Unpin> {
+    method: Method,
+    path: String,
+    version: Version,
+    /// Whether the client asked for the connection to close.
+    close_connection: bool,
+    last_request: bool,
+    upgrade_requested: bool,
+    pending_continue: Option<PendingContinue>,
+    body_error: BodyError,
+    #[cfg(feature = "tracing")]
+    span: Option<Arc<dyn Span>>,
+    body: BodyReader<Reader<RW>>,
+    in_flight: Option<InFlight>,
+    #[cfg(feature = "compression")]
+    accept_encoding: Option<HeaderValues>,
+    #[cfg(feature = "metrics")]
+    started: Option<Instant>,
+}
+
+/// Why reading the next request came to nothing.
+#[derive(Debug)]
+enum Stop {
+    /// There's no request, and the connection should close after this
+    /// response, if there's one.
+    Closed(Option<Response>),
+    /// Reading the request failed. The response explains why, if the
+    /// client can understand it.
+    Failed(http_types::Error, Option<Response>),
+}
+
 /// An enum that represents whether the server should accept a subsequent request
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ConnectionStatus {
@@ -78,8 +807,6 @@ pub enum ConnectionStatus {
 impl<RW, F, Fut> Server<RW, F, Fut>
 where
     RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
-    F: Fn(Request) -> Fut,
-    Fut: Future<Output = http_types::Result<Response>>,
 {
     /// builds a new server
     pub fn new(io: RW, endpoint: F) -> Self {
@@ -87,89 +814,529 @@ where
             io,
             endpoint,
             opts: Default::default(),
+            requests: 0,
+            ahead: 0,
+            unread: Unread::default(),
+            memory: ConnectionMemory::default(),
+            opened: crate::timer::now(),
+            head_buffer: None,
+            #[cfg(feature = "metrics")]
+            bytes_read: Arc::default(),
+            #[cfg(feature = "tracing")]
+            span: None,
+            #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+            zero_copy: None,
             _phantom: PhantomData,
         }
     }
 
     /// with opts
     pub fn with_opts(mut self, opts: ServerOptions) -> Self {
+        self.memory = ConnectionMemory::new(opts.max_connection_memory, opts.limits.clone());
         self.opts = opts;
         self
     }
 
-    /// accept in a loop
-    pub async fn accept(&mut self) -> http_types::Result<()> {
-        while ConnectionStatus::KeepAlive == self.accept_one().await? {}
-        Ok(())
+    /// Send file bodies set with
+    /// [`sendfile::set_file_body`](crate::sendfile::set_file_body) straight
+    /// to `socket`, which must be the socket this connection's stream
+    /// writes to.
+    #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+    pub fn with_zero_copy(mut self, socket: ZeroCopySocket) -> Self {
+        self.zero_copy = Some(socket);
+        self
     }
 
-    /// accept one request
-    pub async fn accept_one(&mut self) -> http_types::Result<ConnectionStatus>
-    where
-        RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
-        F: Fn(Request) -> Fut,
-        Fut: Future<Output = http_types::Result<Response>>,
-    {
+    /// The number of bytes this connection currently holds in its head and
+    /// body buffers.
+    pub fn buffered_bytes(&self) -> usize {
+        self.memory.used()
+    }
+
+    /// Read the next request, or the status the connection should be left
+    /// with if there isn't one.
+    async fn read_exchange(
+        &mut self,
+    ) -> http_types::Result<Result<(Request, Exchange<RW>), ConnectionStatus>> {
+        match self.decode_exchange().await {
+            Ok(read) => Ok(Ok(read)),
+            Err(stop) => self.stop(stop).await.map(Err),
+        }
+    }
+
+    /// Send the response for a request which couldn't be read, if there's
+    /// one, and return the status the connection should be left with.
+    async fn stop(&mut self, stop: Stop) -> http_types::Result<ConnectionStatus> {
+        match stop {
+            Stop::Closed(res) => {
+                if let Some(res) = res {
+                    self.send_error_response(res, Method::Get).await;
+                }
+                Ok(ConnectionStatus::Close)
+            }
+            Stop::Failed(e, res) => {
+                if let Some(res) = res {
+                    self.send_error_response(res, Method::Get).await;
+                }
+                self.run_on_error(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Read the next request, without writing anything yet if there isn't
+    /// one.
+    async fn decode_exchange(&mut self) -> Result<(Request, Exchange<RW>), Stop> {
+        if self.is_draining() {
+            return Err(Stop::Closed(None));
+        }
+        let requests = self.requests + self.ahead;
+
         // Decode a new request, timing out if this takes longer than the timeout duration.
-        let fut = decode(self.io.clone());
+        // Stop waiting for it if draining starts, this connection is
+        // evicted to make room for other idle ones in the meantime, or it
+        // stays idle for too long between requests.
+        let started = AtomicBool::new(false);
+        let drain = self.opts.drain.clone();
+        let idle = match &self.opts.idle_connections {
+            Some(idle_connections) if requests > 0 => Some(idle_connections.idle()),
+            _ => None,
+        };
+        let hang_up = async {
+            let draining = async {
+                match &drain {
+                    Some(drain) => drain.draining().await,
+                    None => future::pending().await,
+                }
+            };
+            let evicted = async {
+                match &idle {
+                    Some(idle) => idle.evicted().await,
+                    None => future::pending().await,
+                }
+            };
+            let expired = async {
+                match self.opts.idle_timeout {
+                    Some(duration) if requests > 0 => {
+                        let _ = timeout(duration, future::pending::<()>()).await;
+                        if started.load(Ordering::Relaxed) {
+                            // The head is on its way: the headers timeout
+                            // applies from here.
+                            future::pending::<()>().await;
+                        }
+                        trace!("closing connection idle for {:?}", duration);
+                    }
+                    _ => future::pending().await,
+                }
+            };
+            future::or(draining, future::or(evicted, expired)).await;
+            Ok(None)
+        };
+        #[cfg(feature = "metrics")]
+        let io = metrics::CountedReads::new(self.io.clone(), self.bytes_read.clone());
+        #[cfg(not(feature = "metrics"))]
+        let io = self.io.clone();
+        let io = Rewind::new(io, self.unread.clone());
+        let fut = future::or(
+            decode::decode_with_opts(io, &self.opts, &self.memory, &started),
+            hang_up,
+        );
 
-        let (req, mut body) = if let Some(timeout_duration) = self.opts.headers_timeout {
+        let decoded = if let Some(timeout_duration) = self.opts.headers_timeout {
             match timeout(timeout_duration, fut).await {
-                Ok(Ok(Some(r))) => r,
-                Ok(Ok(None)) | Err(TimeoutError { .. }) => return Ok(ConnectionStatus::Close), /* EOF or timeout */
-                Ok(Err(e)) => return Err(e),
+                Ok(decoded) => decoded,
+                Err(TimedOut) if started.load(Ordering::Relaxed) => Err(Error::timed_out(
+                    TimeoutPhase::Head,
+                    "Timed out reading the request head",
+                )
+                .into_http()),
+                Err(TimedOut) => {
+                    trace!(
+                        "closing connection after {:?} without a request",
+                        timeout_duration
+                    );
+                    return Err(Stop::Closed(None));
+                }
             }
         } else {
-            match fut.await? {
-                Some(r) => r,
-                None => return Ok(ConnectionStatus::Close), /* EOF */
+            fut.await
+        };
+        drop(idle);
+
+        let (mut req, body) = match decoded {
+            Ok(Some(r)) => r,
+            Ok(None) => {
+                // Rather than cutting off a request which was on its way when
+                // the connection was told to hang up, ask the client to retry
+                // it elsewhere.
+                let draining = started.load(Ordering::Relaxed) && self.is_draining();
+                let res = draining.then(|| limits::overloaded(self.opts.retry_after));
+                return Err(Stop::Closed(res)); /* EOF */
+            }
+            Err(e) => {
+                // Let the client know why we're hanging up, if we still can
+                // and it can understand us.
+                let understood =
+                    ForeignProtocol::of(&e).is_none() || self.opts.protocol_mismatch_response;
+                let res = recommended_response(&e).filter(|_| understood);
+                return Err(Stop::Failed(e, res));
             }
         };
 
-        let has_upgrade_header = req.header(UPGRADE).is_some();
-        let connection_header_as_str = req
-            .header(CONNECTION)
-            .map(|connection| connection.as_str())
-            .unwrap_or("");
+        #[cfg(feature = "metrics")]
+        let started = metrics::now();
+
+        req.ext_mut().insert(ConnectionUsage {
+            requests,
+            opened: self.opened,
+        });
+        req.set_peer_addr(self.opts.peer_addr);
+        req.set_local_addr(self.opts.local_addr);
+        req.ext_mut().insert(ConnectionInfo {
+            peer_addr: self.opts.peer_addr,
+            local_addr: self.opts.local_addr,
+            tls: self.opts.tls_info.clone(),
+            peer_credentials: self.opts.peer_credentials,
+        });
 
-        let connection_header_is_upgrade = connection_header_as_str
-            .split(',')
-            .any(|s| s.trim().eq_ignore_ascii_case("upgrade"));
-        let mut close_connection = connection_header_as_str.eq_ignore_ascii_case("close");
+        let has_upgrade_header = req.header(UPGRADE).is_some();
+        let connection = req.header(CONNECTION);
+        let connection_header_is_upgrade = has_connection_option(connection, "upgrade");
+        // HTTP/1.0 connections close after each exchange unless the client
+        // asks to keep them alive.
+        let version = req.version().unwrap_or(Version::Http1_1);
+        let close_connection = match version {
+            Version::Http1_0 => !has_connection_option(connection, "keep-alive"),
+            _ => has_connection_option(connection, "close"),
+        };
+        let last_request = self
+            .opts
+            .max_requests_per_connection
+            .is_some_and(|max| requests + 1 >= max);
 
         let upgrade_requested = has_upgrade_header && connection_header_is_upgrade;
 
-        let method = req.method();
+        let upgrade_offers = match req.header(UPGRADE) {
+            Some(upgrade) if upgrade_requested => upgrade
+                .iter()
+                .flat_map(|value| value.as_str().split(','))
+                .map(|protocol| protocol.trim().to_owned())
+                .filter(|protocol| !protocol.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        };
+        let negotiation = Negotiation {
+            version,
+            keep_alive: !close_connection && !self.is_draining() && !last_request,
+            upgrade_offers,
+            expectation: Expectation::of(req.header(EXPECT).map(|h| h.as_str())),
+            tls: self.opts.tls_info.clone(),
+        };
+        req.ext_mut().insert(negotiation);
+
+        #[cfg(feature = "tracing")]
+        let span = {
+            let method = req.method();
+            let mut fields = vec![
+                ("http.request.method", Value::Str(method.as_ref())),
+                ("url.path", Value::Str(req.url().path())),
+                (
+                    "network.protocol.version",
+                    Value::Str(protocol_version(version)),
+                ),
+            ];
+            if let Some(len) = req.len() {
+                fields.push(("http.request.body.size", Value::U64(len as u64)));
+            }
+            let parent = self.span.as_deref();
+            (self.opts.tracer).new_span(tracing::SERVER_REQUEST, parent, &fields)
+        };
+
+        let exchange = Exchange {
+            method: req.method(),
+            path: req.url().path().to_owned(),
+            version,
+            close_connection,
+            last_request,
+            upgrade_requested,
+            pending_continue: req.ext_mut().remove::<PendingContinue>(),
+            body_error: req.ext_mut().remove::<BodyError>().unwrap_or_default(),
+            body,
+            in_flight: None,
+            #[cfg(feature = "tracing")]
+            span,
+            #[cfg(feature = "compression")]
+            accept_encoding: None,
+            #[cfg(feature = "metrics")]
+            started,
+        };
+        Ok((req, exchange))
+    }
+
+    /// Check the request's digest and decode its content-coding, if set up
+    /// to, and admit it unless the server is overloaded or a hook responds
+    /// to it first, in which case that's the response to send.
+    fn admit(&self, req: &mut Request, exchange: &mut Exchange<RW>) -> Option<Response> {
+        if self.opts.passthrough.is_none() {
+            digest::verify_request(&self.opts.digest_validation, req);
+        }
+
+        #[cfg(feature = "compression")]
+        {
+            exchange.accept_encoding = req.header(ACCEPT_ENCODING).cloned();
+        }
+        #[cfg(feature = "compression")]
+        if let (Some(compression), None) = (&self.opts.compression, self.opts.passthrough) {
+            if compression.decodes_requests() {
+                if let Some(res) = compression.unsupported_request_coding(req) {
+                    return Some(res);
+                }
+                let encoded = req.header(CONTENT_ENCODING).is_some();
+                compression.decode_request(req);
+                // Limit what the body decodes to, as well as what was sent.
+                if let (true, Some(max)) = (encoded, self.opts.max_body_size) {
+                    let body = Limited::new(req.take_body(), Some(max));
+                    let body = BufReader::new(exchange.body_error.watch(body));
+                    req.set_body(Body::from_reader(body, None));
+                }
+            }
+        }
+
+        match self.opts.limits.as_ref().map(Limits::admit) {
+            Some(None) => Some(limits::overloaded(self.opts.retry_after)),
+            in_flight => {
+                exchange.in_flight = in_flight.flatten();
+                self.opts
+                    .hooks
+                    .iter()
+                    .find_map(|hook| hook.before_endpoint(req))
+            }
+        }
+    }
+
+    /// Fail the exchange if the request body turned out to be malformed or
+    /// the client stalled while sending it, answering that rather than
+    /// whatever was made of the error, since the rest of the stream can't
+    /// be trusted.
+    async fn check_body(&mut self, exchange: &Exchange<RW>) -> http_types::Result<()> {
+        if let Some(e) = exchange.body_error.take() {
+            let e = e.into_http();
+            if let Some(res) = recommended_response(&e) {
+                self.send_error_response(res, exchange.method).await;
+            }
+            self.run_on_error(&e);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Write the response to a request, and discard what's left of the
+    /// request body.
+    async fn write_exchange(
+        &mut self,
+        exchange: Exchange<RW>,
+        mut res: Response,
+        pump: &mut Option<Pump>,
+    ) -> http_types::Result<ConnectionStatus> {
+        let Exchange {
+            method,
+            path,
+            version,
+            mut close_connection,
+            last_request,
+            upgrade_requested,
+            pending_continue,
+            mut body,
+            in_flight: _in_flight,
+            ..
+        } = exchange;
+
+        #[cfg(feature = "compression")]
+        if let (Some(compression), None) = (&self.opts.compression, self.opts.passthrough) {
+            compression.encode_response(exchange.accept_encoding.as_ref(), &mut res);
+        }
+
+        self.run_before_encode(&mut res);
 
-        // Pass the request to the endpoint and encode the response.
-        let mut res = (self.endpoint)(req).await?;
+        // A successful CONNECT turns the connection into a tunnel, much
+        // like an upgrade.
+        let tunnel = method == Method::Connect && res.status().is_success();
+        let switches = res.status() == StatusCode::SwitchingProtocols || tunnel;
+        if (self.is_draining() || last_request) && !switches {
+            res.insert_header(CONNECTION, "close");
+        }
+
+        // If the client is still waiting for `100 Continue`, it won't send
+        // the body, so rather than waiting to discard it the connection is
+        // closed after the response.
+        let body_unsent = pending_continue
+            .as_ref()
+            .is_some_and(|pending| pending.claim());
+        if body_unsent {
+            res.insert_header(CONNECTION, "close");
+        }
+
+        // A `100 Continue` sent after the endpoint responded, as it's
+        // reading the body, is still queued.
+        if let Some(interim) = pending_continue.and_then(|pending| pending.queue()) {
+            let done = future::ready(());
+            interim.alongside(self.io.clone(), None, done).await;
+        }
 
-        close_connection |= res
-            .header(CONNECTION)
-            .map(|c| c.as_str().eq_ignore_ascii_case("close"))
-            .unwrap_or(false);
+        // HTTP/1.0 clients don't understand chunked bodies, so bodies of
+        // unknown length end when the connection closes.
+        let unframed = res.len().is_none() && method != Method::Head;
+        if version == Version::Http1_0 && unframed && !switches {
+            res.insert_header(CONNECTION, "close");
+        }
+
+        // Answer the client's `Connection` header: confirm that the
+        // connection closes if it asked for that, or that it's kept alive
+        // if it's an HTTP/1.0 client which asked for that.
+        let response_closes = has_connection_option(res.header(CONNECTION), "close");
+        if !switches && !response_closes {
+            if close_connection {
+                res.append_header(CONNECTION, "close");
+            } else if version == Version::Http1_0
+                && !has_connection_option(res.header(CONNECTION), "keep-alive")
+            {
+                res.append_header(CONNECTION, "keep-alive");
+            }
+        }
+        close_connection |= response_closes;
 
         let upgrade_provided = res.status() == StatusCode::SwitchingProtocols && res.has_upgrade();
 
-        let upgrade_sender = if upgrade_requested && upgrade_provided {
-            Some(res.send_upgrade())
-        } else {
-            None
-        };
+        let upgrade_sender =
+            if (upgrade_requested && upgrade_provided) || (tunnel && res.has_upgrade()) {
+                Some(res.send_upgrade())
+            } else {
+                None
+            };
+
+        let status = res.status();
 
-        let mut encoder = Encoder::new(res, method);
+        // The file is sent in place of the body only if the body would be
+        // written as it is.
+        #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+        let file = match &self.zero_copy {
+            Some(_) if self.opts.content_digest.is_none() && self.opts.passthrough.is_none() => {
+                sendfile::take_file(&mut res, &method)
+            }
+            _ => None,
+        };
 
-        let bytes_written = io::copy(&mut encoder, &mut self.io).await?;
-        log::trace!("wrote {} response bytes", bytes_written);
+        let mut encoder =
+            Encoder::new(res, method).with_max_head_length(self.opts.max_response_head_length);
+        if version == Version::Http1_0 {
+            encoder = encoder.with_http_1_0();
+        }
+        if let Some(algorithm) = &self.opts.content_digest {
+            encoder = encoder.with_content_digest(algorithm.clone());
+        }
+        if self.opts.cooperative_yielding {
+            encoder = encoder.with_yield_before_body();
+        }
+        if self.opts.passthrough.is_some() {
+            encoder = encoder.with_passthrough();
+        }
+        #[cfg(feature = "chunked")]
+        if let Some(size) = self.opts.chunk_buffer_size {
+            encoder = encoder.with_chunk_buffer_size(size);
+        }
+        if let Some(buffer) = self.head_buffer.take() {
+            encoder = encoder.with_head_buffer(buffer);
+        }
+        #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+        if file.is_some() {
+            encoder = encoder.with_head_only();
+        }
 
-        let body_bytes_discarded = io::copy(&mut body, &mut io::sink()).await?;
-        log::trace!(
-            "discarded {} unread request body bytes",
-            body_bytes_discarded
+        let writer = TimedStream::new(
+            self.io.clone(),
+            self.opts.write_timeout,
+            TimeoutPhase::Write,
+        );
+        #[cfg(feature = "metrics")]
+        let writer = metrics::FirstWrite::new(writer);
+        let mut writer = writer;
+        let written = async {
+            let written = encoder.write_to(&mut writer, self.opts.poll_budget).await?;
+            #[cfg(all(feature = "sendfile", any(target_os = "linux", target_os = "android")))]
+            if let (Some(file), Some(socket)) = (&file, &self.zero_copy) {
+                return Ok(written + socket.send(file, self.opts.write_timeout).await?);
+            }
+            io::Result::Ok(written)
+        };
+        let bytes_written = match alongside(pump, written).await {
+            Ok(bytes_written) => bytes_written,
+            Err(e) => {
+                let e = http_types::Error::from(e);
+                // If the head couldn't be encoded, none of it was written, so
+                // there's still room for a response explaining the failure.
+                if encoder.is_unstarted() {
+                    let mut res = Response::new(StatusCode::InternalServerError);
+                    res.insert_header(CONNECTION, "close");
+                    self.send_error_response(res, method).await;
+                }
+                self.run_on_error(&e);
+                return Err(e);
+            }
+        };
+        self.requests += 1;
+        self.head_buffer = encoder.take_head_buffer();
+        trace!("wrote {} response bytes", bytes_written);
+        // Keys follow the OpenTelemetry HTTP semantic conventions.
+        trace!(
+            "http.request.method={} url.path={} http.response.status_code={}",
+            method,
+            path,
+            status as u16
         );
 
+        #[cfg(feature = "tracing")]
+        if let Some(span) = &exchange.span {
+            span.record(&[
+                ("http.response.status_code", Value::U64(status as u64)),
+                ("http.response.size", Value::U64(bytes_written)),
+            ]);
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            let metrics = &*self.opts.metrics;
+            metrics.increment_counter(metrics::SERVER_BYTES_WRITTEN, bytes_written, &[]);
+            let names = (metrics::SERVER_REQUESTS, metrics::SERVER_REQUEST_DURATION);
+            metrics::record_exchange(metrics, names, method, status, exchange.started);
+            if let (Some(started), Some(first_byte)) = (exchange.started, writer.at) {
+                let status = (status as u16).to_string();
+                let labels = [("method", method.as_ref()), ("status", status.as_str())];
+                let elapsed = first_byte.saturating_duration_since(started).as_secs_f64();
+                metrics.record_histogram(metrics::SERVER_TIME_TO_FIRST_BYTE, elapsed, &labels);
+            }
+        }
+
+        pump.take();
+        if !body_unsent {
+            let body_bytes_discarded = body
+                .drain_with(self.opts.poll_budget, self.opts.body_timeout)
+                .await?;
+            trace!(
+                "discarded {} unread request body bytes",
+                body_bytes_discarded
+            );
+        }
+        self.unread.put_back(body.take_buffered());
+        #[cfg(feature = "metrics")]
+        {
+            let bytes_read = self.bytes_read.swap(0, Ordering::Relaxed);
+            let metrics = &*self.opts.metrics;
+            metrics.increment_counter(metrics::SERVER_BYTES_READ, bytes_read, &[]);
+        }
+
         if let Some(upgrade_sender) = upgrade_sender {
-            upgrade_sender.send(Connection::new(self.io.clone())).await;
+            let upgraded = Upgraded::new(self.unread.take(), self.io.clone());
+            upgrade_sender.send(UpgradedConnection::new(upgraded)).await;
             Ok(ConnectionStatus::Close)
         } else if close_connection {
             Ok(ConnectionStatus::Close)
@@ -177,4 +1344,279 @@ where
             Ok(ConnectionStatus::KeepAlive)
         }
     }
+
+    fn run_before_encode(&self, res: &mut Response) {
+        for hook in self.opts.hooks.iter().rev() {
+            hook.before_encode(res);
+        }
+    }
+
+    fn run_on_error(&self, error: &http_types::Error) {
+        for hook in &self.opts.hooks {
+            hook.on_error(error);
+        }
+    }
+
+    /// Send a response after a failure, ignoring errors since the
+    /// connection is closed afterwards anyway.
+    async fn send_error_response(&mut self, mut res: Response, method: Method) {
+        self.run_before_encode(&mut res);
+        let mut encoder =
+            Encoder::new(res, method).with_max_head_length(self.opts.max_response_head_length);
+        let mut writer = TimedStream::new(
+            self.io.clone(),
+            self.opts.write_timeout,
+            TimeoutPhase::Write,
+        );
+        io::copy(&mut encoder, &mut writer).await.ok();
+    }
+
+    fn is_draining(&self) -> bool {
+        self.opts.drain.as_ref().is_some_and(Drain::is_draining)
+    }
+
+    /// Open the span the connection's requests are traced within, and
+    /// return it.
+    #[cfg(feature = "tracing")]
+    fn open_span(&mut self) -> Option<Arc<dyn Span>> {
+        let peer_addr = self.opts.peer_addr.map(|addr| addr.ip().to_string());
+        let peer_port = self.opts.peer_addr.map(|addr| addr.port());
+        let fields = match (&peer_addr, peer_port) {
+            (Some(addr), Some(port)) => vec![
+                ("network.peer.address", Value::Str(addr)),
+                ("network.peer.port", Value::U64(port.into())),
+            ],
+            _ => Vec::new(),
+        };
+        self.span = self
+            .opts
+            .tracer
+            .new_span(tracing::SERVER_CONNECTION, None, &fields);
+        self.span.clone()
+    }
+}
+
+impl<RW, F, Fut> Server<RW, F, Fut>
+where
+    RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = http_types::Result<Response>>,
+{
+    /// accept in a loop
+    pub async fn accept(&mut self) -> http_types::Result<()> {
+        #[cfg(feature = "metrics")]
+        let _connection = metrics::ActiveConnection::new(self.opts.metrics.clone());
+        let mut tracked = self.opts.drain.as_ref().map(Drain::track);
+        if let Some(peer_addr) = self.opts.peer_addr {
+            trace!("serving connection from {}", peer_addr);
+        }
+        #[cfg(feature = "tracing")]
+        let span = self.open_span();
+
+        // Serve requests until the connection closes, or until a shutdown
+        // deadline passes and it's closed for us.
+        let drain = self.opts.drain.clone();
+        let served = future::or(
+            async {
+                self.serve().await?;
+                http_types::Result::Ok(true)
+            },
+            async {
+                match drain {
+                    Some(drain) => drain.forced().await,
+                    None => future::pending().await,
+                }
+                Ok(false)
+            },
+        );
+        #[cfg(feature = "tracing")]
+        let served = tracing::instrument(span.as_deref(), served);
+        let served = served.await;
+        // Close the connection's span once it has been served.
+        #[cfg(feature = "tracing")]
+        {
+            self.span = None;
+            drop(span);
+        }
+        let served = served?;
+
+        if let (false, Some(tracked)) = (served, &mut tracked) {
+            tracked.forced = true;
+        }
+        Ok(())
+    }
+
+    /// accept one request
+    pub async fn accept_one(&mut self) -> http_types::Result<ConnectionStatus> {
+        let (req, exchange) = match self.read_exchange().await? {
+            Ok(read) => read,
+            Err(status) => return Ok(status),
+        };
+        #[cfg(feature = "tracing")]
+        let span = exchange.span.clone();
+        // Boxed like the responses in `serve`.
+        let respond = Box::pin(self.respond(req, exchange));
+        #[cfg(feature = "tracing")]
+        let respond = tracing::instrument(span.as_deref(), respond);
+        respond.await
+    }
+
+    /// Serve requests until the connection closes, reading ahead the
+    /// requests which were pipelined up to the pipeline depth.
+    async fn serve(&mut self) -> http_types::Result<()> {
+        let depth = self.opts.pipeline_depth;
+        let mut queue = VecDeque::new();
+        loop {
+            while queue.is_empty()
+                || (queue.len() < depth
+                    && queue.back().is_some_and(Queued::allows_read_ahead)
+                    && self.unread.has_head())
+            {
+                self.ahead = queue.len();
+                let queued = match self.decode_exchange().await {
+                    Ok((req, exchange)) => {
+                        #[cfg(feature = "tracing")]
+                        let span = exchange.span.clone();
+                        let start = || self.start(req, exchange);
+                        #[cfg(feature = "tracing")]
+                        let started = tracing::in_span(span.as_deref(), start);
+                        #[cfg(not(feature = "tracing"))]
+                        let started = start();
+                        Queued::Started(started)
+                    }
+                    Err(stop) => Queued::Stopped(stop),
+                };
+                queue.push_back(queued);
+            }
+            self.ahead = 0;
+
+            let status = match queue.pop_front() {
+                Some(Queued::Started(started)) => {
+                    #[cfg(feature = "tracing")]
+                    let span = started.exchange.span.clone();
+                    // Boxed, so that wrapping this large future doesn't copy
+                    // it around on the stack.
+                    let finish = Box::pin(self.finish(started));
+                    #[cfg(feature = "tracing")]
+                    let finish = tracing::instrument(span.as_deref(), finish);
+                    future::or(finish, pipeline::poll_behind(&mut queue)).await?
+                }
+                Some(Queued::Stopped(stop)) => self.stop(stop).await?,
+                None => unreachable!("a request is read whenever none are queued"),
+            };
+            if status == ConnectionStatus::Close {
+                return Ok(());
+            }
+            if self.opts.cooperative_yielding {
+                future::yield_now().await;
+            }
+        }
+    }
+
+    /// Pass a request to the endpoint, and write its response.
+    async fn respond(
+        &mut self,
+        req: Request,
+        exchange: Exchange<RW>,
+    ) -> http_types::Result<ConnectionStatus> {
+        let started = self.start(req, exchange);
+        self.finish(started).await
+    }
+
+    /// Pass a request to the endpoint, unless the server is overloaded or a
+    /// hook responds to it first.
+    fn start(&self, mut req: Request, mut exchange: Exchange<RW>) -> Started<RW, Fut> {
+        let pump = match self.opts.body_channel {
+            Some(frames) if req.len() != Some(0) => {
+                let (sender, receiver) = body_channel::channel(frames, self.memory.clone());
+                req.ext_mut().insert(receiver.flow());
+                req.ext_mut().insert(receiver.frames());
+                let len = req.len();
+                let body = req.take_body();
+                // Running out of memory for the frames fails the body on
+                // the receiving end, which the server answers itself.
+                let receiver = exchange.body_error.watch(receiver);
+                let mut channel = Body::from_reader(BufReader::new(receiver), len);
+                channel.set_mime(body.mime().clone());
+                req.set_body(channel);
+                Some(body_channel::pump(body, sender))
+            }
+            _ => None,
+        };
+
+        // Once the head of a request without a body has been read, so has
+        // the request, and the next one can be read.
+        let read_ahead = req.len() == Some(0)
+            && !exchange.close_connection
+            && !exchange.last_request
+            && !exchange.upgrade_requested
+            && exchange.method != Method::Connect;
+
+        let interim = Interim::new(exchange.version);
+        let outcome = match self.admit(&mut req, &mut exchange) {
+            Some(res) => Outcome::Answered(res),
+            None => {
+                if let Some(pending) = &exchange.pending_continue {
+                    pending.send_through(&interim);
+                }
+                req.ext_mut().insert(interim.clone());
+                Outcome::Running(Box::pin((self.endpoint)(req)))
+            }
+        };
+        if read_ahead {
+            self.unread.put_back(exchange.body.take_buffered());
+        }
+        Started {
+            exchange,
+            interim,
+            pump,
+            outcome,
+            read_ahead,
+        }
+    }
+
+    /// Wait for the response to a request, and write it.
+    async fn finish(&mut self, started: Started<RW, Fut>) -> http_types::Result<ConnectionStatus> {
+        let Started {
+            exchange,
+            interim,
+            mut pump,
+            outcome,
+            ..
+        } = started;
+        let res = match outcome {
+            Outcome::Answered(res) => res,
+            outcome => self.wait(&exchange, interim, &mut pump, outcome).await?,
+        };
+        self.write_exchange(exchange, res, &mut pump).await
+    }
+
+    /// Wait for the endpoint to respond, sending the interim responses it
+    /// asks for meanwhile.
+    async fn wait(
+        &mut self,
+        exchange: &Exchange<RW>,
+        interim: Interim,
+        pump: &mut Option<Pump>,
+        outcome: Outcome<Fut>,
+    ) -> http_types::Result<Response> {
+        // HTTP/1.0 clients don't expect interim responses.
+        let heartbeat = match exchange.version {
+            Version::Http1_0 => None,
+            _ => self.opts.heartbeat.as_ref(),
+        };
+        let endpoint = interim.alongside(self.io.clone(), heartbeat, outcome.response());
+        let res = alongside(pump, endpoint).await;
+        self.check_body(exchange).await?;
+        res
+    }
+}
+
+/// The version of HTTP a request was sent with, as spans name it.
+#[cfg(feature = "tracing")]
+fn protocol_version(version: Version) -> &'static str {
+    match version {
+        Version::Http1_0 => "1.0",
+        _ => "1.1",
+    }
 }
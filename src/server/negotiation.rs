@@ -0,0 +1,128 @@
+//! Tell endpoints what was negotiated for their connection and request.
+
+use http_types::Version;
+
+/// What the client and server have agreed on for a request, summarized
+/// from its head and its connection.
+///
+/// Available in the extensions of every request, so endpoints can adapt
+/// their responses without parsing `Connection`, `Upgrade` and `Expect`
+/// headers themselves.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::Negotiation;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// async fn endpoint(req: Request) -> http_types::Result<Response> {
+///     let negotiation = req.ext().get::<Negotiation>().unwrap();
+///     if negotiation.upgrade_offers().iter().any(|p| p == "websocket") {
+///         // ...
+///     }
+///     Ok(Response::new(StatusCode::Ok))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Negotiation {
+    pub(crate) version: Version,
+    pub(crate) keep_alive: bool,
+    pub(crate) upgrade_offers: Vec<String>,
+    pub(crate) expectation: Expectation,
+    pub(crate) tls: Option<TlsInfo>,
+}
+
+impl Negotiation {
+    /// The HTTP version of the request.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Whether the connection will be kept open after the response, unless
+    /// the response asks to close it.
+    pub fn is_keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// The protocols the client offered to upgrade to, in order of
+    /// preference. Empty unless the request asked for an upgrade.
+    pub fn upgrade_offers(&self) -> &[String] {
+        &self.upgrade_offers
+    }
+
+    /// What the client expects before sending the request body.
+    pub fn expectation(&self) -> Expectation {
+        self.expectation
+    }
+
+    /// What was negotiated by the TLS layer, if the connection was accepted
+    /// with [`ServerOptions::with_tls_info`](super::ServerOptions::with_tls_info).
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        self.tls.as_ref()
+    }
+}
+
+/// The `Expect` header of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Expectation {
+    /// The request has no expectation.
+    None,
+    /// The client waits for `100 Continue` before sending the body, which
+    /// is sent on the endpoint's first read of the body. If the endpoint
+    /// responds without reading it, the connection is closed afterwards.
+    Continue,
+    /// The request has an expectation which isn't supported, which
+    /// endpoints should answer with `417 Expectation Failed`.
+    Unsupported,
+}
+
+impl Expectation {
+    pub(crate) fn of(expect: Option<&str>) -> Self {
+        match expect {
+            None => Expectation::None,
+            Some("100-continue") => Expectation::Continue,
+            Some(_) => Expectation::Unsupported,
+        }
+    }
+}
+
+/// What was negotiated by the TLS layer a connection runs over.
+///
+/// This crate doesn't do TLS itself, so this is filled in by the code
+/// accepting connections and passed along with
+/// [`ServerOptions::with_tls_info`](super::ServerOptions::with_tls_info).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    alpn_protocol: Option<Vec<u8>>,
+    server_name: Option<String>,
+}
+
+impl TlsInfo {
+    /// Create an empty instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the protocol selected through ALPN, e.g. `http/1.1`.
+    pub fn with_alpn_protocol(mut self, protocol: impl Into<Vec<u8>>) -> Self {
+        self.alpn_protocol = Some(protocol.into());
+        self
+    }
+
+    /// Set the server name the client asked for through SNI.
+    pub fn with_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// The protocol selected through ALPN.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// The server name the client asked for through SNI.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+}
@@ -0,0 +1,199 @@
+//! Carry bytes over between the requests on a connection, and keep the
+//! endpoints of pipelined requests working while earlier ones are
+//! answered.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write};
+use http_types::Response;
+
+use super::body_channel::Pump;
+use super::{Exchange, Interim, Stop};
+use crate::head::HeadScanner;
+
+/// Bytes read from a connection along with one request which belong to
+/// the requests after it.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Unread(Arc<Mutex<Vec<u8>>>);
+
+impl Unread {
+    fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Put `bytes` back in front of the bytes left unread.
+    pub(super) fn put_back(&self, mut bytes: Vec<u8>) {
+        if bytes.is_empty() {
+            return;
+        }
+        let mut unread = self.lock();
+        bytes.extend_from_slice(&unread);
+        *unread = bytes;
+    }
+
+    /// Take the bytes left unread.
+    pub(super) fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Whether a whole request head was left unread, so that decoding the
+    /// next request won't wait for the client.
+    pub(super) fn has_head(&self) -> bool {
+        // Decoding skips the empty lines ahead of a request line.
+        let unread = self.lock();
+        let start = unread.iter().position(|&b| b != b'\r' && b != b'\n');
+        start.is_some_and(|start| HeadScanner::new().feed(&unread[start..]).is_some())
+    }
+}
+
+/// A connection's stream, which yields the bytes left unread by earlier
+/// requests before reading more.
+#[derive(Debug, Clone)]
+pub(super) struct Rewind<IO> {
+    io: IO,
+    unread: Unread,
+}
+
+impl<IO> Rewind<IO> {
+    pub(super) fn new(io: IO, unread: Unread) -> Self {
+        Self { io, unread }
+    }
+}
+
+impl<IO: Read + Unpin> Read for Rewind<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        {
+            let mut unread = self.unread.lock();
+            if !unread.is_empty() {
+                let bytes = unread.len().min(buf.len());
+                buf[..bytes].copy_from_slice(&unread[..bytes]);
+                unread.drain(..bytes);
+                return Poll::Ready(Ok(bytes));
+            }
+        }
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl<IO: Write + Unpin> Write for Rewind<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_close(cx)
+    }
+}
+
+/// A request waiting for its turn to be answered.
+pub(super) enum Queued<RW: Read + Unpin, Fut> {
+    Started(Started<RW, Fut>),
+    /// Reading the request came to nothing: the connection stops there.
+    Stopped(Stop),
+}
+
+impl<RW: Read + Unpin, Fut> Queued<RW, Fut> {
+    /// Whether the request after this one may be read before this one is
+    /// answered.
+    pub(super) fn allows_read_ahead(&self) -> bool {
+        matches!(self, Queued::Started(started) if started.read_ahead)
+    }
+}
+
+/// A request which was passed to the endpoint, or answered without it.
+pub(super) struct Started<RW: Read + Unpin, Fut> {
+    pub(super) exchange: Exchange<RW>,
+    pub(super) interim: Interim,
+    pub(super) pump: Option<Pump>,
+    pub(super) outcome: Outcome<Fut>,
+    /// Whether the request is over once its head is read, so that the
+    /// next one can be read before it's answered.
+    pub(super) read_ahead: bool,
+}
+
+impl<RW, Fut> Started<RW, Fut>
+where
+    RW: Read + Unpin,
+    Fut: Future<Output = http_types::Result<Response>>,
+{
+    /// Poll the endpoint if it's still working, keeping its response.
+    fn poll_endpoint(&mut self, cx: &mut Context<'_>) {
+        if let Outcome::Running(fut) = &mut self.outcome {
+            #[cfg(feature = "tracing")]
+            let poll =
+                crate::tracing::in_span(self.exchange.span.as_deref(), || fut.as_mut().poll(cx));
+            #[cfg(not(feature = "tracing"))]
+            let poll = fut.as_mut().poll(cx);
+            if let Poll::Ready(res) = poll {
+                self.outcome = Outcome::Done(res);
+            }
+        }
+    }
+}
+
+/// Where a request's response comes from.
+pub(super) enum Outcome<Fut> {
+    /// The request was answered without calling the endpoint, by the
+    /// limits or a hook.
+    Answered(Response),
+    /// The endpoint is working on the response.
+    Running(Pin<Box<Fut>>),
+    /// The endpoint is done.
+    Done(http_types::Result<Response>),
+}
+
+impl<Fut: Future<Output = http_types::Result<Response>>> Outcome<Fut> {
+    /// The response, once the endpoint is done.
+    pub(super) async fn response(self) -> http_types::Result<Response> {
+        match self {
+            Outcome::Answered(res) => Ok(res),
+            Outcome::Running(fut) => fut.await,
+            Outcome::Done(res) => res,
+        }
+    }
+}
+
+/// Keep the endpoints of the requests waiting in `queue` working. Never
+/// completes, so it's meant to run alongside answering the request ahead
+/// of them.
+pub(super) async fn poll_behind<RW, Fut, T>(queue: &mut VecDeque<Queued<RW, Fut>>) -> T
+where
+    RW: Read + Unpin,
+    Fut: Future<Output = http_types::Result<Response>>,
+{
+    future::poll_fn(|cx| {
+        for queued in queue.iter_mut() {
+            if let Queued::Started(started) = queued {
+                started.poll_endpoint(cx);
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
@@ -0,0 +1,158 @@
+//! Serve one address from several accept loops using `SO_REUSEPORT`.
+
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::time::Duration;
+use std::{io, thread};
+
+use async_executor::LocalExecutor;
+use async_io::{Async, Timer};
+use futures_lite::future;
+use http_types::{Request, Response};
+use rustix::io::{fcntl_setfd, Errno, FdFlags};
+use rustix::net::{self, sockopt, AddressFamily, SocketType};
+
+use super::{accept_with_opts, ServerOptions};
+
+/// The length of the queue of pending connections on each listener.
+const BACKLOG: i32 = 1024;
+
+/// The pause after a first transient accept failure, doubled on each
+/// failure in a row up to `MAX_ACCEPT_BACKOFF`.
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(5);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Bind a TCP listener with `SO_REUSEPORT` set.
+///
+/// Any number of listeners bound this way can share the same address, and
+/// the kernel balances incoming connections between them.
+pub fn bind_reuseport(addr: SocketAddr) -> io::Result<TcpListener> {
+    let family = match addr {
+        SocketAddr::V4(_) => AddressFamily::INET,
+        SocketAddr::V6(_) => AddressFamily::INET6,
+    };
+    let socket = net::socket(family, SocketType::STREAM, None)?;
+    fcntl_setfd(&socket, FdFlags::CLOEXEC)?;
+    sockopt::set_socket_reuseaddr(&socket, true)?;
+    sockopt::set_socket_reuseport(&socket, true)?;
+    net::bind(&socket, &addr)?;
+    net::listen(&socket, BACKLOG)?;
+    Ok(TcpListener::from(socket))
+}
+
+/// Serve HTTP on `addr` from `workers` threads, each running its own accept
+/// loop on its own `SO_REUSEPORT` listener.
+///
+/// Connections are driven on a thread-local executor, so the endpoint's
+/// future doesn't need to be `Send`. All listeners are bound before this
+/// returns an error for a taken address; after that it blocks until every
+/// accept loop has stopped, which only happens if a listener fails for
+/// good. Transient failures, such as running out of file descriptors, are
+/// retried after a pause.
+pub fn serve_reuseport<F, Fut>(
+    addr: SocketAddr,
+    workers: usize,
+    opts: ServerOptions,
+    endpoint: F,
+) -> io::Result<()>
+where
+    F: Fn(Request) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = http_types::Result<Response>> + 'static,
+{
+    let listeners = (0..workers.max(1))
+        .map(|_| bind_reuseport(addr))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let handles: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| {
+            let opts = opts.clone();
+            let endpoint = endpoint.clone();
+            thread::spawn(move || accept_loop(listener, opts, endpoint))
+        })
+        .collect();
+
+    let mut result = Ok(());
+    for handle in handles {
+        let res = handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("accept loop panicked")));
+        result = result.and(res);
+    }
+    result
+}
+
+fn accept_loop<F, Fut>(listener: TcpListener, opts: ServerOptions, endpoint: F) -> io::Result<()>
+where
+    F: Fn(Request) -> Fut + Clone + 'static,
+    Fut: Future<Output = http_types::Result<Response>> + 'static,
+{
+    let local_addr = listener.local_addr()?;
+    let listener = Async::new(listener)?;
+    let executor = LocalExecutor::new();
+    future::block_on(executor.run(async {
+        let mut backoff = None;
+        loop {
+            let (stream, peer_addr) = match listener.accept().await {
+                Ok(accepted) => {
+                    backoff = None;
+                    accepted
+                }
+                Err(err) if is_transient(&err) => {
+                    let pause = backoff.map_or(MIN_ACCEPT_BACKOFF, |pause: Duration| {
+                        (pause * 2).min(MAX_ACCEPT_BACKOFF)
+                    });
+                    trace!(
+                        "accepting on {} failed: {}, retrying in {:?}",
+                        local_addr,
+                        err,
+                        pause
+                    );
+                    backoff = Some(pause);
+                    Timer::after(pause).await;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let stream = async_dup::Arc::new(stream);
+            let opts = opts
+                .clone()
+                .with_peer_addr(peer_addr)
+                .with_local_addr(local_addr);
+            let endpoint = endpoint.clone();
+            executor
+                .spawn(async move {
+                    if let Err(err) = accept_with_opts(stream, endpoint, opts).await {
+                        trace!("connection from {} failed: {}", peer_addr, err);
+                    }
+                })
+                .detach();
+        }
+    }))
+}
+
+/// Whether accepting failed because of the connection, or for lack of
+/// resources which may free up, rather than because of the listener.
+fn is_transient(err: &io::Error) -> bool {
+    let transient = [Errno::MFILE, Errno::NFILE, Errno::NOBUFS, Errno::NOMEM];
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+    ) || Errno::from_io_error(err).is_some_and(|errno| transient.contains(&errno))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_accept_errors() {
+        assert!(is_transient(&Errno::MFILE.into()));
+        assert!(is_transient(&Errno::NFILE.into()));
+        assert!(is_transient(&io::ErrorKind::ConnectionAborted.into()));
+        assert!(!is_transient(&Errno::BADF.into()));
+        assert!(!is_transient(&Errno::INVAL.into()));
+    }
+}
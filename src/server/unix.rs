@@ -0,0 +1,69 @@
+//! Serve HTTP over Unix domain sockets.
+
+use std::future::Future;
+use std::os::unix::io::AsFd;
+
+use futures_lite::io::{AsyncRead as Read, AsyncWrite as Write};
+use http_types::{Request, Response};
+
+use super::{accept_with_opts, PeerCredentials, ServerOptions};
+
+/// The host of requests over a Unix domain socket which don't name one.
+const DEFAULT_HOST: &str = "localhost";
+
+/// Serve a connection accepted on a Unix domain socket, such as an
+/// `async_std::os::unix::net::UnixStream`.
+///
+/// Endpoints find the credentials of the process on the other end in the
+/// [`ConnectionInfo`](super::ConnectionInfo) in each request's extensions.
+/// Requests without a `Host` header are accepted, as for `localhost`
+/// unless `opts` has a [default
+/// host](ServerOptions::with_default_host) already.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::server::{accept_unix, ConnectionInfo, ServerOptions};
+/// use async_std::os::unix::net::UnixListener;
+/// use async_std::prelude::*;
+/// use http_types::{Request, Response, StatusCode};
+///
+/// # async fn serve() -> http_types::Result<()> {
+/// let listener = UnixListener::bind("/tmp/app.sock").await?;
+/// let mut incoming = listener.incoming();
+/// while let Some(stream) = incoming.next().await {
+///     let stream = stream?;
+///     async_std::task::spawn(async move {
+///         let endpoint = |req: Request| async move {
+///             let info = req.ext().get::<ConnectionInfo>();
+///             let uid = info.and_then(|info| info.peer_credentials()).map(|c| c.uid());
+///             let mut res = Response::new(StatusCode::Ok);
+///             res.set_body(format!("Hello, user {:?}", uid));
+///             Ok(res)
+///         };
+///         accept_unix(stream, endpoint, ServerOptions::new()).await
+///     });
+/// }
+/// # Ok(()) }
+/// ```
+pub async fn accept_unix<RW, F, Fut>(
+    io: RW,
+    endpoint: F,
+    mut opts: ServerOptions,
+) -> http_types::Result<()>
+where
+    RW: AsFd + Read + Write + Clone + Send + Sync + Unpin + 'static,
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = http_types::Result<Response>>,
+{
+    match PeerCredentials::of(&io) {
+        Ok(credentials) => opts = opts.with_peer_credentials(credentials),
+        Err(e) => {
+            trace!("couldn't read the peer's credentials: {}", e);
+        }
+    }
+    if opts.default_host.is_none() {
+        opts = opts.with_default_host(DEFAULT_HOST);
+    }
+    accept_with_opts(io, endpoint, opts).await
+}
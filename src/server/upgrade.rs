@@ -0,0 +1,62 @@
+//! Hand connections over to endpoints after `101 Switching Protocols`.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write};
+
+/// The connection handed to an endpoint after an upgrade. It first yields
+/// the bytes the client sent after the request head which were already
+/// read from the stream, so none are lost.
+#[derive(Debug)]
+pub(crate) struct Upgraded<IO> {
+    buffered: Vec<u8>,
+    consumed: usize,
+    io: IO,
+}
+
+impl<IO> Upgraded<IO> {
+    pub(crate) fn new(buffered: Vec<u8>, io: IO) -> Self {
+        Self {
+            buffered,
+            consumed: 0,
+            io,
+        }
+    }
+}
+
+impl<IO: Read + Unpin> Read for Upgraded<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let buffered = &this.buffered[this.consumed..];
+        if buffered.is_empty() {
+            return Pin::new(&mut this.io).poll_read(cx, buf);
+        }
+        let bytes = buffered.len().min(buf.len());
+        buf[..bytes].copy_from_slice(&buffered[..bytes]);
+        this.consumed += bytes;
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+impl<IO: Write + Unpin> Write for Upgraded<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_close(cx)
+    }
+}
@@ -0,0 +1,46 @@
+//! Tell endpoints how much their connection has been used.
+
+use std::time::{Duration, Instant};
+
+/// How much the connection a request arrived on has been used.
+///
+/// Available in the extensions of every request, so that endpoints can apply
+/// their own policies, such as closing connections after a number of
+/// requests or some time to rebalance clients across servers.
+///
+/// # Example
+///
+/// ```
+/// use async_h1::server::ConnectionUsage;
+/// use http_types::{Request, Response, StatusCode};
+/// use std::time::Duration;
+///
+/// async fn endpoint(req: Request) -> http_types::Result<Response> {
+///     let mut res = Response::new(StatusCode::Ok);
+///     if let Some(usage) = req.ext().get::<ConnectionUsage>() {
+///         let too_old = usage.age().is_some_and(|age| age > Duration::from_secs(300));
+///         if usage.requests_served() >= 1000 || too_old {
+///             res.insert_header("connection", "close");
+///         }
+///     }
+///     Ok(res)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionUsage {
+    pub(crate) requests: usize,
+    pub(crate) opened: Option<Instant>,
+}
+
+impl ConnectionUsage {
+    /// The number of requests served on the connection before this one.
+    pub fn requests_served(&self) -> usize {
+        self.requests
+    }
+
+    /// How long ago the connection was opened, or `None` on targets without
+    /// a clock.
+    pub fn age(&self) -> Option<Duration> {
+        self.opened.map(|opened| opened.elapsed())
+    }
+}
@@ -0,0 +1,180 @@
+//! Serve connections on a fixed pool of worker threads.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use async_channel::{Receiver, Sender};
+use async_executor::LocalExecutor;
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write};
+use http_types::{Request, Response};
+
+use super::{accept_with_opts, ServerOptions};
+
+/// How a [`WorkerPool`] picks the worker for a new connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Distribution {
+    /// Hand connections to each worker in turn. This is the default.
+    #[default]
+    RoundRobin,
+    /// Hand each connection to the worker with the fewest open connections.
+    LeastLoaded,
+}
+
+/// A fixed set of worker threads which serve the connections dispatched to
+/// them.
+///
+/// Each worker runs its own single-threaded executor, so the number of
+/// threads doesn't grow with the number of connections and the endpoint's
+/// future doesn't need to be `Send`.
+///
+/// # Example
+///
+/// ```no_run
+/// use async_h1::server::{Distribution, ServerOptions, WorkerPool};
+/// use async_io::Async;
+/// use http_types::{Response, StatusCode};
+/// use std::net::TcpListener;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let pool = WorkerPool::new(4, Distribution::LeastLoaded, ServerOptions::new(), |_req| async {
+///     Ok(Response::new(StatusCode::Ok))
+/// });
+///
+/// futures_lite::future::block_on(async {
+///     let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 8080))?;
+///     loop {
+///         let (stream, _) = listener.accept().await?;
+///         pool.dispatch(async_dup::Arc::new(stream))?;
+///     }
+/// })
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct WorkerPool<RW> {
+    workers: Vec<Worker<RW>>,
+    distribution: Distribution,
+    next: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct Worker<RW> {
+    sender: Sender<RW>,
+    load: Arc<AtomicUsize>,
+    handle: JoinHandle<()>,
+}
+
+impl<RW> WorkerPool<RW>
+where
+    RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
+{
+    /// Start `workers` threads which serve connections with `endpoint`.
+    pub fn new<F, Fut>(
+        workers: usize,
+        distribution: Distribution,
+        opts: ServerOptions,
+        endpoint: F,
+    ) -> Self
+    where
+        F: Fn(Request) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = http_types::Result<Response>> + 'static,
+    {
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let (sender, receiver) = async_channel::unbounded();
+                let load = Arc::new(AtomicUsize::new(0));
+                let (opts, endpoint, worker_load) = (opts.clone(), endpoint.clone(), load.clone());
+                let handle = thread::spawn(move || run(receiver, worker_load, opts, endpoint));
+                Worker {
+                    sender,
+                    load,
+                    handle,
+                }
+            })
+            .collect();
+
+        Self {
+            workers,
+            distribution,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Hand a connection to one of the workers.
+    pub fn dispatch(&self, io: RW) -> io::Result<()> {
+        let worker = match self.distribution {
+            Distribution::RoundRobin => {
+                let next = self.next.fetch_add(1, Ordering::Relaxed);
+                &self.workers[next % self.workers.len()]
+            }
+            Distribution::LeastLoaded => self
+                .workers
+                .iter()
+                .min_by_key(|worker| worker.load.load(Ordering::Relaxed))
+                .expect("pool has at least one worker"),
+        };
+
+        worker.load.fetch_add(1, Ordering::Relaxed);
+        worker.sender.try_send(io).map_err(|_| {
+            worker.load.fetch_sub(1, Ordering::Relaxed);
+            io::Error::other("worker thread has stopped")
+        })
+    }
+
+    /// The number of worker threads.
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Always `false`: a pool has at least one worker.
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// The number of open connections on each worker.
+    pub fn loads(&self) -> Vec<usize> {
+        self.workers
+            .iter()
+            .map(|worker| worker.load.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Stop accepting connections and wait for the workers to finish serving
+    /// the ones they have.
+    pub fn join(self) {
+        for worker in self.workers {
+            drop(worker.sender);
+            worker.handle.join().ok();
+        }
+    }
+}
+
+/// The body of a worker thread.
+fn run<RW, F, Fut>(receiver: Receiver<RW>, load: Arc<AtomicUsize>, opts: ServerOptions, endpoint: F)
+where
+    RW: Read + Write + Clone + Send + Sync + Unpin + 'static,
+    F: Fn(Request) -> Fut + Clone + 'static,
+    Fut: Future<Output = http_types::Result<Response>> + 'static,
+{
+    let executor = LocalExecutor::new();
+    future::block_on(executor.run(async {
+        while let Ok(io) = receiver.recv().await {
+            let (opts, endpoint, load) = (opts.clone(), endpoint.clone(), load.clone());
+            executor
+                .spawn(async move {
+                    if let Err(err) = accept_with_opts(io, endpoint, opts).await {
+                        trace!("connection failed: {}", err);
+                    }
+                    load.fetch_sub(1, Ordering::Relaxed);
+                })
+                .detach();
+        }
+    }));
+
+    // The pool is gone; finish the connections that are still open.
+    while !executor.is_empty() {
+        future::block_on(executor.tick());
+    }
+}
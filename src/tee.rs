@@ -0,0 +1,167 @@
+//! Copy body bytes to an auditing sink as they're read.
+//!
+//! Wrap a request body with [`tee_body`], e.g. from a
+//! [`Hook`](crate::server::Hook), to capture what the endpoint reads for
+//! debugging, inspection, or replay, without buffering the body up front.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::server::{Hook, ServerOptions};
+//! use async_h1::tee::{tee_body, BoundedBuffer};
+//! use http_types::{Request, Response};
+//!
+//! #[derive(Debug)]
+//! struct Capture(BoundedBuffer);
+//!
+//! impl Hook for Capture {
+//!     fn before_endpoint(&self, req: &mut Request) -> Option<Response> {
+//!         tee_body(req, self.0.clone());
+//!         None
+//!     }
+//! }
+//!
+//! let captured = BoundedBuffer::new(64 * 1024);
+//! let opts = ServerOptions::new().with_hook(Capture(captured.clone()));
+//! ```
+
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_lite::io::{self, AsyncRead as Read, BufReader};
+use http_types::{Body, Request};
+
+/// Receives a copy of the bytes read from a body.
+pub trait TeeSink: Send + Sync + 'static {
+    /// Called with each run of bytes as it's read.
+    fn write(&mut self, bytes: &[u8]);
+}
+
+impl<F> TeeSink for F
+where
+    F: FnMut(&[u8]) + Send + Sync + 'static,
+{
+    fn write(&mut self, bytes: &[u8]) {
+        self(bytes)
+    }
+}
+
+/// A [`TeeSink`] which keeps up to a fixed number of bytes in memory.
+///
+/// Clones share the same buffer, so keep one to inspect what was captured.
+#[derive(Debug, Clone)]
+pub struct BoundedBuffer {
+    inner: Arc<Mutex<Captured>>,
+    limit: usize,
+}
+
+#[derive(Debug, Default)]
+struct Captured {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+impl BoundedBuffer {
+    /// Create a buffer keeping at most `limit` bytes.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            inner: Arc::default(),
+            limit,
+        }
+    }
+
+    /// The bytes captured so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.inner.lock().unwrap().bytes.clone()
+    }
+
+    /// Whether bytes were dropped because the buffer was full.
+    pub fn is_truncated(&self) -> bool {
+        self.inner.lock().unwrap().truncated
+    }
+}
+
+impl TeeSink for BoundedBuffer {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut inner = self.inner.lock().unwrap();
+        let room = self.limit - inner.bytes.len();
+        if bytes.len() > room {
+            inner.truncated = true;
+        }
+        inner
+            .bytes
+            .extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+}
+
+/// A [`TeeSink`] which writes to a blocking writer, such as a file.
+///
+/// Write errors are ignored so that a failing sink doesn't fail the request.
+#[derive(Debug)]
+pub struct WriteSink<W>(pub W);
+
+impl<W> TeeSink for WriteSink<W>
+where
+    W: std::io::Write + Send + Sync + 'static,
+{
+    fn write(&mut self, bytes: &[u8]) {
+        if let Err(err) = self.0.write_all(bytes) {
+            trace!("failed to write to tee sink: {}", err);
+        }
+    }
+}
+
+/// A reader which copies the bytes read from it to a [`TeeSink`].
+pub struct TeeReader<R, S> {
+    reader: R,
+    sink: S,
+}
+
+impl<R, S> TeeReader<R, S> {
+    /// Wrap a reader.
+    pub fn new(reader: R, sink: S) -> Self {
+        Self { reader, sink }
+    }
+
+    /// Unwrap the reader and the sink.
+    pub fn into_inner(self) -> (R, S) {
+        (self.reader, self.sink)
+    }
+}
+
+impl<R: Debug, S> Debug for TeeReader<R, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TeeReader")
+            .field("reader", &self.reader)
+            .finish()
+    }
+}
+
+impl<R: Read + Unpin, S: TeeSink + Unpin> Read for TeeReader<R, S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let bytes = futures_core::ready!(Pin::new(&mut this.reader).poll_read(cx, buf))?;
+        if bytes > 0 {
+            this.sink.write(&buf[..bytes]);
+        }
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+/// Replace a request's body with one which copies the bytes read from it to
+/// `sink`.
+pub fn tee_body(req: &mut Request, sink: impl TeeSink + Unpin) {
+    let len = req.len();
+    let body = req.take_body();
+    let mime = body.mime().clone();
+    let reader = BufReader::new(TeeReader::new(body, sink));
+    let mut body = Body::from_reader(reader, len);
+    body.set_mime(mime);
+    req.set_body(body);
+}
@@ -0,0 +1,190 @@
+//! In-memory connections for testing endpoints without sockets.
+//!
+//! [`MockTcpStream::pair`] opens the two ends of a connection. Serve one
+//! end with [`accept`](crate::accept) or a [`Server`](crate::server::Server)
+//! as usual, and talk to it through the other, with
+//! [`send_request_bytes`] and [`read_response_bytes`] or anything else
+//! which works with a stream.
+//!
+//! # Example
+//!
+//! ```
+//! use async_h1::testing::{read_response_bytes, send_request_bytes, MockTcpStream};
+//! use http_types::{Request, Response};
+//!
+//! # futures_lite::future::block_on(async {
+//! let (mut client, server) = MockTcpStream::pair();
+//! send_request_bytes(&mut client, b"GET /hello HTTP/1.1\r\nHost: example.com\r\n\r\n").await?;
+//! // The server stops at the end of the requests.
+//! client.close();
+//!
+//! async_h1::accept(server, |req: Request| async move {
+//!     let mut res = Response::new(200);
+//!     res.set_body(format!("Hello from {}", req.url().path()));
+//!     Ok(res)
+//! })
+//! .await?;
+//!
+//! let res = read_response_bytes(&mut client).await?;
+//! assert!(res.starts_with(b"HTTP/1.1 200 OK\r\n"));
+//! assert!(res.ends_with(b"Hello from /hello"));
+//! # http_types::Result::Ok(())
+//! # }).unwrap();
+//! ```
+
+use std::fmt::{self, Debug};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_lite::future;
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write, AsyncWriteExt};
+
+/// One end of an in-memory connection.
+///
+/// Bytes written to one end can be read from the other. Writes never wait,
+/// and reads wait until the other end writes or closes. Clones share the
+/// same end, so one can be handed to the server while the test keeps
+/// another.
+#[derive(Debug, Clone)]
+pub struct MockTcpStream {
+    read: Arc<Pipe>,
+    write: Arc<Pipe>,
+}
+
+impl MockTcpStream {
+    /// Open both ends of a connection, as `(client, server)`.
+    pub fn pair() -> (Self, Self) {
+        let to_server = Arc::new(Pipe::default());
+        let to_client = Arc::new(Pipe::default());
+        let client = Self {
+            read: to_client.clone(),
+            write: to_server.clone(),
+        };
+        let server = Self {
+            read: to_server,
+            write: to_client,
+        };
+        (client, server)
+    }
+
+    /// Stop writing from this end, so that the other end reads to the end
+    /// of the stream once it has read what was written before.
+    pub fn close(&self) {
+        self.write.close();
+    }
+
+    /// The number of bytes written to this end which the other end hasn't
+    /// read yet.
+    pub fn unread(&self) -> usize {
+        self.write.state().data.len()
+    }
+}
+
+/// Write `bytes` to the server from the client end of a connection.
+pub async fn send_request_bytes(client: &mut MockTcpStream, bytes: &[u8]) -> io::Result<()> {
+    client.write_all(bytes).await
+}
+
+/// Read everything the server has written to the client end of a
+/// connection, waiting until it has written something or closed its end.
+///
+/// Returns what was written so far without waiting for more, so call it
+/// once the server has responded, e.g. after
+/// [`accept`](crate::accept) returns.
+pub async fn read_response_bytes(client: &mut MockTcpStream) -> io::Result<Vec<u8>> {
+    let pipe = &client.read;
+    future::poll_fn(|cx| {
+        let mut state = pipe.state();
+        if state.data.is_empty() && !state.closed {
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(std::mem::take(&mut state.data)))
+    })
+    .await
+}
+
+/// The bytes travelling in one direction of a connection.
+#[derive(Default)]
+struct Pipe(Mutex<PipeState>);
+
+#[derive(Default)]
+struct PipeState {
+    data: Vec<u8>,
+    closed: bool,
+    /// The reader waiting for bytes.
+    waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn state(&self) -> std::sync::MutexGuard<'_, PipeState> {
+        self.0.lock().unwrap()
+    }
+
+    fn close(&self) {
+        let mut state = self.state();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Debug for Pipe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("Pipe")
+            .field("buffered", &state.data.len())
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl Read for MockTcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.read.state();
+        if !state.data.is_empty() {
+            let bytes = buf.len().min(state.data.len());
+            buf[..bytes].copy_from_slice(&state.data[..bytes]);
+            state.data.drain(..bytes);
+            Poll::Ready(Ok(bytes))
+        } else if state.closed {
+            Poll::Ready(Ok(0))
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Write for MockTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.write.state();
+        if state.closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        state.data.extend_from_slice(buf);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.write.close();
+        Poll::Ready(Ok(()))
+    }
+}
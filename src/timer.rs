@@ -0,0 +1,242 @@
+//! Runtime-neutral timeouts.
+
+use std::future::Future;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_lite::io::{self, AsyncRead as Read, AsyncWrite as Write};
+
+use crate::error::{Error, TimeoutPhase};
+
+#[cfg(not(target_arch = "wasm32"))]
+use async_io::Timer;
+#[cfg(not(target_arch = "wasm32"))]
+use futures_lite::future;
+
+/// The error returned when a future didn't complete in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimedOut;
+
+/// Await a future, giving up once `duration` has elapsed.
+///
+/// The timer is driven by `async-io`, so this works on any executor rather
+/// than only on async-std.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn timeout<F>(duration: Duration, fut: F) -> Result<F::Output, TimedOut>
+where
+    F: Future,
+{
+    future::or(async { Ok(fut.await) }, async {
+        Timer::after(duration).await;
+        Err(TimedOut)
+    })
+    .await
+}
+
+/// There's no timer available on WASM targets, so the future is awaited
+/// without a deadline. Hosts are expected to enforce their own limits.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout<F>(_duration: Duration, fut: F) -> Result<F::Output, TimedOut>
+where
+    F: Future,
+{
+    Ok(fut.await)
+}
+
+/// Wraps a stream, failing reads and writes which can't make progress for
+/// longer than `duration` with a timeout error for `phase`.
+///
+/// The deadline restarts whenever a read or write completes, so streams
+/// which are slow but keep moving aren't cut off, while peers which stall
+/// partway through a body, like a slowloris attack, are. The client and
+/// the server both read bodies and write messages through it.
+#[derive(Debug)]
+pub(crate) struct TimedStream<S> {
+    inner: S,
+    duration: Option<Duration>,
+    phase: TimeoutPhase,
+    #[cfg(not(target_arch = "wasm32"))]
+    timer: Option<Timer>,
+}
+
+impl<S> TimedStream<S> {
+    /// Wrap `inner`, or pass it through if `duration` is `None`.
+    pub(crate) fn new(inner: S, duration: Option<Duration>, phase: TimeoutPhase) -> Self {
+        Self {
+            inner,
+            duration,
+            phase,
+            #[cfg(not(target_arch = "wasm32"))]
+            timer: None,
+        }
+    }
+
+    /// Change how long the stream may go without progress from now on.
+    pub(crate) fn set_duration(&mut self, duration: Option<Duration>) {
+        self.duration = duration;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.timer = None;
+        }
+    }
+
+    /// Check an operation on the inner stream against the deadline,
+    /// starting it if the operation is the first to wait.
+    fn check<T>(&mut self, cx: &mut Context<'_>, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if poll.is_ready() {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                self.timer = None;
+            }
+            return poll;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(duration) = self.duration {
+            let timer = self.timer.get_or_insert_with(|| Timer::after(duration));
+            if Pin::new(timer).poll(cx).is_ready() {
+                self.timer = None;
+                let message = format!("No progress for {:?}", duration);
+                return Poll::Ready(Err(Error::timed_out(self.phase, message).into()));
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = (cx, self.duration);
+        Poll::Pending
+    }
+}
+
+impl<S: Read + Unpin> Read for TimedStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.check(cx, poll)
+    }
+}
+
+impl<S: Write + Unpin> Write for TimedStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.check(cx, poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.check(cx, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.check(cx, poll)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Closing flushes what's left, which stalls just like writing.
+        let poll = Pin::new(&mut self.inner).poll_close(cx);
+        self.check(cx, poll)
+    }
+}
+
+/// The current instant, or `None` on targets without a clock, where
+/// `Instant::now` panics.
+pub(crate) fn now() -> Option<Instant> {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        None
+    } else {
+        Some(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future;
+
+    #[test]
+    fn completes_before_deadline() {
+        let res = future::block_on(timeout(Duration::from_secs(1), async { 42 }));
+        assert_eq!(res, Ok(42));
+    }
+
+    #[test]
+    fn times_out() {
+        let res = future::block_on(timeout(Duration::from_millis(10), future::pending::<()>()));
+        assert_eq!(res, Err(TimedOut));
+    }
+
+    #[test]
+    fn stalled_stream_times_out() {
+        use futures_lite::io::AsyncReadExt;
+
+        struct Stalled;
+        impl Read for Stalled {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                _: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Pending
+            }
+        }
+
+        let duration = Some(Duration::from_millis(10));
+        let mut stream = TimedStream::new(Stalled, duration, TimeoutPhase::Body);
+        let err = future::block_on(stream.read(&mut [0; 8])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(TimeoutPhase::of_io(&err), Some(TimeoutPhase::Body));
+    }
+
+    #[test]
+    fn slow_but_progressing_stream_does_not_time_out() {
+        use futures_lite::io::AsyncReadExt;
+
+        /// Yields a byte every 5ms, 8 times.
+        struct Trickle {
+            left: usize,
+            timer: Option<Timer>,
+        }
+        impl Read for Trickle {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                if self.left == 0 {
+                    return Poll::Ready(Ok(0));
+                }
+                let timer = self
+                    .timer
+                    .get_or_insert_with(|| Timer::after(Duration::from_millis(5)));
+                futures_core::ready!(Pin::new(timer).poll(cx));
+                self.timer = None;
+                self.left -= 1;
+                buf[0] = b'x';
+                Poll::Ready(Ok(1))
+            }
+        }
+
+        // The whole read takes longer than the deadline, but no single
+        // byte does.
+        let trickle = Trickle {
+            left: 8,
+            timer: None,
+        };
+        let duration = Some(Duration::from_millis(25));
+        let mut stream = TimedStream::new(trickle, duration, TimeoutPhase::Body);
+        let mut body = Vec::new();
+        future::block_on(stream.read_to_end(&mut body)).unwrap();
+        assert_eq!(body, b"xxxxxxxx");
+    }
+}
@@ -0,0 +1,198 @@
+//! Run connections over TLS.
+//!
+//! This module doesn't ship a TLS implementation of its own. Instead it
+//! provides the handshake hooks to wire one such as
+//! [`futures-rustls`](https://docs.rs/futures-rustls) into both ends of a
+//! connection, and takes care of the parts every integration otherwise
+//! repeats: sending the request's host as the SNI server name, offering
+//! only `http/1.1` through ALPN and refusing anything else, and passing what
+//! was negotiated on to endpoints as a [`TlsInfo`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use std::sync::Arc;
+//!
+//! use async_h1::server::TlsInfo;
+//! use async_h1::tls::{Handshake, TlsAccept};
+//! use async_std::net::TcpStream;
+//! use futures_rustls::{server::TlsStream, TlsAcceptor};
+//!
+//! struct Rustls(Arc<rustls::ServerConfig>);
+//!
+//! impl TlsAccept<TcpStream> for Rustls {
+//!     type Stream = TlsStream<TcpStream>;
+//!
+//!     fn accept<'a>(&'a self, alpn: &'a [&'a [u8]], io: TcpStream) -> Handshake<'a, Self::Stream> {
+//!         let mut config = (*self.0).clone();
+//!         config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+//!         Box::pin(async move {
+//!             let stream = TlsAcceptor::from(Arc::new(config)).accept(io).await?;
+//!             let (_, session) = stream.get_ref();
+//!             let mut info = TlsInfo::new();
+//!             if let Some(protocol) = session.alpn_protocol() {
+//!                 info = info.with_alpn_protocol(protocol);
+//!             }
+//!             if let Some(name) = session.server_name() {
+//!                 info = info.with_server_name(name);
+//!             }
+//!             Ok((stream, info))
+//!         })
+//!     }
+//! }
+//!
+//! let acceptor = Rustls(Arc::new(config));
+//! async_h1::tls::accept(&acceptor, stream, |_req| async { /* ... */ }).await?;
+//! ```
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+
+use futures_lite::io::{AsyncRead as Read, AsyncWrite as Write};
+use http_types::{Request, Response};
+
+use crate::client::{self, ClientOptions, Proxy, Tunnel};
+use crate::server::{self, ServerOptions, TlsInfo};
+
+/// The ALPN protocol ID of HTTP/1.1, the only one offered and accepted.
+pub const HTTP_11: &[u8] = b"http/1.1";
+
+/// The future returned by a TLS handshake: the encrypted stream, and what
+/// was negotiated for it.
+pub type Handshake<'a, S> = Pin<Box<dyn Future<Output = io::Result<(S, TlsInfo)>> + Send + 'a>>;
+
+/// Performs the client side of a TLS handshake over a stream of type `IO`.
+pub trait TlsConnect<IO>: Send + Sync {
+    /// The encrypted stream.
+    type Stream: Read + Write + Send + Sync + Unpin + 'static;
+
+    /// Handshake with the server, sending `server_name` through SNI and
+    /// offering only the `alpn` protocols.
+    fn connect<'a>(
+        &'a self,
+        server_name: &'a str,
+        alpn: &'a [&'a [u8]],
+        io: IO,
+    ) -> Handshake<'a, Self::Stream>;
+}
+
+/// Performs the server side of a TLS handshake over a stream of type `IO`.
+pub trait TlsAccept<IO>: Send + Sync {
+    /// The encrypted stream.
+    type Stream: Read + Write + Send + Sync + Unpin + 'static;
+
+    /// Handshake with the client, selecting a protocol through ALPN only
+    /// from the `alpn` protocols.
+    fn accept<'a>(&'a self, alpn: &'a [&'a [u8]], io: IO) -> Handshake<'a, Self::Stream>;
+}
+
+/// Send a request over a new TLS connection, using the request's host as
+/// the SNI server name.
+pub async fn connect<IO, C>(connector: &C, io: IO, req: Request) -> http_types::Result<Response>
+where
+    C: TlsConnect<IO>,
+{
+    connect_with_opts(connector, io, req, Default::default()).await
+}
+
+/// Send a request over a new TLS connection, using the request's host as
+/// the SNI server name.
+pub async fn connect_with_opts<IO, C>(
+    connector: &C,
+    io: IO,
+    req: Request,
+    opts: ClientOptions,
+) -> http_types::Result<Response>
+where
+    C: TlsConnect<IO>,
+{
+    let server_name = req
+        .url()
+        .host_str()
+        .ok_or_else(|| err_kind!(Encode, "Missing hostname").into_http())?
+        .to_owned();
+    let (stream, info) = connector.connect(&server_name, &[HTTP_11], io).await?;
+    check_alpn(&info)?;
+    client::connect_with_opts(stream, req, opts).await
+}
+
+/// Send a request for an `https` URL through a proxy, over a connection to
+/// the proxy: a [`tunnel`](client::tunnel) is opened to the request's
+/// origin, and TLS is run over it.
+pub async fn connect_via_proxy<IO, C>(
+    connector: &C,
+    io: IO,
+    proxy: &Proxy,
+    req: Request,
+) -> http_types::Result<Response>
+where
+    IO: Read + Write + Unpin,
+    C: TlsConnect<Tunnel<IO>>,
+{
+    connect_via_proxy_with_opts(connector, io, proxy, req, Default::default()).await
+}
+
+/// Send a request for an `https` URL through a proxy, over a connection to
+/// the proxy: a [`tunnel`](client::tunnel) is opened to the request's
+/// origin, and TLS is run over it.
+pub async fn connect_via_proxy_with_opts<IO, C>(
+    connector: &C,
+    io: IO,
+    proxy: &Proxy,
+    req: Request,
+    opts: ClientOptions,
+) -> http_types::Result<Response>
+where
+    IO: Read + Write + Unpin,
+    C: TlsConnect<Tunnel<IO>>,
+{
+    let tunnel = client::tunnel(io, proxy, req.url()).await?;
+    connect_with_opts(connector, tunnel, req, opts).await
+}
+
+/// Accept a new TLS connection, and serve HTTP/1.1 requests on it.
+pub async fn accept<IO, A, F, Fut>(acceptor: &A, io: IO, endpoint: F) -> http_types::Result<()>
+where
+    A: TlsAccept<IO>,
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = http_types::Result<Response>>,
+{
+    accept_with_opts(acceptor, io, endpoint, Default::default()).await
+}
+
+/// Accept a new TLS connection, and serve HTTP/1.1 requests on it.
+///
+/// Endpoints find what was negotiated in the request's
+/// [`Negotiation`](server::Negotiation).
+pub async fn accept_with_opts<IO, A, F, Fut>(
+    acceptor: &A,
+    io: IO,
+    endpoint: F,
+    opts: ServerOptions,
+) -> http_types::Result<()>
+where
+    A: TlsAccept<IO>,
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = http_types::Result<Response>>,
+{
+    let (stream, info) = acceptor.accept(&[HTTP_11], io).await?;
+    check_alpn(&info)?;
+    // The server reads and writes through separate handles.
+    let stream = async_dup::Arc::new(async_dup::Mutex::new(stream));
+    server::accept_with_opts(stream, endpoint, opts.with_tls_info(info)).await
+}
+
+/// Refuse a connection which agreed on a protocol other than HTTP/1.1. No
+/// protocol at all means the peer doesn't do ALPN, which is fine.
+fn check_alpn(info: &TlsInfo) -> io::Result<()> {
+    match info.alpn_protocol() {
+        Some(protocol) if protocol != HTTP_11 => Err(err_kind!(
+            MalformedMessage,
+            "Unexpected ALPN protocol {}",
+            String::from_utf8_lossy(protocol)
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
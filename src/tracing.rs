@@ -0,0 +1,155 @@
+//! Hooks for structured tracing spans.
+//!
+//! Implement [`Tracer`] to open spans in a tracing system such as the
+//! [`tracing`](https://docs.rs/tracing) crate or OpenTelemetry, and pass it
+//! to the server through
+//! [`ServerOptions::with_tracer`](crate::ServerOptions::with_tracer).
+//!
+//! Each connection served with [`accept`](crate::server::accept) gets a
+//! [`SERVER_CONNECTION`] span, with the peer's address if it was given with
+//! [`ServerOptions::with_peer_addr`](crate::ServerOptions::with_peer_addr).
+//! Each request on it gets a [`SERVER_REQUEST`] span within the
+//! connection's, opened once its head has been read, which records the
+//! response's status and size once it's been written. Spans are entered
+//! whenever the server polls the work they stand for, endpoints included,
+//! and closed when they're dropped.
+//!
+//! Field names follow the OpenTelemetry HTTP semantic conventions.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::Arc;
+//! use async_h1::tracing::{Fields, Span, Tracer};
+//!
+//! /// Log spans as they're opened and closed.
+//! #[derive(Debug)]
+//! struct LogTracer;
+//!
+//! #[derive(Debug)]
+//! struct LogSpan(&'static str);
+//!
+//! impl Tracer for LogTracer {
+//!     fn new_span(
+//!         &self,
+//!         name: &'static str,
+//!         _parent: Option<&dyn Span>,
+//!         fields: Fields<'_>,
+//!     ) -> Option<Arc<dyn Span>> {
+//!         for (field, value) in fields {
+//!             println!("{} {}={}", name, field, value);
+//!         }
+//!         Some(Arc::new(LogSpan(name)))
+//!     }
+//! }
+//!
+//! impl Span for LogSpan {
+//!     fn record(&self, fields: Fields<'_>) {
+//!         for (field, value) in fields {
+//!             println!("{} {}={}", self.0, field, value);
+//!         }
+//!     }
+//! }
+//!
+//! impl Drop for LogSpan {
+//!     fn drop(&mut self) {
+//!         println!("{} closed", self.0);
+//!     }
+//! }
+//!
+//! let opts = async_h1::ServerOptions::new().with_tracer(LogTracer);
+//! ```
+
+use std::fmt::{self, Debug, Display};
+use std::future::Future;
+use std::sync::Arc;
+
+use futures_lite::{future, pin};
+
+/// A connection accepted by the server.
+pub const SERVER_CONNECTION: &str = "http.server.connection";
+/// A request/response exchange on a server connection.
+pub const SERVER_REQUEST: &str = "http.server.request";
+
+/// Fields describing a span.
+pub type Fields<'a> = &'a [(&'static str, Value<'a>)];
+
+/// The value of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    /// Text, such as a method or a path.
+    Str(&'a str),
+    /// A number, such as a status code or a byte count.
+    U64(u64),
+}
+
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(value) => f.write_str(value),
+            Value::U64(value) => Display::fmt(value, f),
+        }
+    }
+}
+
+/// Opens spans for the work the server does.
+pub trait Tracer: Debug + Send + Sync + 'static {
+    /// Open a span named `name` within `parent`, or `None` to leave the
+    /// work untraced.
+    fn new_span(
+        &self,
+        _name: &'static str,
+        _parent: Option<&dyn Span>,
+        _fields: Fields<'_>,
+    ) -> Option<Arc<dyn Span>> {
+        None
+    }
+}
+
+/// A span opened by a [`Tracer`], which is closed when it's dropped.
+pub trait Span: Debug + Send + Sync {
+    /// Make this the current span, before the work it stands for is
+    /// polled.
+    fn enter(&self) {}
+
+    /// Stop this being the current span, after the work it stands for has
+    /// been polled.
+    fn exit(&self) {}
+
+    /// Add fields learned after the span was opened.
+    fn record(&self, _fields: Fields<'_>) {}
+}
+
+/// A [`Tracer`] which doesn't open any spans.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopTracer;
+
+impl Tracer for NoopTracer {}
+
+/// Run `f` with `span` entered.
+pub(crate) fn in_span<T>(span: Option<&dyn Span>, f: impl FnOnce() -> T) -> T {
+    if let Some(span) = span {
+        span.enter();
+    }
+    let out = f();
+    if let Some(span) = span {
+        span.exit();
+    }
+    out
+}
+
+/// Run `fut`, entering `span` whenever it's polled.
+pub(crate) async fn instrument<F: Future>(span: Option<&dyn Span>, fut: F) -> F::Output {
+    let span = match span {
+        Some(span) => span,
+        None => return fut.await,
+    };
+    pin!(fut);
+    future::poll_fn(|cx| {
+        span.enter();
+        let poll = fut.as_mut().poll(cx);
+        span.exit();
+        poll
+    })
+    .await
+}
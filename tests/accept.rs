@@ -1,9 +1,15 @@
 mod test_utils;
 mod accept {
     use super::test_utils::TestServer;
-    use async_h1::{client::Encoder, server::ConnectionStatus};
-    use async_std::io::{self, prelude::WriteExt, Cursor};
+    use async_h1::error::{ErrorKind, TimeoutPhase};
+    use async_h1::server::{ConnectionStatus, ConnectionUsage, ServerOptions};
+    use async_h1::{client::Encoder, Profile};
+    use async_std::io::{self, prelude::ReadExt, prelude::WriteExt, Cursor};
     use http_types::{headers::CONNECTION, Body, Request, Response, Result};
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
 
     #[async_std::test]
     async fn basic() -> Result<()> {
@@ -48,6 +54,80 @@ mod accept {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn request_close_among_other_options() -> Result<()> {
+        let mut server = TestServer::new(|_| async { Ok(Response::new(200)) });
+
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: TE, Close\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        // The response confirms that the connection closes.
+        let mut response = vec![0; 1024];
+        let len = server.read(&mut response).await?;
+        let response = std::str::from_utf8(&response[..len])?;
+        assert!(response.contains("\r\nconnection: close\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn http_1_0_requests_close_by_default() -> Result<()> {
+        let mut server = TestServer::new(|_| async { Ok(Response::new(200)) });
+
+        server
+            .write_all(b"GET / HTTP/1.0\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        let mut response = vec![0; 1024];
+        let len = server.read(&mut response).await?;
+        let response = std::str::from_utf8(&response[..len])?;
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(response.contains("\r\nconnection: close\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn http_1_0_keep_alive() -> Result<()> {
+        let mut server = TestServer::new(|_| async { Ok(Response::new(200)) });
+
+        server
+            .write_all(b"GET / HTTP/1.0\r\nHost: example.com\r\nConnection: keep-alive\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut response = vec![0; 1024];
+        let len = server.read(&mut response).await?;
+        let response = std::str::from_utf8(&response[..len])?;
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(response.contains("\r\nconnection: keep-alive\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn http_1_0_bodies_of_unknown_length_end_with_the_connection() -> Result<()> {
+        let mut server = TestServer::new(|_| async {
+            let mut response = Response::new(200);
+            let body = Cursor::new("unknown length");
+            response.set_body(Body::from_reader(io::BufReader::new(body), None));
+            Ok(response)
+        });
+
+        server
+            .write_all(b"GET / HTTP/1.0\r\nHost: example.com\r\nConnection: keep-alive\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        let mut response = vec![0; 1024];
+        let len = server.read(&mut response).await?;
+        let response = std::str::from_utf8(&response[..len])?;
+        assert!(response.contains("\r\nconnection: close\r\n"));
+        assert!(!response.contains("transfer-encoding"));
+        assert!(response.ends_with("\r\n\r\nunknown length"));
+        Ok(())
+    }
+
     #[async_std::test]
     async fn response_close() -> Result<()> {
         let mut server = TestServer::new(|_| async {
@@ -178,4 +258,513 @@ mod accept {
 
         Ok(())
     }
+
+    #[async_std::test]
+    async fn malformed_request_gets_error_response() -> Result<()> {
+        let mut server = TestServer::new(|_| async { Ok(Response::new(200)) });
+
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\nContent-Length: x\r\n\r\n")
+            .await?;
+        assert!(server.accept_one().await.is_err());
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 400);
+        assert_eq!(res[CONNECTION], "close");
+        assert_eq!(res.body_string().await?, "400 Bad Request\n");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strict_profile_limits_headers() -> Result<()> {
+        let opts = ServerOptions::new().with_profile(Profile::Strict);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        let mut request = String::from("GET / HTTP/1.1\r\nHost: example.com\r\n");
+        for i in 0..100 {
+            request.push_str(&format!("x-header-{}: {}\r\n", i, i));
+        }
+        request.push_str("\r\n");
+        server.write_all(request.as_bytes()).await?;
+
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 431);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn folded_headers_are_unfolded_when_allowed() -> Result<()> {
+        let opts = ServerOptions::new().with_unfold_headers(true);
+        let endpoint = |req: Request| async move {
+            assert_eq!(req["x-folded"], "first second third");
+            assert_eq!(req["x-after"], "value");
+            Ok(Response::new(200))
+        };
+        let mut server = TestServer::new_with_opts(endpoint, opts);
+
+        server
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: example.com\r\nX-Folded: first \r\n  second\r\n\tthird\r\nX-After: value\r\n\r\n",
+            )
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 200);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn idle_connections_are_evicted_lru() -> Result<()> {
+        let opts = ServerOptions::new().with_max_idle_connections(1);
+        let endpoint = |_| async { Ok(Response::new(200)) };
+        let mut older = TestServer::new_with_opts(endpoint, opts.clone());
+        let mut newer = TestServer::new_with_opts(endpoint, opts);
+
+        for server in [&mut older, &mut newer] {
+            server
+                .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await?;
+            assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        }
+
+        // Both connections go idle; the one which did so first is closed.
+        let (closed, status) =
+            futures_lite::future::or(async { ("older", older.accept_one().await) }, async {
+                ("newer", newer.accept_one().await)
+            })
+            .await;
+        assert_eq!(closed, "older");
+        assert_eq!(status?, ConnectionStatus::Close);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pipelined_requests_with_and_without_yielding() -> Result<()> {
+        for yielding in [true, false] {
+            let opts = ServerOptions::new().with_cooperative_yielding(yielding);
+            let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+            for _ in 0..3 {
+                server
+                    .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                    .await?;
+            }
+            server.close();
+            server.accept().await?;
+            assert!(server.all_read());
+
+            let mut responses = vec![0; 1024];
+            let len = server.read(&mut responses).await?;
+            let responses = std::str::from_utf8(&responses[..len])?;
+            assert_eq!(responses.matches("HTTP/1.1 200 OK\r\n").count(), 3);
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn responses_on_a_connection_have_their_own_heads() -> Result<()> {
+        let mut server = TestServer::new(|req| async move {
+            let mut res = Response::new(200);
+            if req.url().path() == "/long" {
+                res.insert_header("x-long", "x".repeat(200));
+            }
+            Ok(res)
+        });
+        for path in ["/long", "/short"] {
+            let req = format!("GET {} HTTP/1.1\r\nHost: example.com\r\n\r\n", path);
+            server.write_all(req.as_bytes()).await?;
+            assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        }
+
+        let mut responses = vec![0; 1024];
+        let len = server.read(&mut responses).await?;
+        let responses = std::str::from_utf8(&responses[..len])?;
+        let heads: Vec<_> = responses.split("\r\n\r\n").collect();
+        assert_eq!(heads.len(), 3);
+        assert!(heads[0].contains("x-long"));
+        assert!(heads[1].starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!heads[1].contains("x-long"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn limits_connection_memory() -> Result<()> {
+        let opts = ServerOptions::new().with_max_connection_memory(100);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        let request = format!(
+            "GET / HTTP/1.1\r\nHost: example.com\r\nx-padding: {}\r\n\r\n",
+            "a".repeat(100)
+        );
+        server.write_all(request.as_bytes()).await?;
+
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn foreign_protocols_get_an_answer_if_asked_for() -> Result<()> {
+        let preface = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+        let mut server = TestServer::new(|_| async { Ok(Response::new(200)) });
+        server.write_all(preface).await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::ProtocolMismatch);
+        // Nothing is written back by default.
+        let mut buf = [0; 64];
+        let read = io::timeout(Duration::from_millis(50), server.read(&mut buf)).await;
+        assert_eq!(read.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+
+        let opts = ServerOptions::new().with_protocol_mismatch_response(true);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+        server.write_all(preface).await?;
+        server.accept_one().await.unwrap_err();
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 505);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn head_timeout_reports_phase() -> Result<()> {
+        let opts = ServerOptions::new().with_headers_timeout(Some(Duration::from_millis(50)));
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        server.write_all(b"GET / HTTP/1.1\r\n").await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Timeout);
+        assert_eq!(TimeoutPhase::of(&err), Some(TimeoutPhase::Head));
+        assert!(!TimeoutPhase::Head.is_retry_safe());
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 408);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn idle_timeout_closes_quietly() -> Result<()> {
+        let opts = ServerOptions::new().with_headers_timeout(Some(Duration::from_millis(50)));
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn exposes_connection_usage() -> Result<()> {
+        let mut server = TestServer::new(|req: Request| async move {
+            let usage = req.ext().get::<ConnectionUsage>().unwrap();
+            assert!(usage.age().is_some());
+            let mut res = Response::new(200);
+            res.insert_header("x-served", usage.requests_served().to_string());
+            Ok(res)
+        });
+
+        for served in ["0", "1"] {
+            server
+                .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await?;
+            assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+            let mut buf = [0; 1024];
+            let bytes = io::ReadExt::read(&mut server, &mut buf).await?;
+            let res = String::from_utf8_lossy(&buf[..bytes]).to_string();
+            assert!(
+                res.contains(&format!("x-served: {}\r\n", served)),
+                "{}",
+                res
+            );
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn closes_after_max_requests_per_connection() -> Result<()> {
+        let opts = ServerOptions::new().with_max_requests_per_connection(2);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        for expected in [ConnectionStatus::KeepAlive, ConnectionStatus::Close] {
+            server
+                .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await?;
+            assert_eq!(server.accept_one().await?, expected);
+            let mut buf = [0; 1024];
+            let bytes = io::ReadExt::read(&mut server, &mut buf).await?;
+            let res = String::from_utf8_lossy(&buf[..bytes]).to_string();
+            let closes = res.contains("connection: close\r\n");
+            assert_eq!(closes, expected == ConnectionStatus::Close, "{}", res);
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strict_profile_limits_requests_per_connection() -> Result<()> {
+        let opts = ServerOptions::new().with_profile(Profile::Strict);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        for _ in 0..99 {
+            server
+                .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+                .await?;
+            assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        }
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn idle_timeout_applies_between_requests() -> Result<()> {
+        let opts = ServerOptions::new()
+            .with_headers_timeout(Some(Duration::from_secs(60)))
+            .with_idle_timeout(Some(Duration::from_millis(50)));
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        let closed = io::timeout(Duration::from_secs(5), async {
+            Ok(server.accept_one().await.unwrap())
+        })
+        .await?;
+        assert_eq!(closed, ConnectionStatus::Close);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn body_timeout_responds_408() -> Result<()> {
+        let opts = ServerOptions::new().with_body_timeout(Some(Duration::from_millis(50)));
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                req.body_string().await?;
+                Ok(Response::new(200))
+            },
+            opts,
+        );
+
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 10\r\n\r\nabc")
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Timeout);
+        assert_eq!(TimeoutPhase::of(&err), Some(TimeoutPhase::Body));
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 408);
+
+        Ok(())
+    }
+
+    /// A client which sends `request` and then never reads the response.
+    #[derive(Debug, Clone)]
+    struct Unread {
+        request: Arc<Mutex<&'static [u8]>>,
+    }
+
+    impl io::Read for Unread {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(std::io::Read::read(&mut *self.request.lock().unwrap(), buf))
+        }
+    }
+
+    impl io::Write for Unread {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Pending
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[async_std::test]
+    async fn write_timeout_applies_to_error_responses() -> Result<()> {
+        let client = Unread {
+            request: Arc::new(Mutex::new(b"GET / HTTP/1.1\r\nHost\r\n\r\n")),
+        };
+        let opts = ServerOptions::new().with_write_timeout(Some(Duration::from_millis(50)));
+        let accepted = io::timeout(Duration::from_secs(5), async {
+            async_h1::accept_with_opts(client, |_| async { Ok(Response::new(200)) }, opts)
+                .await
+                .ok();
+            Ok(())
+        })
+        .await;
+        assert!(accepted.is_ok(), "the 400 response never timed out");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "chunked")]
+    #[async_std::test]
+    async fn malformed_body_gets_400() -> Result<()> {
+        // The endpoint makes light of the error, but the client still hears
+        // that its request was malformed.
+        let mut server = TestServer::new(|mut req: Request| async move {
+            let status = match req.body_string().await {
+                Ok(_) => 200,
+                Err(_) => 202,
+            };
+            Ok(Response::new(status))
+        });
+
+        server
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            )
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::BodyFraming);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 400);
+        assert_eq!(res[CONNECTION], "close");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn declared_bodies_over_the_maximum_get_413() -> Result<()> {
+        let opts = ServerOptions::new().with_max_body_size(4);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello")
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 413);
+        assert_eq!(res[CONNECTION], "close");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strict_profile_limits_bodies() -> Result<()> {
+        let opts = ServerOptions::new().with_profile(Profile::Strict);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 2000000\r\n\r\n")
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 413);
+        Ok(())
+    }
+
+    #[cfg(feature = "chunked")]
+    #[async_std::test]
+    async fn streamed_bodies_over_the_maximum_get_413() -> Result<()> {
+        let opts = ServerOptions::new().with_max_body_size(4);
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                let status = match req.body_string().await {
+                    Ok(_) => 200,
+                    Err(_) => 202,
+                };
+                Ok(Response::new(status))
+            },
+            opts,
+        );
+
+        server
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n\
+                  3\r\nhel\r\n2\r\nlo\r\n0\r\n\r\n",
+            )
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 413);
+        Ok(())
+    }
+
+    #[cfg(feature = "chunked")]
+    #[async_std::test]
+    async fn bodies_at_the_maximum_are_accepted() -> Result<()> {
+        let opts = ServerOptions::new().with_max_body_size(5);
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                assert_eq!(req.body_string().await?, "hello");
+                Ok(Response::new(200))
+            },
+            opts,
+        );
+
+        server
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n\
+                  3\r\nhel\r\n2\r\nlo\r\n0\r\n\r\n",
+            )
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn long_request_line_gets_414() -> Result<()> {
+        let opts = ServerOptions::new().with_max_request_line_length(64);
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        let request = format!(
+            "GET /{} HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            "a".repeat(64)
+        );
+        server.write_all(request.as_bytes()).await?;
+
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), 414);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn request_line_at_limit_is_accepted() -> Result<()> {
+        let line = format!("GET /{} HTTP/1.1", "a".repeat(50));
+        let opts = ServerOptions::new().with_max_request_line_length(line.len());
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        let request = format!("{}\r\nHost: example.com\r\n\r\n", line);
+        server.write_all(request.as_bytes()).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        Ok(())
+    }
 }
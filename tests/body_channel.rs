@@ -0,0 +1,181 @@
+mod test_utils;
+mod body_channel {
+    use super::test_utils::TestServer;
+    use async_h1::error::ErrorKind;
+    use async_h1::server::{BodyFlow, BodyFrames, ConnectionStatus, ServerOptions};
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Body, Request, Response, Result, StatusCode};
+
+    fn opts() -> ServerOptions {
+        ServerOptions::new().with_body_channel(1)
+    }
+
+    #[async_std::test]
+    async fn delivers_body() -> Result<()> {
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                let body = req.body_string().await?;
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(body.len().to_string());
+                Ok(res)
+            },
+            opts(),
+        );
+        let body = "x".repeat(100_000);
+        server
+            .write_all(
+                format!(
+                    "POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert_eq!(res.body_string().await?, "100000");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn hands_over_frames() -> Result<()> {
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                let mut frames = req.ext_mut().remove::<BodyFrames>().unwrap();
+                let mut len = 0;
+                while let Some(frame) = frames.next().await? {
+                    assert!(frame.len() <= 8 * 1024);
+                    len += frame.len();
+                }
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(len.to_string());
+                Ok(res)
+            },
+            opts(),
+        );
+        let body = "x".repeat(100_000);
+        server
+            .write_all(
+                format!(
+                    "POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert_eq!(res.body_string().await?, "100000");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn streams_body_into_response() -> Result<()> {
+        let mut server = TestServer::new_with_opts(
+            |req: Request| async move {
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(Body::from_reader(req, None));
+                Ok(res)
+            },
+            opts(),
+        );
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert_eq!(res.body_string().await?, "hello");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn discards_unread_body() -> Result<()> {
+        let mut server =
+            TestServer::new_with_opts(|_| async { Ok(Response::new(StatusCode::Ok)) }, opts());
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        server.close();
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+        assert!(server.all_read());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pauses_and_resumes() -> Result<()> {
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                let flow = req.ext().get::<BodyFlow>().unwrap().clone();
+                flow.pause();
+                assert!(flow.is_paused());
+
+                // Nothing is read while paused.
+                let mut buf = [0; 5];
+                let read = futures_lite::future::poll_once(req.read(&mut buf)).await;
+                assert!(read.is_none());
+
+                flow.resume();
+                let body = req.body_string().await?;
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(body);
+                Ok(res)
+            },
+            opts(),
+        );
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert_eq!(res.body_string().await?, "hello");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn limits_queued_frames() -> Result<()> {
+        for (frames, fails) in [(1, false), (4, true)] {
+            let opts = ServerOptions::new()
+                .with_body_channel(frames)
+                .with_max_connection_memory(10_000);
+            let mut server = TestServer::new_with_opts(
+                |mut req: Request| async move {
+                    req.body_bytes().await?;
+                    Ok(Response::new(StatusCode::Ok))
+                },
+                opts,
+            );
+            server
+                .write_all(
+                    b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 100000\r\n\r\n",
+                )
+                .await?;
+            server.write_all(&[b'x'; 100_000]).await?;
+
+            let res = server.accept_one().await;
+            match res {
+                Err(err) => {
+                    assert!(fails);
+                    assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+                    let res = async_h1::client::decode(server).await?;
+                    assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+                }
+                Ok(status) => {
+                    assert!(!fails);
+                    assert_eq!(status, ConnectionStatus::KeepAlive);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "chunked")]
+
+mod test_utils;
+mod body_writer {
+    use super::test_utils::TestIO;
+    use async_h1::server::BodyWriter;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use async_std::task;
+    use http_types::{Request, Response, Result, StatusCode};
+
+    const REQUEST: &[u8] = b"GET /events HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+    /// Read from `client` until what's been read ends with `suffix`.
+    async fn read_until(client: &mut TestIO, read: &mut String, suffix: &str) -> Result<()> {
+        while !read.ends_with(suffix) {
+            let mut buf = [0; 1024];
+            let bytes = client.read(&mut buf).await?;
+            assert!(bytes > 0, "connection closed after {:?}", read);
+            read.push_str(std::str::from_utf8(&buf[..bytes])?);
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn each_flush_is_sent_as_a_chunk() -> Result<()> {
+        let (next_sender, next) = async_channel::bounded::<()>(1);
+        let (mut client, server) = TestIO::new();
+        client.write_all(REQUEST).await?;
+
+        let endpoint = move |_req: Request| {
+            let next = next.clone();
+            async move {
+                let (mut writer, body) = BodyWriter::new();
+                task::spawn(async move {
+                    for event in ["data: 1\n\n", "data: 2\n\n"] {
+                        // Written in pieces, but sent as one chunk.
+                        let (start, end) = event.split_at(4);
+                        writer.write_all(start.as_bytes()).await?;
+                        writer.write_all(end.as_bytes()).await?;
+                        writer.flush().await?;
+                        next.recv().await.ok();
+                    }
+                    std::io::Result::Ok(())
+                });
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(body);
+                Ok(res)
+            }
+        };
+        let served = task::spawn(async_h1::server::accept(server, endpoint));
+
+        // Each event arrives while the writer waits to write the next one.
+        let mut read = String::new();
+        read_until(&mut client, &mut read, "9\r\ndata: 1\n\n\r\n").await?;
+        next_sender.send(()).await?;
+        read_until(&mut client, &mut read, "9\r\ndata: 2\n\n\r\n").await?;
+        next_sender.send(()).await?;
+        read_until(&mut client, &mut read, "0\r\n\r\n").await?;
+        assert!(read.contains("transfer-encoding: chunked\r\n"));
+
+        client.close();
+        served.await?;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn writes_fail_once_the_body_is_dropped() -> Result<()> {
+        let (mut writer, body) = BodyWriter::new();
+        drop(body);
+        assert!(writer.write_all(b"data: 1\n\n").await.is_err());
+        Ok(())
+    }
+}
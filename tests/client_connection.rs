@@ -0,0 +1,134 @@
+mod test_utils;
+mod client_connection {
+    use super::test_utils::TestIO;
+    use async_h1::client::Connection;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Method, Request, Result, Url};
+
+    fn get(path: &str) -> Result<Request> {
+        let url = Url::parse("http://example.com/")?.join(path)?;
+        Ok(Request::new(Method::Get, url))
+    }
+
+    /// Read one chunk of what the client wrote.
+    async fn read_request(server: &mut TestIO) -> Result<String> {
+        let mut buf = [0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        Ok(String::from_utf8(buf[..bytes].to_vec())?)
+    }
+
+    #[async_std::test]
+    async fn unread_bodies_are_discarded_before_the_next_request() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        let mut connection = Connection::new(client);
+
+        // Both responses arrive at once, so the second is buffered while
+        // the first is read.
+        server
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nfirst\
+                  HTTP/1.1 200 OK\r\ncontent-length: 6\r\n\r\nsecond",
+            )
+            .await?;
+
+        let first = connection.send(get("/first")?).await?;
+        assert!(read_request(&mut server)
+            .await?
+            .starts_with("GET /first HTTP/1.1\r\n"));
+        drop(first);
+
+        let mut second = connection.send(get("/second")?).await?;
+        assert!(read_request(&mut server)
+            .await?
+            .starts_with("GET /second HTTP/1.1\r\n"));
+        assert_eq!(second.body_string().await?, "second");
+        assert!(connection.is_reusable());
+        Ok(())
+    }
+
+    #[cfg(feature = "chunked")]
+    #[async_std::test]
+    async fn bodies_can_be_read_before_the_next_request() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        let mut connection = Connection::new(client);
+
+        server
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n5\r\nfirst\r\n0\r\n\r\n",
+            )
+            .await?;
+        let mut res = connection.send(get("/")?).await?;
+        assert_eq!(res.body_string().await?, "first");
+
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 6\r\n\r\nsecond")
+            .await?;
+        let mut res = connection.send(get("/")?).await?;
+        assert_eq!(res.body_string().await?, "second");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn head_responses_have_no_body_to_discard() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        let mut connection = Connection::new(client);
+
+        server
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\n\
+                  HTTP/1.1 204 No Content\r\n\r\n",
+            )
+            .await?;
+        let url = Url::parse("http://example.com/")?;
+        connection.send(Request::new(Method::Head, url)).await?;
+        let res = connection.send(get("/")?).await?;
+        assert_eq!(res.status(), 204);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn closed_connections_are_not_reused() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        let mut connection = Connection::new(client);
+
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 0\r\n\r\n")
+            .await?;
+        connection.send(get("/")?).await?;
+        assert!(!connection.is_reusable());
+        assert!(connection.send(get("/")?).await.is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn bodies_ending_with_the_connection_close_it() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        let mut connection = Connection::new(client);
+
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\n\r\nuntil closed")
+            .await?;
+        let mut res = connection.send(get("/")?).await?;
+        assert!(!connection.is_reusable());
+        server.close();
+        assert_eq!(res.body_string().await?, "until closed");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn responses_read_too_late_fail() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        let mut connection = Connection::new(client);
+
+        server
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nfirst\
+                  HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+            )
+            .await?;
+        let mut first = connection.send(get("/")?).await?;
+        connection.send(get("/")?).await?;
+        assert!(first.body_string().await.is_err());
+        Ok(())
+    }
+}
@@ -3,12 +3,15 @@ mod test_utils;
 mod client_decode {
     use std::io::Write;
 
-    use super::test_utils::CloseableCursor;
-    use async_h1::client;
+    use super::test_utils::{CloseableCursor, TestIO};
+    use async_h1::client::{self, ClientOptions, RawHeaders};
+    use async_h1::error::ErrorKind;
+    use async_h1::Profile;
     use async_std::io::Cursor;
+    use futures_lite::AsyncWriteExt;
     use http_types::headers;
-    use http_types::Response;
     use http_types::Result;
+    use http_types::{Method, Request, Response, Url};
     use pretty_assertions::assert_eq;
 
     async fn decode_lines(s: Vec<&str>) -> Result<Response> {
@@ -59,11 +62,31 @@ mod client_decode {
 
         let cursor = CloseableCursor::default();
         cursor.close();
-        assert_eq!(
-            client::decode(cursor).await.unwrap_err().to_string(),
-            "connection closed"
-        );
+        let err = client::decode(cursor).await.unwrap_err();
+        assert_eq!(err.to_string(), "connection closed");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::ConnectionClosed);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn bodies_without_framing_end_with_the_connection() -> Result<()> {
+        let mut res = decode_lines(vec!["HTTP/1.1 200 OK", "", "until the end"]).await?;
+        assert_eq!(res.len(), None);
+        assert_eq!(res.body_string().await?, "until the end");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn bodiless_responses_dont_wait_for_the_connection_to_close() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        server.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await?;
+        let req = Request::new(Method::Head, Url::parse("http://example.com")?);
+        let mut res = async_h1::connect(client, req).await?;
+        assert_eq!(res.body_string().await?, "");
 
+        let mut res = decode_lines(vec!["HTTP/1.1 304 Not Modified", "", ""]).await?;
+        assert_eq!(res.body_string().await?, "");
         Ok(())
     }
 
@@ -90,4 +113,143 @@ mod client_decode {
 
         Ok(())
     }
+
+    async fn decode_with_opts(head: &[u8], opts: ClientOptions) -> Result<Response> {
+        let (client, mut server) = TestIO::new();
+        server.write_all(head).await?;
+        let req = Request::new(Method::Get, Url::parse("http://example.com")?);
+        async_h1::connect_with_opts(client, req, opts).await
+    }
+
+    async fn decode_strict(head: &[u8]) -> Result<Response> {
+        decode_with_opts(head, ClientOptions::new().with_strict_utf8(true)).await
+    }
+
+    #[async_std::test]
+    async fn strict_profile_limits_headers() -> Result<()> {
+        let mut head = String::from("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n");
+        for i in 0..100 {
+            head.push_str(&format!("x-header-{}: {}\r\n", i, i));
+        }
+        head.push_str("\r\n");
+
+        let res = decode_with_opts(head.as_bytes(), ClientOptions::new()).await?;
+        assert_eq!(res.status(), 200);
+
+        let opts = ClientOptions::new().with_profile(Profile::Strict);
+        let err = decode_with_opts(head.as_bytes(), opts).await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strict_profile_checks_utf8() {
+        let head = b"HTTP/1.1 200 OK\r\nx-raw: \xff\r\ncontent-length: 0\r\n\r\n";
+        let opts = ClientOptions::new().with_profile(Profile::Strict);
+        let err = decode_with_opts(head, opts).await.unwrap_err();
+        assert_eq!(err.to_string(), "Header value isn't valid UTF-8");
+
+        let opts = ClientOptions::new().with_profile(Profile::Lenient);
+        assert!(decode_with_opts(head, opts).await.is_ok());
+    }
+
+    #[async_std::test]
+    async fn non_ascii_header_values_are_opaque() -> Result<()> {
+        let res = client::decode(Cursor::new(
+            b"HTTP/1.1 200 OK\r\nx-name: caf\xc3\xa9\r\nx-raw: \xff!\r\ncontent-length: 0\r\n\r\n"
+                .to_vec(),
+        ))
+        .await?;
+        assert_eq!(res["x-name"], "caf??");
+        assert_eq!(res["x-raw"], "?!");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strict_utf8_accepts_valid_values() -> Result<()> {
+        let res = decode_strict(
+            "HTTP/1.1 200 Très bien\r\nx-name: café\r\ncontent-length: 0\r\n\r\n".as_bytes(),
+        )
+        .await?;
+        assert_eq!(res["x-name"], "caf?");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn strict_utf8_rejects_invalid_header_values() {
+        let err = decode_strict(b"HTTP/1.1 200 OK\r\nx-raw: \xff\r\ncontent-length: 0\r\n\r\n")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Header value isn't valid UTF-8");
+    }
+
+    #[async_std::test]
+    async fn strict_utf8_rejects_invalid_reason_phrases() {
+        let err = decode_strict(b"HTTP/1.1 200 \xffK\r\ncontent-length: 0\r\n\r\n")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Reason phrase isn't valid UTF-8");
+    }
+
+    #[async_std::test]
+    async fn folded_header_values_are_unfolded() -> Result<()> {
+        let res = decode_lines(vec![
+            "HTTP/1.1 200 OK",
+            "x-folded: first",
+            "  second",
+            "\tthird",
+            "content-length: 0",
+            "",
+            "",
+        ])
+        .await?;
+        assert_eq!(res["x-folded"], "first second third");
+        assert_eq!(res["content-length"], "0");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn raw_headers_keep_wire_form() -> Result<()> {
+        let res = client::decode(Cursor::new(
+            b"HTTP/1.1 200 OK\r\nX-Trace: a\r\nContent-Length: 0\r\nx-trace: \xffb\r\n\r\n"
+                .to_vec(),
+        ))
+        .await?;
+        let raw = res.ext().get::<RawHeaders>().unwrap();
+        let fields: Vec<_> = raw.iter().collect();
+        assert_eq!(
+            fields,
+            [
+                ("X-Trace", &b"a"[..]),
+                ("Content-Length", b"0"),
+                ("x-trace", b"\xffb")
+            ]
+        );
+        assert_eq!(raw.get_all("x-TRACE").count(), 2);
+        Ok(())
+    }
+
+    #[cfg(feature = "chunked")]
+    #[async_std::test]
+    async fn trailers_are_received_after_the_body() -> Result<()> {
+        let mut res = decode_lines(vec![
+            "HTTP/1.1 200 OK",
+            "transfer-encoding: chunked",
+            "trailer: x-checksum",
+            "",
+            "5",
+            "hello",
+            "0",
+            "x-checksum: 5d41402a",
+            "",
+            "",
+        ])
+        .await?;
+
+        let trailers = res.recv_trailers();
+        assert_eq!(res.body_string().await?, "hello");
+        let trailers = trailers.await.unwrap();
+        assert_eq!(trailers["x-checksum"], "5d41402a");
+        Ok(())
+    }
 }
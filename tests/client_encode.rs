@@ -1,5 +1,6 @@
 mod client_encode {
     use async_h1::client;
+    use async_h1::error::ErrorKind;
     use async_std::io::Cursor;
     use async_std::prelude::*;
     use client::Encoder;
@@ -65,6 +66,15 @@ mod client_encode {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn client_encode_connect_without_host() -> Result<()> {
+        let url = Url::parse("file:///tmp/socket").unwrap();
+        let req = Request::new(Method::Connect, url);
+        let err = encode_to_string(req, 100).await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Encode);
+        Ok(())
+    }
+
     // The fragment of an URL is not send to the server, see RFC7230 and RFC3986.
     #[async_std::test]
     async fn client_encode_request_with_fragment() -> Result<()> {
@@ -0,0 +1,238 @@
+mod test_utils;
+
+use async_h1::client::{ClientOptions, Pool, PoolKey};
+use async_std::io::prelude::WriteExt;
+use http_types::{Method, Request, Url};
+use std::cell::Cell;
+use std::time::Duration;
+use test_utils::TestIO;
+
+fn key() -> PoolKey {
+    PoolKey::from_url(&Url::parse("http://example.com/path").unwrap()).unwrap()
+}
+
+#[test]
+fn key_from_url() {
+    let key = key();
+    assert_eq!(key.scheme(), "http");
+    assert_eq!(key.host(), "example.com");
+    assert_eq!(key.port(), 80);
+}
+
+#[test]
+fn reuses_most_recent_connection() {
+    let pool = Pool::new();
+    pool.put(key(), 1);
+    pool.put(key(), 2);
+    assert_eq!(pool.get(&key()), Some(2));
+    assert_eq!(pool.get(&key()), Some(1));
+    assert_eq!(pool.get(&key()), None);
+}
+
+#[test]
+fn max_idle_per_host_drops_oldest() {
+    let pool = Pool::new().with_max_idle_per_host(2);
+    for conn in 1..=3 {
+        pool.put(key(), conn);
+    }
+    pool.put(PoolKey::new("http", "other.com", 80), 4);
+    assert_eq!(pool.idle(&key()), 2);
+    assert_eq!(pool.get(&key()), Some(3));
+    assert_eq!(pool.get(&key()), Some(2));
+    assert_eq!(pool.get(&key()), None);
+}
+
+#[test]
+fn expired_connections_are_reaped() {
+    let pool = Pool::new().with_idle_timeout(Duration::from_millis(10));
+    pool.put(key(), 1);
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(pool.idle(&key()), 1);
+    pool.reap();
+    assert_eq!(pool.idle(&key()), 0);
+
+    pool.put(key(), 2);
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(pool.get(&key()), None);
+}
+
+#[async_std::test]
+async fn reaper_runs_in_background() {
+    let pool = Pool::new().with_idle_timeout(Duration::from_millis(10));
+    async_std::task::spawn(pool.reaper(Duration::from_millis(5)));
+    pool.put(key(), 1);
+    async_std::task::sleep(Duration::from_millis(50)).await;
+    assert_eq!(pool.idle(&key()), 0);
+}
+
+#[async_std::test]
+async fn checkout_hands_out_idle_connections() -> http_types::Result<()> {
+    let pool = Pool::new();
+    let mut slot = pool.checkout(&key()).await?;
+    assert_eq!(slot.take(), None);
+    assert_eq!(pool.active(&key()), 1);
+    slot.put(1);
+    assert_eq!(pool.active(&key()), 0);
+
+    let mut slot = pool.checkout(&key()).await?;
+    assert_eq!(slot.take(), Some(1));
+    Ok(())
+}
+
+#[async_std::test]
+async fn checkout_waits_in_line() -> http_types::Result<()> {
+    let pool = Pool::new().with_max_connections_per_host(1);
+    let first = pool.checkout(&key()).await?;
+
+    let (sender, receiver) = async_channel::unbounded();
+    for n in 0..2 {
+        let (task_pool, sender) = (pool.clone(), sender.clone());
+        async_std::task::spawn(async move {
+            let mut slot = task_pool.checkout(&key()).await.unwrap();
+            sender.send((n, slot.take())).await.unwrap();
+            slot.put(n + 10);
+        });
+        // Make sure the first task is in line before the second.
+        while pool.waiting(&key()) <= n {
+            async_std::task::yield_now().await;
+        }
+    }
+
+    first.put(5);
+    assert_eq!(receiver.recv().await?, (0, Some(5)));
+    assert_eq!(receiver.recv().await?, (1, Some(10)));
+    Ok(())
+}
+
+#[async_std::test]
+async fn checkout_times_out() -> http_types::Result<()> {
+    let pool = Pool::<usize>::new()
+        .with_max_connections_per_host(1)
+        .with_checkout_timeout(Some(Duration::from_millis(10)));
+    let first = pool.checkout(&key()).await?;
+    let err = pool.checkout(&key()).await.unwrap_err();
+    assert_eq!(
+        async_h1::error::ErrorKind::of(&err),
+        async_h1::error::ErrorKind::Timeout
+    );
+    let phase = async_h1::error::TimeoutPhase::of(&err).unwrap();
+    assert!(phase.is_retry_safe());
+
+    // Giving up leaves the line, so the next checkout isn't stuck behind it.
+    drop(first);
+    pool.checkout(&key()).await?;
+    Ok(())
+}
+
+#[test]
+fn put_after_honors_connection_hints() {
+    use http_types::{Response, Version};
+
+    let pool = Pool::new();
+    let mut res = Response::new(200);
+    res.insert_header("connection", "close");
+    pool.put_after(key(), 1, &res);
+    assert_eq!(pool.idle(&key()), 0);
+
+    let mut res = Response::new(200);
+    res.insert_header("keep-alive", "timeout=5, max=0");
+    pool.put_after(key(), 2, &res);
+    assert_eq!(pool.idle(&key()), 0);
+
+    let mut res = Response::new(200);
+    res.set_version(Some(Version::Http1_0));
+    pool.put_after(key(), 3, &res);
+    assert_eq!(pool.idle(&key()), 0);
+    res.insert_header("connection", "Keep-Alive");
+    pool.put_after(key(), 4, &res);
+    assert_eq!(pool.get(&key()), Some(4));
+
+    let mut res = Response::new(200);
+    res.insert_header("keep-alive", "timeout=0");
+    pool.put_after(key(), 5, &res);
+    assert_eq!(pool.get(&key()), None);
+}
+
+#[async_std::test]
+async fn slot_put_after_counts_closed_connections() -> http_types::Result<()> {
+    use http_types::Response;
+
+    let pool = Pool::new();
+    let slot = pool.checkout(&key()).await?;
+    let mut res = Response::new(200);
+    res.insert_header("connection", "close");
+    slot.put_after(1, &res);
+    assert_eq!(pool.active(&key()), 0);
+    assert_eq!(pool.idle(&key()), 0);
+
+    let slot = pool.checkout(&key()).await?;
+    slot.put_after(2, &Response::new(200));
+    assert_eq!(pool.idle(&key()), 1);
+    Ok(())
+}
+
+/// A pool holding a connection the server has closed, and a fresh one
+/// which answers with an empty response.
+async fn stale_and_fresh() -> http_types::Result<(Pool<TestIO>, TestIO)> {
+    let pool = Pool::new();
+    let (stale, mut server) = TestIO::new();
+    server.close();
+    pool.put(key(), stale);
+
+    let (fresh, mut server) = TestIO::new();
+    server
+        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+        .await?;
+    Ok((pool, fresh))
+}
+
+fn request(method: Method) -> Request {
+    Request::new(method, Url::parse("http://example.com/path").unwrap())
+}
+
+#[async_std::test]
+async fn idempotent_requests_are_resent_over_stale_connections() -> http_types::Result<()> {
+    let (pool, fresh) = stale_and_fresh().await?;
+    let connects = Cell::new(0);
+    let mut slot = pool.checkout(&key()).await?;
+    let res = slot
+        .send(request(Method::Get), ClientOptions::new(), || {
+            connects.set(connects.get() + 1);
+            let fresh = fresh.clone();
+            async { Ok(fresh) }
+        })
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(connects.get(), 1);
+
+    let io = slot.take().unwrap();
+    slot.put_after(io, &res);
+    assert_eq!(pool.idle(&key()), 1);
+    Ok(())
+}
+
+#[async_std::test]
+async fn other_requests_are_not_resent() -> http_types::Result<()> {
+    let mut with_body = request(Method::Put);
+    with_body.set_body("data");
+    let cases = vec![
+        (request(Method::Post), ClientOptions::new()),
+        (with_body, ClientOptions::new()),
+        (
+            request(Method::Get),
+            ClientOptions::new().with_stale_retry(false),
+        ),
+    ];
+    for (req, opts) in cases {
+        let (pool, fresh) = stale_and_fresh().await?;
+        let mut slot = pool.checkout(&key()).await?;
+        let res = slot
+            .send(req, opts, || {
+                let fresh = fresh.clone();
+                async { Ok(fresh) }
+            })
+            .await;
+        assert!(res.is_err());
+    }
+    Ok(())
+}
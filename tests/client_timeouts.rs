@@ -0,0 +1,114 @@
+mod test_utils;
+mod client_timeouts {
+    use super::test_utils::TestIO;
+    use async_h1::client::{self, ClientOptions, Connection};
+    use async_h1::error::TimeoutPhase;
+    use async_std::io::prelude::WriteExt;
+    use futures_lite::io::{AsyncRead, AsyncWrite};
+    use http_types::{Method, Request, Result, Url};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn get() -> Result<Request> {
+        Ok(Request::new(
+            Method::Get,
+            Url::parse("http://example.com/")?,
+        ))
+    }
+
+    /// A server which never takes what's written to it.
+    #[derive(Debug, Clone)]
+    struct Stalled;
+
+    impl AsyncRead for Stalled {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for Stalled {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Pending
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Pending
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[async_std::test]
+    async fn stalled_writes_time_out() -> Result<()> {
+        let opts = ClientOptions::new().with_write_timeout(Some(TIMEOUT));
+        let err = client::connect_with_opts(Stalled, get()?, opts)
+            .await
+            .unwrap_err();
+        assert_eq!(TimeoutPhase::of(&err), Some(TimeoutPhase::Write));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn slow_servers_time_out_waiting_for_the_head() -> Result<()> {
+        let (client, _server) = TestIO::new();
+        let opts = ClientOptions::new()
+            .with_head_timeout(Some(TIMEOUT))
+            .with_body_timeout(Some(TIMEOUT * 10));
+        let err = client::connect_with_opts(client, get()?, opts)
+            .await
+            .unwrap_err();
+        assert_eq!(TimeoutPhase::of(&err), Some(TimeoutPhase::Head));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn stalled_bodies_time_out() -> Result<()> {
+        let (client, mut server) = TestIO::new();
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\nhello")
+            .await?;
+        let opts = ClientOptions::new()
+            .with_head_timeout(Some(TIMEOUT * 10))
+            .with_body_timeout(Some(TIMEOUT));
+        let mut res = client::connect_with_opts(client, get()?, opts).await?;
+        let err = res.body_string().await.unwrap_err();
+        assert_eq!(TimeoutPhase::of(&err), Some(TimeoutPhase::Body));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn connections_apply_the_timeouts() -> Result<()> {
+        let (client, _server) = TestIO::new();
+        let opts = ClientOptions::new()
+            .with_head_timeout(Some(TIMEOUT))
+            .with_body_timeout(Some(TIMEOUT));
+        let mut connection = Connection::with_opts(client, opts);
+
+        let err = connection.send(get()?).await.unwrap_err();
+        assert_eq!(TimeoutPhase::of(&err), Some(TimeoutPhase::Head));
+
+        let (client, mut server) = TestIO::new();
+        let opts = ClientOptions::new().with_body_timeout(Some(TIMEOUT));
+        connection = Connection::with_opts(client, opts);
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\nhello")
+            .await?;
+        let mut res = connection.send(get()?).await?;
+        let err = res.body_string().await.unwrap_err();
+        assert_eq!(TimeoutPhase::of(&err), Some(TimeoutPhase::Body));
+        Ok(())
+    }
+}
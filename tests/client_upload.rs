@@ -0,0 +1,60 @@
+#![cfg(feature = "chunked")]
+
+mod test_utils;
+mod client_upload {
+    use super::test_utils::TestIO;
+    use async_h1::server::BodyWriter;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use async_std::task;
+    use http_types::{Method, Request, Result, StatusCode, Url};
+
+    /// Read from `server` until what's been read ends with `suffix`.
+    async fn read_until(server: &mut TestIO, read: &mut String, suffix: &str) -> Result<()> {
+        while !read.ends_with(suffix) {
+            let mut buf = [0; 1024];
+            let bytes = server.read(&mut buf).await?;
+            assert!(bytes > 0, "connection closed after {:?}", read);
+            read.push_str(std::str::from_utf8(&buf[..bytes])?);
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn bodies_of_unknown_length_are_streamed() -> Result<()> {
+        let (next_sender, next) = async_channel::bounded::<()>(1);
+        let (client, mut server) = TestIO::new();
+
+        let (mut writer, body) = BodyWriter::new();
+        let mut req = Request::new(Method::Post, Url::parse("http://example.com/upload")?);
+        req.set_body(body);
+        let exchange = task::spawn(async_h1::connect(client, req));
+
+        let upload = task::spawn(async move {
+            for piece in ["hello", " world"] {
+                writer.write_all(piece.as_bytes()).await?;
+                writer.flush().await?;
+                next.recv().await.ok();
+            }
+            // Dropping the writer ends the body.
+            drop(writer);
+            Ok::<_, std::io::Error>(())
+        });
+
+        // Each piece reaches the server before the next one is written.
+        let mut read = String::new();
+        read_until(&mut server, &mut read, "\r\n\r\n5\r\nhello\r\n").await?;
+        assert!(read.contains("transfer-encoding: chunked\r\n"));
+        next_sender.send(()).await?;
+        read_until(&mut server, &mut read, "6\r\n world\r\n").await?;
+        next_sender.send(()).await?;
+        read_until(&mut server, &mut read, "0\r\n\r\n").await?;
+        upload.await?;
+
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .await?;
+        let res = exchange.await?;
+        assert_eq!(res.status(), StatusCode::Ok);
+        Ok(())
+    }
+}
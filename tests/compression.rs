@@ -0,0 +1,248 @@
+#![cfg(feature = "compression")]
+
+mod test_utils;
+mod compression {
+    use super::test_utils::TestServer;
+    use async_h1::compression::{Coder, Compression};
+    use async_h1::server::{ConnectionStatus, ServerOptions};
+    use async_std::io::{self, BufReader, Read};
+    use async_std::task::{Context, Poll};
+    use http_types::headers::{CONTENT_ENCODING, TRANSFER_ENCODING, VARY};
+    use http_types::{Body, Request, Response, Result};
+    use std::pin::Pin;
+
+    /// A toy coding which flips every bit, so it is its own inverse.
+    #[derive(Debug)]
+    struct Flip;
+
+    struct FlipReader(Body);
+
+    impl Read for FlipReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let bytes = futures_core::ready!(Pin::new(&mut self.0).poll_read(cx, buf))?;
+            buf[..bytes].iter_mut().for_each(|byte| *byte = !*byte);
+            Poll::Ready(Ok(bytes))
+        }
+    }
+
+    impl Coder for Flip {
+        fn name(&self) -> &'static str {
+            "x-flip"
+        }
+
+        fn encode(&self, body: Body) -> Body {
+            Body::from_reader(BufReader::new(FlipReader(body)), None)
+        }
+
+        fn decode(&self, body: Body) -> Body {
+            self.encode(body)
+        }
+    }
+
+    /// A toy coding which leaves bodies as they are.
+    #[derive(Debug)]
+    struct Same;
+
+    impl Coder for Same {
+        fn name(&self) -> &'static str {
+            "x-same"
+        }
+
+        fn encode(&self, body: Body) -> Body {
+            body
+        }
+
+        fn decode(&self, body: Body) -> Body {
+            body
+        }
+    }
+
+    /// A toy coding whose bodies all decode to 100 bytes.
+    #[derive(Debug)]
+    struct Bomb;
+
+    impl Coder for Bomb {
+        fn name(&self) -> &'static str {
+            "x-bomb"
+        }
+
+        fn encode(&self, _: Body) -> Body {
+            Body::empty()
+        }
+
+        fn decode(&self, _: Body) -> Body {
+            Body::from_reader(BufReader::new(io::Cursor::new(vec![b'a'; 100])), None)
+        }
+    }
+
+    fn flip(s: &str) -> Vec<u8> {
+        s.bytes().map(|byte| !byte).collect()
+    }
+
+    #[async_std::test]
+    async fn round_trip() -> Result<()> {
+        let opts = ServerOptions::new().with_compression(Compression::new().with_coder(Flip));
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                assert!(req.header(CONTENT_ENCODING).is_none());
+                let mut res = Response::new(200);
+                res.set_body(req.body_string().await?);
+                Ok(res)
+            },
+            opts,
+        );
+
+        let mut req = Request::post("http://example.com/");
+        req.insert_header(CONTENT_ENCODING, "x-flip");
+        req.insert_header("accept-encoding", "gzip;q=0.5, x-flip");
+        req.set_body(flip("hello"));
+        server.write_request(req).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert_eq!(res[CONTENT_ENCODING], "x-flip");
+        assert_eq!(res[TRANSFER_ENCODING], "chunked");
+        assert_eq!(res[VARY], "accept-encoding");
+
+        Compression::new()
+            .with_coder(Flip)
+            .decode_response(&mut res);
+        assert!(res.header(CONTENT_ENCODING).is_none());
+        assert_eq!(res.body_string().await?, "hello");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn refused_coding_is_not_applied() -> Result<()> {
+        let opts = ServerOptions::new().with_compression(Compression::new().with_coder(Flip));
+        let mut server = TestServer::new_with_opts(
+            |_| async {
+                let mut res = Response::new(200);
+                res.set_body("hello");
+                Ok(res)
+            },
+            opts,
+        );
+
+        let mut req = Request::get("http://example.com/");
+        req.insert_header("accept-encoding", "x-flip;q=0");
+        server.write_request(req).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert!(res.header(CONTENT_ENCODING).is_none());
+        assert_eq!(res.body_string().await?, "hello");
+
+        Ok(())
+    }
+
+    /// The `Content-Encoding` of the response to a GET request with this
+    /// `Accept-Encoding`, from an endpoint responding with `res()`.
+    async fn negotiate(accept_encoding: &str, res: fn() -> Response) -> Result<Option<String>> {
+        let compression = Compression::new().with_coder(Flip).with_coder(Same);
+        let opts = ServerOptions::new().with_compression(compression);
+        let mut server = TestServer::new_with_opts(move |_| async move { Ok(res()) }, opts);
+
+        let mut req = Request::get("http://example.com/");
+        req.insert_header("accept-encoding", accept_encoding);
+        server.write_request(req).await?;
+        server.accept_one().await?;
+        let res = async_h1::client::decode(server).await?;
+        Ok(res
+            .header(CONTENT_ENCODING)
+            .map(|value| value.as_str().to_owned()))
+    }
+
+    fn hello() -> Response {
+        let mut res = Response::new(200);
+        res.set_body("hello");
+        res
+    }
+
+    #[async_std::test]
+    async fn codings_are_chosen_by_quality() -> Result<()> {
+        let chosen = negotiate("x-flip;q=0.5, x-same", hello).await?;
+        assert_eq!(chosen.as_deref(), Some("x-same"));
+
+        // Ties go to the coder registered first.
+        let chosen = negotiate("x-same, x-flip", hello).await?;
+        assert_eq!(chosen.as_deref(), Some("x-flip"));
+
+        let chosen = negotiate("x-flip;q=0, *;q=0.1", hello).await?;
+        assert_eq!(chosen.as_deref(), Some("x-same"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn partial_and_no_transform_responses_are_not_encoded() -> Result<()> {
+        let partial = || {
+            let mut res = Response::new(206);
+            res.insert_header("content-range", "bytes 0-4/10");
+            res.set_body("hello");
+            res
+        };
+        assert_eq!(negotiate("x-flip", partial).await?, None);
+
+        let no_transform = || {
+            let mut res = hello();
+            res.insert_header("cache-control", "public, no-transform");
+            res
+        };
+        assert_eq!(negotiate("x-flip", no_transform).await?, None);
+        Ok(())
+    }
+
+    /// The response to a POST request with a body in this coding, from an
+    /// endpoint echoing the body.
+    async fn post_encoded(compression: Compression, coding: &str) -> Result<Response> {
+        let opts = ServerOptions::new()
+            .with_compression(compression)
+            .with_max_body_size(10);
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                let mut res = Response::new(200);
+                res.set_body(req.body_bytes().await?);
+                Ok(res)
+            },
+            opts,
+        );
+
+        let mut req = Request::post("http://example.com/");
+        req.insert_header(CONTENT_ENCODING, coding);
+        req.set_body("hello");
+        server.write_request(req).await?;
+        server.accept_one().await.ok();
+        async_h1::client::decode(server).await
+    }
+
+    #[async_std::test]
+    async fn unknown_request_codings_get_415() -> Result<()> {
+        let res = post_encoded(Compression::new().with_coder(Flip), "gzip").await?;
+        assert_eq!(res.status(), 415);
+        assert_eq!(res["accept-encoding"], "x-flip");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn request_decoding_can_be_disabled() -> Result<()> {
+        let compression = Compression::new()
+            .with_coder(Flip)
+            .with_request_decoding(false);
+        let mut res = post_encoded(compression, "x-flip").await?;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body_string().await?, "hello");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn decoded_bodies_over_the_maximum_get_413() -> Result<()> {
+        let res = post_encoded(Compression::new().with_coder(Bomb), "x-bomb").await?;
+        assert_eq!(res.status(), 413);
+        Ok(())
+    }
+}
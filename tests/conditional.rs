@@ -0,0 +1,80 @@
+use async_h1::conditional::{evaluate, is_not_modified};
+use async_h1::server::Encoder;
+use async_std::io::ReadExt;
+use http_types::{Method, Request, Result, StatusCode, Url};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn request(method: Method, headers: &[(&str, &str)]) -> Request {
+    let mut req = Request::new(method, Url::parse("http://example.com/").unwrap());
+    for (name, value) in headers {
+        req.insert_header(*name, *value);
+    }
+    req
+}
+
+/// Fri, 15 May 2015 15:34:21 GMT
+fn last_modified() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1431704061)
+}
+
+#[test]
+fn if_none_match() {
+    let req = request(Method::Get, &[("if-none-match", "\"a\", W/\"b\"")]);
+    assert!(is_not_modified(&req, Some("\"b\""), None));
+    assert!(is_not_modified(&req, Some("W/\"a\""), None));
+    assert!(!is_not_modified(&req, Some("\"c\""), None));
+    assert!(!is_not_modified(&req, None, None));
+
+    let req = request(Method::Get, &[("if-none-match", "*")]);
+    assert!(is_not_modified(&req, Some("\"c\""), None));
+}
+
+#[test]
+fn if_modified_since() {
+    let since = "Fri, 15 May 2015 15:34:21 GMT";
+    let req = request(Method::Get, &[("if-modified-since", since)]);
+    let sub_second = last_modified() + Duration::from_millis(500);
+    assert!(is_not_modified(&req, None, Some(sub_second)));
+    let later = last_modified() + Duration::from_secs(1);
+    assert!(!is_not_modified(&req, None, Some(later)));
+
+    let req = request(Method::Get, &[("if-modified-since", "yesterday")]);
+    assert!(!is_not_modified(&req, None, Some(last_modified())));
+}
+
+#[test]
+fn if_none_match_takes_precedence() {
+    let req = request(
+        Method::Get,
+        &[
+            ("if-none-match", "\"a\""),
+            ("if-modified-since", "Fri, 15 May 2015 15:34:21 GMT"),
+        ],
+    );
+    assert!(!is_not_modified(&req, Some("\"b\""), Some(last_modified())));
+}
+
+#[test]
+fn only_safe_methods() {
+    let req = request(Method::Post, &[("if-none-match", "*")]);
+    assert!(!is_not_modified(&req, Some("\"a\""), None));
+}
+
+#[async_std::test]
+async fn not_modified_response_is_framed_without_body() -> Result<()> {
+    let req = request(Method::Get, &[("if-none-match", "\"a\"")]);
+    let res = evaluate(&req, Some("\"a\""), Some(last_modified())).unwrap();
+    assert_eq!(res.status(), StatusCode::NotModified);
+
+    let mut encoded = String::new();
+    Encoder::new(res, Method::Get)
+        .read_to_string(&mut encoded)
+        .await?;
+    assert!(encoded.starts_with("HTTP/1.1 304 "));
+    assert!(encoded.contains("etag: \"a\"\r\n"));
+    assert!(encoded.contains("last-modified: Fri, 15 May 2015 15:34:21 GMT\r\n"));
+    assert!(!encoded.contains("content-length"));
+    assert!(!encoded.contains("transfer-encoding"));
+    assert!(encoded.ends_with("\r\n\r\n"));
+    Ok(())
+}
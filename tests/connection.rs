@@ -0,0 +1,73 @@
+mod test_utils;
+mod connection {
+    use super::test_utils::TestIO;
+    use async_h1::server::{Connection, ConnectionStatus};
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Response, Result, StatusCode};
+
+    /// Read one chunk of what the server wrote.
+    async fn read_response(client: &mut TestIO) -> Result<String> {
+        let mut buf = [0; 1024];
+        let bytes = client.read(&mut buf).await?;
+        Ok(String::from_utf8(buf[..bytes].to_vec())?)
+    }
+
+    #[async_std::test]
+    async fn requests_are_read_and_answered_in_turn() -> Result<()> {
+        let (mut client, server) = TestIO::new();
+        let mut connection = Connection::new(server);
+
+        for path in ["/first", "/second"] {
+            let req = format!("GET {} HTTP/1.1\r\nHost: example.com\r\n\r\n", path);
+            client.write_all(req.as_bytes()).await?;
+
+            let req = connection.read_request().await?.unwrap();
+            assert_eq!(req.url().path(), path);
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(path);
+            let status = connection.write_response(res).await?;
+            assert_eq!(status, ConnectionStatus::KeepAlive);
+
+            let response = read_response(&mut client).await?;
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.ends_with(&format!("\r\n\r\n{}", path)));
+        }
+
+        client.close();
+        assert!(connection.read_request().await?.is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn closing_requests_end_the_connection() -> Result<()> {
+        let (mut client, server) = TestIO::new();
+        let mut connection = Connection::new(server);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n")
+            .await?;
+
+        connection.read_request().await?.unwrap();
+        let status = connection
+            .write_response(Response::new(StatusCode::Ok))
+            .await?;
+        assert_eq!(status, ConnectionStatus::Close);
+        assert!(connection.read_request().await?.is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn requests_are_answered_before_the_next_is_read() -> Result<()> {
+        let (mut client, server) = TestIO::new();
+        let mut connection = Connection::new(server);
+
+        let res = Response::new(StatusCode::Ok);
+        assert!(connection.write_response(res).await.is_err());
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        connection.read_request().await?.unwrap();
+        assert!(connection.read_request().await.is_err());
+        Ok(())
+    }
+}
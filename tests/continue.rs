@@ -52,3 +52,87 @@ async fn test_without_expect_when_not_reading_body() -> Result<()> {
 
     Ok(())
 }
+
+#[async_std::test]
+async fn test_closes_when_endpoint_responds_without_reading_body() -> Result<()> {
+    use async_h1::server::ConnectionStatus;
+    use http_types::{Request, Response, StatusCode};
+
+    let mut server = test_utils::TestServer::new(|_req: Request| async {
+        Ok(Response::new(StatusCode::Unauthorized))
+    });
+    server.write_all(REQUEST_WITH_EXPECT).await?;
+
+    // The body is never sent, so the server mustn't wait to discard it.
+    let status = io::timeout(Duration::from_secs(1), async {
+        server
+            .accept_one()
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))
+    })
+    .await?;
+    assert_eq!(status, ConnectionStatus::Close);
+
+    let mut buf = vec![0; 1024];
+    let bytes = server.read(&mut buf).await?;
+    let written = String::from_utf8(buf[..bytes].to_vec())?;
+    assert!(written.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+    assert!(written.contains("connection: close\r\n"));
+    Ok(())
+}
+
+#[async_std::test]
+async fn test_keeps_alive_when_endpoint_reads_body() -> Result<()> {
+    use async_h1::server::ConnectionStatus;
+    use http_types::{Request, Response, StatusCode};
+
+    let mut server = test_utils::TestServer::new(|mut req: Request| async move {
+        let body = req.body_string().await?;
+        Ok(Response::new(if body == "0123456789" {
+            StatusCode::Ok
+        } else {
+            StatusCode::BadRequest
+        }))
+    });
+    server.write_all(REQUEST_WITH_EXPECT).await?;
+    server.write_all(b"0123456789").await?;
+    assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+    let mut buf = vec![0; 1024];
+    let bytes = server.read(&mut buf).await?;
+    let written = String::from_utf8(buf[..bytes].to_vec())?;
+    assert!(written.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    Ok(())
+}
+
+#[async_std::test]
+async fn test_continue_is_queued_with_early_hints() -> Result<()> {
+    use async_h1::server::{ConnectionStatus, Interim};
+    use http_types::{Request, Response, StatusCode};
+
+    let mut server = test_utils::TestServer::new(|mut req: Request| async move {
+        let mut hints = Response::new(StatusCode::EarlyHints);
+        hints.insert_header("link", "</style.css>; rel=preload");
+        assert!(req.ext().get::<Interim>().unwrap().send(&hints));
+        let body = req.body_string().await?;
+        assert!(req.ext().get::<Interim>().unwrap().send(&hints));
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body(body);
+        Ok(res)
+    });
+    server.write_all(REQUEST_WITH_EXPECT).await?;
+    server.write_all(b"0123456789").await?;
+    assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+    let mut buf = vec![0; 1024];
+    let bytes = server.read(&mut buf).await?;
+    let written = String::from_utf8(buf[..bytes].to_vec())?;
+    let hints = "HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload\r\n\r\n";
+    let expected = format!(
+        "{}HTTP/1.1 100 Continue\r\n\r\n{}HTTP/1.1 200 OK\r\n",
+        hints, hints
+    );
+    assert!(written.starts_with(&expected), "{}", written);
+    assert!(written.ends_with("\r\n\r\n0123456789"), "{}", written);
+    Ok(())
+}
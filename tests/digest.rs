@@ -0,0 +1,134 @@
+mod test_utils;
+mod digest {
+    use super::test_utils::TestServer;
+    use async_h1::digest::{DigestAlgorithm, Hasher};
+    use async_h1::error::ErrorKind;
+    use async_h1::server::ServerOptions;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use async_std::io::Cursor;
+    use http_types::{Body, Request, Response, Result};
+
+    /// Sums the bytes of the body, wrapping on overflow.
+    #[derive(Debug)]
+    struct Sum(&'static str);
+
+    struct SumHasher(u8);
+
+    impl DigestAlgorithm for Sum {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn hasher(&self) -> Box<dyn Hasher> {
+            Box::new(SumHasher(0))
+        }
+    }
+
+    impl Hasher for SumHasher {
+        fn update(&mut self, bytes: &[u8]) {
+            self.0 = bytes
+                .iter()
+                .fold(self.0, |sum, byte| sum.wrapping_add(*byte));
+        }
+
+        fn finish(self: Box<Self>) -> Vec<u8> {
+            vec![self.0]
+        }
+    }
+
+    async fn respond(body: Body) -> Result<String> {
+        let opts = ServerOptions::new().with_content_digest(Sum("x-sum"));
+        let body = std::sync::Mutex::new(Some(body));
+        let mut server = TestServer::new_with_opts(
+            move |_| {
+                let body = body.lock().unwrap().take().unwrap();
+                async move {
+                    let mut res = Response::new(200);
+                    res.set_body(body);
+                    Ok(res)
+                }
+            },
+            opts,
+        );
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n")
+            .await?;
+        server.accept_one().await?;
+        let mut response = vec![0; 4096];
+        let n = server.read(&mut response).await?;
+        Ok(String::from_utf8_lossy(&response[..n]).into_owned())
+    }
+
+    #[async_std::test]
+    async fn chunked_response_gets_digest_trailer() -> Result<()> {
+        let body = Body::from_reader(Cursor::new(b"abc".to_vec()), None);
+        let response = respond(body).await?;
+        assert!(response.contains("trailer: content-digest\r\n"));
+        // 'a' + 'b' + 'c' = 0x26, which is "Jg==" in base64.
+        assert!(response.ends_with("3\r\nabc\r\n0\r\ncontent-digest: x-sum=:Jg==:\r\n\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn fixed_length_response_has_no_trailer() -> Result<()> {
+        let response = respond(Body::from_string("abc".into())).await?;
+        assert!(!response.contains("trailer"));
+        assert!(response.ends_with("\r\n\r\nabc"));
+        Ok(())
+    }
+
+    async fn validate(header: &str) -> Result<std::result::Result<String, ErrorKind>> {
+        let opts = ServerOptions::new()
+            .with_digest_validation(Sum("x-sum"))
+            .with_digest_validation(Sum("md5"));
+        let (sender, receiver) = async_channel::bounded(1);
+        let mut server = TestServer::new_with_opts(
+            move |mut req: Request| {
+                let sender = sender.clone();
+                async move {
+                    let body = req.body_string().await;
+                    let body = body.map_err(|err| ErrorKind::of(&err));
+                    sender.send(body).await.unwrap();
+                    Ok(Response::new(200))
+                }
+            },
+            opts,
+        );
+        let request = format!(
+            "POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 3\r\n{}\r\n\r\nabc",
+            header
+        );
+        server.write_all(request.as_bytes()).await?;
+        server.accept_one().await?;
+        Ok(receiver.recv().await?)
+    }
+
+    #[async_std::test]
+    async fn matching_digest_is_accepted() -> Result<()> {
+        let body = validate("Content-Digest: x-other=:AA==:, x-sum=:Jg==:").await?;
+        assert_eq!(body.unwrap(), "abc");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn mismatching_digest_fails_body() -> Result<()> {
+        let body = validate("Content-Digest: x-sum=:AA==:").await?;
+        assert_eq!(body.unwrap_err(), ErrorKind::DigestMismatch);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn content_md5_is_checked() -> Result<()> {
+        assert_eq!(validate("Content-MD5: Jg==").await?.unwrap(), "abc");
+        let body = validate("Content-MD5: AA==").await?;
+        assert_eq!(body.unwrap_err(), ErrorKind::DigestMismatch);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn unknown_algorithms_are_ignored() -> Result<()> {
+        let body = validate("Content-Digest: sha-256=:AA==:").await?;
+        assert_eq!(body.unwrap(), "abc");
+        Ok(())
+    }
+}
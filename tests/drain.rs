@@ -0,0 +1,145 @@
+mod test_utils;
+mod drain {
+    use super::test_utils::{TestIO, TestServer};
+    use async_h1::server::{ConnectionStatus, Drain, ServerOptions, ShutdownReport};
+    use async_std::io::{prelude::WriteExt, ReadExt};
+    use async_std::task;
+    use http_types::{Response, Result};
+    use std::time::Duration;
+
+    #[async_std::test]
+    async fn in_flight_response_closes_connection() -> Result<()> {
+        let drain = Drain::new();
+        let endpoint_drain = drain.clone();
+        let opts = ServerOptions::new().with_drain(drain.clone());
+        let mut server = TestServer::new_with_opts(
+            move |_| {
+                endpoint_drain.start();
+                async { Ok(Response::new(200)) }
+            },
+            opts,
+        );
+
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        let mut response = vec![0; 1024];
+        let n = server.read(&mut response).await?;
+        assert!(String::from_utf8_lossy(&response[..n]).contains("connection: close\r\n"));
+
+        // Further requests on this connection are refused.
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn idle_connections_close() -> Result<()> {
+        let drain = Drain::new();
+        let opts = ServerOptions::new().with_drain(drain.clone());
+        let (mut client, server) = TestIO::new();
+        let connection = task::spawn(async_h1::accept_with_opts(
+            server,
+            |_| async { Ok(Response::new(200)) },
+            opts,
+        ));
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        let mut response = vec![0; 1024];
+        let n = client.read(&mut response).await?;
+        assert!(!String::from_utf8_lossy(&response[..n]).contains("connection: close"));
+        assert_eq!(drain.connections(), 1);
+
+        drain.start();
+        drain.drained().await;
+        assert_eq!(drain.connections(), 0);
+        connection.await?;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn listeners_can_wait_for_draining() -> Result<()> {
+        let drain = Drain::new();
+        let waiting = task::spawn({
+            let drain = drain.clone();
+            async move { drain.draining().await }
+        });
+        task::yield_now().await;
+        assert!(!drain.is_draining());
+
+        let report = drain.shutdown(Duration::from_millis(50)).await;
+        waiting.await;
+        assert_eq!(report, ShutdownReport::default());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn shutdown_forces_stuck_connections() -> Result<()> {
+        let drain = Drain::new();
+        let opts = ServerOptions::new().with_drain(drain.clone());
+
+        let (mut stuck, server) = TestIO::new();
+        let stuck_connection = task::spawn(async_h1::accept_with_opts(
+            server,
+            |_| async {
+                futures_lite::future::pending::<()>().await;
+                Ok(Response::new(200))
+            },
+            opts.clone(),
+        ));
+        stuck
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+
+        let (_idle, server) = TestIO::new();
+        let idle_connection = task::spawn(async_h1::accept_with_opts(
+            server,
+            |_| async { Ok(Response::new(200)) },
+            opts,
+        ));
+
+        while drain.connections() < 2 {
+            task::yield_now().await;
+        }
+        let report = drain.shutdown(Duration::from_millis(50)).await;
+        assert_eq!(
+            report,
+            ShutdownReport {
+                graceful: 1,
+                forced: 1
+            }
+        );
+        stuck_connection.await?;
+        idle_connection.await?;
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn partial_requests_are_asked_to_retry() -> Result<()> {
+        let drain = Drain::new();
+        let opts = ServerOptions::new()
+            .with_drain(drain.clone())
+            .with_retry_after(Some(Duration::from_millis(2500)));
+        let (mut client, server) = TestIO::new();
+        let connection = task::spawn(async_h1::accept_with_opts(
+            server,
+            |_| async { Ok(Response::new(200)) },
+            opts,
+        ));
+
+        client.write_all(b"GET / HTTP/1.1\r\nHost: ").await?;
+        task::sleep(Duration::from_millis(20)).await;
+        drain.start();
+
+        let mut response = vec![0; 1024];
+        let n = client.read(&mut response).await?;
+        let response = String::from_utf8_lossy(&response[..n]);
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains("retry-after: 3\r\n"));
+        connection.await?;
+        Ok(())
+    }
+}
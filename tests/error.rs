@@ -0,0 +1,33 @@
+use async_h1::error::{recommended_response, ErrorKind};
+use http_types::{Error, StatusCode};
+use std::io;
+
+#[test]
+fn foreign_errors_are_other() {
+    let err = Error::from_str(StatusCode::NotFound, "no such page");
+    assert_eq!(ErrorKind::of(&err), ErrorKind::Other);
+
+    let err = Error::from_str(StatusCode::InternalServerError, "database is down");
+    assert_eq!(ErrorKind::of(&err), ErrorKind::Other);
+
+    let err = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+    assert_eq!(ErrorKind::of(&err), ErrorKind::Io);
+}
+
+#[test]
+fn recommended_responses_keep_foreign_statuses() {
+    let err = Error::from_str(StatusCode::NotFound, "no such page");
+    let res = recommended_response(&err).unwrap();
+    assert_eq!(res.status(), StatusCode::NotFound);
+
+    let err = Error::from_str(StatusCode::InternalServerError, "database is down");
+    let res = recommended_response(&err).unwrap();
+    assert_eq!(res.status(), StatusCode::InternalServerError);
+
+    let err = Error::from(io::Error::new(io::ErrorKind::TimedOut, "too slow"));
+    let res = recommended_response(&err).unwrap();
+    assert_eq!(res.status(), StatusCode::RequestTimeout);
+
+    let err = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+    assert!(recommended_response(&err).is_none());
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "fuzz")]
+
+use async_h1::fuzz;
+use http_types::Method;
+
+#[test]
+fn requests_decode_with_their_bodies() {
+    let input = b"PUT /a HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n\
+                  2\r\nhi\r\n0\r\n\r\n";
+    let (req, body) = fuzz::decode_request_bytes(input).unwrap().unwrap();
+    assert_eq!(req.method(), Method::Put);
+    assert_eq!(body, b"hi");
+    assert!(fuzz::decode_request_bytes(b"").unwrap().is_none());
+}
+
+#[test]
+fn responses_decode_with_their_bodies() {
+    let input = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+    let (res, body) = fuzz::decode_response_bytes(input).unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(body, b"hello");
+}
+
+#[test]
+fn chunked_bodies_decode() {
+    let body = fuzz::decode_chunked_bytes(b"3;ext=1\r\nabc\r\n0\r\nx: y\r\n\r\n").unwrap();
+    assert_eq!(body, b"abc");
+}
+
+#[test]
+fn malformed_input_is_an_error() {
+    let inputs: &[&[u8]] = &[
+        b"GET / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n",
+        b"\0\xff\r\n\r\n",
+        b"GET / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+    ];
+    for input in inputs {
+        assert!(fuzz::decode_request_bytes(input).is_err());
+    }
+    assert!(fuzz::decode_response_bytes(b"HTTP/1.1 abc\r\n\r\n").is_err());
+    assert!(fuzz::decode_chunked_bytes(b"3\r\nabcd\r\n").is_err());
+}
+
+/// Every prefix of a valid message either decodes or fails, without
+/// panicking.
+#[test]
+fn truncated_input_does_not_panic() {
+    let request =
+        b"POST /upload HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n\
+                    4\r\ndata\r\n0\r\nx-trailer: 1\r\n\r\n";
+    let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\ndata\r\n0\r\n\r\n";
+    for len in 0..request.len() {
+        let _ = fuzz::decode_request_bytes(&request[..len]);
+    }
+    for len in 0..response.len() {
+        let _ = fuzz::decode_response_bytes(&response[..len]);
+        let _ = fuzz::decode_chunked_bytes(&response[..len]);
+    }
+}
@@ -0,0 +1,79 @@
+mod test_utils;
+mod heartbeat {
+    use super::test_utils::TestServer;
+    use async_h1::server::ServerOptions;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use async_std::task;
+    use http_types::{Response, Result, StatusCode};
+    use std::time::Duration;
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+    async fn respond_after(delay: Duration, opts: ServerOptions) -> Result<String> {
+        respond_to(REQUEST, delay, opts).await
+    }
+
+    async fn respond_to(request: &[u8], delay: Duration, opts: ServerOptions) -> Result<String> {
+        let mut server = TestServer::new_with_opts(
+            move |_| async move {
+                task::sleep(delay).await;
+                Ok(Response::new(StatusCode::Ok))
+            },
+            opts,
+        );
+        server.write_all(request).await?;
+        server.accept_one().await?;
+
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        Ok(String::from_utf8(buf[..bytes].to_vec())?)
+    }
+
+    #[async_std::test]
+    async fn sends_heartbeats_while_endpoint_works() -> Result<()> {
+        let opts = ServerOptions::new().with_heartbeat(Duration::from_millis(20));
+        let written = respond_after(Duration::from_millis(100), opts).await?;
+
+        let (interim, last) = written.rsplit_once("HTTP/1.1 200 OK\r\n").unwrap();
+        assert!(interim.starts_with("HTTP/1.1 102 Processing\r\n\r\n"));
+        assert_eq!(
+            interim.replace("HTTP/1.1 102 Processing\r\n\r\n", ""),
+            "",
+            "only whole heartbeats precede the response"
+        );
+        assert!(last.contains("content-length: 0"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn sends_configured_status() -> Result<()> {
+        let opts = ServerOptions::new().with_heartbeat_status(Duration::from_millis(20), 103);
+        let written = respond_after(Duration::from_millis(60), opts).await?;
+        assert!(written.starts_with("HTTP/1.1 103 Early Hints\r\n\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn fast_endpoints_get_no_heartbeats() -> Result<()> {
+        let opts = ServerOptions::new().with_heartbeat(Duration::from_secs(10));
+        let written = respond_after(Duration::from_millis(0), opts).await?;
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn http_1_0_clients_get_no_heartbeats() -> Result<()> {
+        let opts = ServerOptions::new().with_heartbeat(Duration::from_millis(20));
+        let request = b"GET / HTTP/1.0\r\nHost: example.com\r\n\r\n";
+        let written = respond_to(request, Duration::from_millis(100), opts).await?;
+        assert!(!written.contains(" 102 "), "{}", written);
+        assert!(written.starts_with("HTTP/1.0 200 OK\r\n"), "{}", written);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_final_statuses() {
+        ServerOptions::new().with_heartbeat_status(Duration::from_secs(1), 200);
+    }
+}
@@ -0,0 +1,140 @@
+mod test_utils;
+mod hook {
+    use super::test_utils::TestServer;
+    use async_h1::error::ErrorKind;
+    use async_h1::server::{ConnectionStatus, Hook, ServerOptions};
+    use async_std::io::prelude::WriteExt;
+    use http_types::{Request, Response, Result, StatusCode};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug)]
+    struct StripPrefix;
+
+    impl Hook for StripPrefix {
+        fn before_endpoint(&self, req: &mut Request) -> Option<Response> {
+            let path = match req.url().path().strip_prefix("/api") {
+                Some(path) => path.to_owned(),
+                None => return Some(Response::new(StatusCode::NotFound)),
+            };
+            req.url_mut().set_path(&path);
+            req.insert_header("x-stripped", "true");
+            None
+        }
+    }
+
+    #[derive(Debug)]
+    struct Tag(&'static str);
+
+    impl Hook for Tag {
+        fn before_encode(&self, res: &mut Response) {
+            res.append_header("x-seen-by", self.0);
+        }
+    }
+
+    fn server() -> TestServer<
+        impl Fn(Request) -> std::future::Ready<Result<Response>>,
+        std::future::Ready<Result<Response>>,
+    > {
+        let opts = ServerOptions::new()
+            .with_hook(Tag("outer"))
+            .with_hook(StripPrefix)
+            .with_hook(Tag("inner"));
+        TestServer::new_with_opts(
+            |req: Request| {
+                assert_eq!(req.url().path(), "/users");
+                assert!(req.header("x-stripped").is_some());
+                std::future::ready(Ok(Response::new(StatusCode::Ok)))
+            },
+            opts,
+        )
+    }
+
+    #[async_std::test]
+    async fn rewrites_request() -> Result<()> {
+        let mut server = server();
+        server
+            .write_all(b"GET /api/users HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), StatusCode::Ok);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn short_circuits() -> Result<()> {
+        let mut server = server();
+        server
+            .write_all(b"GET /users HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), StatusCode::NotFound);
+        assert_eq!(res["x-seen-by"].iter().count(), 2);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn post_processes_responses() -> Result<()> {
+        let mut server = server();
+        server
+            .write_all(b"GET /api/users HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        server.accept_one().await?;
+        let res = async_h1::client::decode(server).await?;
+        let seen_by: Vec<_> = res["x-seen-by"].iter().map(|v| v.as_str()).collect();
+        assert_eq!(seen_by, ["inner", "outer"]);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn post_processes_error_responses() -> Result<()> {
+        let mut server = server();
+        server.write_all(b"GARBAGE\r\n\r\n").await?;
+        server.accept_one().await.unwrap_err();
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert_eq!(res["x-seen-by"].iter().count(), 2);
+        Ok(())
+    }
+
+    #[derive(Debug, Default)]
+    struct Errors(Arc<Mutex<Vec<ErrorKind>>>);
+
+    impl Hook for Errors {
+        fn on_error(&self, error: &http_types::Error) {
+            self.0.lock().unwrap().push(ErrorKind::of(error));
+        }
+    }
+
+    #[async_std::test]
+    async fn rejects_oversized_response_heads() -> Result<()> {
+        let errors = Errors::default();
+        let seen = errors.0.clone();
+        let opts = ServerOptions::new()
+            .with_max_response_head_length(1024)
+            .with_hook(errors);
+        let mut server = TestServer::new_with_opts(
+            |_| {
+                let mut res = Response::new(StatusCode::Ok);
+                res.insert_header("x-huge", "a".repeat(4096));
+                std::future::ready(Ok(res))
+            },
+            opts,
+        );
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+        assert_eq!(*seen.lock().unwrap(), [ErrorKind::LimitExceeded]);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), StatusCode::InternalServerError);
+        assert!(res.header("x-huge").is_none());
+        assert_eq!(res["connection"], "close");
+        Ok(())
+    }
+}
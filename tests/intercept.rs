@@ -0,0 +1,57 @@
+mod test_utils;
+mod intercept {
+    use super::test_utils::TestIO;
+    use async_h1::client::{ClientOptions, Interceptor};
+    use http_types::{Method, Request, Response, Result, Url};
+    use std::sync::{Arc, Mutex};
+
+    /// Tags requests and records the responses it sees.
+    #[derive(Debug, Clone, Default)]
+    struct Tag(&'static str, Arc<Mutex<Vec<String>>>);
+
+    impl Interceptor for Tag {
+        fn before_request(&self, req: &mut Request) {
+            req.append_header("x-tag", self.0);
+            self.1.lock().unwrap().push(format!("request {}", self.0));
+        }
+
+        fn after_response(&self, method: Method, url: &Url, res: &mut Response) {
+            res.append_header("x-seen-by", self.0);
+            let status = res.status() as u16;
+            let event = format!("{} {} {} {}", self.0, method, url.path(), status);
+            self.1.lock().unwrap().push(event);
+        }
+    }
+
+    #[async_std::test]
+    async fn interceptors_wrap_exchange() -> Result<()> {
+        let (client, server) = TestIO::new();
+        async_std::task::spawn(async_h1::accept(server, |req: Request| async move {
+            let tags = req.header("x-tag").unwrap().iter().map(|v| v.as_str());
+            let mut res = Response::new(200);
+            res.set_body(tags.collect::<Vec<_>>().join(","));
+            Ok(res)
+        }));
+
+        let log = Arc::new(Mutex::new(vec![]));
+        let opts = ClientOptions::new()
+            .with_interceptor(Tag("outer", log.clone()))
+            .with_interceptor(Tag("inner", log.clone()));
+        let req = Request::new(Method::Get, Url::parse("http://example.com/path")?);
+        let mut res = async_h1::connect_with_opts(client, req, opts).await?;
+
+        let seen_by: Vec<_> = res["x-seen-by"].iter().map(|v| v.as_str()).collect();
+        assert_eq!(seen_by, ["inner", "outer"]);
+        assert_eq!(res.body_string().await?, "outer,inner");
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "request outer",
+                "request inner",
+                "inner GET /path 200",
+                "outer GET /path 200"
+            ]
+        );
+        Ok(())
+    }
+}
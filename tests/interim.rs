@@ -0,0 +1,72 @@
+mod test_utils;
+mod interim {
+    use super::test_utils::TestServer;
+    use async_h1::server::Interim;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Request, Response, Result, StatusCode};
+    use std::sync::{Arc, Mutex};
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+    #[async_std::test]
+    async fn sends_early_hints_before_response() -> Result<()> {
+        let mut server = TestServer::new(|req: Request| async move {
+            let interim = req.ext().get::<Interim>().unwrap();
+            let mut hints = Response::new(StatusCode::EarlyHints);
+            hints.insert_header("link", "</style.css>; rel=preload");
+            assert!(interim.send(&hints));
+            hints.insert_header("link", "</app.js>; rel=preload");
+            assert!(interim.send(&hints));
+            Ok(Response::new(StatusCode::Ok))
+        });
+        server.write_all(REQUEST).await?;
+        server.accept_one().await?;
+
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        let expected = "HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload\r\n\r\n\
+            HTTP/1.1 103 Early Hints\r\nlink: </app.js>; rel=preload\r\n\r\n\
+            HTTP/1.1 200 OK\r\n";
+        assert!(written.starts_with(expected), "{}", written);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn refuses_after_final_response() -> Result<()> {
+        let kept = Arc::new(Mutex::new(None));
+        let endpoint_kept = kept.clone();
+        let mut server = TestServer::new(move |req: Request| {
+            let kept = endpoint_kept.clone();
+            async move {
+                *kept.lock().unwrap() = req.ext().get::<Interim>().cloned();
+                Ok(Response::new(StatusCode::Ok))
+            }
+        });
+        server.write_all(REQUEST).await?;
+        server.accept_one().await?;
+
+        let interim = kept.lock().unwrap().take().unwrap();
+        assert!(!interim.send(&Response::new(StatusCode::Continue)));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn refuses_http_1_0_requests() -> Result<()> {
+        let mut server = TestServer::new(|req: Request| async move {
+            let interim = req.ext().get::<Interim>().unwrap();
+            assert!(!interim.send(&Response::new(StatusCode::EarlyHints)));
+            Ok(Response::new(StatusCode::Ok))
+        });
+        server
+            .write_all(b"GET / HTTP/1.0\r\nHost: example.com\r\n\r\n")
+            .await?;
+        server.accept_one().await?;
+
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(written.starts_with("HTTP/1.0 200 OK\r\n"), "{}", written);
+        Ok(())
+    }
+}
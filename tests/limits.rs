@@ -0,0 +1,72 @@
+mod test_utils;
+mod limits {
+    use super::test_utils::TestServer;
+    use async_h1::server::{ConnectionStatus, Limits, ServerOptions};
+    use async_std::io::prelude::WriteExt;
+    use async_std::task;
+    use http_types::{Response, Result, StatusCode};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+    #[async_std::test]
+    async fn sheds_requests_beyond_in_flight_limit() -> Result<()> {
+        let limits = Limits::new().with_max_in_flight(1);
+        let opts = ServerOptions::new().with_limits(limits.clone());
+        let release = Arc::new(AtomicBool::new(false));
+
+        let waiting = release.clone();
+        let mut busy = TestServer::new_with_opts(
+            move |_| {
+                let waiting = waiting.clone();
+                async move {
+                    while !waiting.load(Ordering::SeqCst) {
+                        task::yield_now().await;
+                    }
+                    Ok(Response::new(StatusCode::Ok))
+                }
+            },
+            opts.clone(),
+        );
+        let mut shed =
+            TestServer::new_with_opts(|_| async { Ok(Response::new(StatusCode::Ok)) }, opts);
+
+        busy.write_all(REQUEST).await?;
+        shed.write_all(REQUEST).await?;
+        let (busy_status, shed_status) = futures_lite::future::zip(busy.accept_one(), async {
+            while limits.in_flight() == 0 {
+                task::yield_now().await;
+            }
+            let status = shed.accept_one().await;
+            release.store(true, Ordering::SeqCst);
+            status
+        })
+        .await;
+        assert_eq!(busy_status?, ConnectionStatus::KeepAlive);
+        assert_eq!(shed_status?, ConnectionStatus::Close);
+        assert_eq!(limits.in_flight(), 0);
+
+        let res = async_h1::client::decode(busy).await?;
+        assert_eq!(res.status(), StatusCode::Ok);
+        let res = async_h1::client::decode(shed).await?;
+        assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+        assert_eq!(res["retry-after"], "1");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn sheds_requests_beyond_buffered_limit() -> Result<()> {
+        let limits = Limits::new().with_max_buffered(0);
+        let opts = ServerOptions::new().with_limits(limits.clone());
+        let mut server =
+            TestServer::new_with_opts(|_| async { Ok(Response::new(StatusCode::Ok)) }, opts);
+
+        server.write_all(REQUEST).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+        assert_eq!(limits.buffered(), 0);
+        Ok(())
+    }
+}
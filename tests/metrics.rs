@@ -0,0 +1,81 @@
+#![cfg(feature = "metrics")]
+
+mod test_utils;
+mod metrics {
+    use super::test_utils::TestServer;
+    use async_h1::metrics::{
+        Labels, Metrics, SERVER_BYTES_READ, SERVER_BYTES_WRITTEN, SERVER_REQUESTS,
+        SERVER_TIME_TO_FIRST_BYTE,
+    };
+    use async_h1::server::{ConnectionStatus, ServerOptions};
+    use async_std::io::prelude::WriteExt;
+    use http_types::{Response, Result};
+    use std::sync::{Arc, Mutex};
+
+    type Measurement = (&'static str, u64, String);
+
+    #[derive(Debug, Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Measurement>>>);
+
+    impl Metrics for Recorder {
+        fn increment_counter(&self, name: &'static str, value: u64, labels: Labels<'_>) {
+            let labels = labels
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join(",");
+            self.0.lock().unwrap().push((name, value, labels));
+        }
+
+        fn record_histogram(&self, name: &'static str, _value: f64, labels: Labels<'_>) {
+            self.increment_counter(name, 1, labels);
+        }
+    }
+
+    #[async_std::test]
+    async fn counts_requests() -> Result<()> {
+        let recorder = Recorder::default();
+        let opts = ServerOptions::new().with_metrics(recorder.clone());
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(404)) }, opts);
+
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let recorded = recorder.0.lock().unwrap();
+        assert!(recorded
+            .iter()
+            .any(|(name, value, _)| *name == SERVER_BYTES_WRITTEN && *value > 0));
+        assert!(recorded
+            .iter()
+            .any(|(name, value, labels)| *name == SERVER_REQUESTS
+                && *value == 1
+                && labels == "method=GET,status=404"));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn counts_bytes_read_and_time_to_first_byte() -> Result<()> {
+        let recorder = Recorder::default();
+        let opts = ServerOptions::new().with_metrics(recorder.clone());
+        let mut server = TestServer::new_with_opts(|_| async { Ok(Response::new(200)) }, opts);
+
+        // The body is read by the server, though the endpoint ignores it.
+        let request = b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello";
+        server.write_all(request).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let recorded = recorder.0.lock().unwrap();
+        assert!(recorded
+            .iter()
+            .any(|(name, value, _)| *name == SERVER_BYTES_READ && *value == request.len() as u64));
+        assert!(recorded
+            .iter()
+            .any(|(name, _, labels)| *name == SERVER_TIME_TO_FIRST_BYTE
+                && labels == "method=POST,status=200"));
+
+        Ok(())
+    }
+}
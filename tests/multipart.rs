@@ -0,0 +1,118 @@
+mod test_utils;
+mod multipart {
+    use super::test_utils::TestServer;
+    use async_h1::body::Multipart;
+    use async_h1::error::ErrorKind;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use async_std::io::Cursor;
+    use http_types::{Method, Request, Response, Result, StatusCode, Url};
+
+    const BODY: &str = "preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Holiday\r\n\
+        --XyZ \r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"beach.jpg\"\r\n\
+        Content-Type: image/jpeg\r\n\
+        \r\n\
+        not quite \r\n--Xy, but close\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    /// Read a multipart body one byte at a time, so delimiters are split
+    /// across reads.
+    struct Trickle(Cursor<&'static [u8]>);
+
+    impl futures_lite::AsyncRead for Trickle {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let len = buf.len().min(1);
+            std::pin::Pin::new(&mut self.0).poll_read(cx, &mut buf[..len])
+        }
+    }
+
+    #[async_std::test]
+    async fn parts_are_read_in_turn() -> Result<()> {
+        let mut multipart = Multipart::new(Trickle(Cursor::new(BODY.as_bytes())), "XyZ");
+
+        let mut part = multipart.next_part().await?.unwrap();
+        assert_eq!(part.name(), Some("title"));
+        assert_eq!(part.filename(), None);
+        let mut contents = String::new();
+        part.read_to_string(&mut contents).await?;
+        assert_eq!(contents, "Holiday");
+
+        let mut part = multipart.next_part().await?.unwrap();
+        assert_eq!(part.name(), Some("photo"));
+        assert_eq!(part.filename(), Some("beach.jpg"));
+        assert_eq!(part.content_type(), Some("image/jpeg"));
+        let mut contents = String::new();
+        part.read_to_string(&mut contents).await?;
+        assert_eq!(contents, "not quite \r\n--Xy, but close");
+
+        assert!(multipart.next_part().await?.is_none());
+        assert!(multipart.next_part().await?.is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn unread_parts_are_skipped() -> Result<()> {
+        let mut multipart = Multipart::new(Cursor::new(BODY.as_bytes()), "XyZ");
+        multipart.next_part().await?.unwrap();
+        let part = multipart.next_part().await?.unwrap();
+        assert_eq!(part.name(), Some("photo"));
+        assert!(multipart.next_part().await?.is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn truncated_bodies_fail() -> Result<()> {
+        let body = "--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\ncut short";
+        let mut multipart = Multipart::new(Cursor::new(body.as_bytes()), "XyZ");
+        let mut part = multipart.next_part().await?.unwrap();
+        let err = part.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(ErrorKind::of_io(&err), ErrorKind::BodyFraming);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn requests_must_be_multipart() -> Result<()> {
+        let mut req = Request::new(Method::Post, Url::parse("http://example.com/")?);
+        req.set_body("plain");
+        let err = Multipart::from_request(&mut req).unwrap_err();
+        assert_eq!(err.status(), StatusCode::UnsupportedMediaType);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn uploads_are_streamed_from_the_request() -> Result<()> {
+        let mut server = TestServer::new(|mut req: Request| async move {
+            let mut multipart = Multipart::from_request(&mut req)?;
+            let mut names = Vec::new();
+            while let Some(part) = multipart.next_part().await? {
+                names.push(part.name().unwrap_or_default().to_owned());
+            }
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(names.join(","));
+            Ok(res)
+        });
+
+        let head = format!(
+            "POST / HTTP/1.1\r\nHost: example.com\r\n\
+             Content-Type: multipart/form-data; boundary=XyZ\r\n\
+             Content-Length: {}\r\n\r\n",
+            BODY.len()
+        );
+        server.write_all(head.as_bytes()).await?;
+        server.write_all(BODY.as_bytes()).await?;
+        server.accept_one().await?;
+
+        let mut res = async_h1::client::decode(server).await?;
+        assert_eq!(res.body_string().await?, "title,photo");
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use async_h1::negotiate::Preferences;
+use http_types::{Method, Request, Url};
+
+fn request(headers: &[(&str, &str)]) -> Request {
+    let mut req = Request::new(Method::Get, Url::parse("http://example.com/").unwrap());
+    for (name, value) in headers {
+        req.append_header(*name, *value);
+    }
+    req
+}
+
+#[test]
+fn missing_headers_accept_anything() {
+    let req = request(&[]);
+    for preferences in [
+        Preferences::accept(&req),
+        Preferences::accept_encoding(&req),
+        Preferences::accept_language(&req),
+    ] {
+        assert!(!preferences.is_present());
+        assert_eq!(preferences.quality("anything"), 1.0);
+        assert_eq!(preferences.choose(["a", "b"]), Some("a"));
+    }
+}
+
+#[test]
+fn accept() {
+    let req = request(&[(
+        "accept",
+        "text/*;q=0.3, text/html;q=0.7, text/html;level=1, */*;q=0.5",
+    )]);
+    let accept = Preferences::accept(&req);
+    assert!(accept.is_present());
+    assert_eq!(accept.quality("text/html;level=1"), 1.0);
+    assert_eq!(accept.quality("text/html"), 0.7);
+    assert_eq!(accept.quality("text/html;level=2"), 0.7);
+    assert_eq!(accept.quality("text/plain"), 0.3);
+    assert_eq!(accept.quality("image/jpeg"), 0.5);
+    assert_eq!(
+        accept.choose(["text/plain", "image/png"]),
+        Some("image/png")
+    );
+
+    let accept = Preferences::accept(request(&[("accept", "application/json")]));
+    assert_eq!(accept.quality("text/html"), 0.0);
+    assert_eq!(accept.choose(["text/html"]), None);
+    assert_eq!(
+        accept.choose(["text/html", "APPLICATION/JSON"]),
+        Some("APPLICATION/JSON")
+    );
+}
+
+#[test]
+fn accept_encoding() {
+    let req = request(&[("accept-encoding", "gzip;q=0.5"), ("accept-encoding", "br")]);
+    let accept_encoding = Preferences::accept_encoding(&req);
+    assert_eq!(accept_encoding.quality("br"), 1.0);
+    assert_eq!(accept_encoding.quality("GZIP"), 0.5);
+    assert_eq!(accept_encoding.quality("deflate"), 0.0);
+    assert_eq!(accept_encoding.choose(["gzip", "br"]), Some("br"));
+    assert_eq!(
+        accept_encoding.choose(["deflate", "identity"]),
+        Some("identity")
+    );
+    assert_eq!(
+        accept_encoding.choose(["identity", "gzip"]),
+        Some("gzip"),
+        "identity is only chosen over what the client listed as a fallback"
+    );
+
+    let req = request(&[("accept-encoding", "*;q=0.2, identity;q=0")]);
+    let accept_encoding = Preferences::accept_encoding(&req);
+    assert_eq!(accept_encoding.quality("zstd"), 0.2);
+    assert_eq!(accept_encoding.quality("identity"), 0.0);
+
+    let req = request(&[("accept-encoding", "*;q=0")]);
+    let accept_encoding = Preferences::accept_encoding(&req);
+    assert_eq!(accept_encoding.choose(["gzip", "identity"]), None);
+}
+
+#[test]
+fn accept_language() {
+    let req = request(&[("accept-language", "de-CH, de;q=0.9, en;q=0.8, *;q=0.1")]);
+    let accept_language = Preferences::accept_language(&req);
+    assert_eq!(accept_language.quality("de-ch"), 1.0);
+    assert_eq!(accept_language.quality("de-AT"), 0.9);
+    assert_eq!(accept_language.quality("en-US"), 0.8);
+    assert_eq!(accept_language.quality("eng"), 0.1);
+    assert_eq!(accept_language.quality("fr"), 0.1);
+    assert_eq!(accept_language.choose(["fr", "en", "de"]), Some("de"));
+}
+
+#[test]
+fn malformed_qualities() {
+    let req = request(&[(
+        "accept-encoding",
+        "gzip;q=high, br;q=2, , deflate ; q = 0.4, zstd;q=NaN, compress;q=inf",
+    )]);
+    let accept_encoding = Preferences::accept_encoding(&req);
+    assert_eq!(accept_encoding.quality("gzip"), 0.0);
+    assert_eq!(accept_encoding.quality("br"), 0.0);
+    assert_eq!(accept_encoding.quality("deflate"), 0.4);
+    assert_eq!(accept_encoding.quality("zstd"), 0.0);
+    assert_eq!(accept_encoding.quality("compress"), 0.0);
+    assert_eq!(
+        accept_encoding.choose(["zstd", "compress", "deflate"]),
+        Some("deflate")
+    );
+
+    let req = request(&[(
+        "accept-language",
+        "en;q=1.000, de;q=0.125, fr;q=0.1234, it;q=-0.5, es;q=1.001, nl;q=0.",
+    )]);
+    let accept_language = Preferences::accept_language(&req);
+    assert_eq!(accept_language.quality("en"), 1.0);
+    assert_eq!(accept_language.quality("de"), 0.125);
+    assert_eq!(accept_language.quality("fr"), 0.0);
+    assert_eq!(accept_language.quality("it"), 0.0);
+    assert_eq!(accept_language.quality("es"), 0.0);
+    assert_eq!(accept_language.quality("nl"), 0.0);
+}
@@ -0,0 +1,123 @@
+mod test_utils;
+mod negotiation {
+    use super::test_utils::TestServer;
+    use async_h1::server::{ConnectionInfo, Expectation, Negotiation, ServerOptions, TlsInfo};
+    use async_std::io::prelude::WriteExt;
+    use http_types::{Request, Response, Result, StatusCode, Version};
+    use std::sync::{Arc, Mutex};
+
+    async fn negotiate(request: &str, opts: ServerOptions) -> Result<Negotiation> {
+        let seen = Arc::new(Mutex::new(None));
+        let record = seen.clone();
+        let mut server = TestServer::new_with_opts(
+            move |req: Request| {
+                *record.lock().unwrap() = req.ext().get::<Negotiation>().cloned();
+                async { Ok(Response::new(StatusCode::Ok)) }
+            },
+            opts,
+        );
+        server.write_all(request.as_bytes()).await?;
+        server.accept_one().await?;
+        let negotiation = seen.lock().unwrap().take();
+        Ok(negotiation.unwrap())
+    }
+
+    #[async_std::test]
+    async fn plain_request() -> Result<()> {
+        let negotiation = negotiate(
+            "GET / HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            ServerOptions::new(),
+        )
+        .await?;
+        assert_eq!(negotiation.version(), Version::Http1_1);
+        assert!(negotiation.is_keep_alive());
+        assert!(negotiation.upgrade_offers().is_empty());
+        assert_eq!(negotiation.expectation(), Expectation::None);
+        assert!(negotiation.tls().is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upgrade_and_expect() -> Result<()> {
+        let negotiation = negotiate(
+            concat!(
+                "POST / HTTP/1.1\r\n",
+                "Host: example.com\r\n",
+                "Connection: Upgrade, close\r\n",
+                "Upgrade: websocket, h2c\r\n",
+                "Expect: 100-continue\r\n",
+                "Content-Length: 0\r\n",
+                "\r\n"
+            ),
+            ServerOptions::new(),
+        )
+        .await?;
+        assert_eq!(negotiation.upgrade_offers(), ["websocket", "h2c"]);
+        assert_eq!(negotiation.expectation(), Expectation::Continue);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn close_and_unsupported_expectation() -> Result<()> {
+        let negotiation = negotiate(
+            "GET / HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\nExpect: coffee\r\n\r\n",
+            ServerOptions::new(),
+        )
+        .await?;
+        assert!(!negotiation.is_keep_alive());
+        assert_eq!(negotiation.expectation(), Expectation::Unsupported);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn tls_info() -> Result<()> {
+        let tls = TlsInfo::new()
+            .with_alpn_protocol("http/1.1")
+            .with_server_name("example.com");
+        let opts = ServerOptions::new().with_tls_info(tls.clone());
+        let negotiation = negotiate("GET / HTTP/1.1\r\nHost: example.com\r\n\r\n", opts).await?;
+        assert_eq!(negotiation.tls(), Some(&tls));
+        assert_eq!(
+            negotiation.tls().unwrap().alpn_protocol(),
+            Some(&b"http/1.1"[..])
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn connection_info() -> Result<()> {
+        let seen = Arc::new(Mutex::new(None));
+        let record = seen.clone();
+        let opts = ServerOptions::new()
+            .with_peer_addr("192.0.2.1:4321".parse().unwrap())
+            .with_local_addr("198.51.100.1:443".parse().unwrap())
+            .with_tls_info(TlsInfo::new().with_server_name("example.com"));
+        let mut server = TestServer::new_with_opts(
+            move |req: Request| {
+                let addrs = (
+                    req.peer_addr().unwrap().to_owned(),
+                    req.local_addr().unwrap().to_owned(),
+                );
+                *record.lock().unwrap() = Some((req.ext().get::<ConnectionInfo>().cloned(), addrs));
+                async { Ok(Response::new(StatusCode::Ok)) }
+            },
+            opts,
+        );
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        server.accept_one().await?;
+
+        let (info, (peer_addr, local_addr)) = seen.lock().unwrap().take().unwrap();
+        let info = info.unwrap();
+        assert_eq!(info.peer_addr(), Some("192.0.2.1:4321".parse().unwrap()));
+        assert_eq!(info.local_addr(), Some("198.51.100.1:443".parse().unwrap()));
+        assert_eq!(
+            info.tls().and_then(|tls| tls.server_name()),
+            Some("example.com")
+        );
+        assert_eq!(peer_addr, "192.0.2.1:4321");
+        assert_eq!(local_addr, "198.51.100.1:443");
+        Ok(())
+    }
+}
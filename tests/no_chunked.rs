@@ -0,0 +1,56 @@
+#![cfg(not(feature = "chunked"))]
+
+mod test_utils;
+mod no_chunked {
+    use super::test_utils::TestServer;
+    use async_h1::error::ErrorKind;
+    use async_std::io::prelude::WriteExt;
+    use async_std::io::Cursor;
+    use http_types::{Body, Request, Response, Result, StatusCode};
+
+    #[async_std::test]
+    async fn rejects_chunked_requests() -> Result<()> {
+        let mut server = TestServer::new(|_| async { Ok(Response::new(StatusCode::Ok)) });
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n")
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::BodyFraming);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rejects_streaming_responses() -> Result<()> {
+        let mut server = TestServer::new(|_: Request| async {
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(Body::from_reader(Cursor::new("hello"), None));
+            Ok(res)
+        });
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        let err = server.accept_one().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Encode);
+
+        let res = async_h1::client::decode(server).await?;
+        assert_eq!(res.status(), StatusCode::InternalServerError);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn answers_head_for_streaming_responses() -> Result<()> {
+        let mut server = TestServer::new(|_: Request| async {
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(Body::from_reader(Cursor::new("hello"), None));
+            Ok(res)
+        });
+        server
+            .write_all(b"HEAD / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        server.accept_one().await?;
+        Ok(())
+    }
+}
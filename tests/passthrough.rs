@@ -0,0 +1,89 @@
+#![cfg(feature = "chunked")]
+
+mod test_utils;
+mod passthrough {
+    use super::test_utils::{TestIO, TestServer};
+    use async_h1::client::ClientOptions;
+    use async_h1::server::{ConnectionStatus, ServerOptions};
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Body, Method, Request, Response, Result, StatusCode, Url};
+
+    const CHUNKED: &str = "3\r\nabc\r\n2\r\nde\r\n0\r\nx-sum: 5\r\n\r\n";
+
+    #[async_std::test]
+    async fn server_keeps_chunked_framing() -> Result<()> {
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                let raw = req.body_string().await?;
+                let mut res = Response::new(StatusCode::Ok);
+                res.insert_header("transfer-encoding", "chunked");
+                res.set_body(Body::from_reader(async_std::io::Cursor::new(raw), None));
+                Ok(res)
+            },
+            ServerOptions::new().with_passthrough(64 * 1024),
+        );
+        server
+            .write_all(
+                format!(
+                    "POST / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n{}",
+                    CHUNKED
+                )
+                .as_bytes(),
+            )
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(written.contains("transfer-encoding: chunked\r\n"));
+        assert!(written.ends_with(&format!("\r\n\r\n{}", CHUNKED)));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn client_keeps_chunked_framing() -> Result<()> {
+        let (client, mut upstream) = TestIO::new();
+        upstream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n{}",
+                    CHUNKED
+                )
+                .as_bytes(),
+            )
+            .await?;
+
+        let mut req = Request::new(Method::Post, Url::parse("http://example.com/")?);
+        req.insert_header("transfer-encoding", "chunked");
+        req.set_body(Body::from_reader(async_std::io::Cursor::new(CHUNKED), None));
+        let opts = ClientOptions::new().with_passthrough(64 * 1024);
+        let mut res = async_h1::connect_with_opts(client, req, opts).await?;
+        assert_eq!(res["transfer-encoding"], "chunked");
+        assert_eq!(res.body_string().await?, CHUNKED);
+
+        let mut buf = vec![0; 1024];
+        let bytes = upstream.read(&mut buf).await?;
+        let sent = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(sent.ends_with(&format!("\r\n\r\n{}", CHUNKED)));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn rejects_bad_framing() -> Result<()> {
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                req.body_string().await?;
+                Ok(Response::new(StatusCode::Ok))
+            },
+            ServerOptions::new().with_passthrough(64 * 1024),
+        );
+        server
+            .write_all(
+                b"POST / HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            )
+            .await?;
+        assert!(server.accept_one().await.is_err());
+        Ok(())
+    }
+}
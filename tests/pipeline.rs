@@ -0,0 +1,96 @@
+mod test_utils;
+mod pipeline {
+    use super::test_utils::TestServer;
+    use async_h1::server::ServerOptions;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Request, Response, Result};
+
+    async fn endpoint(req: Request) -> Result<Response> {
+        let mut res = Response::new(200);
+        res.set_body(req.url().path().to_owned());
+        Ok(res)
+    }
+
+    /// The bodies of the responses written to `server`, in order.
+    async fn bodies<F, Fut>(server: &mut TestServer<F, Fut>) -> Result<Vec<String>>
+    where
+        F: Fn(Request) -> Fut,
+        Fut: std::future::Future<Output = Result<Response>>,
+    {
+        let mut responses = vec![0; 4096];
+        let len = server.read(&mut responses).await?;
+        let responses = std::str::from_utf8(&responses[..len])?;
+        Ok(responses
+            .split("HTTP/1.1 ")
+            .skip(1)
+            .map(|res| res.split("\r\n\r\n").nth(1).unwrap().to_owned())
+            .collect())
+    }
+
+    #[async_std::test]
+    async fn pipelined_requests_are_answered_in_order() -> Result<()> {
+        for depth in [1, 2, 8] {
+            let opts = ServerOptions::new().with_pipeline_depth(depth);
+            let mut server = TestServer::new_with_opts(endpoint, opts);
+            server
+                .write_all(
+                    b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n\
+                      POST /b HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello\r\n\
+                      GET /c HTTP/1.1\r\nHost: example.com\r\n\r\n\
+                      GET /d HTTP/1.1\r\nHost: example.com\r\n\r\n",
+                )
+                .await?;
+            server.close();
+            server.accept().await?;
+            assert!(server.all_read());
+            assert_eq!(bodies(&mut server).await?, ["/a", "/b", "/c", "/d"]);
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pipelined_endpoints_run_concurrently() -> Result<()> {
+        // The first endpoint only finishes once the second one has started.
+        let (sender, receiver) = async_channel::bounded::<()>(1);
+        let opts = ServerOptions::new().with_pipeline_depth(2);
+        let mut server = TestServer::new_with_opts(
+            move |req: Request| {
+                let (sender, receiver) = (sender.clone(), receiver.clone());
+                async move {
+                    match req.url().path() {
+                        "/first" => receiver.recv().await?,
+                        _ => sender.send(()).await?,
+                    }
+                    endpoint(req).await
+                }
+            },
+            opts,
+        );
+        server
+            .write_all(
+                b"GET /first HTTP/1.1\r\nHost: example.com\r\n\r\n\
+                  GET /second HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            )
+            .await?;
+        server.close();
+        server.accept().await?;
+        assert_eq!(bodies(&mut server).await?, ["/first", "/second"]);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn requests_closing_the_connection_stop_reading_ahead() -> Result<()> {
+        let opts = ServerOptions::new().with_pipeline_depth(4);
+        let mut server = TestServer::new_with_opts(endpoint, opts);
+        server
+            .write_all(
+                b"GET /a HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n\
+                  GET /b HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            )
+            .await?;
+        server.close();
+        server.accept().await?;
+        assert_eq!(bodies(&mut server).await?, ["/a"]);
+        Ok(())
+    }
+}
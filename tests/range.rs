@@ -0,0 +1,86 @@
+use async_h1::range;
+use async_std::io::Cursor;
+use http_types::{Method, Request, Response, Result, StatusCode, Url};
+
+const RESOURCE: &[u8] = b"0123456789abcdefghij";
+
+fn request(headers: &[(&str, &str)]) -> Request {
+    let mut req = Request::new(Method::Get, Url::parse("http://example.com/").unwrap());
+    for (name, value) in headers {
+        req.insert_header(*name, *value);
+    }
+    req
+}
+
+fn respond(req: &Request) -> Response {
+    let mut res = Response::new(StatusCode::Ok);
+    res.insert_header("content-type", "text/plain");
+    res.insert_header("etag", "\"v1\"");
+    let body = Cursor::new(RESOURCE);
+    range::respond(req, res, body, RESOURCE.len() as u64)
+}
+
+#[async_std::test]
+async fn whole_resource_without_range() -> Result<()> {
+    let mut res = respond(&request(&[]));
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(res["accept-ranges"], "bytes");
+    assert_eq!(res.len(), Some(20));
+    assert_eq!(res.body_bytes().await?, RESOURCE);
+
+    // A range for another version of the resource doesn't apply.
+    let mut res = respond(&request(&[("range", "bytes=0-1"), ("if-range", "\"v0\"")]));
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(res.body_bytes().await?, RESOURCE);
+    Ok(())
+}
+
+#[async_std::test]
+async fn single_range() -> Result<()> {
+    let req = request(&[("range", "bytes=5-9"), ("if-range", "\"v1\"")]);
+    let mut res = respond(&req);
+    assert_eq!(res.status(), StatusCode::PartialContent);
+    assert_eq!(res["content-range"], "bytes 5-9/20");
+    assert_eq!(res["content-type"], "text/plain");
+    assert_eq!(res.len(), Some(5));
+    assert_eq!(res.body_string().await?, "56789");
+    Ok(())
+}
+
+#[async_std::test]
+async fn overlapping_ranges_are_merged() -> Result<()> {
+    let mut res = respond(&request(&[("range", "bytes=4-6, 0-2, 3-4")]));
+    assert_eq!(res["content-range"], "bytes 0-6/20");
+    assert_eq!(res.body_string().await?, "0123456");
+    Ok(())
+}
+
+#[async_std::test]
+async fn several_ranges_are_multipart() -> Result<()> {
+    let mut res = respond(&request(&[("range", "bytes=0-1, -3")]));
+    assert_eq!(res.status(), StatusCode::PartialContent);
+    assert!(res.header("content-range").is_none());
+
+    let content_type = res["content-type"].as_str().to_owned();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .unwrap();
+    let expected = format!(
+        "--{b}\r\ncontent-type: text/plain\r\ncontent-range: bytes 0-1/20\r\n\r\n01\
+         \r\n--{b}\r\ncontent-type: text/plain\r\ncontent-range: bytes 17-19/20\r\n\r\nhij\
+         \r\n--{b}--\r\n",
+        b = boundary
+    );
+    assert_eq!(res.len(), Some(expected.len()));
+    assert_eq!(res.body_string().await?, expected);
+    Ok(())
+}
+
+#[async_std::test]
+async fn unsatisfiable_ranges_get_416() -> Result<()> {
+    let mut res = respond(&request(&[("range", "bytes=20-")]));
+    assert_eq!(res.status(), StatusCode::RequestedRangeNotSatisfiable);
+    assert_eq!(res["content-range"], "bytes */20");
+    assert_eq!(res.body_string().await?, "");
+    Ok(())
+}
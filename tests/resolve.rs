@@ -0,0 +1,42 @@
+use async_h1::client::{PoolKey, Resolve, Resolving, SystemResolver, TcpConnector};
+use async_std::net::TcpListener;
+use http_types::{Method, Request, Response, Url};
+use std::net::SocketAddr;
+
+/// Resolves every host to a single address.
+#[derive(Debug)]
+struct Static(SocketAddr);
+
+impl Resolve for Static {
+    fn resolve<'a>(&'a self, _host: &'a str, _port: u16) -> Resolving<'a> {
+        let addr = self.0;
+        Box::pin(async move { Ok(vec![addr]) })
+    }
+}
+
+#[async_std::test]
+async fn system_resolver_resolves_localhost() -> std::io::Result<()> {
+    let addrs = SystemResolver.resolve("localhost", 8080).await?;
+    assert!(addrs.iter().all(|addr| addr.port() == 8080));
+    assert!(addrs.iter().any(|addr| addr.ip().is_loopback()));
+    Ok(())
+}
+
+#[async_std::test]
+async fn connector_uses_custom_resolver() -> http_types::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    async_std::task::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        async_h1::accept(stream, |_| async { Ok(Response::new(200)) })
+            .await
+            .unwrap();
+    });
+
+    let url = Url::parse("http://example.test/")?;
+    let connector = TcpConnector::new().with_resolver(Static(addr));
+    let stream = connector.connect(&PoolKey::from_url(&url).unwrap()).await?;
+    let res = async_h1::connect(stream, Request::new(Method::Get, url)).await?;
+    assert_eq!(res.status(), 200);
+    Ok(())
+}
@@ -0,0 +1,44 @@
+#![cfg(all(unix, feature = "reuseport"))]
+
+use async_h1::server::{bind_reuseport, serve_reuseport, ServerOptions};
+use async_std::io::prelude::{ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use http_types::{Response, StatusCode};
+use std::thread;
+
+#[test]
+fn listeners_share_address() -> std::io::Result<()> {
+    let first = bind_reuseport("127.0.0.1:0".parse().unwrap())?;
+    let addr = first.local_addr()?;
+    let second = bind_reuseport(addr)?;
+    assert_eq!(second.local_addr()?, addr);
+    Ok(())
+}
+
+#[async_std::test]
+async fn serves_from_workers() -> std::io::Result<()> {
+    // Reserve a port; the workers will bind it alongside this listener.
+    let reserved = bind_reuseport("127.0.0.1:0".parse().unwrap())?;
+    let addr = reserved.local_addr()?;
+    drop(reserved);
+
+    thread::spawn(move || {
+        serve_reuseport(addr, 2, ServerOptions::new(), |_req| async {
+            Ok(Response::new(StatusCode::Ok))
+        })
+    });
+
+    let mut stream = loop {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => break stream,
+            Err(_) => async_std::task::yield_now().await,
+        }
+    };
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: example.com\r\nconnection: close\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    Ok(())
+}
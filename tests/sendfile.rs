@@ -0,0 +1,157 @@
+#![cfg(all(target_os = "linux", feature = "sendfile"))]
+
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use async_h1::sendfile::{self, ZeroCopySocket};
+use async_h1::server::Server;
+use async_std::io::prelude::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use futures_lite::io::{self, AsyncRead, AsyncWrite};
+use http_types::{Request, Response, StatusCode};
+
+/// A stream which counts the bytes written through it.
+#[derive(Clone)]
+struct Counted(TcpStream, Arc<AtomicUsize>);
+
+impl AsyncRead for Counted {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Counted {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.0).poll_write(cx, buf);
+        if let Poll::Ready(Ok(bytes)) = poll {
+            self.1.fetch_add(bytes, Ordering::SeqCst);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+fn contents() -> Vec<u8> {
+    (0..200_000u32).map(|i| (i % 251) as u8).collect()
+}
+
+/// Write the contents to a file of their own.
+fn write_file(name: &str) -> std::io::Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("async-h1-{}-{}", name, std::process::id()));
+    std::fs::write(&path, contents())?;
+    Ok(path)
+}
+
+/// Serve the file at `path` on one connection, writing the body with
+/// `sendfile` if `zero_copy` is set, and return the stream to it and a count
+/// of the bytes written through the stream by the server.
+async fn serve(path: PathBuf, zero_copy: bool) -> std::io::Result<(TcpStream, Arc<AtomicUsize>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let client = TcpStream::connect(listener.local_addr()?).await?;
+    let (stream, _) = listener.accept().await?;
+    let written = Arc::new(AtomicUsize::new(0));
+    let counted = Counted(stream.clone(), written.clone());
+
+    let socket = ZeroCopySocket::new(&stream)?;
+    task::spawn(async move {
+        let mut server = Server::new(counted, move |_req: Request| {
+            let path = path.clone();
+            async move {
+                let mut res = Response::new(StatusCode::Ok);
+                sendfile::set_file_body(&mut res, path).await?;
+                Ok(res)
+            }
+        });
+        if zero_copy {
+            server = server.with_zero_copy(socket);
+        }
+        server.accept().await
+    });
+    Ok((client, written))
+}
+
+/// Read a response head and `len` bytes of body.
+async fn read_response(stream: &mut TcpStream, len: usize) -> std::io::Result<(String, Vec<u8>)> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).await?;
+        head.push(byte[0]);
+    }
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).await?;
+    Ok((String::from_utf8(head).unwrap(), body))
+}
+
+#[async_std::test]
+async fn file_bodies_bypass_the_stream() -> http_types::Result<()> {
+    let path = write_file("zero-copy")?;
+    let (mut client, written) = serve(path.clone(), true).await?;
+
+    // Both responses are framed by their length, so the connection stays
+    // usable after the first.
+    for _ in 0..2 {
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        let (head, body) = read_response(&mut client, contents().len()).await?;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("content-length: 200000\r\n"));
+        assert_eq!(body, contents());
+        // Only the head went through the stream.
+        assert_eq!(written.swap(0, Ordering::SeqCst), head.len());
+    }
+
+    client
+        .write_all(b"HEAD / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+        .await?;
+    let (head, _) = read_response(&mut client, 0).await?;
+    assert!(head.contains("content-length: 200000\r\n"));
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut rest = Vec::new();
+    client.read_to_end(&mut rest).await?;
+    assert!(rest.ends_with(&contents()));
+
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[async_std::test]
+async fn file_bodies_are_copied_without_the_socket() -> http_types::Result<()> {
+    let path = write_file("copied")?;
+    let (mut client, written) = serve(path.clone(), false).await?;
+
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+        .await?;
+    let (head, body) = read_response(&mut client, contents().len()).await?;
+    assert_eq!(body, contents());
+    assert_eq!(
+        written.load(Ordering::SeqCst),
+        head.len() + contents().len()
+    );
+
+    std::fs::remove_file(path)?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
 mod test_utils;
 mod server_decode {
     use super::test_utils::TestIO;
+    use async_h1::error::{ErrorKind, ForeignProtocol, Limit};
     use async_std::io::prelude::*;
     use http_types::headers::TRANSFER_ENCODING;
     use http_types::Request;
@@ -77,6 +78,32 @@ mod server_decode {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn chunked_with_extensions_and_trailers() -> Result<()> {
+        let mut request = decode_lines(vec![
+            "POST / HTTP/1.1",
+            "host: localhost:8080",
+            "transfer-encoding: chunked",
+            "trailer: x-sum",
+            "",
+            "2;ext=1",
+            "he",
+            "3 ; ext=\"a;b\"",
+            "llo",
+            "0;last",
+            "x-sum: 5",
+            "",
+            "",
+        ])
+        .await?
+        .unwrap();
+
+        let trailers = request.recv_trailers();
+        assert_eq!(request.body_string().await?, "hello");
+        assert_eq!(trailers.await.unwrap()["x-sum"], "5");
+        Ok(())
+    }
+
     #[ignore = r#"
        the test previously did not actually assert the correct thing prevously
        and the behavior does not yet work as intended
@@ -125,4 +152,192 @@ mod server_decode {
 
         Ok(())
     }
+
+    #[async_std::test]
+    async fn error_kinds() -> Result<()> {
+        let err = decode_lines(vec!["GET / HTTP/1.1", "", ""])
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::MalformedMessage);
+        assert_eq!(err.status(), 400);
+
+        let err = decode_lines(vec![
+            "POST / HTTP/1.1",
+            "host: example.com",
+            "content-length: 5",
+            "transfer-encoding: chunked",
+            "",
+            "",
+        ])
+        .await
+        .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::BodyFraming);
+
+        let mut request = decode_lines(vec![
+            "POST / HTTP/1.1",
+            "host: example.com",
+            "transfer-encoding: chunked",
+            "",
+            "zz",
+            "",
+        ])
+        .await?
+        .unwrap();
+        let err = request.body_string().await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::BodyFraming);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn folded_headers_are_rejected() -> Result<()> {
+        let err = decode_lines(vec![
+            "GET / HTTP/1.1",
+            "host: example.com",
+            "x-folded: first",
+            " second",
+            "",
+            "",
+        ])
+        .await
+        .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::MalformedMessage);
+        assert_eq!(err.status(), 400);
+
+        let err = decode_lines(vec!["GET / HTTP/1.1", " host: example.com", "", ""])
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::MalformedMessage);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn exceeded_limits_are_named() -> Result<()> {
+        let long = "a".repeat(1024 * 1024);
+        let headers = vec!["x: y"; 1000];
+
+        let path = format!("GET /{} HTTP/1.1", long);
+        let err = decode_lines(vec![&path, "host: example.com", "", ""])
+            .await
+            .unwrap_err();
+        assert_eq!(Limit::of(&err), Some(Limit::RequestLine));
+
+        let header = format!("x: {}", long);
+        let err = decode_lines(vec!["GET / HTTP/1.1", &header, "", ""])
+            .await
+            .unwrap_err();
+        assert_eq!(Limit::of(&err), Some(Limit::HeadLength));
+
+        let mut lines = vec!["GET / HTTP/1.1", "host: example.com"];
+        lines.extend(headers);
+        lines.extend(["", ""]);
+        let err = decode_lines(lines).await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::LimitExceeded);
+        assert_eq!(Limit::of(&err), Some(Limit::HeaderCount));
+
+        let err = decode_lines(vec!["GET / HTTP/1.1", "", ""])
+            .await
+            .unwrap_err();
+        assert_eq!(Limit::of(&err), None);
+        Ok(())
+    }
+
+    async fn decode_framing(framing: &[&str]) -> Result<Option<Request>> {
+        let mut lines = vec!["POST / HTTP/1.1", "host: example.com"];
+        lines.extend_from_slice(framing);
+        lines.extend_from_slice(&["", "5", "hello", "0", "", ""]);
+        decode_lines(lines).await
+    }
+
+    #[async_std::test]
+    async fn ambiguous_framing_is_rejected() -> Result<()> {
+        for framing in [
+            &["content-length: 5", "content-length: 6"][..],
+            &["content-length: 5, 6"],
+            &["content-length: +5"],
+            &["transfer-encoding: chunked, gzip"],
+            &["transfer-encoding: chunked", "transfer-encoding: chunked"],
+            &["transfer-encoding: gzip"],
+            &["transfer-encoding: "],
+        ] {
+            let err = decode_framing(framing).await.unwrap_err();
+            assert_eq!(ErrorKind::of(&err), ErrorKind::BodyFraming, "{:?}", framing);
+            assert_eq!(err.status(), 400, "{:?}", framing);
+        }
+
+        let err = decode_framing(&["transfer-encoding: gzip, chunked"])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), 501);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn repeated_content_lengths_which_agree_are_accepted() -> Result<()> {
+        let request = decode_framing(&["content-length: 5", "content-length: 5"]).await?;
+        assert_eq!(request.unwrap().len(), Some(5));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn drained_bodies_leave_the_next_request() -> Result<()> {
+        let (mut client, server) = TestIO::new();
+        client
+            .write_all(b"POST / HTTP/1.1\r\nhost: example.com\r\ncontent-length: 5\r\n\r\nhello")
+            .await?;
+        let (_, mut body) = async_h1::server::decode(server.clone()).await?.unwrap();
+        assert_eq!(body.drain().await?, 5);
+        assert_eq!(body.drain().await?, 0);
+
+        client
+            .write_all(b"GET /next HTTP/1.1\r\nhost: example.com\r\n\r\n")
+            .await?;
+        let (next, _) = async_h1::server::decode(server).await?.unwrap();
+        assert_eq!(next.url().path(), "/next");
+        Ok(())
+    }
+
+    #[cfg(feature = "chunked")]
+    #[async_std::test]
+    async fn drained_chunked_bodies_are_decoded() -> Result<()> {
+        let (mut client, server) = TestIO::new();
+        client
+            .write_all(
+                b"POST / HTTP/1.1\r\nhost: example.com\r\ntransfer-encoding: chunked\r\n\r\n\
+                  5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+            )
+            .await?;
+        let (_, mut body) = async_h1::server::decode(server).await?.unwrap();
+        assert_eq!(body.drain().await?, 11);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn foreign_protocols_are_recognized() -> Result<()> {
+        // The start of a ClientHello, which has no line ending to wait for.
+        let (mut client, server) = TestIO::new();
+        client
+            .write_all(&[0x16, 0x03, 0x01, 0x02, 0x00, 0x01])
+            .await?;
+        let err = async_h1::server::decode(server).await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::ProtocolMismatch);
+        assert_eq!(ForeignProtocol::of(&err), Some(ForeignProtocol::Tls));
+        assert_eq!(err.status(), 400);
+
+        let (mut client, server) = TestIO::new();
+        client
+            .write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+            .await?;
+        let err = async_h1::server::decode(server).await.unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::ProtocolMismatch);
+        assert_eq!(ForeignProtocol::of(&err), Some(ForeignProtocol::Http2));
+        assert_eq!(err.status(), 505);
+
+        let err = decode_lines(vec!["GET / HTTP/2.0", "host: example.com", "", ""])
+            .await
+            .unwrap_err();
+        assert_eq!(ForeignProtocol::of(&err), None);
+        Ok(())
+    }
 }
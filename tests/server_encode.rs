@@ -1,5 +1,5 @@
 mod server_encode {
-    use async_h1::server::Encoder;
+    use async_h1::server::{Encoder, EncoderPhase};
     use async_std::io::Cursor;
     use async_std::io::ReadExt;
     use http_types::Body;
@@ -116,6 +116,29 @@ mod server_encode {
         Ok(())
     }
 
+    #[cfg(feature = "chunked")]
+    #[async_std::test]
+    async fn chunk_buffer_gathers_small_pieces() -> Result<()> {
+        // A one-byte buffer makes the body hand out a byte at a time.
+        let body = async_std::io::BufReader::with_capacity(1, Cursor::new("hello world"));
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body(Body::from_reader(body, None));
+
+        let mut encoder = Encoder::new(res, Method::Get).with_chunk_buffer_size(4);
+        let mut encoded = String::new();
+        loop {
+            let mut buf = [0; 1024];
+            let bytes = encoder.read(&mut buf).await?;
+            if bytes == 0 {
+                break;
+            }
+            encoded.push_str(std::str::from_utf8(&buf[..bytes])?);
+        }
+        let body = &encoded[encoded.find("\r\n\r\n").unwrap() + 4..];
+        assert_eq!(body, "4\r\nhell\r\n4\r\no wo\r\n3\r\nrld\r\n0\r\n\r\n");
+        Ok(())
+    }
+
     #[async_std::test]
     async fn head_request_fixed_body() -> Result<()> {
         let mut res = Response::new(StatusCode::Ok);
@@ -164,4 +187,150 @@ mod server_encode {
 
         Ok(())
     }
+
+    #[async_std::test]
+    async fn head_request_keeps_declared_length() -> Result<()> {
+        let mut res = Response::new(StatusCode::Ok);
+        res.insert_header("content-length", "1024");
+
+        assert_encoded(
+            10,
+            Method::Head,
+            res,
+            vec![
+                "HTTP/1.1 200 OK",
+                "content-length: 1024",
+                "date: {DATE}",
+                "",
+                "",
+            ],
+        )
+        .await;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn get_request_overrides_declared_length() -> Result<()> {
+        let mut res = Response::new(StatusCode::Ok);
+        res.insert_header("content-length", "1024");
+
+        assert_encoded(
+            10,
+            Method::Get,
+            res,
+            vec![
+                "HTTP/1.1 200 OK",
+                "content-length: 0",
+                "date: {DATE}",
+                "",
+                "",
+            ],
+        )
+        .await;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn no_content_has_no_framing() -> Result<()> {
+        for (body, content_type) in [
+            (
+                Body::from_string("ignored".into()),
+                "content-type: text/plain;charset=utf-8",
+            ),
+            (
+                Body::from_reader(Cursor::new("ignored"), None),
+                "content-type: application/octet-stream",
+            ),
+        ] {
+            let mut res = Response::new(StatusCode::NoContent);
+            res.set_body(body);
+
+            assert_encoded(
+                10,
+                Method::Get,
+                res,
+                vec![
+                    "HTTP/1.1 204 No Content",
+                    content_type,
+                    "date: {DATE}",
+                    "",
+                    "",
+                ],
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn empty_body_skips_body_state() -> Result<()> {
+        let res = Response::new(StatusCode::Ok);
+        let mut encoder = Encoder::new(res, Method::Get);
+        let mut buf = vec![0; 1024];
+        let bytes = encoder.read(&mut buf).await?;
+        let head = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(head.contains("content-length: 0\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+        assert_eq!(encoder.read(&mut buf).await?, 0);
+        assert_eq!(encoder.phase(), EncoderPhase::End);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn reports_progress() -> Result<()> {
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body(Body::from_reader(Cursor::new("hello world"), None));
+        let mut encoder = Encoder::new(res, Method::Get);
+        assert_eq!(encoder.phase(), EncoderPhase::Start);
+        assert_eq!(encoder.head_bytes_remaining(), None);
+
+        let mut buf = [0; 10];
+        encoder.read(&mut buf).await?;
+        assert_eq!(encoder.phase(), EncoderPhase::Head);
+        let remaining = encoder.head_bytes_remaining().unwrap();
+        assert!(remaining > 0);
+
+        let mut buf = vec![0; remaining];
+        encoder.read_exact(&mut buf).await?;
+        assert_eq!(encoder.head_bytes_remaining(), Some(0));
+        assert_eq!(encoder.body_bytes_written(), 0);
+
+        let mut buf = [0; 10];
+        let mut body = 0;
+        loop {
+            let bytes = encoder.read(&mut buf).await?;
+            if bytes == 0 {
+                break;
+            }
+            body += bytes as u64;
+        }
+        assert_eq!(encoder.phase(), EncoderPhase::End);
+        assert_eq!(encoder.body_bytes_written(), body);
+        assert_eq!(encoder.chunks_emitted(), 3);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn encodes_into_owned_buffers() -> Result<()> {
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body("hello");
+        let mut encoder = Encoder::new(res, Method::Get);
+
+        let mut encoded = vec![];
+        loop {
+            let (bytes, buf) = encoder.read_owned(Vec::with_capacity(16)).await;
+            if bytes? == 0 {
+                break;
+            }
+            encoded.extend_from_slice(&buf);
+        }
+        let encoded = String::from_utf8(encoded)?;
+        assert!(encoded.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(encoded.ends_with("\r\n\r\nhello"));
+        Ok(())
+    }
 }
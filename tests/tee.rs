@@ -0,0 +1,54 @@
+mod test_utils;
+mod tee {
+    use super::test_utils::TestServer;
+    use async_h1::server::{Hook, ServerOptions};
+    use async_h1::tee::{tee_body, BoundedBuffer};
+    use async_std::io::prelude::WriteExt;
+    use http_types::headers::CONTENT_TYPE;
+    use http_types::{Request, Response, Result};
+
+    #[derive(Debug)]
+    struct Capture(BoundedBuffer);
+
+    impl Hook for Capture {
+        fn before_endpoint(&self, req: &mut Request) -> Option<Response> {
+            tee_body(req, self.0.clone());
+            None
+        }
+    }
+
+    async fn capture(limit: usize) -> Result<BoundedBuffer> {
+        let captured = BoundedBuffer::new(limit);
+        let opts = ServerOptions::new().with_hook(Capture(captured.clone()));
+        let mut server = TestServer::new_with_opts(
+            |mut req: Request| async move {
+                assert_eq!(req[CONTENT_TYPE], "text/plain");
+                assert_eq!(req.body_string().await?, "hello world");
+                Ok(Response::new(200))
+            },
+            opts,
+        );
+
+        server
+            .write_all(b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Type: text/plain\r\nContent-Length: 11\r\n\r\nhello world")
+            .await?;
+        server.accept_one().await?;
+        Ok(captured)
+    }
+
+    #[async_std::test]
+    async fn captures_body() -> Result<()> {
+        let captured = capture(1024).await?;
+        assert_eq!(captured.contents(), b"hello world");
+        assert!(!captured.is_truncated());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn truncates_body() -> Result<()> {
+        let captured = capture(5).await?;
+        assert_eq!(captured.contents(), b"hello");
+        assert!(captured.is_truncated());
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "testing")]
+
+use async_h1::testing::{read_response_bytes, send_request_bytes, MockTcpStream};
+use async_h1::ServerOptions;
+use futures_lite::io::{AsyncReadExt, AsyncWriteExt};
+use http_types::{Request, Response, Result};
+
+#[async_std::test]
+async fn streams_carry_bytes_both_ways() -> Result<()> {
+    let (mut client, mut server) = MockTcpStream::pair();
+    client.write_all(b"ping").await?;
+    assert_eq!(client.unread(), 4);
+    let mut buf = [0; 4];
+    server.read_exact(&mut buf).await?;
+    assert_eq!(&buf, b"ping");
+    assert_eq!(client.unread(), 0);
+
+    server.write_all(b"pong").await?;
+    server.close();
+    let mut rest = Vec::new();
+    client.read_to_end(&mut rest).await?;
+    assert_eq!(rest, b"pong");
+    assert!(server.write_all(b"more").await.is_err());
+    Ok(())
+}
+
+#[async_std::test]
+async fn endpoints_answer_requests_in_turn() -> Result<()> {
+    let (mut client, server) = MockTcpStream::pair();
+    send_request_bytes(
+        &mut client,
+        b"GET /first HTTP/1.1\r\nHost: example.com\r\n\r\n",
+    )
+    .await?;
+    let serving = async_std::task::spawn(async_h1::accept_with_opts(
+        server,
+        |req: Request| async move {
+            let mut res = Response::new(200);
+            res.set_body(req.url().path().to_owned());
+            Ok(res)
+        },
+        ServerOptions::new(),
+    ));
+
+    // The server is still running, so the response may arrive in pieces.
+    let mut first = Vec::new();
+    while !first.ends_with(b"/first") {
+        first.extend(read_response_bytes(&mut client).await?);
+    }
+    assert!(first.starts_with(b"HTTP/1.1 200 OK\r\n"));
+
+    send_request_bytes(
+        &mut client,
+        b"GET /second HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n",
+    )
+    .await?;
+    serving.await?;
+    let second = read_response_bytes(&mut client).await?;
+    assert!(second.ends_with(b"/second"), "{:?}", second);
+    Ok(())
+}
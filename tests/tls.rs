@@ -0,0 +1,158 @@
+#![cfg(feature = "tls")]
+
+mod test_utils;
+mod tls {
+    use std::sync::Mutex;
+
+    use super::test_utils::TestIO;
+    use async_h1::client::{Proxy, Tunnel};
+    use async_h1::server::{Negotiation, TlsInfo};
+    use async_h1::tls::{self, Handshake, TlsAccept, TlsConnect, HTTP_11};
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use async_std::task;
+    use http_types::{Method, Request, Response, Result, StatusCode, Url};
+
+    /// A stand-in for a TLS implementation which leaves the stream as it is,
+    /// selecting `selected` through ALPN, or the first protocol offered.
+    #[derive(Default)]
+    struct Plaintext {
+        selected: Option<&'static [u8]>,
+        handshakes: Mutex<Vec<(String, Vec<Vec<u8>>)>>,
+    }
+
+    impl Plaintext {
+        fn handshake<'a, IO: Send + 'a>(
+            &'a self,
+            server_name: &str,
+            alpn: &[&[u8]],
+            io: IO,
+        ) -> Handshake<'a, IO> {
+            let offered = alpn.iter().map(|p| p.to_vec()).collect();
+            let selected = self.selected.unwrap_or(alpn[0]);
+            self.handshakes
+                .lock()
+                .unwrap()
+                .push((server_name.to_owned(), offered));
+            let info = TlsInfo::new()
+                .with_alpn_protocol(selected)
+                .with_server_name(server_name);
+            Box::pin(async move { Ok((io, info)) })
+        }
+    }
+
+    impl TlsConnect<TestIO> for Plaintext {
+        type Stream = TestIO;
+
+        fn connect<'a>(
+            &'a self,
+            server_name: &'a str,
+            alpn: &'a [&'a [u8]],
+            io: TestIO,
+        ) -> Handshake<'a, TestIO> {
+            self.handshake(server_name, alpn, io)
+        }
+    }
+
+    impl TlsConnect<Tunnel<TestIO>> for Plaintext {
+        type Stream = Tunnel<TestIO>;
+
+        fn connect<'a>(
+            &'a self,
+            server_name: &'a str,
+            alpn: &'a [&'a [u8]],
+            io: Tunnel<TestIO>,
+        ) -> Handshake<'a, Tunnel<TestIO>> {
+            self.handshake(server_name, alpn, io)
+        }
+    }
+
+    impl TlsAccept<TestIO> for Plaintext {
+        type Stream = TestIO;
+
+        fn accept<'a>(&'a self, alpn: &'a [&'a [u8]], io: TestIO) -> Handshake<'a, TestIO> {
+            self.handshake("example.com", alpn, io)
+        }
+    }
+
+    #[async_std::test]
+    async fn requests_are_served_over_tls() -> Result<()> {
+        let (client, server) = TestIO::new();
+        task::spawn(async move {
+            let acceptor = Plaintext::default();
+            tls::accept(&acceptor, server, |req: Request| async move {
+                let tls = req.ext().get::<Negotiation>().unwrap().tls().unwrap();
+                assert_eq!(tls.alpn_protocol(), Some(HTTP_11));
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body(tls.server_name().unwrap().to_owned());
+                Ok(res)
+            })
+            .await
+        });
+
+        let connector = Plaintext::default();
+        let url = Url::parse("https://example.com:8443/")?;
+        let mut res = tls::connect(&connector, client, Request::new(Method::Get, url)).await?;
+        assert_eq!(res.body_string().await?, "example.com");
+
+        let handshakes = connector.handshakes.lock().unwrap();
+        assert_eq!(
+            *handshakes,
+            [("example.com".to_owned(), vec![HTTP_11.to_vec()])]
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn other_alpn_protocols_are_refused() -> Result<()> {
+        let (client, server) = TestIO::new();
+        let connector = Plaintext {
+            selected: Some(b"h2"),
+            ..Default::default()
+        };
+        let url = Url::parse("https://example.com/")?;
+        let err = tls::connect(&connector, client, Request::new(Method::Get, url))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Unexpected ALPN protocol h2");
+
+        let acceptor = connector;
+        let err = tls::accept(&acceptor, server, |_req| async {
+            Ok(Response::new(StatusCode::Ok))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Unexpected ALPN protocol h2");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn requests_are_sent_through_proxy_tunnels() -> Result<()> {
+        let (client, mut proxy) = TestIO::new();
+        task::spawn(async move {
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                proxy.read_exact(&mut byte).await?;
+                head.push(byte[0]);
+            }
+            assert!(head.starts_with(b"CONNECT example.com:443 HTTP/1.1\r\n"));
+            proxy.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await?;
+            async_h1::accept(proxy, |_req| async {
+                let mut res = Response::new(StatusCode::Ok);
+                res.set_body("through the tunnel");
+                Ok(res)
+            })
+            .await
+        });
+
+        let connector = Plaintext::default();
+        let proxy = Proxy::new(Url::parse("http://proxy.internal:3128")?);
+        let req = Request::new(Method::Get, Url::parse("https://example.com/")?);
+        let mut res = tls::connect_via_proxy(&connector, client, &proxy, req).await?;
+        assert_eq!(res.body_string().await?, "through the tunnel");
+
+        let handshakes = connector.handshakes.lock().unwrap();
+        assert_eq!(handshakes[0].0, "example.com");
+        Ok(())
+    }
+}
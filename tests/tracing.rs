@@ -0,0 +1,139 @@
+#![cfg(feature = "tracing")]
+
+mod test_utils;
+mod tracing {
+    use super::test_utils::TestServer;
+    use async_h1::server::ServerOptions;
+    use async_h1::tracing::{Fields, Span, Tracer, SERVER_CONNECTION, SERVER_REQUEST};
+    use async_std::io::prelude::WriteExt;
+    use http_types::{Response, Result, StatusCode};
+    use std::sync::{Arc, Mutex};
+
+    /// Records what happens to spans, naming them by the order they were
+    /// opened in.
+    #[derive(Debug, Clone, Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+        entered: Arc<Mutex<Vec<usize>>>,
+    }
+
+    #[derive(Debug)]
+    struct RecordedSpan {
+        id: usize,
+        recorder: Recorder,
+    }
+
+    impl Recorder {
+        fn push(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn events(&self) -> Vec<String> {
+            self.events.lock().unwrap().clone()
+        }
+
+        fn current(&self) -> Option<usize> {
+            self.entered.lock().unwrap().last().copied()
+        }
+    }
+
+    fn format(fields: Fields<'_>) -> String {
+        fields
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    impl Tracer for Recorder {
+        fn new_span(
+            &self,
+            name: &'static str,
+            _parent: Option<&dyn Span>,
+            fields: Fields<'_>,
+        ) -> Option<Arc<dyn Span>> {
+            let id = self
+                .events()
+                .iter()
+                .filter(|e| e.starts_with("new"))
+                .count();
+            let parent = self.current();
+            self.push(format!(
+                "new {} {} in {:?}: {}",
+                id,
+                name,
+                parent,
+                format(fields)
+            ));
+            Some(Arc::new(RecordedSpan {
+                id,
+                recorder: self.clone(),
+            }))
+        }
+    }
+
+    impl Span for RecordedSpan {
+        fn enter(&self) {
+            self.recorder.entered.lock().unwrap().push(self.id);
+        }
+
+        fn exit(&self) {
+            self.recorder.entered.lock().unwrap().pop();
+        }
+
+        fn record(&self, fields: Fields<'_>) {
+            self.recorder
+                .push(format!("record {}: {}", self.id, format(fields)));
+        }
+    }
+
+    impl Drop for RecordedSpan {
+        fn drop(&mut self) {
+            self.recorder.push(format!("close {}", self.id));
+        }
+    }
+
+    #[async_std::test]
+    async fn connections_and_requests_get_spans() -> Result<()> {
+        let recorder = Recorder::default();
+        let opts = ServerOptions::new()
+            .with_tracer(recorder.clone())
+            .with_peer_addr("192.0.2.1:4321".parse().unwrap());
+        let current = recorder.clone();
+        let mut server = TestServer::new_with_opts(
+            move |_| {
+                // The endpoint runs within the request's span.
+                assert_eq!(current.current(), Some(1));
+                async { Ok(Response::new(StatusCode::Created)) }
+            },
+            opts,
+        );
+
+        server
+            .write_all(b"POST /upload HTTP/1.1\r\nHost: example.com\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi")
+            .await?;
+        server.accept().await?;
+
+        let events = recorder.events();
+        assert_eq!(
+            events[..2],
+            [
+                format!(
+                    "new 0 {} in None: network.peer.address=192.0.2.1,network.peer.port=4321",
+                    SERVER_CONNECTION
+                ),
+                format!(
+                    "new 1 {} in Some(0): http.request.method=POST,url.path=/upload,\
+                     network.protocol.version=1.1,http.request.body.size=2",
+                    SERVER_REQUEST
+                ),
+            ]
+        );
+        assert!(
+            events[2].starts_with("record 1: http.response.status_code=201,http.response.size=")
+        );
+        assert_eq!(events[3..], ["close 1", "close 0"]);
+        assert_eq!(recorder.current(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "chunked")]
+
+mod test_utils;
+mod trailers {
+    use super::test_utils::TestServer;
+    use async_h1::server::ConnectionStatus;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Body, Request, Response, Result, StatusCode};
+
+    const REQUEST: &str = "GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+    fn chunked_body() -> Body {
+        Body::from_reader(async_std::io::Cursor::new("hello"), None)
+    }
+
+    #[async_std::test]
+    async fn sends_trailers_after_last_chunk() -> Result<()> {
+        let mut server = TestServer::new(|_req: Request| async {
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(chunked_body());
+            let sender = res.send_trailers();
+            let mut trailers = http_types::trailers::Trailers::new();
+            trailers.insert("x-checksum", "abc");
+            sender.send(trailers).await;
+            Ok(res)
+        });
+        server.write_all(REQUEST.as_bytes()).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(written.contains("trailer: x-checksum\r\n"));
+        assert!(written.ends_with("5\r\nhello\r\n0\r\nx-checksum: abc\r\n\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn waits_for_trailers_sent_later() -> Result<()> {
+        let mut server = TestServer::new(|_req: Request| async {
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(chunked_body());
+            let sender = res.send_trailers();
+            async_std::task::spawn(async move {
+                let mut trailers = http_types::trailers::Trailers::new();
+                trailers.insert("x-checksum", "abc");
+                sender.send(trailers).await;
+            });
+            Ok(res)
+        });
+        server.write_all(REQUEST.as_bytes()).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(written.ends_with("0\r\nx-checksum: abc\r\n\r\n"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn ends_without_trailers_when_none_are_sent() -> Result<()> {
+        let mut server = TestServer::new(|_req: Request| async {
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(chunked_body());
+            drop(res.send_trailers());
+            Ok(res)
+        });
+        server.write_all(REQUEST.as_bytes()).await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(!written.contains("trailer:"));
+        assert!(written.ends_with("5\r\nhello\r\n0\r\n\r\n"));
+        Ok(())
+    }
+}
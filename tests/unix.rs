@@ -0,0 +1,33 @@
+#![cfg(all(target_os = "linux", feature = "uds"))]
+
+use async_h1::server::{accept_unix, ConnectionInfo, ServerOptions};
+use async_std::io::prelude::{ReadExt, WriteExt};
+use async_std::os::unix::net::UnixStream;
+use async_std::task;
+use http_types::{Request, Response, StatusCode};
+
+#[async_std::test]
+async fn serves_unix_domain_sockets() -> http_types::Result<()> {
+    let (mut client, server) = UnixStream::pair()?;
+    task::spawn(async move {
+        let endpoint = |req: Request| async move {
+            let info = req.ext().get::<ConnectionInfo>().unwrap();
+            let pid = info.peer_credentials().unwrap().pid();
+            let mut res = Response::new(StatusCode::Ok);
+            res.set_body(format!("{} {}", req.url(), pid));
+            Ok(res)
+        };
+        accept_unix(server, endpoint, ServerOptions::new()).await
+    });
+
+    // Clients often leave the host out over a Unix domain socket.
+    client
+        .write_all(b"GET /status HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    client.read_to_string(&mut response).await?;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let body = format!("http://localhost/status {}", std::process::id());
+    assert!(response.ends_with(&body), "{}", response);
+    Ok(())
+}
@@ -0,0 +1,90 @@
+mod test_utils;
+mod upgrade {
+    use super::test_utils::TestServer;
+    use async_h1::server::ConnectionStatus;
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use async_std::task;
+    use http_types::{Request, Response, Result, StatusCode};
+    use std::time::Duration;
+
+    const UPGRADE_REQUEST: &str = "GET /chat HTTP/1.1\r\n\
+        Host: example.com\r\n\
+        Connection: Upgrade\r\n\
+        Upgrade: echo\r\n\r\n";
+
+    async fn endpoint(_req: Request) -> Result<Response> {
+        let mut res = Response::new(StatusCode::SwitchingProtocols);
+        res.insert_header("upgrade", "echo");
+        res.insert_header("connection", "Upgrade");
+        let upgrade = res.recv_upgrade().await;
+        task::spawn(async move {
+            let mut connection = upgrade.await.expect("the connection is handed over");
+            let mut buf = [0; 4];
+            connection.read_exact(&mut buf).await.unwrap();
+            connection.write_all(&buf).await.unwrap();
+        });
+        Ok(res)
+    }
+
+    #[async_std::test]
+    async fn hands_connection_to_endpoint() -> Result<()> {
+        let mut server = TestServer::new(endpoint);
+        // The client speaks the new protocol right after the request head,
+        // so these bytes may be read along with it.
+        server.write_all(UPGRADE_REQUEST.as_bytes()).await?;
+        server.write_all(b"ping").await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        task::sleep(Duration::from_millis(100)).await;
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(written.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(written.ends_with("\r\n\r\nping"), "{}", written);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn no_handover_without_upgrade_request() -> Result<()> {
+        let mut server = TestServer::new(|_req: Request| async {
+            let mut res = Response::new(StatusCode::SwitchingProtocols);
+            let upgrade = res.recv_upgrade().await;
+            task::spawn(async move { assert!(upgrade.await.is_none()) });
+            Ok(res)
+        });
+        server
+            .write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::KeepAlive);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn connect_hands_tunnel_to_endpoint() -> Result<()> {
+        let mut server = TestServer::new(|req: Request| async move {
+            assert_eq!(req.url().as_str(), "http://example.com:443/");
+            let mut res = Response::new(StatusCode::Ok);
+            let tunnel = res.recv_upgrade().await;
+            task::spawn(async move {
+                let mut connection = tunnel.await.expect("the tunnel is handed over");
+                let mut buf = [0; 4];
+                connection.read_exact(&mut buf).await.unwrap();
+                connection.write_all(&buf).await.unwrap();
+            });
+            Ok(res)
+        });
+        server
+            .write_all(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\nping")
+            .await?;
+        assert_eq!(server.accept_one().await?, ConnectionStatus::Close);
+
+        task::sleep(Duration::from_millis(100)).await;
+        let mut buf = vec![0; 1024];
+        let bytes = server.read(&mut buf).await?;
+        let written = String::from_utf8(buf[..bytes].to_vec())?;
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!written.contains("content-length"), "{}", written);
+        assert!(written.ends_with("\r\n\r\nping"), "{}", written);
+        Ok(())
+    }
+}
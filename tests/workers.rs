@@ -0,0 +1,62 @@
+#![cfg(feature = "workers")]
+
+mod test_utils;
+mod workers {
+    use super::test_utils::TestIO;
+    use async_h1::server::{Distribution, ServerOptions, WorkerPool};
+    use async_std::io::prelude::{ReadExt, WriteExt};
+    use http_types::{Response, StatusCode};
+
+    fn pool(distribution: Distribution) -> WorkerPool<TestIO> {
+        WorkerPool::new(2, distribution, ServerOptions::new(), |_req| async {
+            Ok(Response::new(StatusCode::Ok))
+        })
+    }
+
+    fn load(pool: &WorkerPool<TestIO>) -> usize {
+        pool.loads().iter().sum()
+    }
+
+    async fn request(pool: &WorkerPool<TestIO>) -> String {
+        let before = load(pool);
+        let (mut client, server) = TestIO::new();
+        pool.dispatch(server).unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nhost: example.com\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        client.close();
+
+        // Wait for the worker to finish the connection.
+        while load(pool) > before {
+            async_std::task::yield_now().await;
+        }
+        let mut response = vec![0; 1024];
+        let n = client.read(&mut response).await.unwrap();
+        String::from_utf8_lossy(&response[..n]).into_owned()
+    }
+
+    #[async_std::test]
+    async fn round_robin() {
+        let pool = pool(Distribution::RoundRobin);
+        assert_eq!(pool.len(), 2);
+        for _ in 0..4 {
+            assert!(request(&pool).await.starts_with("HTTP/1.1 200 OK\r\n"));
+        }
+        pool.join();
+    }
+
+    #[async_std::test]
+    async fn least_loaded() {
+        let pool = pool(Distribution::LeastLoaded);
+
+        // Idle connections stay open, so each lands on a different worker.
+        let (_first, server) = TestIO::new();
+        pool.dispatch(server).unwrap();
+        let (_second, server) = TestIO::new();
+        pool.dispatch(server).unwrap();
+        assert_eq!(pool.loads(), vec![1, 1]);
+
+        assert!(request(&pool).await.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+}